@@ -0,0 +1,109 @@
+//! Compares concurrent reads through a single connection with those through a pool of them, see
+//! [AsyncStore::open]
+//!
+//! Like requests of a server, the reads run alongside a slow write, e.g. a request running a
+//! transaction. A single connection makes them wait for it, while those of the pool read the last
+//! committed values in the meantime. Run with `cargo bench --features async`.
+use std::time::{Duration, Instant};
+
+use config_store::{AsyncStore, Filter, Store};
+
+/// The number of entries in the db
+const ENTRIES: usize = 200;
+
+/// The number of `list` calls running at once
+const REQUESTS: usize = 64;
+
+/// How long the write keeps its transaction open
+const WRITE: Duration = Duration::from_millis(50);
+
+/// How often the requests are timed
+const ROUNDS: u32 = 5;
+
+fn main() {
+    let path = std::env::temp_dir().join(format!("config-store-bench-{}.db", std::process::id()));
+
+    let store = Store::open(&path).expect("Failed to create the db");
+    store
+        .transaction(|store| {
+            for i in 0..ENTRIES {
+                store.set(&format!("entry.{}", i), Some("value"), Some("alternate"))?;
+            }
+            Ok(())
+        })
+        .expect("Failed to fill the db");
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("Failed to start tokio");
+
+    runtime.block_on(async {
+        let pooled = AsyncStore::open(&path)
+            .await
+            .expect("Failed to open the db");
+        let single = AsyncStore::from(store);
+
+        for (name, store) in [("single connection", single), ("pool", pooled)] {
+            // Warm up, which also opens the connections of the pool
+            run(&store).await;
+
+            let mut total = Duration::ZERO;
+            for _ in 0..ROUNDS {
+                total += run(&store).await;
+            }
+
+            println!(
+                "{}: {} concurrent lists of {} entries during a write took {:?} on average",
+                name,
+                REQUESTS,
+                ENTRIES,
+                total / ROUNDS
+            );
+        }
+    });
+
+    for suffix in ["", "-wal", "-shm"] {
+        let mut file = path.clone().into_os_string();
+        file.push(suffix);
+        let _ = std::fs::remove_file(file);
+    }
+}
+
+/// Lists all entries `REQUESTS` times while writing & returns how long the lists took
+async fn run(store: &AsyncStore) -> Duration {
+    let writer = store.clone();
+    let write = tokio::spawn(async move {
+        writer
+            .with(|store| {
+                store.transaction(|tx| {
+                    tx.toggle("entry.0")?;
+                    std::thread::sleep(WRITE);
+                    Ok(())
+                })
+            })
+            .await
+    });
+    // Give the write a head start, so it holds its transaction
+    tokio::task::yield_now().await;
+    std::thread::sleep(Duration::from_millis(5));
+
+    let start = Instant::now();
+    let mut tasks = tokio::task::JoinSet::new();
+    for _ in 0..REQUESTS {
+        let store = store.clone();
+        tasks.spawn(async move { store.list(Filter::default()).await });
+    }
+
+    while let Some(result) = tasks.join_next().await {
+        let entries = result.expect("Failed to join").expect("Failed to list");
+        assert_eq!(entries.len(), ENTRIES);
+    }
+    let elapsed = start.elapsed();
+
+    write
+        .await
+        .expect("Failed to join")
+        .expect("Failed to write");
+
+    elapsed
+}
@@ -0,0 +1,70 @@
+/*
+ * C API for config-store
+ *
+ * Build the library with `cargo build --release --features ffi`, which produces
+ * `libconfig_store.so` (or the platform equivalent) in `target/release`.
+ *
+ * This header is written by hand, keep it in sync with `src/ffi.rs`.
+ */
+
+#ifndef CONFIG_STORE_H
+#define CONFIG_STORE_H
+
+#include <stdint.h>
+
+/* Returned on success */
+#define CS_OK 0
+
+/* Returned if the entry doesn't exist */
+#define CS_NO_ENTRY 1
+
+/* Returned for any other error */
+#define CS_ERROR -1
+
+/* A config-store db */
+typedef struct Store Store;
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/*
+ * Opens (or creates) the db at `path`
+ *
+ * Returns null on failure. The store must be closed with `cs_close`.
+ */
+Store *cs_open(const char *path);
+
+/* Closes a store opened with `cs_open` */
+void cs_close(Store *store);
+
+/*
+ * Gets the value of an entry
+ *
+ * Returns null if the entry doesn't exist or on failure. The result must be freed with `cs_free`.
+ */
+char *cs_get(const Store *store, const char *name);
+
+/*
+ * Creates or updates an entry
+ *
+ * `value` & `alternate` may be null to leave them unchanged (or empty & unset for new entries).
+ * Returns `CS_ERROR` if any string isn't valid UTF-8.
+ */
+int cs_set(const Store *store, const char *name, const char *value, const char *alternate);
+
+/*
+ * Toggles an entry between its value & alternate
+ *
+ * The new value is written to `new_value` if it isn't null and must be freed with `cs_free`.
+ */
+int cs_toggle(const Store *store, const char *name, char **new_value);
+
+/* Frees a string returned by this API */
+void cs_free(char *string);
+
+#ifdef __cplusplus
+} /* extern "C" */
+#endif
+
+#endif /* CONFIG_STORE_H */
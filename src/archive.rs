@@ -0,0 +1,150 @@
+//! Module bundling the db into a compressed archive (`.tar.zst`) for backups & migrating machines
+//!
+//! The archive contains a copy of the db made with `VACUUM INTO`, so it holds the entries, their
+//! tags, metadata & settings as well as the audit log. zstd checksums the data & the copy is
+//! checked with sqlite's `integrity_check` before anything is imported.
+use std::path::Path;
+
+use rusqlite::{types::Value, Connection, OpenFlags};
+
+use crate::{backup, Error, Result};
+
+/// The name of the db inside the archive
+const DB: &str = "config-store.db";
+
+/// Writes an archive of the db to `path`
+pub fn create(connection: &Connection, path: &Path) -> Result<()> {
+    let dir = temp_dir()?;
+    let copy = dir.join(DB);
+
+    let result = (|| {
+        connection.execute("VACUUM INTO ?", [copy.to_string_lossy()])?;
+
+        let mut encoder = zstd::Encoder::new(std::fs::File::create(path)?, 0)?;
+        encoder.include_checksum(true)?;
+
+        let mut archive = tar::Builder::new(encoder);
+        archive.append_path_with_name(&copy, DB)?;
+        archive.into_inner()?.finish()?;
+
+        Ok(())
+    })();
+
+    std::fs::remove_dir_all(dir)?;
+    result
+}
+
+/// Replaces the contents of the db with those of the archive at `path`, like [backup::restore]
+///
+/// The audit log of the archive is appended to the one of the db, so the history moves along.
+/// Will return [Error::Invalid] if the archive is damaged, in which case nothing is changed.
+pub fn restore(connection: &Connection, path: &Path) -> Result<()> {
+    let dir = temp_dir()?;
+
+    let result = (|| {
+        let corrupt = |e: std::io::Error| Error::Invalid(format!("corrupt archive: {}", e));
+
+        let decoder = zstd::Decoder::new(std::fs::File::open(path)?)?;
+        tar::Archive::new(decoder).unpack(&dir).map_err(corrupt)?;
+
+        let copy = dir.join(DB);
+        if !copy.exists() {
+            return Err(Error::Invalid(format!(
+                "{} isn't a config-store archive",
+                path.display()
+            )));
+        }
+
+        let archived = Connection::open_with_flags(&copy, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let check: String = archived.query_row("PRAGMA integrity_check", (), |row| row.get(0))?;
+        if check != "ok" {
+            return Err(Error::Invalid(format!("corrupt archive: {}", check)));
+        }
+
+        crate::store::transaction(connection, || {
+            append_log(connection, &archived)?;
+            backup::restore(connection, &copy)
+        })
+    })();
+
+    std::fs::remove_dir_all(dir)?;
+    result
+}
+
+/// Helper function appending the audit log of `archived` to the one of the db
+fn append_log(connection: &Connection, archived: &Connection) -> Result<()> {
+    let columns = archived
+        .prepare("SELECT name FROM pragma_table_info('log') WHERE name != 'id'")?
+        .query_map((), |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    if columns.is_empty() {
+        return Ok(());
+    }
+
+    let mut insert = connection.prepare(&format!(
+        "INSERT INTO log ({}) VALUES ({})",
+        columns.join(", "),
+        vec!["?"; columns.len()].join(", ")
+    ))?;
+    let mut select = archived.prepare(&format!(
+        "SELECT {} FROM log ORDER BY id",
+        columns.join(", ")
+    ))?;
+    let mut rows = select.query(())?;
+
+    while let Some(row) = rows.next()? {
+        let values = (0..columns.len())
+            .map(|i| row.get::<_, Value>(i))
+            .collect::<rusqlite::Result<Vec<Value>>>()?;
+        insert.execute(rusqlite::params_from_iter(values))?;
+    }
+
+    Ok(())
+}
+
+/// Helper function creating an empty directory to work in
+fn temp_dir() -> Result<std::path::PathBuf> {
+    let dir = std::env::temp_dir().join(format!(
+        "config-store-archive-{}-{}",
+        std::process::id(),
+        crate::time::now()
+    ));
+    std::fs::create_dir_all(&dir)?;
+
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn create_and_restore() {
+        let dir = std::env::temp_dir().join(format!("config-store-tar-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let archive = dir.join("out.tar.zst");
+
+        let store = crate::Store::open(dir.join("a.db")).unwrap();
+        store.set("theme", Some("dark"), Some("light")).unwrap();
+        create(store.connection(), &archive).unwrap();
+
+        let other = crate::Store::open(dir.join("b.db")).unwrap();
+        other.set("stale", Some("1"), None).unwrap();
+        restore(other.connection(), &archive).unwrap();
+        assert_eq!(other.get("theme").unwrap().value, "dark");
+        assert!(!other.exists("stale").unwrap());
+        let history: i64 = other
+            .connection()
+            .query_row("SELECT COUNT(*) FROM log WHERE name = 'theme'", (), |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert!(history >= 2);
+
+        std::fs::write(&archive, b"not an archive").unwrap();
+        assert!(restore(other.connection(), &archive).is_err());
+        assert_eq!(other.get("theme").unwrap().value, "dark");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
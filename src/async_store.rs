@@ -0,0 +1,303 @@
+//! Module containing [AsyncStore], an async version of the [Store] API
+//!
+//! Only available with the `async` feature. Every operation runs on tokio's blocking thread pool,
+//! so it never blocks the runtime it is called from.
+use std::{
+    path::PathBuf,
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use crate::{Entry, Filter, Result, Store};
+
+/// The most connections a pool opens, further operations wait for one of them
+const MAX_CONNECTIONS: usize = 16;
+
+/// How long a pooled connection waits for others to finish writing
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// An async wrapper around a [Store]
+///
+/// Cloning an [AsyncStore] is cheap and all clones share the same connections. One opened with
+/// [AsyncStore::open] pools connections to the db in WAL mode, so concurrent operations (e.g. the
+/// requests of a server) aren't serialized behind a single connection; writes still wait for each
+/// other. One created from a [Store] only has its connection.
+#[derive(Debug, Clone)]
+pub struct AsyncStore {
+    pool: Arc<Pool>,
+}
+
+impl AsyncStore {
+    /// Opens (or creates) the db at `path`, switching it to WAL mode
+    ///
+    /// In-memory dbs can't be shared between connections, so they only have one.
+    pub async fn open(path: impl Into<std::path::PathBuf>) -> Result<AsyncStore> {
+        let path = path.into();
+
+        unblock(move || {
+            let store = connect(&path)?;
+            let shared = store.connection().path().is_some_and(|p| !p.is_empty());
+
+            Ok(AsyncStore {
+                pool: Arc::new(Pool {
+                    path: shared.then_some(path),
+                    ..Pool::new(store)
+                }),
+            })
+        })
+        .await
+    }
+
+    /// Runs `f` with one of the underlying [Store]s on the blocking thread pool
+    ///
+    /// This can be used to compose multiple operations without switching threads in between.
+    pub async fn with<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Store) -> Result<T> + Send + 'static,
+    {
+        let pool = self.pool.clone();
+
+        unblock(move || {
+            let store = Lease::take(&pool)?;
+            f(&store)
+        })
+        .await
+    }
+
+    /// See [Store::get]
+    pub async fn get(&self, name: impl Into<String>) -> Result<Entry> {
+        let name = name.into();
+        self.with(move |store| store.get(&name)).await
+    }
+
+    /// See [Store::exists]
+    pub async fn exists(&self, name: impl Into<String>) -> Result<bool> {
+        let name = name.into();
+        self.with(move |store| store.exists(&name)).await
+    }
+
+    /// See [Store::set]
+    pub async fn set(
+        &self,
+        name: impl Into<String>,
+        value: Option<String>,
+        alternate: Option<String>,
+    ) -> Result<Entry> {
+        let name = name.into();
+        self.with(move |store| store.set(&name, value.as_deref(), alternate.as_deref()))
+            .await
+    }
+
+    /// See [Store::toggle]
+    pub async fn toggle(&self, name: impl Into<String>) -> Result<String> {
+        let name = name.into();
+        self.with(move |store| store.toggle(&name)).await
+    }
+
+    /// See [Store::delete]
+    pub async fn delete(&self, name: impl Into<String>) -> Result<bool> {
+        let name = name.into();
+        self.with(move |store| store.delete(&name)).await
+    }
+
+    /// See [Store::list]
+    pub async fn list(&self, filter: Filter) -> Result<Vec<Entry>> {
+        self.with(move |store| store.list(&filter)).await
+    }
+
+    /// See [Store::reset]
+    pub async fn reset(&self, name: impl Into<String>) -> Result<String> {
+        let name = name.into();
+        self.with(move |store| store.reset(&name)).await
+    }
+}
+
+impl From<Store> for AsyncStore {
+    fn from(store: Store) -> Self {
+        AsyncStore {
+            pool: Arc::new(Pool::new(store)),
+        }
+    }
+}
+
+/// The connections of an [AsyncStore], each used by one operation at a time
+#[derive(Debug)]
+struct Pool {
+    /// Where further connections are opened, `None` if there is only the first one
+    path: Option<PathBuf>,
+    connections: Mutex<Connections>,
+    /// Notified whenever a connection becomes idle
+    returned: Condvar,
+}
+
+/// The state of the connections of a [Pool]
+#[derive(Debug)]
+struct Connections {
+    /// The connections which aren't used right now
+    idle: Vec<Store>,
+    /// The number of connections, idle or not
+    open: usize,
+}
+
+impl Pool {
+    /// Creates a pool with the connection of `store` only
+    fn new(store: Store) -> Pool {
+        Pool {
+            path: None,
+            connections: Mutex::new(Connections {
+                idle: vec![store],
+                open: 1,
+            }),
+            returned: Condvar::new(),
+        }
+    }
+
+    /// Helper function locking the state of the connections
+    fn lock(&self) -> MutexGuard<'_, Connections> {
+        self.connections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A connection taken from a [Pool], which it's returned to when dropped (even after a panic)
+struct Lease<'a> {
+    pool: &'a Pool,
+    store: Option<Store>,
+}
+
+impl<'a> Lease<'a> {
+    /// Takes an idle connection, opens a new one if there is none or waits for one if the pool
+    /// can't open more
+    fn take(pool: &'a Pool) -> Result<Lease<'a>> {
+        let mut connections = pool.lock();
+
+        let store = loop {
+            if let Some(store) = connections.idle.pop() {
+                break store;
+            }
+            if let Some(path) = pool
+                .path
+                .as_ref()
+                .filter(|_| connections.open < MAX_CONNECTIONS)
+            {
+                connections.open += 1;
+                drop(connections);
+
+                match connect(path) {
+                    Ok(store) => break store,
+                    Err(error) => {
+                        pool.lock().open -= 1;
+                        pool.returned.notify_one();
+                        return Err(error);
+                    }
+                }
+            }
+            connections = pool
+                .returned
+                .wait(connections)
+                .unwrap_or_else(PoisonError::into_inner);
+        };
+
+        Ok(Lease {
+            pool,
+            store: Some(store),
+        })
+    }
+}
+
+impl std::ops::Deref for Lease<'_> {
+    type Target = Store;
+
+    fn deref(&self) -> &Store {
+        self.store.as_ref().expect("only taken when dropped")
+    }
+}
+
+impl Drop for Lease<'_> {
+    fn drop(&mut self) {
+        self.pool.lock().idle.extend(self.store.take());
+        self.pool.returned.notify_one();
+    }
+}
+
+/// Helper function opening a connection for a [Pool], in WAL mode unless the db is read-only
+fn connect(path: &std::path::Path) -> Result<Store> {
+    let store = Store::open(path)?;
+    let connection = store.connection();
+
+    connection.busy_timeout(BUSY_TIMEOUT)?;
+    if !connection.is_readonly(rusqlite::DatabaseName::Main)? {
+        connection.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+    }
+
+    Ok(store)
+}
+
+/// Helper function running `f` on the blocking thread pool
+///
+/// Panics inside of `f` are resumed on the calling task.
+async fn unblock<T, F>(f: F) -> T
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(result) => result,
+        Err(error) => std::panic::resume_unwind(error.into_panic()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn async_api() {
+        let store = AsyncStore::from(Store::open_in_memory().unwrap());
+
+        store
+            .set("theme", Some("dark".to_string()), Some("light".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(store.toggle("theme").await.unwrap(), "light");
+        assert_eq!(store.clone().get("theme").await.unwrap().value, "light");
+        assert_eq!(store.list(Filter::default()).await.unwrap().len(), 1);
+        assert!(store.delete("theme").await.unwrap());
+        assert!(!store.exists("theme").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn pooled_connections() {
+        let path =
+            std::env::temp_dir().join(format!("config-store-pool-{}.db", std::process::id()));
+        let store = AsyncStore::open(&path).await.unwrap();
+        store
+            .set("theme", Some("dark".to_string()), None)
+            .await
+            .unwrap();
+
+        // Both operations only finish once they run at the same time, on different connections
+        let barrier = Arc::new(std::sync::Barrier::new(2));
+        let wait = |barrier: Arc<std::sync::Barrier>| {
+            store.with(move |store| {
+                barrier.wait();
+                Ok(store
+                    .connection()
+                    .pragma_query_value(None, "journal_mode", |r| r.get::<_, String>(0))?)
+            })
+        };
+        let (first, second) = tokio::join!(wait(barrier.clone()), wait(barrier));
+        assert_eq!(first.unwrap(), "wal");
+        assert_eq!(second.unwrap(), "wal");
+        assert_eq!(store.get("theme").await.unwrap().value, "dark");
+
+        drop(store);
+        for suffix in ["", "-wal", "-shm"] {
+            let mut file = path.clone().into_os_string();
+            file.push(suffix);
+            let _ = std::fs::remove_file(file);
+        }
+    }
+}
@@ -0,0 +1,388 @@
+//! Module containing the audit log
+//!
+//! Every change to the name, value or alternate of an [Entry](crate::entry::Entry) is recorded in
+//! the `log` table. This is done by temporary triggers, which are created for each connection by
+//! [init], so no command has to remember to write to the log itself.
+//!
+//! The command, user & process responsible for a change are read from the temporary `context`
+//! table, which is filled via [set_context]. The triggers also store who made the change in the
+//! `modified_by` column of the entry itself.
+use rusqlite::Connection;
+
+use crate::{color::Style, entry::json_string};
+
+/// Creates the `log` table, the `context` table & the triggers writing to the log
+pub fn init(connection: &Connection) -> rusqlite::Result<()> {
+    connection.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS log (
+            id INTEGER PRIMARY KEY,
+            timestamp INTEGER NOT NULL,
+            command TEXT,
+            user TEXT,
+            name TEXT,
+            old_value TEXT,
+            old_alternate TEXT,
+            new_value TEXT,
+            new_alternate TEXT
+        );
+        ",
+    )?;
+
+    // Logs created before the process was recorded
+    let has_process = connection
+        .prepare("SELECT 1 FROM pragma_table_info('log') WHERE name = 'process'")?
+        .exists(())?;
+    if !has_process {
+        connection.execute("ALTER TABLE log ADD COLUMN process TEXT", ())?;
+    }
+
+    init_temp(connection)
+}
+
+/// Creates only the temporary `context` table & triggers, which works for read-only dbs as well
+pub fn init_temp(connection: &Connection) -> rusqlite::Result<()> {
+    connection.execute_batch(
+        "
+        CREATE TEMP TABLE IF NOT EXISTS context (
+            command TEXT,
+            user TEXT,
+            process TEXT
+        );
+
+        CREATE TEMP TRIGGER IF NOT EXISTS log_insert AFTER INSERT ON data
+        BEGIN
+            INSERT INTO log (timestamp, command, user, process, name, new_value, new_alternate)
+            VALUES (
+                CAST(strftime('%s', 'now') AS INTEGER),
+                (SELECT command FROM context),
+                (SELECT user FROM context),
+                (SELECT process FROM context),
+                NEW.name, NEW.value, NEW.alternate
+            );
+
+            -- The same format as `modifier()`
+            UPDATE data SET modified_by = (
+                SELECT user || IFNULL(' (' || process || ')', '') FROM context
+            ) WHERE id = NEW.id;
+        END;
+
+        CREATE TEMP TRIGGER IF NOT EXISTS log_update AFTER UPDATE OF name, value, alternate ON data
+        BEGIN
+            INSERT INTO log (
+                timestamp, command, user, process, name, old_value, old_alternate, new_value,
+                new_alternate
+            )
+            VALUES (
+                CAST(strftime('%s', 'now') AS INTEGER),
+                (SELECT command FROM context),
+                (SELECT user FROM context),
+                (SELECT process FROM context),
+                NEW.name, OLD.value, OLD.alternate, NEW.value, NEW.alternate
+            );
+
+            -- The same format as `modifier()`
+            UPDATE data SET modified_by = (
+                SELECT user || IFNULL(' (' || process || ')', '') FROM context
+            ) WHERE id = NEW.id;
+        END;
+
+        CREATE TEMP TRIGGER IF NOT EXISTS log_delete AFTER DELETE ON data
+        BEGIN
+            INSERT INTO log (timestamp, command, user, process, name, old_value, old_alternate)
+            VALUES (
+                CAST(strftime('%s', 'now') AS INTEGER),
+                (SELECT command FROM context),
+                (SELECT user FROM context),
+                (SELECT process FROM context),
+                OLD.name, OLD.value, OLD.alternate
+            );
+        END;
+        ",
+    )
+}
+
+/// Sets the command (and the current user & process) recorded for all following changes
+pub fn set_context(connection: &Connection, command: &str) -> rusqlite::Result<()> {
+    connection.execute("DELETE FROM context", ())?;
+    connection.execute(
+        "INSERT INTO context (command, user, process) VALUES (?, ?, ?)",
+        (command, user(), process()),
+    )?;
+
+    Ok(())
+}
+
+/// Returns the id of the most recent change, or `0` if the log is empty
+pub fn last_id(connection: &Connection) -> rusqlite::Result<i64> {
+    connection.query_row("SELECT COALESCE(MAX(id), 0) FROM log", [], |row| row.get(0))
+}
+
+/// Returns all changes made after the change with the given id
+pub fn since(connection: &Connection, id: i64) -> rusqlite::Result<Vec<LogEntry>> {
+    connection
+        .prepare(&format!(
+            "SELECT {} FROM log WHERE id > ? ORDER BY id",
+            LogEntry::COLUMNS
+        ))?
+        .query_map([id], LogEntry::from_row)?
+        .collect()
+}
+
+/// Returns the name of the user running config-store
+pub fn user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("LOGNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Returns the pid & name of the process which ran config-store, e.g. `1234 sh backup.sh`
+///
+/// This is usually the script using config-store. The name is the program & its first argument
+/// unless that's an option (so scripts are named by interpreter & file), without directories.
+/// It's only known on Linux, on other unix systems only the pid is returned & elsewhere `None`.
+pub fn process() -> Option<String> {
+    #[cfg(unix)]
+    {
+        let pid = std::os::unix::process::parent_id();
+        let command = std::fs::read(format!("/proc/{}/cmdline", pid))
+            .ok()
+            .map(|c| {
+                let c = String::from_utf8_lossy(&c);
+                let mut args = c.split('\0');
+                let file_name = |a: &str| a.rsplit('/').next().unwrap_or_default().to_string();
+
+                let mut name = file_name(args.next().unwrap_or_default());
+                if let Some(script) = args.next().filter(|a| !a.is_empty() && !a.starts_with('-')) {
+                    name = format!("{} {}", name, file_name(script));
+                }
+                name
+            })
+            .filter(|c| !c.is_empty());
+
+        Some(command.map_or(pid.to_string(), |c| format!("{} {}", pid, c)))
+    }
+
+    #[cfg(not(unix))]
+    None
+}
+
+/// Describes who is making changes, e.g. `alice (1234 sh backup.sh)`, see [user] & [process]
+pub fn modifier() -> String {
+    match process() {
+        Some(process) => format!("{} ({})", user(), process),
+        None => user(),
+    }
+}
+
+/// A single row of the audit log
+#[derive(Debug)]
+pub struct LogEntry {
+    pub timestamp: i64,
+    pub command: Option<String>,
+    pub user: Option<String>,
+    pub process: Option<String>,
+    pub name: Option<String>,
+    pub old_value: Option<String>,
+    pub old_alternate: Option<String>,
+    pub new_value: Option<String>,
+    pub new_alternate: Option<String>,
+}
+
+impl LogEntry {
+    /// The columns to select to be able to construct a [LogEntry] via [LogEntry::from_row]
+    pub const COLUMNS: &'static str =
+        "timestamp, command, user, name, old_value, old_alternate, new_value, new_alternate, process";
+
+    /// Constructs a [LogEntry] from a row selected with [LogEntry::COLUMNS]
+    pub fn from_row(row: &rusqlite::Row) -> rusqlite::Result<LogEntry> {
+        Ok(LogEntry {
+            timestamp: row.get(0)?,
+            command: row.get(1)?,
+            user: row.get(2)?,
+            name: row.get(3)?,
+            old_value: row.get(4)?,
+            old_alternate: row.get(5)?,
+            new_value: row.get(6)?,
+            new_alternate: row.get(7)?,
+            process: row.get(8)?,
+        })
+    }
+
+    /// A json object describing the change, used by `listen`
+    pub fn json(self) -> String {
+        let event = match (self.old_value.is_some(), self.new_value.is_some()) {
+            (false, _) => "create",
+            (true, false) => "delete",
+            (true, true) => "update",
+        };
+
+        format!(
+            r#"{{"event":"{}","timestamp":"{}","command":{},"user":{},"name":{},"old_value":{},"old_alternate":{},"new_value":{},"new_alternate":{},"process":{}}}"#,
+            event,
+            crate::time::format(self.timestamp),
+            json_string(self.command),
+            json_string(self.user),
+            json_string(self.name),
+            json_string(self.old_value),
+            json_string(self.old_alternate),
+            json_string(self.new_value),
+            json_string(self.new_alternate),
+            json_string(self.process)
+        )
+    }
+
+    /// Describes the change, e.g. `update theme: "dark" "light" -> "light" "dark"`
+    ///
+    /// If `color` is set, the name is highlighted & the old & new values are red & green.
+    pub fn summary(&self, color: bool) -> String {
+        let name = Style::Name.paint(self.name.as_deref().unwrap_or_default(), color);
+        let old = Style::Removed.paint(&pair(&self.old_value, &self.old_alternate), color);
+        let new = Style::Added.paint(&pair(&self.new_value, &self.new_alternate), color);
+
+        match (self.old_value.is_some(), self.new_value.is_some()) {
+            (false, _) => format!("create {}: {}", name, new),
+            (true, false) => format!("delete {}: {}", name, old),
+            (true, true) => format!("update {}: {} -> {}", name, old, new),
+        }
+    }
+}
+
+/// The json object printed with `--json`, the output of a command & the changes it made
+///
+/// The changes are objects like those of `listen` (see [LogEntry::json]), so `event` tells whether
+/// an entry was created, updated or deleted.
+pub fn json_report(output: &str, changes: Vec<LogEntry>, dry_run: bool) -> String {
+    format!(
+        r#"{{"output":{},"dry_run":{},"changes":[{}]}}"#,
+        json_string(Some(output.to_string())),
+        dry_run,
+        changes
+            .into_iter()
+            .map(LogEntry::json)
+            .collect::<Vec<String>>()
+            .join(",")
+    )
+}
+
+/// Formats a value & alternate pair, which is missing if the entry didn't exist
+fn pair(value: &Option<String>, alternate: &Option<String>) -> String {
+    match (value, alternate) {
+        (None, None) => "-".to_string(),
+        _ => format!(
+            "{:?} {:?}",
+            value.as_deref().unwrap_or_default(),
+            alternate.as_deref().unwrap_or_default()
+        ),
+    }
+}
+
+impl std::fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}{} {} {}: {} -> {}",
+            crate::time::format(self.timestamp),
+            self.user.as_deref().unwrap_or("unknown"),
+            self.process
+                .as_ref()
+                .map_or(String::new(), |p| format!(" ({})", p)),
+            self.command.as_deref().unwrap_or("unknown"),
+            self.name.as_deref().unwrap_or_default(),
+            pair(&self.old_value, &self.old_alternate),
+            pair(&self.new_value, &self.new_alternate)
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn json_reports() {
+        let store = crate::Store::open_in_memory().unwrap();
+        let connection = store.connection();
+        store.set("gone", Some("1"), None).unwrap();
+        let last = last_id(connection).unwrap();
+
+        store.set("theme", Some("dark"), None).unwrap();
+        store.set("theme", None, Some("light")).unwrap();
+        store.delete("gone").unwrap();
+
+        let report: serde_json::Value =
+            serde_json::from_str(&json_report("Ok", since(connection, last).unwrap(), false))
+                .unwrap();
+        assert_eq!(report["output"], "Ok");
+        let events = report["changes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| (c["event"].as_str().unwrap(), c["name"].as_str().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [("create", "theme"), ("update", "theme"), ("delete", "gone")]
+        );
+        assert_eq!(
+            report["changes"][0]["new_alternate"],
+            serde_json::Value::Null
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn modified_by() {
+        let store = crate::Store::open_in_memory().unwrap();
+        let connection = store.connection();
+        let modified_by = |name| store.get(name).unwrap().modified_by;
+
+        // Without a context nobody is known to have made the change
+        connection.execute("DELETE FROM context", ()).unwrap();
+        store.set("theme", Some("dark"), None).unwrap();
+        assert_eq!(modified_by("theme"), None);
+
+        set_context(connection, "config-store toggle theme").unwrap();
+        store.toggle("theme").unwrap();
+        store.set("font", Some("mono"), None).unwrap();
+        assert_eq!(modified_by("theme"), Some(modifier()));
+        assert_eq!(modified_by("font"), Some(modifier()));
+
+        let process = process().unwrap();
+        assert!(process.starts_with(&std::os::unix::process::parent_id().to_string()));
+        let mut changes = since(connection, 0).unwrap();
+        assert_eq!(changes[0].process, None);
+        assert_eq!(changes[1].process.as_deref(), Some(process.as_str()));
+        assert_eq!(changes[1].user, Some(user()));
+        assert!(changes[1].to_string().contains(&format!(
+            "{} ({}) config-store toggle theme",
+            user(),
+            process
+        )));
+        assert!(changes
+            .remove(1)
+            .json()
+            .contains(&format!(r#""process":"{}""#, process)));
+    }
+
+    #[test]
+    fn log_without_process() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE log (
+                    id INTEGER PRIMARY KEY, timestamp INTEGER NOT NULL, command TEXT, user TEXT,
+                    name TEXT, old_value TEXT, old_alternate TEXT, new_value TEXT, new_alternate TEXT
+                );
+                INSERT INTO log (timestamp, name, new_value) VALUES (1, 'theme', 'dark');",
+            )
+            .unwrap();
+
+        crate::schema::init(&connection).unwrap();
+        let changes = since(&connection, 0).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].process, None);
+        // Initializing again doesn't add the column twice
+        init(&connection).unwrap();
+    }
+}
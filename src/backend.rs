@@ -0,0 +1,296 @@
+//! Module containing the [Backend] trait, which abstracts over where entries are stored
+//!
+//! The sqlite db ([Store]) is the main backend & the only one supporting every command, since most
+//! of them are built on SQL. Other backends only store the entries themselves, so the command
+//! line only offers the basic commands for them, see [run].
+use clap::ValueEnum;
+
+use crate::{
+    cli::{Action, CheckArgs, DeleteArgs, GetArgs, ListArgs, SetArgs, ToggleArgs},
+    color::Style,
+    entry::Entry,
+    error::{Error, Result},
+    filter::Filter,
+    Store,
+};
+
+/// The available backends
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BackendKind {
+    /// A sqlite db, see [Store]
+    Sqlite,
+    /// A json file, see [JsonStore](crate::json_store::JsonStore)
+    Json,
+    /// A Redis server, see [RedisStore](crate::redis_store::RedisStore)
+    #[cfg(feature = "redis")]
+    Redis,
+}
+
+impl BackendKind {
+    /// Chooses the backend for a `--db-path` by its scheme or extension, defaulting to sqlite
+    pub fn detect(path: &str) -> BackendKind {
+        #[cfg(feature = "redis")]
+        if path.starts_with("redis://") || path.starts_with("rediss://") {
+            return BackendKind::Redis;
+        }
+
+        if path.ends_with(".json") {
+            BackendKind::Json
+        } else {
+            BackendKind::Sqlite
+        }
+    }
+}
+
+/// The basic operations every storage backend supports
+pub trait Backend {
+    /// Gets an [Entry] by its name
+    ///
+    /// Will return [Error::EntryNotFound] if the entry doesn't exist.
+    fn get(&self, name: &str) -> Result<Entry>;
+
+    /// Checks if an [Entry] with the name exists
+    fn exists(&self, name: &str) -> Result<bool>;
+
+    /// Creates or updates an [Entry], returning its new state
+    ///
+    /// `value` & `alternate` which are `None` are left unchanged for existing entries. New ones
+    /// get an empty value & no alternate.
+    fn set(&self, name: &str, value: Option<&str>, alternate: Option<&str>) -> Result<Entry>;
+
+    /// Toggles an [Entry]'s value & alternate returning the new value
+    fn toggle(&self, name: &str) -> Result<String>;
+
+    /// Deletes an [Entry], returning whether it existed
+    fn delete(&self, name: &str) -> Result<bool>;
+
+    /// Lists all entries matching the [Filter]
+    fn list(&self, filter: &Filter) -> Result<Vec<Entry>>;
+}
+
+impl Backend for Store {
+    fn get(&self, name: &str) -> Result<Entry> {
+        Store::get(self, name)
+    }
+
+    fn exists(&self, name: &str) -> Result<bool> {
+        Store::exists(self, name)
+    }
+
+    fn set(&self, name: &str, value: Option<&str>, alternate: Option<&str>) -> Result<Entry> {
+        Store::set(self, name, value, alternate)
+    }
+
+    fn toggle(&self, name: &str) -> Result<String> {
+        Store::toggle(self, name)
+    }
+
+    fn delete(&self, name: &str) -> Result<bool> {
+        Store::delete(self, name)
+    }
+
+    fn list(&self, filter: &Filter) -> Result<Vec<Entry>> {
+        Store::list(self, filter)
+    }
+}
+
+/// Runs a command against a backend other than the sqlite db
+///
+/// Only `get`, `set`, `toggle`, `enable`, `disable`, `delete`, `check` & `list` without options
+/// needing SQL are supported. The output is the same as for the sqlite db, highlighted if `color`
+/// is set. Will return [Error::Invalid] for everything else.
+pub fn run(backend: &dyn Backend, command: Action, color: bool) -> Result<String> {
+    match command {
+        Action::Get(GetArgs {
+            name,
+            value_only,
+            alternate_only,
+            json_format,
+            as_of: None,
+            nuon: false,
+            quote: None,
+            glob: false,
+            resolve: false,
+            copy: false,
+            as_type: None,
+            separator: None,
+            line: false,
+            pair: false,
+        }) => {
+            let entry = backend.get(&name)?;
+
+            Ok(if value_only {
+                entry.value
+            } else if alternate_only {
+                entry.alternate.unwrap_or_default()
+            } else if json_format {
+                entry.json()
+            } else {
+                match &entry.alternate {
+                    Some(alternate) => format!(
+                        "{} {}",
+                        Style::Value.paint(&entry.value, color),
+                        Style::Alternate.paint(alternate, color)
+                    ),
+                    None => Style::Value.paint(&entry.value, color),
+                }
+            })
+        }
+        Action::Set(SetArgs {
+            name: Some(name),
+            value,
+            alternate,
+            description: None,
+            default: None,
+            paste: false,
+            prompt: false,
+            prompt_hidden: false,
+            keyring: false,
+            change_only: false,
+            create_only: false,
+            if_value: None,
+            expr: None,
+            reset_at: None,
+            session: None,
+            json: None,
+            from_file: None,
+            ..
+        }) => {
+            backend.set(&name, value.as_deref(), alternate.as_deref())?;
+            Ok("Ok".to_string())
+        }
+        Action::Toggle(ToggleArgs {
+            mut names,
+            filter,
+            create: false,
+            if_value: None,
+            require_alternate: false,
+            ..
+        }) => {
+            if names.is_empty() {
+                names = backend.list(&filter)?.into_iter().map(|e| e.name).collect();
+            }
+
+            Ok(names
+                .iter()
+                .map(|name| backend.toggle(name))
+                .collect::<Result<Vec<String>>>()?
+                .join("\n"))
+        }
+        Action::Enable { names } => switch(backend, &names, true),
+        Action::Disable { names } => switch(backend, &names, false),
+        Action::Delete(DeleteArgs {
+            name: Some(name), ..
+        }) => {
+            backend.delete(&name)?;
+            Ok("Ok".to_string())
+        }
+        Action::Check(CheckArgs {
+            name,
+            glob: false,
+            count,
+            value: None,
+            matches: None,
+        }) => {
+            let exists = backend.exists(&name)?;
+            Ok(if count {
+                i32::from(exists).to_string()
+            } else {
+                exists.to_string()
+            })
+        }
+        Action::List(ListArgs {
+            json_format,
+            long,
+            ndjson: false,
+            quote: None,
+            nuon: false,
+            filter,
+            recent: None,
+            limit,
+            offset,
+            case_duplicates: false,
+        }) => Ok(backend
+            .list(&filter)?
+            .into_iter()
+            .skip(offset.unwrap_or_default())
+            .take(limit.unwrap_or(usize::MAX))
+            .map(|e| {
+                if json_format {
+                    e.json()
+                } else if long {
+                    e.long(color)
+                } else {
+                    e.plain(color)
+                }
+            })
+            .fold(String::new(), |acc, e| acc + e.as_str() + "\n")),
+        _ => Err(Error::Invalid(
+            "this command (or option) is only supported by the default store of sqlite dbs"
+                .to_string(),
+        )),
+    }
+}
+
+/// Helper function switching the entries `names` on (or off) like `enable`, see
+/// [crate::commands::switch_cmd]
+fn switch(backend: &dyn Backend, names: &[String], on: bool) -> Result<String> {
+    Ok(names
+        .iter()
+        .map(|name| {
+            if !backend.exists(name)? {
+                backend.set(name, Some(&on.to_string()), Some(&(!on).to_string()))?;
+            } else if backend.get(name)?.needs_toggle(on)? {
+                backend.toggle(name)?;
+            }
+            Ok(backend.get(name)?.value)
+        })
+        .collect::<Result<Vec<String>>>()?
+        .join("\n"))
+}
+
+#[cfg(test)]
+mod test {
+    use clap::Parser;
+
+    use super::*;
+    use crate::cli::Args;
+
+    #[test]
+    fn run_commands() {
+        let store = Store::open_in_memory().unwrap();
+        let run = |args: &[&str]| {
+            let args = Args::try_parse_from([&["config-store"], args].concat()).unwrap();
+            run(&store, args.command, false)
+        };
+
+        assert_eq!(
+            run(&["set", "theme", "-v", "dark", "-a", "light"]).unwrap(),
+            "Ok"
+        );
+        assert_eq!(run(&["toggle", "theme"]).unwrap(), "light");
+        assert_eq!(run(&["get", "theme"]).unwrap(), "light dark");
+        assert_eq!(run(&["check", "theme", "-c"]).unwrap(), "1");
+        assert_eq!(run(&["list", "-p", "th"]).unwrap().lines().count(), 1);
+        assert_eq!(run(&["delete", "theme"]).unwrap(), "Ok");
+        assert!(matches!(
+            run(&["get", "theme"]),
+            Err(Error::EntryNotFound { .. })
+        ));
+
+        assert!(matches!(
+            run(&["set", "a", "-D", "x"]),
+            Err(Error::Invalid(_))
+        ));
+        assert!(matches!(run(&["log"]), Err(Error::Invalid(_))));
+    }
+
+    #[test]
+    fn detect_backends() {
+        assert_eq!(
+            BackendKind::detect("/tmp/config-store.db"),
+            BackendKind::Sqlite
+        );
+        assert_eq!(BackendKind::detect("config.json"), BackendKind::Json);
+    }
+}
@@ -0,0 +1,336 @@
+//! Module containing the backups of the db
+//!
+//! Backups are complete copies of the db made with `VACUUM INTO`. They are stored in a directory
+//! next to the db (`<db path>.backups`) & named after the time they were made (with a counter
+//! appended for backups made in the same second).
+//!
+//! Destructive commands (see [Action::is_destructive](crate::cli::Action::is_destructive)) create
+//! a backup automatically before running.
+//!
+//! The backups can also be copied to another machine, see [crate::remote].
+//!
+//! Snapshots are backups made on request with a label. They are stored in `<db path>.snapshots`
+//! & never removed automatically.
+use std::path::{Path, PathBuf};
+
+use rusqlite::{types::Value, Connection, OpenFlags};
+
+use crate::{remote::Remote, settings, table_store, time, Error, Result};
+
+/// The tables copied back by [restore]
+///
+/// The `log` table is deliberately not restored, so the restore itself shows up in the audit log.
+/// The tables of the stores created with `--table` are restored as well.
+const TABLES: &[&str] = &[
+    "data", "bindings", "tags", "meta", "samples", "blobs", "presets", "aliases", "settings",
+];
+
+/// Returns the directory the backups of the db of `connection` are stored in
+///
+/// Returns `None` for in memory dbs, which can't be backed up.
+pub fn dir(connection: &Connection) -> Option<PathBuf> {
+    connection
+        .path()
+        .filter(|p| !p.is_empty())
+        .map(|p| dir_of(Path::new(p)))
+}
+
+/// Returns the directory the backups of the db at `path` are stored in, see [dir]
+pub fn dir_of(path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.backups", path.display()))
+}
+
+/// Creates a new backup & removes all but the `keep` most recent ones
+///
+/// Returns the path of the new backup, or `None` if `keep == 0` or the db is in memory.
+pub fn create(connection: &Connection, keep: usize) -> Result<Option<PathBuf>> {
+    let Some(dir) = dir(connection).filter(|_| keep > 0) else {
+        return Ok(None);
+    };
+    std::fs::create_dir_all(&dir)?;
+
+    let now = time::now();
+    let path = (0..)
+        .map(|n| match n {
+            0 => dir.join(format!("{}.db", now)),
+            n => dir.join(format!("{}-{}.db", now, n)),
+        })
+        .find(|p| !p.exists())
+        .unwrap();
+
+    connection.execute("VACUUM INTO ?", [path.to_string_lossy()])?;
+    tracing::info!("Created backup {}", path.display());
+
+    for old in list(&dir)?.iter().rev().skip(keep) {
+        std::fs::remove_file(old)?;
+    }
+
+    Ok(Some(path))
+}
+
+/// Copies the backups to the configured [Remote], returning it
+///
+/// Returns `None` if no remote is configured or there are no backups yet. Refuses to copy them
+/// for dbs others can write, see [crate::permissions::trusted].
+pub fn push(connection: &Connection) -> Result<Option<Remote>> {
+    let Some(destination) = settings::get(connection, settings::BACKUP_REMOTE)? else {
+        return Ok(None);
+    };
+    let Some(dir) = dir(connection).filter(|d| d.exists()) else {
+        return Ok(None);
+    };
+
+    // Anyone who can write the db could send the whole db anywhere
+    crate::permissions::trusted(connection, "copy the backups to the remote")?;
+
+    let remote = Remote::parse(&destination).map_err(Error::Invalid)?;
+    remote.push(&dir)?;
+
+    Ok(Some(remote))
+}
+
+/// Returns the directory the snapshots of the db of `connection` are stored in
+///
+/// Returns `None` for in memory dbs, which can't be snapshotted.
+pub fn snapshot_dir(connection: &Connection) -> Option<PathBuf> {
+    connection
+        .path()
+        .filter(|p| !p.is_empty())
+        .map(|p| PathBuf::from(format!("{}.snapshots", p)))
+}
+
+/// Creates a snapshot with the given label, returning its path
+///
+/// Will return [Error::EntryExists] if a snapshot with the label already exists & [Error::Invalid]
+/// if the label can't be used as a file name or the db is in memory.
+pub fn snapshot(connection: &Connection, label: &str) -> Result<PathBuf> {
+    let dir = snapshot_dir(connection)
+        .ok_or_else(|| Error::Invalid("in memory dbs can't be snapshotted".to_string()))?;
+
+    if label.is_empty() || label.starts_with('.') || label.contains(['/', '\\']) {
+        return Err(Error::Invalid(format!(
+            "invalid snapshot label '{}'",
+            label
+        )));
+    }
+
+    let path = dir.join(format!("{}.db", label));
+    if path.exists() {
+        return Err(Error::EntryExists);
+    }
+
+    std::fs::create_dir_all(&dir)?;
+    connection.execute("VACUUM INTO ?", [path.to_string_lossy()])?;
+    tracing::info!("Created snapshot {}", path.display());
+
+    Ok(path)
+}
+
+/// Returns the labels & creation times of all snapshots, oldest first
+pub fn snapshots(connection: &Connection) -> Result<Vec<(String, i64)>> {
+    let Some(dir) = snapshot_dir(connection).filter(|d| d.exists()) else {
+        return Ok(Vec::new());
+    };
+
+    let mut snapshots = Vec::new();
+    for file in std::fs::read_dir(dir)? {
+        let path = file?.path();
+
+        if let Some(label) = path
+            .file_stem()
+            .filter(|_| path.extension().is_some_and(|e| e == "db"))
+        {
+            let created = std::fs::metadata(&path)?
+                .modified()?
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs() as i64);
+            snapshots.push((label.to_string_lossy().into_owned(), created));
+        }
+    }
+    snapshots.sort_by(|a, b| (a.1, &a.0).cmp(&(b.1, &b.0)));
+
+    Ok(snapshots)
+}
+
+/// Returns the paths of all backups in `dir`, oldest first
+pub fn list(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = std::fs::read_dir(dir)?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<std::io::Result<Vec<PathBuf>>>()?;
+    backups.retain(|p| p.extension().is_some_and(|e| e == "db") && timestamp(p).is_some());
+    backups.sort_by_key(|p| {
+        let counter = p
+            .file_stem()
+            .and_then(|s| s.to_str()?.split_once('-')?.1.parse::<u32>().ok());
+        (timestamp(p), counter)
+    });
+
+    Ok(backups)
+}
+
+/// Returns when the backup at `path` was made (seconds since the unix epoch)
+pub fn timestamp(path: &Path) -> Option<i64> {
+    path.file_stem()?.to_str()?.split('-').next()?.parse().ok()
+}
+
+/// Replaces the contents of the db with the backup at `path`
+///
+/// The restore is done with regular statements in a transaction, so it is recorded in the audit
+/// log & works with `--dry-run`. Will return [Error::NotFound] if there is no backup at `path`.
+pub fn restore(connection: &Connection, path: &Path) -> Result<()> {
+    if !path.exists() {
+        return Err(Error::NotFound(format!("backup {}", path.display())));
+    }
+
+    let backup = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+    let mut tables: Vec<String> = TABLES.iter().map(|t| t.to_string()).collect();
+    for name in table_store::names(&backup)?
+        .into_iter()
+        .chain(table_store::names(connection)?)
+    {
+        let table = format!("{}{}", table_store::PREFIX, name);
+        if !tables.contains(&table) {
+            tables.push(table);
+        }
+    }
+
+    crate::store::transaction(connection, || {
+        // Frozen entries are replaced as well, they are frozen again if they are in the backup
+        connection.execute("UPDATE data SET frozen = NULL", ())?;
+
+        for table in &tables {
+            if table.starts_with(table_store::PREFIX) {
+                table_store::create(connection, table)?;
+            }
+
+            let columns = backup
+                .prepare("SELECT name FROM pragma_table_info(?)")?
+                .query_map([table], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+
+            connection.execute(&format!("DELETE FROM {}", table), ())?;
+
+            // Backups made before a table existed simply don't have it
+            if columns.is_empty() {
+                continue;
+            }
+
+            let mut insert = connection.prepare(&format!(
+                "INSERT INTO {} ({}) VALUES ({})",
+                table,
+                columns.join(", "),
+                vec!["?"; columns.len()].join(", ")
+            ))?;
+            let mut select =
+                backup.prepare(&format!("SELECT {} FROM {}", columns.join(", "), table))?;
+            let mut rows = select.query(())?;
+
+            while let Some(row) = rows.next()? {
+                let values = (0..columns.len())
+                    .map(|i| row.get::<_, Value>(i))
+                    .collect::<rusqlite::Result<Vec<Value>>>()?;
+                insert.execute(rusqlite::params_from_iter(values))?;
+            }
+        }
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn create_and_restore() {
+        let dir = std::env::temp_dir().join(format!("config-store-backup-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let store = crate::Store::open(dir.join("test.db")).unwrap();
+        let connection = store.connection();
+
+        store.set("a", Some("1"), Some("2")).unwrap();
+        let first = create(connection, 2).unwrap().unwrap();
+        store.set("a", Some("3"), None).unwrap();
+        store.set("b", Some("4"), None).unwrap();
+        let bound = dir.join("a.txt");
+        crate::mirror::bind(connection, "a", &bound, crate::mirror::Direction::ToFile).unwrap();
+        table_store::create(connection, "store_work").unwrap();
+        connection
+            .execute(
+                "INSERT INTO store_work (name, value, alternate) VALUES ('c', '5', '')",
+                (),
+            )
+            .unwrap();
+
+        create(connection, 2).unwrap();
+        create(connection, 2).unwrap();
+        let backups = list(&dir.join("test.db.backups")).unwrap();
+        assert_eq!(backups.len(), 2);
+        assert!(!backups.contains(&first));
+
+        store.delete("a").unwrap();
+        connection.execute("DELETE FROM store_work", ()).unwrap();
+        restore(connection, &backups[0]).unwrap();
+        assert_eq!(store.get("a").unwrap().value, "3");
+        // Replacing the entries mustn't lose their bindings
+        let bindings = crate::mirror::list(connection).unwrap();
+        assert_eq!(bindings.len(), 1);
+        assert_eq!(bindings[0].path, bound);
+        let value: String = connection
+            .query_row("SELECT value FROM store_work WHERE name = 'c'", (), |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(value, "5");
+
+        let path = snapshot(connection, "before").unwrap();
+        assert!(matches!(
+            snapshot(connection, "before"),
+            Err(Error::EntryExists)
+        ));
+        assert!(matches!(
+            snapshot(connection, "../escape"),
+            Err(Error::Invalid(_))
+        ));
+        store.set("a", Some("5"), None).unwrap();
+        restore(connection, &path).unwrap();
+        assert_eq!(store.get("a").unwrap().value, "3");
+        assert_eq!(crate::mirror::list(connection).unwrap().len(), 1);
+        assert_eq!(snapshots(connection).unwrap()[0].0, "before");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn push_untrusted() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir =
+            std::env::temp_dir().join(format!("config-store-backup-push-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("test.db");
+        let store = crate::Store::open(&db).unwrap();
+        let connection = store.connection();
+        let remote = dir.join("remote");
+        settings::set(
+            connection,
+            settings::BACKUP_REMOTE,
+            Some(&remote.to_string_lossy()),
+        )
+        .unwrap();
+        create(connection, 1).unwrap();
+
+        // A db others may write could choose where the whole db is sent
+        std::fs::set_permissions(&db, std::fs::Permissions::from_mode(0o666)).unwrap();
+        assert!(matches!(push(connection), Err(Error::Invalid(_))));
+        assert!(!remote.exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,81 @@
+//! Module storing larger binary artifacts in the db by their SHA-256 hash, see `blob`
+//!
+//! Blobs live in their own table, so the entries stay small. Entries reference a blob with the
+//! value `blob:HASH`, which `blob get` accepts as well. Storing the same data twice keeps a single
+//! copy & `blob gc` removes the blobs no entry references anymore.
+use rusqlite::{Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+
+use crate::{time, Error, Result};
+
+/// The prefix of values referencing a blob
+pub const PREFIX: &str = "blob:";
+
+/// Returns the hex encoded SHA-256 hash of `data`
+pub fn hash(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Stores `data` unless it's stored already & returns its hash
+pub fn put(connection: &Connection, data: &[u8]) -> Result<String> {
+    let hash = hash(data);
+
+    connection.execute(
+        "INSERT INTO blobs (hash, data, created_at) VALUES (?, ?, ?)
+            ON CONFLICT (hash) DO NOTHING",
+        (&hash, data, time::now()),
+    )?;
+
+    Ok(hash)
+}
+
+/// Returns the data of the blob with the hash `hash`, which may be a reference like `blob:HASH`
+///
+/// Will return [Error::NotFound] if there is no such blob.
+pub fn get(connection: &Connection, hash: &str) -> Result<Vec<u8>> {
+    let hash = hash.strip_prefix(PREFIX).unwrap_or(hash);
+
+    connection
+        .query_row("SELECT data FROM blobs WHERE hash = ?", [hash], |row| {
+            row.get(0)
+        })
+        .optional()?
+        .ok_or_else(|| Error::NotFound(format!("blob {}", hash)))
+}
+
+/// Deletes the blobs which no value, alternate or default references & returns their number
+pub fn gc(connection: &Connection) -> Result<usize> {
+    Ok(connection.execute(
+        "DELETE FROM blobs WHERE ?1 || hash NOT IN (
+            SELECT value FROM data UNION SELECT alternate FROM data WHERE alternate IS NOT NULL
+            UNION SELECT default_value FROM data WHERE default_value IS NOT NULL
+        )",
+        [PREFIX],
+    )?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn blobs() {
+        let store = crate::Store::open_in_memory().unwrap();
+        let connection = store.connection();
+
+        let hash = put(connection, b"\x00artifact").unwrap();
+        assert_eq!(hash.len(), 64);
+        assert_eq!(put(connection, b"\x00artifact").unwrap(), hash);
+        assert_eq!(get(connection, &hash).unwrap(), b"\x00artifact");
+
+        let reference = format!("{}{}", PREFIX, hash);
+        store.set("build", Some(&reference), None).unwrap();
+        put(connection, b"unused").unwrap();
+        assert_eq!(gc(connection).unwrap(), 1);
+        assert_eq!(get(connection, &reference).unwrap(), b"\x00artifact");
+        assert!(matches!(get(connection, "0123"), Err(Error::NotFound(_))));
+    }
+}
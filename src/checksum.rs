@@ -0,0 +1,110 @@
+//! Module keeping a SHA-256 checksum of every value, so `verify` can detect changes made outside of
+//! config-store
+//!
+//! Checksums are opt-in (`verify --checksums on`). While they are on, temporary triggers of the
+//! connection store the checksum of each new value in the `checksum` column. Other programs (e.g.
+//! the `sqlite3` shell) don't have these triggers, so their edits & a corrupted db leave values
+//! whose checksum doesn't match.
+use rusqlite::{functions::FunctionFlags, Connection};
+
+use crate::{blob::hash, settings, Filter, Result};
+
+/// Registers the `sha256` SQL function & creates the triggers updating the checksums
+pub fn init(connection: &Connection) -> rusqlite::Result<()> {
+    connection.create_scalar_function(
+        "sha256",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |context| {
+            Ok(context
+                .get::<Option<String>>(0)?
+                .map(|v| hash(v.as_bytes())))
+        },
+    )?;
+
+    connection.execute_batch(&format!(
+        "
+        CREATE TEMP TRIGGER IF NOT EXISTS checksum_insert AFTER INSERT ON data
+        WHEN EXISTS (SELECT 1 FROM settings WHERE key = '{0}')
+        BEGIN
+            UPDATE data SET checksum = sha256(NEW.value) WHERE id = NEW.id;
+        END;
+
+        CREATE TEMP TRIGGER IF NOT EXISTS checksum_update AFTER UPDATE OF value ON data
+        WHEN EXISTS (SELECT 1 FROM settings WHERE key = '{0}')
+        BEGIN
+            UPDATE data SET checksum = sha256(NEW.value) WHERE id = NEW.id;
+        END;",
+        settings::CHECKSUMS
+    ))
+}
+
+/// Turns checksums on, computing them for all values, or off, removing them
+pub fn enable(connection: &Connection, on: bool) -> Result<()> {
+    settings::set(connection, settings::CHECKSUMS, on.then_some("on"))?;
+    connection.execute(
+        "UPDATE data SET checksum = CASE WHEN ? THEN sha256(value) END",
+        [on],
+    )?;
+
+    Ok(())
+}
+
+/// Whether checksums are on
+pub fn enabled(connection: &Connection) -> Result<bool> {
+    Ok(settings::get(connection, settings::CHECKSUMS)?.is_some())
+}
+
+/// Returns the names of the entries matching `filter` whose value doesn't match its checksum, as
+/// well as the number of checked entries
+///
+/// Entries without a checksum were created outside of config-store & don't match either.
+pub fn verify(connection: &Connection, filter: &Filter) -> Result<(Vec<String>, usize)> {
+    let (clause, params) = filter.sql();
+    let mut statement = connection.prepare(&format!(
+        "SELECT name, value, checksum FROM data {} ORDER BY name",
+        clause
+    ))?;
+    let mut rows = statement.query(rusqlite::params_from_iter(params))?;
+
+    let (mut mismatched, mut checked) = (Vec::new(), 0);
+    while let Some(row) = rows.next()? {
+        let value: String = row.get(1)?;
+        if row.get::<_, Option<String>>(2)? != Some(hash(value.as_bytes())) {
+            mismatched.push(row.get(0)?);
+        }
+        checked += 1;
+    }
+
+    Ok((mismatched, checked))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn checksums() {
+        let store = crate::Store::open_in_memory().unwrap();
+        let connection = store.connection();
+        store.set("a", Some("1"), None).unwrap();
+
+        enable(connection, true).unwrap();
+        store.set("b", Some("2"), None).unwrap();
+        store.set("a", Some("3"), None).unwrap();
+        assert_eq!(verify(connection, &Filter::default()).unwrap(), (vec![], 2));
+
+        // Without the triggers, like the `sqlite3` shell
+        connection
+            .execute_batch("DROP TRIGGER temp.checksum_update")
+            .unwrap();
+        store.set("b", Some("4"), None).unwrap();
+        assert_eq!(
+            verify(connection, &Filter::default()).unwrap(),
+            (vec!["b".to_string()], 2)
+        );
+
+        enable(connection, false).unwrap();
+        assert!(!enabled(connection).unwrap());
+    }
+}
@@ -0,0 +1,1771 @@
+//! Module containing the command line interface definition
+use clap::{Parser, Subcommand};
+use clap_complete::ArgValueCompleter;
+
+use crate::{
+    backend::BackendKind, commands, filter::Filter, permissions, remote::Remote, time,
+    transform::Substitution,
+};
+
+/// The exit codes shown by `--help`, see [crate::Error::exit_code]
+const EXIT_CODES: &str = "\
+Exit codes:
+  0  Success
+  1  Any other failure, e.g. a corrupt db
+  2  The entry (or preset, snapshot, ...) doesn't exist
+  3  Invalid input or an invalid command line
+  4  Try again later: a timeout, a locked db or a reached rate limit
+  5  An entry with that name already exists
+  6  The entry is frozen or protected, or the command wasn't confirmed
+  7  A condition like `--if-value` wasn't met
+  8  A file other than the db can't be accessed";
+
+/// Struct containing all command line options
+/// For more information, see [clap documentation](https://docs.rs/clap/latest/clap/index.html)
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    about = "config-store is a simple key-value store designed for use from shell scripts",
+    author,
+    after_help = EXIT_CODES
+)]
+pub struct Args {
+    /// What you want to do
+    #[command(subcommand)]
+    pub command: Action,
+    /// Set an alternate path for the db
+    #[arg(long, default_value_t = default_db_path())]
+    pub db_path: String,
+    /// How to store the entries (chosen by the extension of the db path by default, e.g. `.json`)
+    ///
+    /// Backends other than sqlite only support `get`, `set`, `toggle`, `delete`, `check` & `list`.
+    #[arg(long, value_enum)]
+    pub backend: Option<BackendKind>,
+    /// Use a separate store kept in its own table of the sqlite db (see `tables`)
+    ///
+    /// Like other backends, these only support `get`, `set`, `toggle`, `delete`, `check` & `list`.
+    #[arg(long, value_name = "NAME")]
+    pub table: Option<String>,
+    /// Read entries missing from the db from this db, e.g. machine-wide defaults (repeatable,
+    /// highest priority first)
+    ///
+    /// Changes always go to the db itself. Layers which don't exist are skipped.
+    #[arg(long = "layer", value_name = "PATH")]
+    pub layers: Vec<std::path::PathBuf>,
+    /// The permissions (in octal) a new db & its directories are created with
+    #[arg(long, value_name = "MODE", default_value = "600", value_parser = permissions::parse_mode)]
+    pub file_mode: u32,
+    /// How to trade durability for speed, e.g. on slow SD cards or a tmpfs
+    #[command(flatten, next_help_heading = "Tuning")]
+    pub tuning: crate::tuning::Tuning,
+    /// Only report the changes to entries a command would make without writing them
+    ///
+    /// Nothing outside the db is changed either, e.g. the keyring, bound files or tmux.
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+    /// Print a json object with the output of the command & the entries it created, updated or
+    /// deleted (with their old & new values), e.g. `config-store --json set theme -v dark`
+    ///
+    /// Has to be given before the command. Commands streaming their output (like `list`) aren't
+    /// affected. Errors are printed to stderr as json objects with the message, the exit code &
+    /// suggested entry names.
+    #[arg(long)]
+    pub json: bool,
+    /// Log the executed SQL, timings & the resolved db path to stderr (repeat for more detail)
+    #[arg(long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+    /// Don't ask for confirmation before destructive commands like `drop`
+    ///
+    /// Confirmation is only asked for when stdin is a terminal, so scripts don't need this.
+    #[arg(short, long, global = true, visible_alias = "no-input")]
+    pub yes: bool,
+    /// Fail with an error instead of doing nothing when deleting or removing something missing
+    #[arg(long, global = true)]
+    pub strict: bool,
+    /// When to color the output of e.g. `get`, `list` & `snapshot diff`
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        value_name = "WHEN",
+        default_value = "auto"
+    )]
+    pub color: crate::color::ColorChoice,
+    /// Give up on blocking operations after this long (e.g. `30`, `90s` or `5m`) & exit with code 4
+    ///
+    /// Covers waiting for a db locked by another process, `listen` & `wait-for`. Without it, a
+    /// locked db is waited for 5 seconds, while `listen` & `wait-for` wait forever.
+    #[arg(long, global = true, value_parser = crate::time::parse_duration)]
+    pub timeout: Option<i64>,
+    /// The number of automatic backups to keep (0 disables them)
+    #[arg(long, global = true, value_name = "N", default_value_t = 5)]
+    pub keep_backups: usize,
+    /// The file containing the key entries are signed with, see `sign`
+    ///
+    /// Changes are signed with it & reads fail for entries whose signature is missing or doesn't
+    /// match, e.g. because a process without the key changed them.
+    #[arg(long, global = true, value_name = "FILE")]
+    pub hmac_key: Option<std::path::PathBuf>,
+    /// Show a desktop notification for every change (entries tagged `notify` always show one)
+    #[cfg(feature = "notify")]
+    #[arg(long, global = true)]
+    pub notify: bool,
+}
+
+/// The default path of the db, `config-store.db` in the temporary directory of the platform
+///
+/// That's `/tmp` on Linux, `$TMPDIR` on macOS & `%TEMP%` on Windows. Debug builds use `test.db` in
+/// the current directory instead.
+fn default_db_path() -> String {
+    if cfg!(debug_assertions) {
+        return "test.db".to_string();
+    }
+
+    temp_db_path()
+}
+
+/// Helper function returning the path of `config-store.db` in the temporary directory
+fn temp_db_path() -> String {
+    std::env::temp_dir()
+        .join("config-store.db")
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Parses the separator of `get`, a single character or one of the escapes `\t`, `\n` & `\0`
+fn parse_separator(separator: &str) -> Result<char, String> {
+    let mut chars = separator.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some(c), None, _) => Ok(c),
+        (Some('\\'), Some('t'), None) => Ok('\t'),
+        (Some('\\'), Some('n'), None) => Ok('\n'),
+        (Some('\\'), Some('0'), None) => Ok('\0'),
+        _ => Err("expected a single character or \\t, \\n or \\0".to_string()),
+    }
+}
+
+/// The different (sub-)commands that are available
+#[derive(Debug, Subcommand)]
+pub enum Action {
+    /// Set / Change a value & it's alternate
+    Set(SetArgs),
+    /// Get a value & it's alternate
+    Get(GetArgs),
+    /// Toggle one or more entries between their value & alternate
+    Toggle(ToggleArgs),
+    /// Switch one or more boolean-style entries on, whether they are on already or not
+    ///
+    /// Entries whose alternate is on (`true`, `on`, `yes`, `1` or `enabled`) are toggled, missing
+    /// ones are created as `true` with the alternate `false`.
+    Enable {
+        /// The names of the entries to switch on
+        #[arg(required = true)]
+        names: Vec<String>,
+    },
+    /// Switch one or more boolean-style entries off, whether they are off already or not
+    ///
+    /// Entries whose alternate is off (`false`, `off`, `no`, `0` or `disabled`) are toggled,
+    /// missing ones are created as `false` with the alternate `true`.
+    Disable {
+        /// The names of the entries to switch off
+        #[arg(required = true)]
+        names: Vec<String>,
+    },
+    /// Update an entry's modification timestamp
+    Touch {
+        /// The name of the entry to touch
+        name: String,
+    },
+    /// Freeze an entry, so it can't be changed or deleted until it's unfrozen
+    Freeze {
+        /// The name of the entry to freeze
+        name: String,
+    },
+    /// Unfreeze a frozen entry
+    Unfreeze {
+        /// The name of the entry to unfreeze
+        name: String,
+    },
+    /// Protect an entry, so it can't be deleted or overwritten without `--force`
+    Protect {
+        /// The name of the entry to protect
+        name: String,
+    },
+    /// Remove the protection of an entry
+    Unprotect {
+        /// The name of the entry to unprotect
+        name: String,
+    },
+    /// Make the value of an entry the output of a shell command, which `get` runs
+    ///
+    /// Only entries made computed this way run commands, `list --long` shows which ones are.
+    Compute(ComputeArgs),
+    /// Reset an entry's value to its default
+    Reset {
+        /// The name of the entry to reset
+        name: String,
+    },
+    /// Reset all entries (matching a filter) which have a default
+    ResetAll {
+        #[command(flatten)]
+        filter: Filter,
+    },
+    /// Delete an entry, or all entries matching a filter
+    Delete(DeleteArgs),
+    /// Check if an entry exists, or has a given value
+    Check(CheckArgs),
+    /// List all entries
+    List(ListArgs),
+    /// Show the audit log of all changes
+    Log {
+        /// Only show changes to this entry
+        name: Option<String>,
+        /// Only show the N most recent changes
+        #[arg(short = 'n', long, value_name = "N")]
+        limit: Option<usize>,
+    },
+    /// Delete all entries <span style="color: red;">!! BE VERY CAREFUL WITH THIS !!</span>
+    #[command(about = "Delete all entries !! BE VERY CAREFUL WITH THIS !!")]
+    Drop {
+        /// Drop the entries even if some are protected
+        #[arg(long)]
+        force: bool,
+    },
+    /// Generate shell completions
+    Completions {
+        /// The shell to generate completions for (detected from `$SHELL` with `--install`)
+        #[arg(required_unless_present = "install")]
+        shell: Option<clap_complete::Shell>,
+        /// Write the completions to the usual location of the shell instead of printing them
+        #[arg(long)]
+        install: bool,
+    },
+    /// Print wrapper functions (`cs-get`, `cs-toggle` & `cs-env`) for the rc file of a shell
+    ///
+    /// E.g. `eval "$(config-store shell-init bash)"`. `cs-env` exports all entries as environment
+    /// variables like `exec`, cached until the db changes.
+    ShellInit(ShellInitArgs),
+    /// Create an alias resolving to an existing entry for get, toggle & check
+    Alias {
+        /// The name of the alias
+        alias: String,
+        /// The name of the entry the alias points to
+        target: String,
+    },
+    /// Remove an alias
+    Unalias {
+        /// The name of the alias
+        alias: String,
+    },
+    /// Save & apply named groups of entry values
+    Preset {
+        #[command(subcommand)]
+        action: PresetAction,
+    },
+    /// Manage the tags of entries
+    Tag {
+        #[command(subcommand)]
+        action: TagAction,
+    },
+    /// Manage free-form metadata of entries, e.g. presentation hints for status bars
+    Meta {
+        #[command(subcommand)]
+        action: MetaAction,
+    },
+    /// Generate a random value & store it
+    Generate(GenerateArgs),
+    /// Measure the throughput & latency of config-store against a temporary db
+    Bench {
+        /// The number of times each operation is run
+        #[arg(short = 'n', long, default_value_t = 1000)]
+        iterations: usize,
+        /// The number of times the binary is invoked to measure end-to-end latency
+        #[arg(short, long, default_value_t = 20)]
+        invocations: usize,
+    },
+    /// Remove entries sharing a name with another entry
+    Dedupe {
+        /// Which of the duplicates to keep
+        #[arg(short, long, value_enum, default_value_t = commands::DedupeStrategy::Newest)]
+        strategy: commands::DedupeStrategy,
+    },
+    /// Check the db for corruption & inconsistencies
+    Doctor {
+        /// Repair the problems found
+        #[arg(short, long)]
+        fix: bool,
+    },
+    /// Copy entries from another db
+    CopyFrom(CopyFromArgs),
+    /// Create or update many entries at once from json, e.g. the output of `list --ndjson`
+    ///
+    /// The input has one object like `{"name": "x", "value": "1", "alternate": "0"}` per line or
+    /// is an array of them. Everything is imported in one transaction.
+    Import(ImportArgs),
+    /// Save environment variables starting with a prefix as entries
+    ///
+    /// The entries are named after the variables without the prefix, e.g. `MYAPP_PORT` becomes
+    /// `PORT` (or `port` with `--lowercase`) for `--prefix MYAPP_`.
+    ImportEnv(ImportEnvArgs),
+    /// Create the entries declared in a manifest which don't exist yet
+    ///
+    /// Existing entries are left unchanged, so seeding again is safe. The manifest is a json file
+    /// like the input of `set --json` or a toml file with a table per entry, see
+    /// [crate::manifest].
+    Seed {
+        /// The manifest, a `.json` or `.toml` file
+        file: std::path::PathBuf,
+    },
+    /// Make the entries match a manifest, creating & updating the declared ones
+    ///
+    /// Fields the manifest doesn't declare are left unchanged. Use `--dry-run` to preview the
+    /// changes.
+    Apply {
+        /// The manifest, a `.json` or `.toml` file
+        file: std::path::PathBuf,
+        /// Delete the entries the manifest doesn't declare as well
+        #[arg(long)]
+        prune: bool,
+    },
+    /// Check the entries against the constraints declared in a manifest
+    ///
+    /// Fails listing every violation, e.g. for CI of dotfiles, if any entry doesn't match its
+    /// declaration or a required one is missing.
+    Validate {
+        /// The manifest, a `.json` or `.toml` file
+        file: std::path::PathBuf,
+    },
+    /// Rename all entries starting with a prefix
+    RenamePrefix {
+        /// The prefix to replace
+        old: String,
+        /// The prefix to replace it with
+        new: String,
+    },
+    /// Apply a regex substitution to the values of many entries at once
+    ///
+    /// Use `--dry-run` to preview the changes.
+    Replace {
+        /// The substitution to apply, e.g. `s/old-host/new-host/g`
+        #[arg(value_name = "s/REGEX/REPLACEMENT/FLAGS", value_parser = Substitution::parse)]
+        substitution: Substitution,
+        /// Only change entries whose name matches this glob pattern
+        #[arg(long = "in", value_name = "PATTERN")]
+        pattern: Option<String>,
+    },
+    /// Show the entries as a tree, splitting their names at `.`
+    Tree {
+        /// Collapse branches deeper than N levels
+        #[arg(short, long, value_name = "N")]
+        depth: Option<usize>,
+        #[command(flatten)]
+        filter: Filter,
+    },
+    /// Search the names, values & descriptions of entries, best matches first
+    ///
+    /// The query uses sqlite's FTS5 syntax, e.g. `dark OR light`, `"exact phrase"` or `vpn*`.
+    Fts(FtsArgs),
+    /// Print every change as a line of json until interrupted (or `--timeout` is reached)
+    Listen {
+        /// Only print changes to entries matching this glob pattern
+        pattern: Option<String>,
+        /// How often to check for changes in milliseconds
+        #[arg(short, long, default_value_t = 250)]
+        interval: u64,
+    },
+    /// Wait until an entry has the given value (see `--timeout`)
+    WaitFor(WaitForArgs),
+    /// Run a command each time an entry changes until interrupted (or `--timeout` is reached)
+    Watch(WatchArgs),
+    /// Print the state of an entry for a status bar like waybar or polybar
+    Status {
+        /// The name of the entry
+        name: String,
+        /// The format expected by the status bar
+        #[arg(short, long, value_enum, default_value_t = commands::StatusFormat::Waybar)]
+        format: commands::StatusFormat,
+    },
+    /// Run a read-only SQL statement against the db, e.g. `SELECT name, value FROM data`
+    ///
+    /// Only a single statement is run, on a read-only connection. Statements which would change
+    /// the db or attach others are refused.
+    Query {
+        /// The SQL statement
+        sql: String,
+        /// Print every row as a json object on its own line
+        #[arg(short, long = "json")]
+        json_format: bool,
+    },
+    /// Print a short segment for a shell prompt (e.g. `PS1` or a starship custom command)
+    ///
+    /// The segment is empty for entries which are off or don't exist, unless `--false-text` is
+    /// given. Colors are used unless `--color never` is given or `NO_COLOR` is set.
+    Prompt {
+        /// The name of the entry
+        name: String,
+        #[command(flatten)]
+        segment: crate::segment::Segment,
+    },
+    /// Export entries, e.g. to recreate them on another machine
+    Export(ExportArgs),
+    /// Run a command with the values of the entries as environment variables
+    ///
+    /// The variables are named after the entries in upper case, with characters other than
+    /// letters, digits & `_` replaced by `_`, e.g. `vpn.home` becomes `VPN_HOME`.
+    Exec(ExecArgs),
+    /// Mirror entries into universal variables of fish, so prompts & abbreviations see changes at
+    /// once
+    ///
+    /// The variables are named like those of `exec`, e.g. `vpn.home` becomes `VPN_HOME`.
+    FishSync(FishSyncArgs),
+    /// Mirror entries into the global environment of tmux, named like the variables of `exec`
+    ///
+    /// New panes get the current values, as do status line scripts with `--options`, e.g. as
+    /// `#{@theme}`.
+    TmuxSync(TmuxSyncArgs),
+    /// Print entries as `name: value` lines for dmenu / rofi & act on the selected line
+    ///
+    /// E.g. `config-store menu --toggle | rofi -dmenu | config-store menu --toggle --select`
+    Menu(MenuArgs),
+    /// Append a numeric reading to the history of an entry & make it the entry's value
+    ///
+    /// `record stats` & `record export` summarize & print the readings.
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    Record(RecordArgs),
+    /// Take a token from a rate limit, failing with exit code 4 if none is left
+    ///
+    /// Each name is a bucket holding up to `--max` tokens, which is refilled at `--max` tokens per
+    /// `--per`. Prints the number of tokens left.
+    Ratelimit(RatelimitArgs),
+    /// Measure how long something takes with a named stopwatch, e.g. for a pomodoro in a script
+    ///
+    /// The stopwatches are stored in the db, so they keep running between calls.
+    Timer {
+        #[command(subcommand)]
+        action: TimerAction,
+    },
+    /// Keep an entry & a file in sync, for programs which only read files
+    ///
+    /// Edited files are picked up whenever config-store opens the db & files are updated after
+    /// each command changing entries, so the file follows e.g. `toggle` at once. Use `--watch` to
+    /// pick up edits of files at once.
+    Bind(BindArgs),
+    /// List or end the sessions entries are bound to, see `set --session`
+    Session {
+        #[command(subcommand)]
+        action: SessionAction,
+    },
+    /// Store larger binary artifacts by their hash, which entries can reference as `blob:HASH`
+    Blob {
+        #[command(subcommand)]
+        action: BlobAction,
+    },
+    /// Create, list & restore backups of the db
+    Backup {
+        #[command(subcommand)]
+        action: BackupAction,
+    },
+    /// Create, list & restore labeled snapshots of the db
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotAction,
+    },
+    /// Rewrite the value of an entry in place & print the new value
+    Transform {
+        name: String,
+        #[command(flatten)]
+        transform: crate::transform::Transform,
+    },
+    /// Apply a JSON merge patch (RFC 7386) to the JSON value of an entry & print the new value
+    ///
+    /// Fields of the patch replace those of the value, nested objects are merged & fields set to
+    /// `null` are removed.
+    Patch {
+        /// The name of the entry to patch
+        name: String,
+        /// The patch, e.g. `{"nested": {"flag": true}}`, `-` reads it from stdin
+        patch: String,
+    },
+    /// Show or change the maximum number of entries
+    ///
+    /// Once the cap is exceeded, the least recently read or changed entries are deleted.
+    Cap {
+        /// The new maximum, prints the current one if not given
+        max: Option<usize>,
+        /// Remove the cap
+        #[arg(long, conflicts_with = "max")]
+        off: bool,
+    },
+    /// Show or change the git repository every change is committed to
+    ///
+    /// After each command changing entries, all entries are written to `config-store.sh` in the
+    /// repository & committed with the command line as the message.
+    Git {
+        /// The path of the repository, prints the current one if not given
+        repo: Option<std::path::PathBuf>,
+        /// Stop committing changes
+        #[arg(long, conflicts_with = "repo")]
+        off: bool,
+    },
+    /// Show or change the schema, a manifest declaring the known entries (see `validate`)
+    ///
+    /// `set` & `toggle` refuse values which break the declarations & `list --long` shows them.
+    Schema {
+        /// The manifest, prints the current one if not given
+        file: Option<std::path::PathBuf>,
+        /// Stop using a schema
+        #[arg(long, conflicts_with = "file")]
+        off: bool,
+    },
+    /// Show how often entries are read
+    Stats {
+        /// Show the reads & last read of every entry
+        #[arg(short, long)]
+        entries: bool,
+        /// Turn counting reads on or off
+        #[arg(long, value_name = "on|off", value_parser = clap::builder::BoolishValueParser::new())]
+        track: Option<bool>,
+    },
+    /// Turn signing entries with the key of `--hmac-key` on or off
+    ///
+    /// Turning it on signs all entries. Afterwards reads with the key refuse entries which were
+    /// changed without it.
+    Sign {
+        /// Stop signing entries & remove the signatures
+        #[arg(long)]
+        off: bool,
+    },
+    /// Check the values against their checksums, to detect corruption or edits by other programs
+    ///
+    /// Fails listing the entries whose value changed outside of config-store. Checksums have to be
+    /// turned on first with `--checksums on`.
+    Verify {
+        /// Only check entries matching this glob pattern
+        pattern: Option<String>,
+        /// Turn keeping checksums on or off
+        #[arg(
+            long,
+            value_name = "on|off",
+            value_parser = clap::builder::BoolishValueParser::new(),
+            conflicts_with = "pattern"
+        )]
+        checksums: Option<bool>,
+    },
+    /// Show whether names are case-insensitive, or turn that on or off
+    IgnoreCase {
+        #[arg(
+            value_name = "on|off",
+            value_parser = clap::builder::BoolishValueParser::new()
+        )]
+        on: Option<bool>,
+    },
+    /// Show or change the naming policy new entries have to follow
+    ///
+    /// Each option changes a single rule & keeps the others. Existing entries are never refused.
+    Naming(NamingArgs),
+    /// Show or change the maximum sizes of values & alternates
+    ///
+    /// Writes exceeding a limit fail, existing entries are kept even if they are larger.
+    Limits(LimitsArgs),
+    /// List the stores in the db, see `--table`
+    Tables,
+    /// Show information about the db & the binary
+    Info {
+        #[arg(short, long)]
+        json_format: bool,
+    },
+    /// Any other command `foo` runs the plugin `config-store-foo` from the PATH, see
+    /// [crate::plugin]
+    #[command(external_subcommand)]
+    External(Vec<String>),
+}
+
+/// The options of `set`
+#[derive(Debug, clap::Args)]
+pub struct SetArgs {
+    /// The name of the Entry
+    #[arg(required_unless_present_any = ["json", "from_file"])]
+    pub name: Option<String>,
+    /// The value
+    #[arg(short, long, add = ArgValueCompleter::new(crate::completion::values))]
+    pub value: Option<String>,
+    /// The alternate
+    #[arg(short, long, add = ArgValueCompleter::new(crate::completion::values))]
+    pub alternate: Option<String>,
+    /// A description of what the entry is used for
+    #[arg(short, long)]
+    pub description: Option<String>,
+    /// The value restored by `reset`
+    #[arg(short = 'D', long, add = ArgValueCompleter::new(crate::completion::values))]
+    pub default: Option<String>,
+    /// Take the value from the clipboard, so it isn't shown or stored in the shell history
+    #[arg(long, conflicts_with_all = ["value", "prompt", "prompt_hidden"])]
+    pub paste: bool,
+    /// Ask for the value instead, so it isn't stored in the shell history or shown by `ps`
+    #[arg(long, conflicts_with_all = ["value", "prompt_hidden"])]
+    pub prompt: bool,
+    /// Ask for the value without showing what is typed, e.g. for tokens
+    #[arg(long, conflicts_with = "value")]
+    pub prompt_hidden: bool,
+    /// Store the value in the keyring of the desktop instead of the db (needs `secret-tool`)
+    #[arg(long)]
+    pub keyring: bool,
+    /// Only change entries; don't create new ones
+    #[arg(short, long)]
+    pub change_only: bool,
+    /// Only create new entries; fail if the entry already exists
+    #[arg(short = 'C', long, conflicts_with = "change_only")]
+    pub create_only: bool,
+    /// Only change the entry if its value currently is CURRENT (exits with code 7 otherwise)
+    #[arg(short, long, value_name = "CURRENT", conflicts_with = "create_only")]
+    pub if_value: Option<String>,
+    /// Set the value to an arithmetic expression of the current value `x`, e.g. `x + 10`
+    #[arg(
+        short,
+        long,
+        conflicts_with_all = [
+            "value", "paste", "prompt", "prompt_hidden", "keyring", "alternate", "create_only",
+            "if_value"
+        ]
+    )]
+    pub expr: Option<String>,
+    /// The smallest result of `--expr`
+    #[arg(long, requires = "expr", allow_negative_numbers = true)]
+    pub min: Option<f64>,
+    /// The largest result of `--expr`
+    #[arg(long, requires = "expr", allow_negative_numbers = true)]
+    pub max: Option<f64>,
+    /// Reset the entry to its default (or alternate) at a local time like `22:00` or after a
+    /// duration like `2h`
+    #[arg(short, long, value_name = "TIME", value_parser = time::parse_at, conflicts_with = "expr")]
+    pub reset_at: Option<time::At>,
+    /// Bind the entry to the current login session, or to TOKEN, deleting it once that ends
+    ///
+    /// Entries of the login session are deleted after logging out, those of a token with
+    /// `session end TOKEN`.
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        num_args = 0..=1,
+        default_missing_value = "",
+        conflicts_with = "expr"
+    )]
+    pub session: Option<String>,
+    /// Set the entries described by a json object like `{"name": "x", "value": "1"}` (or an
+    /// array of them) instead, `-` reads it from stdin
+    ///
+    /// The fields are `name`, `value`, `alternate`, `description` & `default`. Fields which
+    /// are missing are treated like the missing options.
+    #[arg(
+        long,
+        value_name = "JSON",
+        conflicts_with_all = [
+            "name", "value", "paste", "prompt", "prompt_hidden", "keyring", "alternate",
+            "description", "default", "expr", "reset_at", "session"
+        ]
+    )]
+    pub json: Option<String>,
+    /// Set the entries of a file of `name=value[:alternate]` lines instead, `-` reads stdin
+    ///
+    /// All lines are set in a single transaction. Empty lines & lines starting with `#` are
+    /// skipped, `\:` is a `:` in the value.
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = [
+            "name", "value", "paste", "prompt", "prompt_hidden", "keyring", "alternate",
+            "description", "default", "expr", "reset_at", "session", "json"
+        ]
+    )]
+    pub from_file: Option<std::path::PathBuf>,
+}
+
+/// The options of `get`
+#[derive(Debug, clap::Args)]
+pub struct GetArgs {
+    /// The name of the entry to get
+    pub name: String,
+    /// Only get the value
+    #[arg(short, long, conflicts_with = "alternate_only")]
+    pub value_only: bool,
+    /// Only get the alternate
+    #[arg(short, long, conflicts_with = "value_only")]
+    pub alternate_only: bool,
+    /// Return the entire entry as a json object
+    #[arg(
+        short,
+        long,
+        conflicts_with = "value_only",
+        conflicts_with = "alternate_only"
+    )]
+    pub json_format: bool,
+    /// Get the value the entry had at a time like `2024-05-01 10:00` or `2h` (ago)
+    ///
+    /// The value is reconstructed from the audit log, see `log`.
+    #[arg(long, value_name = "TIME", conflicts_with = "json_format")]
+    pub as_of: Option<String>,
+    /// Return the entire entry as a nushell record, for `from nuon`
+    #[arg(
+        long,
+        conflicts_with_all = ["value_only", "alternate_only", "json_format", "as_of"]
+    )]
+    pub nuon: bool,
+    /// Quote the value & alternate, e.g. `shell` for use in shell scripts
+    #[arg(long, value_enum, conflicts_with_all = ["json_format", "nuon"])]
+    pub quote: Option<commands::Quote>,
+    /// Treat the name as a glob pattern (e.g. `vpn.*`) & print every matching entry, one per
+    /// line & prefixed by its name
+    #[arg(short, long, conflicts_with = "as_of")]
+    pub glob: bool,
+    /// Copy the value (or the alternate with `-a`) to the clipboard instead of printing it
+    #[arg(
+        long,
+        conflicts_with_all = ["json_format", "nuon", "quote", "glob", "as_of"]
+    )]
+    pub copy: bool,
+    /// Replace `{{name}}` placeholders in the value & alternate by the values of those entries
+    #[arg(short, long, conflicts_with_all = ["as_of", "glob"])]
+    pub resolve: bool,
+    /// Return the entry as a json object with the value as a TYPE (`string`, `bool`, `int`,
+    /// `float` or `json`) instead of a string, failing if it isn't one
+    ///
+    /// Without this, `--json-format` uses the type declared in the schema of the db (see
+    /// `schema`).
+    #[arg(
+        long = "as",
+        value_name = "TYPE",
+        value_parser = crate::manifest::ValueType::parse,
+        conflicts_with_all = ["value_only", "alternate_only", "nuon", "quote", "copy", "as_of"]
+    )]
+    pub as_type: Option<crate::manifest::ValueType>,
+    /// Separate the value & alternate (& the name with `--glob`) by SEP instead of a space,
+    /// e.g. `\t` for a tab or `\0` for NUL
+    #[arg(
+        short,
+        long,
+        value_name = "SEP",
+        value_parser = parse_separator,
+        conflicts_with_all = ["json_format", "nuon", "quote", "copy", "as_type"]
+    )]
+    pub separator: Option<char>,
+    /// Print each field on its own line, like `--separator '\n'`
+    #[arg(
+        short,
+        long,
+        conflicts_with_all = ["separator", "json_format", "nuon", "quote", "copy", "as_type"]
+    )]
+    pub line: bool,
+    /// Print `name=value` (& `name.alt=alternate`) lines, e.g. for logs or env files
+    ///
+    /// With `--quote` the values are quoted, e.g. for `eval`.
+    #[arg(
+        short,
+        long,
+        conflicts_with_all = [
+            "json_format", "nuon", "copy", "as_type", "as_of", "separator", "line"
+        ]
+    )]
+    pub pair: bool,
+}
+
+/// The options of `toggle`
+#[derive(Debug, clap::Args)]
+pub struct ToggleArgs {
+    /// The names of the entries to toggle
+    #[arg(required_unless_present = "filter", conflicts_with = "filter")]
+    pub names: Vec<String>,
+    /// Toggle every entry matching these criteria at once instead, e.g. `--tag gaming`
+    #[command(flatten)]
+    pub filter: Filter,
+    /// Create missing entries with the given value & alternate before toggling them
+    #[arg(short, long)]
+    pub create: bool,
+    /// The value of created entries (before toggling)
+    #[arg(short, long, requires = "create", default_value = "")]
+    pub value: String,
+    /// The alternate of created entries (before toggling)
+    #[arg(short, long, requires = "create", default_value = "")]
+    pub alternate: String,
+    /// Only toggle if the current value is CURRENT (exits with code 7 otherwise)
+    #[arg(short, long, value_name = "CURRENT")]
+    pub if_value: Option<String>,
+    /// Fail for entries whose alternate was never set instead of toggling to an empty value
+    #[arg(long)]
+    pub require_alternate: bool,
+}
+
+/// The options of `compute`
+#[derive(Debug, clap::Args)]
+pub struct ComputeArgs {
+    /// The name of the entry
+    pub name: String,
+    /// The shell command printing the value, e.g. `cat /sys/class/power_supply/BAT0/capacity`
+    #[arg(required_unless_present = "off")]
+    pub command: Option<String>,
+    /// Keep the output for this long (e.g. `30s`) instead of running the command on every read
+    #[arg(long, value_parser = crate::time::parse_duration)]
+    pub ttl: Option<i64>,
+    /// Make the entry a regular one again, keeping its stored value
+    #[arg(long, conflicts_with_all = ["command", "ttl"])]
+    pub off: bool,
+    /// Run the command as a Rhai script instead of a shell command
+    #[cfg(feature = "script")]
+    #[arg(long, conflicts_with = "off")]
+    pub script: bool,
+}
+
+/// The options of `delete`
+#[derive(Debug, clap::Args)]
+pub struct DeleteArgs {
+    /// The name of the entry to delete
+    #[arg(required_unless_present = "filter", conflicts_with = "filter")]
+    pub name: Option<String>,
+    #[command(flatten)]
+    pub filter: Filter,
+    /// Delete protected entries as well
+    #[arg(long)]
+    pub force: bool,
+}
+
+/// The options of `check`
+#[derive(Debug, clap::Args)]
+pub struct CheckArgs {
+    /// The name of the entry to check
+    pub name: String,
+    /// Treat the name as a glob pattern (e.g. `vpn.*`) matching any number of entries
+    #[arg(short, long)]
+    pub glob: bool,
+    /// Print the number of matching entries instead of whether there are any
+    #[arg(short, long)]
+    pub count: bool,
+    /// Check that the entry has this value instead (exits with code 7 otherwise)
+    #[arg(short, long, conflicts_with_all = ["glob", "count"])]
+    pub value: Option<String>,
+    /// Check that the value of the entry matches this glob pattern instead, e.g. `dark*`
+    #[arg(
+        short,
+        long,
+        value_name = "PATTERN",
+        conflicts_with_all = ["glob", "count", "value"]
+    )]
+    pub matches: Option<String>,
+}
+
+/// The options of `list`
+#[derive(Debug, clap::Args)]
+pub struct ListArgs {
+    #[arg(short, long)]
+    pub json_format: bool,
+    /// Include the timestamps & description of each entry
+    #[arg(short, long, conflicts_with = "json_format")]
+    pub long: bool,
+    /// Print one json object per line (the same as `--json-format`)
+    #[arg(long, conflicts_with_all = ["json_format", "long"])]
+    pub ndjson: bool,
+    /// Print the name, value & alternate of each entry quoted, e.g. `shell` for use in shell
+    /// scripts
+    #[arg(long, value_enum, conflicts_with_all = ["json_format", "long", "ndjson"])]
+    pub quote: Option<commands::Quote>,
+    /// Print the entries as a nushell table, for `from nuon`
+    #[arg(long, conflicts_with_all = ["json_format", "long", "ndjson", "quote"])]
+    pub nuon: bool,
+    #[command(flatten)]
+    pub filter: Filter,
+    /// Only list the N most recently changed entries
+    #[arg(short, long, value_name = "N", num_args = 0..=1, default_missing_value = "10")]
+    pub recent: Option<usize>,
+    /// Only list up to N entries
+    #[arg(short = 'n', long, value_name = "N", conflicts_with = "recent")]
+    pub limit: Option<usize>,
+    /// Skip the first N entries
+    #[arg(short, long, value_name = "N")]
+    pub offset: Option<usize>,
+    /// Only list the names of entries which differ in case alone, one group per line
+    #[arg(long, conflicts_with_all = ["json_format", "long", "ndjson", "quote", "nuon"])]
+    pub case_duplicates: bool,
+}
+
+/// The options of `shell-init`
+#[derive(Debug, clap::Args)]
+pub struct ShellInitArgs {
+    /// The shell to print the functions for
+    pub shell: commands::InitShell,
+    /// Prepended to the names of the variables exported by `cs-env`, e.g. `CS_`
+    #[arg(short, long, default_value = "")]
+    pub prefix: String,
+    /// Print the commands exporting the entries instead, which `cs-env` caches
+    #[arg(long)]
+    pub env: bool,
+}
+
+/// The options of `generate`
+#[derive(Debug, clap::Args)]
+pub struct GenerateArgs {
+    /// The name of the entry
+    pub name: String,
+    /// The kind of value to generate
+    #[arg(short, long, value_enum, default_value_t = commands::GenerateKind::Hex)]
+    pub kind: commands::GenerateKind,
+    /// The number of characters to generate (ignored for uuid)
+    #[arg(short, long, default_value_t = 32)]
+    pub length: usize,
+    /// Overwrite the value if the entry already exists
+    #[arg(short, long)]
+    pub force: bool,
+}
+
+/// The options of `copy-from`
+#[derive(Debug, clap::Args)]
+pub struct CopyFromArgs {
+    /// The path of the other db
+    pub path: std::path::PathBuf,
+    /// Only copy entries matching this glob pattern
+    pub pattern: Option<String>,
+    /// What to do with entries which already exist
+    #[arg(short, long, value_enum, default_value_t = commands::ConflictPolicy::Skip)]
+    pub on_conflict: commands::ConflictPolicy,
+    /// Overwrite protected entries as well
+    #[arg(long)]
+    pub force: bool,
+}
+
+/// The options of `import`
+#[derive(Debug, clap::Args)]
+pub struct ImportArgs {
+    /// The file to read, stdin if not given
+    pub input: Option<std::path::PathBuf>,
+    /// The format of the file
+    #[arg(short, long, value_enum, default_value_t = commands::ImportFormat::Json)]
+    pub format: commands::ImportFormat,
+    /// What to do with entries which already exist
+    #[arg(short, long, value_enum, default_value_t = commands::ConflictPolicy::Overwrite)]
+    pub on_conflict: commands::ConflictPolicy,
+    /// Don't wait for the data to reach the disk, much faster but unsafe if the system crashes
+    #[arg(long)]
+    pub no_fsync: bool,
+    /// Overwrite protected entries as well
+    #[arg(long)]
+    pub force: bool,
+    /// Replace the whole db with an archive made by `export --archive`
+    #[cfg(feature = "archive")]
+    #[arg(long, requires = "input", conflicts_with_all = ["format", "on_conflict", "no_fsync"])]
+    pub archive: bool,
+}
+
+/// The options of `import-env`
+#[derive(Debug, clap::Args)]
+pub struct ImportEnvArgs {
+    /// Only import variables whose names start with this, e.g. `MYAPP_`
+    #[arg(short, long)]
+    pub prefix: String,
+    /// Convert the names of the entries to lower case
+    #[arg(short, long)]
+    pub lowercase: bool,
+    /// Overwrite protected entries as well
+    #[arg(long)]
+    pub force: bool,
+}
+
+/// The options of `fts`
+#[derive(Debug, clap::Args)]
+pub struct FtsArgs {
+    /// What to search for
+    pub query: String,
+    #[arg(short, long)]
+    pub json_format: bool,
+    /// Only list up to N entries
+    #[arg(short = 'n', long, value_name = "N")]
+    pub limit: Option<usize>,
+}
+
+/// The options of `wait-for`
+#[derive(Debug, clap::Args)]
+pub struct WaitForArgs {
+    /// The name of the entry
+    pub name: String,
+    /// The value to wait for
+    pub value: String,
+    /// How often to check the value in milliseconds
+    #[arg(short, long, default_value_t = 250)]
+    pub interval: u64,
+}
+
+/// The options of `watch`
+#[derive(Debug, clap::Args)]
+pub struct WatchArgs {
+    /// The name of the entry
+    pub name: String,
+    /// The shell command to run, with the new value in `$CONFIG_STORE_VALUE`
+    ///
+    /// Without it the new value is printed instead.
+    #[arg(short, long, value_name = "CMD")]
+    pub exec: Option<String>,
+    /// A Rhai script to run instead of a shell command, with the new value in `value`
+    #[cfg(feature = "script")]
+    #[arg(long, conflicts_with = "exec")]
+    pub script: Option<String>,
+    /// How often to check the value in milliseconds
+    #[arg(short, long, default_value_t = 250)]
+    pub interval: u64,
+}
+
+/// The options of `export`
+#[derive(Debug, clap::Args)]
+pub struct ExportArgs {
+    /// The format to export in
+    #[arg(short, long, value_enum, default_value_t = commands::ExportFormat::Json)]
+    pub format: commands::ExportFormat,
+    /// Only export entries matching this glob pattern, like `--glob`
+    #[arg(conflicts_with = "glob")]
+    pub pattern: Option<String>,
+    #[command(flatten)]
+    pub filter: Filter,
+    /// Write the whole db, including the audit log, to a compressed archive (`.tar.zst`)
+    #[cfg(feature = "archive")]
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["format", "pattern", "filter"])]
+    pub archive: Option<std::path::PathBuf>,
+}
+
+/// The options of `exec`
+#[derive(Debug, clap::Args)]
+pub struct ExecArgs {
+    /// Only export entries matching this glob pattern
+    pub pattern: Option<String>,
+    /// Prepended to the names of the variables, e.g. `CS_`
+    #[arg(short, long, default_value = "")]
+    pub prefix: String,
+    /// The command to run & its arguments, after `--`
+    #[arg(last = true, required = true)]
+    pub command: Vec<String>,
+}
+
+/// The options of `fish-sync`
+#[derive(Debug, clap::Args)]
+pub struct FishSyncArgs {
+    /// Only mirror entries matching this glob pattern
+    pub pattern: Option<String>,
+    /// Prepended to the names of the variables, e.g. `CS_`
+    ///
+    /// Variables with the prefix whose entry doesn't match anymore are erased.
+    #[arg(short, long, default_value = "")]
+    pub prefix: String,
+    /// Copy the values of the variables back into the existing entries instead
+    #[arg(short, long)]
+    pub back: bool,
+    /// Keep mirroring changes until interrupted (or `--timeout` is reached)
+    #[arg(short, long, conflicts_with = "back")]
+    pub watch: bool,
+    /// How often to check for changes in milliseconds
+    #[arg(short, long, default_value_t = 250, requires = "watch")]
+    pub interval: u64,
+}
+
+/// The options of `tmux-sync`
+#[derive(Debug, clap::Args)]
+pub struct TmuxSyncArgs {
+    /// Only mirror entries matching this glob pattern
+    pub pattern: Option<String>,
+    /// Prepended to the names of the variables (or options), e.g. `CS_`
+    ///
+    /// Variables with the prefix whose entry doesn't match anymore are unset.
+    #[arg(short, long, default_value = "")]
+    pub prefix: String,
+    /// Set user options named `@` & the prefix & the name of the entry instead
+    #[arg(short, long)]
+    pub options: bool,
+    /// Keep mirroring changes until interrupted (or `--timeout` is reached)
+    #[arg(short, long)]
+    pub watch: bool,
+    /// How often to check for changes in milliseconds
+    #[arg(short, long, default_value_t = 250, requires = "watch")]
+    pub interval: u64,
+}
+
+/// The options of `menu`
+#[derive(Debug, clap::Args)]
+pub struct MenuArgs {
+    /// Only list entries with an alternate & toggle the selected entry
+    #[arg(long)]
+    pub toggle: bool,
+    /// Act on the selected line instead of listing entries, `-` (the default) reads it from stdin
+    ///
+    /// Without `--toggle` the value after `: ` becomes the new value of the entry, so a
+    /// changed line (e.g. typed into rofi) edits it.
+    #[arg(
+        short,
+        long,
+        value_name = "LINE",
+        num_args = 0..=1,
+        default_missing_value = "-"
+    )]
+    pub select: Option<String>,
+    #[command(flatten)]
+    pub filter: Filter,
+}
+
+/// The options of `record`
+#[derive(Debug, clap::Args)]
+pub struct RecordArgs {
+    #[command(subcommand)]
+    pub action: Option<RecordAction>,
+    /// The name of the entry, which is created if it doesn't exist
+    #[arg(required = true)]
+    pub name: Option<String>,
+    /// The reading, e.g. `87` or `-3.5`
+    #[arg(required = true, allow_negative_numbers = true)]
+    pub value: Option<String>,
+}
+
+/// The options of `ratelimit`
+#[derive(Debug, clap::Args)]
+pub struct RatelimitArgs {
+    /// The name of the rate limit, independent of the entries
+    pub name: String,
+    /// The number of calls allowed per interval
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max: u32,
+    /// The interval, e.g. `60s` or `1h`
+    #[arg(long, value_parser = crate::time::parse_duration)]
+    pub per: i64,
+}
+
+/// The options of `bind`
+#[derive(Debug, clap::Args)]
+pub struct BindArgs {
+    /// The name of the entry
+    #[arg(required_unless_present_any = ["list", "watch"])]
+    pub name: Option<String>,
+    /// The file to mirror the entry into, replaced atomically
+    #[arg(short, long, value_name = "PATH", required_unless_present_any = ["remove", "list", "watch"])]
+    pub file: Option<std::path::PathBuf>,
+    /// Which way changes are copied
+    #[arg(short, long, value_enum, default_value_t = crate::mirror::Direction::ToFile)]
+    pub direction: crate::mirror::Direction,
+    /// Remove the binding of the entry instead, keeping the file
+    #[arg(long, conflicts_with = "file")]
+    pub remove: bool,
+    /// List the bindings instead
+    #[arg(short, long, conflicts_with_all = ["name", "file", "remove"])]
+    pub list: bool,
+    /// Keep syncing all bindings until interrupted (or `--timeout` is reached)
+    #[arg(short, long, conflicts_with_all = ["name", "file", "remove", "list"])]
+    pub watch: bool,
+    /// How often to sync in milliseconds
+    #[arg(short, long, default_value_t = 250, requires = "watch")]
+    pub interval: u64,
+}
+
+/// The options of `naming`
+#[derive(Debug, clap::Args)]
+pub struct NamingArgs {
+    /// The characters allowed in names, like a regex character class, e.g. `a-z0-9._-`
+    #[arg(long)]
+    pub charset: Option<String>,
+    /// The maximum length of names in characters
+    #[arg(long, value_name = "N")]
+    pub max_length: Option<usize>,
+    /// A prefix names have to start with, may be given multiple times to allow any of them
+    #[arg(long)]
+    pub prefix: Vec<String>,
+    /// Remove all rules
+    #[arg(long, conflicts_with_all = ["charset", "max_length", "prefix"])]
+    pub off: bool,
+}
+
+/// The options of `limits`
+#[derive(Debug, clap::Args)]
+pub struct LimitsArgs {
+    /// The maximum size of values in bytes
+    #[arg(long, value_name = "BYTES")]
+    pub value: Option<usize>,
+    /// The maximum size of alternates in bytes
+    #[arg(long, value_name = "BYTES")]
+    pub alternate: Option<usize>,
+    /// Remove both limits
+    #[arg(long, conflicts_with_all = ["value", "alternate"])]
+    pub off: bool,
+}
+
+impl Action {
+    /// Whether the command writes its output directly instead of returning it
+    pub fn is_streaming(&self) -> bool {
+        match self {
+            #[cfg(feature = "archive")]
+            Action::Export(ExportArgs {
+                archive: Some(_), ..
+            }) => false,
+            Action::Listen { .. }
+            | Action::Blob {
+                action: BlobAction::Get { output: None, .. },
+            }
+            | Action::Watch(WatchArgs { .. })
+            | Action::List(ListArgs {
+                case_duplicates: false,
+                ..
+            })
+            | Action::Export(ExportArgs { .. }) => true,
+            _ => false,
+        }
+    }
+
+    /// The format `get` prints the entry in, [GetFormat::Plain] for other commands
+    ///
+    /// [GetFormat::Plain]: commands::GetFormat::Plain
+    pub fn get_format(&self, color: bool) -> commands::GetFormat {
+        use commands::GetFormat;
+
+        let Action::Get(GetArgs {
+            json_format,
+            as_type,
+            nuon,
+            quote,
+            separator,
+            line,
+            pair,
+            ..
+        }) = self
+        else {
+            return GetFormat::Plain {
+                color,
+                separator: ' ',
+            };
+        };
+
+        match (*json_format || as_type.is_some(), nuon, quote) {
+            (true, _, _) => GetFormat::Json(*as_type),
+            (_, true, _) => GetFormat::Nuon,
+            _ if *pair => GetFormat::Pair(*quote),
+            (_, _, Some(quote)) => GetFormat::Quoted(*quote),
+            _ => GetFormat::Plain {
+                color,
+                separator: if *line {
+                    '\n'
+                } else {
+                    separator.unwrap_or(' ')
+                },
+            },
+        }
+    }
+
+    /// Whether the command can delete or overwrite many entries at once
+    ///
+    /// A backup is created before running these, see [crate::backup].
+    pub fn is_destructive(&self) -> bool {
+        use commands::ConflictPolicy::{Newer, Overwrite};
+
+        match self {
+            Action::Drop { .. }
+            | Action::Apply { prune: true, .. }
+            | Action::ResetAll { .. }
+            | Action::Replace { .. }
+            | Action::RenamePrefix { .. }
+            | Action::ImportEnv(ImportEnvArgs { force: true, .. }) => true,
+            Action::Delete(DeleteArgs { name, .. }) => name.is_none(),
+            #[cfg(feature = "archive")]
+            Action::Import(ImportArgs { archive: true, .. }) => true,
+            Action::Import(ImportArgs { force, .. }) if *force => true,
+            Action::Import(ImportArgs {
+                on_conflict: Overwrite | Newer,
+                ..
+            })
+            | Action::CopyFrom(CopyFromArgs {
+                on_conflict: Overwrite | Newer,
+                ..
+            }) => true,
+            _ => false,
+        }
+    }
+
+    /// The question asked before running the action, if it's destructive enough to need
+    /// confirmation, see [crate::prompt]
+    pub fn confirmation(&self) -> Option<&'static str> {
+        use commands::ConflictPolicy::{Newer, Overwrite};
+
+        match self {
+            Action::Drop { .. } => Some("Drop all entries?"),
+            Action::Delete(DeleteArgs { name: None, .. }) => Some("Delete all matching entries?"),
+            Action::ResetAll { .. } => Some("Reset all matching entries to their defaults?"),
+            #[cfg(feature = "archive")]
+            Action::Import(ImportArgs { archive: true, .. }) => {
+                Some("Replace all entries with the archive?")
+            }
+            Action::Import(ImportArgs { force: true, .. })
+            | Action::ImportEnv(ImportEnvArgs { force: true, .. }) => {
+                Some("Overwrite protected entries?")
+            }
+            Action::Apply { prune: true, .. } => Some("Delete all entries not in the manifest?"),
+            Action::Import(ImportArgs {
+                on_conflict: Overwrite | Newer,
+                ..
+            })
+            | Action::CopyFrom(CopyFromArgs {
+                on_conflict: Overwrite | Newer,
+                ..
+            }) => Some("Overwrite existing entries?"),
+            Action::Replace { .. } => Some("Change the values of all matching entries?"),
+            Action::RenamePrefix { .. } => Some("Rename all entries with the prefix?"),
+            _ => None,
+        }
+    }
+}
+
+/// The actions available for managing backups
+#[derive(Debug, Subcommand)]
+pub enum BackupAction {
+    /// Create a backup now
+    Create,
+    /// List all backups, oldest first
+    List,
+    /// Replace all entries with those of a backup (e.g. to undo a drop)
+    Restore {
+        /// The backup to restore (defaults to the most recent one)
+        backup: Option<std::path::PathBuf>,
+    },
+    /// Copy the backups to the remote now
+    Push,
+    /// Show or change where backups are copied to after being created
+    ///
+    /// The remote mirrors the local backups, so old backups are removed there as well.
+    Remote {
+        /// A path for rsync like `host:dir/` or (with the `s3` feature) a bucket like
+        /// `s3://bucket/dir`
+        #[arg(value_parser = Remote::parse)]
+        remote: Option<Remote>,
+        /// Stop copying backups
+        #[arg(long, conflicts_with = "remote")]
+        off: bool,
+    },
+}
+
+/// The actions available for managing snapshots
+#[derive(Debug, Subcommand)]
+pub enum SnapshotAction {
+    /// Save the current state of the db
+    Create {
+        /// The name of the snapshot (defaults to the current time)
+        label: Option<String>,
+    },
+    /// List all snapshots, oldest first
+    List,
+    /// Replace all entries with those of a snapshot
+    Restore {
+        /// The name of the snapshot
+        label: String,
+    },
+    /// Show the entries created, deleted & updated between two snapshots
+    Diff {
+        /// The name of the older snapshot
+        from: String,
+        /// The name of the newer snapshot (defaults to the current state of the db)
+        to: Option<String>,
+        /// Print the changes as a json array
+        #[arg(short, long)]
+        json_format: bool,
+    },
+}
+
+/// The actions available for managing tags
+#[derive(Debug, Subcommand)]
+pub enum TagAction {
+    /// Add tags to an entry
+    Add {
+        /// The name of the entry
+        name: String,
+        /// The tags to add
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+    /// Remove tags from an entry
+    Remove {
+        /// The name of the entry
+        name: String,
+        /// The tags to remove
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+    /// List the tags of an entry, or all tags in use
+    List {
+        /// The name of the entry
+        name: Option<String>,
+    },
+}
+
+/// The actions available for managing the metadata of entries
+#[derive(Debug, Subcommand)]
+pub enum MetaAction {
+    /// Set a metadata attribute of an entry, replacing its previous value
+    Set {
+        /// The name of the entry
+        name: String,
+        /// The attribute, e.g. `color`
+        key: String,
+        /// The value of the attribute
+        value: String,
+    },
+    /// Print the value of a metadata attribute of an entry
+    Get {
+        /// The name of the entry
+        name: String,
+        /// The attribute
+        key: String,
+    },
+    /// Remove metadata attributes from an entry
+    Unset {
+        /// The name of the entry
+        name: String,
+        /// The attributes to remove
+        #[arg(required = true)]
+        keys: Vec<String>,
+    },
+    /// List the metadata of an entry as `key=value` lines
+    List {
+        /// The name of the entry
+        name: String,
+    },
+}
+
+/// The actions available for the readings of `record`
+#[derive(Debug, Subcommand)]
+pub enum RecordAction {
+    /// Print the number, minimum, maximum & average of the readings of an entry
+    Stats {
+        /// The name of the entry
+        name: String,
+        /// Only use the readings of this last period, e.g. `24h`
+        #[arg(long, value_name = "DURATION", value_parser = crate::time::parse_duration)]
+        last: Option<i64>,
+    },
+    /// Print the readings of an entry as CSV, oldest first
+    Export {
+        /// The name of the entry
+        name: String,
+        /// Only print the readings of this last period, e.g. `7d`
+        #[arg(long, value_name = "DURATION", value_parser = crate::time::parse_duration)]
+        last: Option<i64>,
+    },
+}
+
+/// The actions available for stopwatches, see `timer`
+#[derive(Debug, Subcommand)]
+pub enum TimerAction {
+    /// Start a stopwatch from zero, restarting it if it's already running
+    Start {
+        /// The name of the stopwatch, independent of the entries
+        name: String,
+    },
+    /// Stop a stopwatch & print how long it ran
+    Stop {
+        /// The name of the stopwatch
+        name: String,
+        /// Print the number of seconds instead of e.g. `25m3s`
+        #[arg(short, long)]
+        seconds: bool,
+    },
+    /// Print how long a stopwatch has run, until now if it's still running
+    Show {
+        /// The name of the stopwatch
+        name: String,
+        /// Print the number of seconds instead of e.g. `25m3s`
+        #[arg(short, long)]
+        seconds: bool,
+    },
+}
+
+/// The actions available for sessions, see [crate::session]
+#[derive(Debug, Subcommand)]
+pub enum SessionAction {
+    /// List the sessions with the number of their entries
+    List,
+    /// Delete the entries of a session
+    End {
+        /// The token of the session, the current login session if not given
+        token: Option<String>,
+    },
+}
+
+/// The actions available for blobs, see [crate::blob]
+#[derive(Debug, Subcommand)]
+pub enum BlobAction {
+    /// Store a file & print its hash
+    Put {
+        /// The file to store, `-` for stdin
+        file: std::path::PathBuf,
+        /// Also set this entry to reference the blob
+        #[arg(short, long)]
+        name: Option<String>,
+    },
+    /// Write a blob to a file or stdout
+    Get {
+        /// The hash of the blob, `blob:HASH` or the name of an entry referencing it
+        hash: String,
+        /// The file to write to instead of stdout
+        #[arg(short, long)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Delete the blobs no entry references anymore
+    Gc,
+}
+
+/// The actions available for managing presets
+#[derive(Debug, Subcommand)]
+pub enum PresetAction {
+    /// Save the current values of entries as a preset
+    Save {
+        /// The name of the preset
+        preset: String,
+        /// The names of the entries to save
+        #[arg(required_unless_present = "filter", conflicts_with = "filter")]
+        names: Vec<String>,
+        #[command(flatten)]
+        filter: Filter,
+    },
+    /// Apply a preset, setting all of its entries at once
+    Apply {
+        /// The name of the preset
+        preset: String,
+    },
+    /// List all presets
+    List,
+    /// Delete a preset
+    Delete {
+        /// The name of the preset
+        preset: String,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn destructive_actions() {
+        let action = |args: &[&str]| {
+            Args::try_parse_from([&["config-store"], args].concat())
+                .unwrap()
+                .command
+        };
+
+        for args in [
+            &["drop"][..],
+            &["delete", "-p", "vpn."],
+            &["reset-all"],
+            &["import", "in.json"],
+            &["import", "in.json", "--on-conflict", "newer"],
+            &["copy-from", "other.db", "--on-conflict", "overwrite"],
+            &["replace", "s/a/b/"],
+            &["rename-prefix", "a.", "b."],
+        ] {
+            let action = action(args);
+            assert!(action.is_destructive(), "{:?}", args);
+            assert!(action.confirmation().is_some(), "{:?}", args);
+        }
+
+        // Archives replace every entry, so they're backed up first, with or without `--force`
+        #[cfg(feature = "archive")]
+        for args in [
+            &["import", "--archive", "in.tar.zst"][..],
+            &["import", "--archive", "in.tar.zst", "--force"],
+        ] {
+            let action = action(args);
+            assert!(action.is_destructive(), "{:?}", args);
+            assert_eq!(
+                action.confirmation(),
+                Some("Replace all entries with the archive?")
+            );
+        }
+
+        for args in [
+            &["delete", "theme"][..],
+            &["import", "in.json", "--on-conflict", "skip"],
+            &["copy-from", "other.db"],
+            &["set", "theme", "-v", "dark"],
+        ] {
+            let action = action(args);
+            assert!(!action.is_destructive(), "{:?}", args);
+            assert!(action.confirmation().is_none(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn if_value() {
+        let parse = |args: &[&str]| Args::try_parse_from([&["config-store"], args].concat());
+
+        assert!(matches!(
+            parse(&["set", "lock", "-v", "b", "--if-value", "a"]).unwrap().command,
+            Action::Set(SetArgs { if_value: Some(current), .. }) if current == "a"
+        ));
+        assert!(parse(&["set", "lock", "-v", "b", "--if-value", "a", "-C"]).is_err());
+        assert!(parse(&["set", "lock", "-v", "b", "--if-value"]).is_err());
+    }
+
+    #[test]
+    fn default_db_paths() {
+        let args = Args::try_parse_from(["config-store", "list"]).unwrap();
+        assert_eq!(args.db_path, default_db_path());
+
+        // Joined with the separator of the platform
+        let path = temp_db_path();
+        let path = std::path::Path::new(&path);
+        assert_eq!(path.parent(), Some(std::env::temp_dir().as_path()));
+        assert_eq!(path.file_name().unwrap(), "config-store.db");
+
+        let args = Args::try_parse_from(["config-store", "--db-path", "other.db", "list"]).unwrap();
+        assert_eq!(args.db_path, "other.db");
+    }
+
+    #[test]
+    fn ndjson() {
+        let parse =
+            |args: &[&str]| Args::try_parse_from([&["config-store", "list"], args].concat());
+
+        assert!(matches!(
+            parse(&["--ndjson"]).unwrap().command,
+            Action::List(ListArgs { ndjson: true, .. })
+        ));
+        for other in ["--long", "--json-format", "--nuon", "--case-duplicates"] {
+            assert!(parse(&["--ndjson", other]).is_err(), "{}", other);
+        }
+    }
+
+    #[test]
+    fn timeout() {
+        let parse = |args: &[&str]| Args::try_parse_from([&["config-store"], args].concat());
+
+        assert_eq!(parse(&["list"]).unwrap().timeout, None);
+        assert_eq!(
+            parse(&["--timeout", "30", "list"]).unwrap().timeout,
+            Some(30)
+        );
+        // Global, so it may follow the command as well
+        let args = parse(&["wait-for", "theme", "dark", "--timeout", "5m"]).unwrap();
+        assert_eq!(args.timeout, Some(300));
+        assert!(parse(&["--timeout", "soon", "list"]).is_err());
+    }
+
+    #[test]
+    fn clipboard() {
+        let parse = |args: &[&str]| Args::try_parse_from([&["config-store"], args].concat());
+
+        assert!(matches!(
+            parse(&["get", "token", "--copy", "-a"]).unwrap().command,
+            Action::Get(GetArgs {
+                copy: true,
+                alternate_only: true,
+                ..
+            })
+        ));
+        for other in ["--json-format", "--nuon", "--glob"] {
+            assert!(
+                parse(&["get", "token", "--copy", other]).is_err(),
+                "{}",
+                other
+            );
+        }
+
+        assert!(matches!(
+            parse(&["set", "token", "--paste"]).unwrap().command,
+            Action::Set(SetArgs {
+                paste: true,
+                value: None,
+                ..
+            })
+        ));
+        for other in [&["-v", "x"][..], &["--prompt"], &["-e", "1 + 1"]] {
+            let args = [&["set", "token", "--paste"], other].concat();
+            assert!(parse(&args).is_err(), "{:?}", other);
+        }
+    }
+
+    #[test]
+    fn changed_since() {
+        let parse =
+            |args: &[&str]| Args::try_parse_from([&["config-store", "list"], args].concat());
+        let changed_since = |args: &[&str]| match parse(args).unwrap().command {
+            Action::List(ListArgs { filter, .. }) => filter.changed_since,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(
+            changed_since(&["--changed-since", "2024-05-01T10:00:00Z"]),
+            Some(1_714_557_600)
+        );
+        let ago = changed_since(&["--changed-since", "1h"]).unwrap();
+        assert!((crate::time::now() - 3600 - ago).abs() <= 1);
+        assert!(parse(&["--changed-since", "soon"]).is_err());
+    }
+
+    #[test]
+    fn separator() {
+        assert_eq!(parse_separator(","), Ok(','));
+        assert_eq!(parse_separator("\\"), Ok('\\'));
+        assert_eq!(parse_separator("\\t"), Ok('\t'));
+        assert_eq!(parse_separator("\\n"), Ok('\n'));
+        assert_eq!(parse_separator("\\0"), Ok('\0'));
+        for invalid in ["", "ab", "\\x", "\\tt"] {
+            assert!(parse_separator(invalid).is_err(), "{:?}", invalid);
+        }
+
+        let parse =
+            |args: &[&str]| Args::try_parse_from([&["config-store", "get", "a"], args].concat());
+        let separator = |args: &[&str]| match parse(args).unwrap().command.get_format(false) {
+            commands::GetFormat::Plain { separator, .. } => separator,
+            format => panic!("{:?}", format),
+        };
+        assert_eq!(separator(&[]), ' ');
+        assert_eq!(separator(&["--separator", "\\t"]), '\t');
+        assert_eq!(separator(&["--line"]), '\n');
+        for other in [
+            &["--separator", ","][..],
+            &["--json-format"],
+            &["--quote", "shell"],
+        ] {
+            let args = [&["--line"], other].concat();
+            assert!(parse(&args).is_err(), "{:?}", other);
+        }
+        assert!(parse(&["--separator", ",", "--nuon"]).is_err());
+    }
+
+    #[test]
+    fn pair() {
+        use commands::{GetFormat, Quote};
+
+        let parse =
+            |args: &[&str]| Args::try_parse_from([&["config-store", "get", "a"], args].concat());
+
+        assert!(matches!(
+            parse(&["--pair"]).unwrap().command.get_format(true),
+            GetFormat::Pair(None)
+        ));
+        assert!(matches!(
+            parse(&["--pair", "--quote", "shell"])
+                .unwrap()
+                .command
+                .get_format(true),
+            GetFormat::Pair(Some(Quote::Shell))
+        ));
+        for other in [
+            &["--json-format"][..],
+            &["--nuon"],
+            &["--line"],
+            &["--separator", ","],
+            &["--as-of", "1h"],
+        ] {
+            let args = [&["--pair"], other].concat();
+            assert!(parse(&args).is_err(), "{:?}", other);
+        }
+    }
+
+    #[test]
+    fn older_than() {
+        let parse =
+            |args: &[&str]| Args::try_parse_from([&["config-store", "delete"], args].concat());
+
+        assert!(matches!(
+            parse(&["--older-than", "7d"]).unwrap().command,
+            Action::Delete(DeleteArgs { name: None, filter, .. })
+                if filter.older_than == Some(7 * 86_400)
+        ));
+        assert!(parse(&["theme", "--older-than", "7d"]).is_err());
+        assert!(parse(&["--older-than", "soon"]).is_err());
+    }
+}
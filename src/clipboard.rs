@@ -0,0 +1,157 @@
+//! Module copying values to & pasting them from the system clipboard, see `get --copy` & `set
+//! --paste`
+//!
+//! The clipboard is accessed through the usual command line tools: `wl-copy` / `wl-paste` on
+//! Wayland, `xclip` on X11 & `pbcopy` / `pbpaste` on macOS. Without any of them values are copied
+//! with the OSC 52 escape sequence, which most terminals (also over SSH) understand. Values never
+//! show up in the terminal or in the arguments of other processes.
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use base64::Engine;
+
+use crate::{Error, Result};
+
+/// A clipboard tool, its arguments & the env var telling whether its display server is running
+type Tool = (&'static str, &'static [&'static str], Option<&'static str>);
+
+/// The tools tried for copying
+const COPY: &[Tool] = &[
+    ("wl-copy", &[], Some("WAYLAND_DISPLAY")),
+    ("xclip", &["-selection", "clipboard"], Some("DISPLAY")),
+    ("pbcopy", &[], None),
+];
+
+/// The tools tried for pasting, see [COPY]
+const PASTE: &[Tool] = &[
+    ("wl-paste", &["--no-newline"], Some("WAYLAND_DISPLAY")),
+    ("xclip", &["-selection", "clipboard", "-o"], Some("DISPLAY")),
+    ("pbpaste", &[], None),
+];
+
+/// Places `text` on the clipboard, falling back to OSC 52 if no clipboard tool is available
+pub fn copy(text: &str) -> Result<()> {
+    if copy_with(available(COPY), text)? {
+        return Ok(());
+    }
+
+    // The terminal reads the sequence, so it's written to it directly instead of to stdout
+    let mut terminal = std::fs::OpenOptions::new()
+        .write(true)
+        .open("/dev/tty")
+        .map_err(|_| Error::Invalid("no clipboard tool or terminal to copy to".to_string()))?;
+    write!(
+        terminal,
+        "\x1b]52;c;{}\x07",
+        base64::engine::general_purpose::STANDARD.encode(text)
+    )?;
+
+    Ok(())
+}
+
+/// Helper function piping `text` into the first of `tools` which succeeds, returning whether one
+/// did
+fn copy_with<'a>(tools: impl Iterator<Item = &'a Tool>, text: &str) -> Result<bool> {
+    for &(program, args, _) in tools {
+        let child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let Ok(mut child) = child else {
+            continue;
+        };
+
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(text.as_bytes())?;
+        if child.wait()?.success() {
+            return Ok(true);
+        }
+        tracing::debug!("{} failed to copy", program);
+    }
+
+    Ok(false)
+}
+
+/// Returns the contents of the clipboard
+///
+/// Will return [Error::Invalid] if no clipboard tool is available, since OSC 52 can't be read
+/// reliably.
+pub fn paste() -> Result<String> {
+    paste_with(available(PASTE))
+}
+
+/// Helper function returning the output of the first of `tools` which succeeds
+fn paste_with<'a>(tools: impl Iterator<Item = &'a Tool>) -> Result<String> {
+    for &(program, args, _) in tools {
+        let Ok(output) = Command::new(program)
+            .args(args)
+            .stderr(Stdio::null())
+            .output()
+        else {
+            continue;
+        };
+
+        if output.status.success() {
+            return String::from_utf8(output.stdout)
+                .map_err(|_| Error::Invalid("the clipboard doesn't contain text".to_string()));
+        }
+    }
+
+    Err(Error::Invalid(
+        "no clipboard tool found, install wl-clipboard or xclip".to_string(),
+    ))
+}
+
+/// Helper function returning the tools whose display server is running
+fn available(tools: &'static [Tool]) -> impl Iterator<Item = &'static Tool> {
+    tools
+        .iter()
+        .filter(|(_, _, env)| env.is_none_or(|env| std::env::var_os(env).is_some()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn copy_and_paste() {
+        // Each tool only succeeds if it's given the text it expects
+        let expecting: &[Tool] = &[
+            ("config-store-missing-tool", &[], None),
+            ("sh", &["-c", "test \"$(cat)\" = other"], None),
+            ("sh", &["-c", "test \"$(cat)\" = secret"], None),
+        ];
+        assert!(copy_with(expecting.iter(), "secret").unwrap());
+        assert!(!copy_with(expecting.iter(), "unexpected").unwrap());
+        assert!(!copy_with([].iter(), "secret").unwrap());
+
+        let pasting: &[Tool] = &[
+            ("config-store-missing-tool", &[], None),
+            ("false", &[], None),
+            ("printf", &["pasted"], None),
+        ];
+        assert_eq!(paste_with(pasting.iter()).unwrap(), "pasted");
+        assert!(matches!(paste_with([].iter()), Err(Error::Invalid(_))));
+        let binary: &[Tool] = &[("printf", &["\\377"], None)];
+        assert!(matches!(paste_with(binary.iter()), Err(Error::Invalid(_))));
+    }
+
+    #[test]
+    fn available_tools() {
+        let tools: &'static [Tool] = &[
+            ("unset", &[], Some("CONFIG_STORE_UNSET_DISPLAY")),
+            ("set", &[], Some("PATH")),
+            ("always", &[], None),
+        ];
+        let names: Vec<&str> = available(tools).map(|(program, _, _)| *program).collect();
+        assert_eq!(names, ["set", "always"]);
+    }
+}
@@ -0,0 +1,92 @@
+//! Module containing the [ColorChoice] & the [Style]s used to highlight human readable output
+//!
+//! Only output meant for people is colored (e.g. `list` without `--json`), never values printed
+//! on their own, which scripts read.
+use std::io::IsTerminal;
+
+use clap::ValueEnum;
+
+/// When to color the output, see `--color`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Color output to a terminal, unless the `NO_COLOR` env var is set
+    #[default]
+    Auto,
+    /// Always color the output, e.g. when piping it into `less -R`
+    Always,
+    /// Never color the output
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether output to stdout should be colored
+    ///
+    /// `NO_COLOR` is ignored if it's empty, see <https://no-color.org>.
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+                    && std::io::stdout().is_terminal()
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+
+    /// Whether output which is shown on a terminal later (e.g. a prompt) may be colored
+    ///
+    /// Unlike [ColorChoice::enabled] this doesn't depend on where stdout goes.
+    pub fn allowed(self) -> bool {
+        match self {
+            ColorChoice::Auto => std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// The highlighted parts of the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// The name of an entry (bold blue)
+    Name,
+    /// The value of an entry (green)
+    Value,
+    /// The alternate of an entry (dimmed)
+    Alternate,
+    /// What a change removed (red)
+    Removed,
+    /// What a change added (green)
+    Added,
+}
+
+impl Style {
+    /// Wraps `text` in the ANSI escapes of the style if `color` is set
+    pub fn paint(self, text: &str, color: bool) -> String {
+        if !color {
+            return text.to_string();
+        }
+
+        let code = match self {
+            Style::Name => "1;34",
+            Style::Value | Style::Added => "32",
+            Style::Alternate => "2",
+            Style::Removed => "31",
+        };
+
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn paint() {
+        assert_eq!(Style::Name.paint("theme", false), "theme");
+        assert_eq!(Style::Removed.paint("dark", true), "\x1b[31mdark\x1b[0m");
+        assert!(ColorChoice::Always.enabled());
+        assert!(!ColorChoice::Never.enabled());
+    }
+}
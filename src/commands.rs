@@ -1,298 +1,8665 @@
 //! Module containing all the functions called by different (sub-)commands
 //!
-//! Each function ending in `_cmd` is mapped to one [crate::Action] and is only to be used by that
-//! one [crate::Action].
+//! Each function ending in `_cmd` is mapped to one [crate::cli::Action] and is only to be used by
+//! that one [crate::cli::Action].
 //!
-use clap::CommandFactory;
-use rusqlite::Connection;
-use std::{fmt::Write, io::Cursor};
+use base64::Engine;
+use clap::{CommandFactory, ValueEnum};
+use rusqlite::{Connection, OptionalExtension};
+use std::fmt::Write;
 
-use crate::entry::Entry;
+use crate::{
+    audit::LogEntry,
+    blob, checksum,
+    color::Style,
+    entry::{json_escape, Entry},
+    filter::Filter,
+    layers, limits,
+    manifest::{Declaration, ValueType},
+    naming, settings,
+    store::{
+        self, evict, exists, insert, not_found, record_access, resolve, select, select_filtered,
+        set, toggle, transaction, DUPLICATE,
+    },
+    time,
+    transform::{Substitution, Transform},
+};
 
-/// A custom error type wrapping [rusqlite::Error]
-///
-/// Wraps [rusqlite::Error] and adds a more descriptive [Error::NoEntry] to make errors clearer to
-/// users.
-#[derive(Debug)]
-pub enum Error {
-    #[allow(dead_code)]
-    /// A wrapper around an [rusqlite::Error]
-    RusqliteError(rusqlite::Error),
-    /// Error for trying to operate on an [Entry] that doesn't exist
-    NoEntry,
+pub use crate::error::{Error, Result};
+
+/// The different kinds of values [generate_cmd] can create
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum GenerateKind {
+    /// A random (version 4) UUID
+    Uuid,
+    /// A lowercase hex string
+    Hex,
+    /// A url-safe base64 string without padding
+    Base64,
 }
 
-impl From<rusqlite::Error> for Error {
-    fn from(error: rusqlite::Error) -> Self {
-        match error {
-            rusqlite::Error::QueryReturnedNoRows => Error::NoEntry,
-            _ => Error::RusqliteError(error),
-        }
-    }
+/// Whether [set_cmd] may create and/or change entries
+#[derive(Debug, Clone)]
+pub enum SetMode {
+    /// Create the entry if it doesn't exist, otherwise change it
+    Upsert,
+    /// Only change existing entries
+    ChangeOnly,
+    /// Only create new entries
+    CreateOnly,
+    /// Only change an existing entry if its value currently equals the given one
+    IfValue(String),
 }
 
-pub type Result<T> = std::result::Result<T, Error>;
+/// What [watch_cmd] runs when the value changes
+#[derive(Debug, Clone)]
+pub enum Hook {
+    /// A shell command, run through `sh -c`
+    Shell(String),
+    /// A Rhai script, see [crate::store::run_script]
+    Script(String),
+}
 
-/// Helper function to get an [Entry] from the db
-///
-/// Since it uses [rusqlite::Connection::query_row] it will only ever return the first match.
-///
-/// Having multiple different entries with the same name is not supported.
-fn select(connection: &Connection, name: &str) -> Result<Entry> {
-    Ok(
-        connection.query_row("SELECT * FROM data WHERE name = ?", [name], |row| {
-            Ok(Entry {
-                _id: row.get(0)?,
-                name: row.get(1)?,
-                value: row.get(2)?,
-                alternate: row.get(3)?,
-            })
-        })?,
-    )
+/// The ways [dedupe_cmd] can resolve duplicate entries
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum DedupeStrategy {
+    /// Keep the most recently updated entry
+    Newest,
+    /// Keep the most recently updated entry, but take over the tags & metadata of the others &
+    /// fill in its description & default from them
+    Merge,
+    /// Ask which entry to keep for every duplicated name
+    Interactive,
 }
 
-/// Helper function to check if an [Entry] exists
-fn exists(connection: &Connection, name: &str) -> Result<bool> {
-    Ok(connection
-        .prepare("SELECT name FROM data WHERE name = ?")?
-        .exists([name])?)
+/// The formats [export_cmd] can write
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    /// A json array of all entries, like `list --json-format`
+    Json,
+    /// A shell script of `config-store` commands recreating the entries
+    Script,
+    /// `NAME=value` lines for systemd's `EnvironmentFile=` or `~/.config/environment.d/`
+    ///
+    /// The variables are named like those of `exec`, e.g. `vpn.home` becomes `VPN_HOME`.
+    SystemdEnv,
 }
 
-/// Helper function to create a new [Entry]
-fn new(connection: &Connection, name: String, value: String, alternate: String) -> Result<String> {
-    connection.execute(
-        "INSERT INTO data (name, value, alternate) VALUES (?1, ?2, ?3)",
-        [name, value, alternate],
-    )?;
+/// The formats [import_cmd] can read
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ImportFormat {
+    /// json objects like those of `set --json`, e.g. the output of `export` or `list --ndjson`
+    Json,
+    /// A git config file, whose keys become entries like `core.editor`
+    Gitconfig,
+    /// The output of `dconf dump /`, whose paths become dotted names
+    Dconf,
+}
+
+/// What [import_cmd] & [copy_from_cmd] do with entries which already exist
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ConflictPolicy {
+    /// Keep the existing entry
+    Skip,
+    /// Replace the existing entry's value, alternate, description & default
+    Overwrite,
+    /// Replace the existing entry only if the new one was changed after it, keep it otherwise
+    ///
+    /// New entries without an `updated_at` timestamp never replace existing ones.
+    Newer,
+    /// Copy nothing if any entry already exists
+    Fail,
+}
+
+/// The formats [list_cmd] can print entries in
+#[derive(Debug, Clone, Copy)]
+pub enum ListFormat {
+    /// The short debug like format of [Entry::plain], highlighted if `color` is set
+    Plain { color: bool },
+    /// The format of [Entry::long] including all metadata, highlighted if `color` is set
+    Long { color: bool },
+    /// One json object per line
+    Json,
+    /// The name, value & alternate of each entry quoted with [Quote]
+    Quoted(Quote),
+    /// A nushell table (a list of [Entry::nuon] records) in NUON
+    Nuon,
+}
+
+/// The formats [get_cmd] can print
+#[derive(Debug, Clone, Copy)]
+pub enum GetFormat {
+    /// The value and/or alternate separated by `separator`, highlighted if `color` is set
+    Plain { color: bool, separator: char },
+    /// The value and/or alternate quoted with [Quote]
+    Quoted(Quote),
+    /// `name=value` & `name.alt=alternate` lines, with the values quoted with [Quote] if given
+    Pair(Option<Quote>),
+    /// The format of [Entry::json], with the values as literals of the type (by default the one
+    /// declared in the schema of the db, see [Entry::json_with_meta])
+    Json(Option<ValueType>),
+    /// The format of [Entry::nuon]
+    Nuon,
+}
+
+/// How values are quoted for use in other programs, see `get --quote`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Quote {
+    /// Single quoted for a POSIX shell, safe to use with `eval`
+    Shell,
+}
+
+/// The shells [shell_init_cmd] prints functions for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum InitShell {
+    /// Functions for bash, e.g. in `~/.bashrc`
+    Bash,
+    /// The same functions for zsh, e.g. in `~/.zshrc`
+    Zsh,
+    /// Functions for fish (3.5 or newer), e.g. in `~/.config/fish/config.fish`
+    Fish,
+}
 
-    Ok(String::from("Ok"))
+/// The formats [status_cmd] can print
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum StatusFormat {
+    /// The json object expected by waybar's custom modules
+    Waybar,
+    /// Only the value, e.g. for polybar's script modules
+    Plain,
 }
 
 /// Check for the existence of an [Entry] in the db
 ///
-/// This is merely a wrapper around [exists], which is needed to convert from
-/// [`Result<bool>`] to [`Result<String>`].
-pub fn exists_cmd(connection: &Connection, name: String) -> Result<String> {
-    exists(connection, &name).map(|b| b.to_string())
+/// If `glob == true`, `name` is a glob pattern & any matching entry counts. If `count == true`
+/// the number of matching entries is returned instead of whether there are any.
+pub fn exists_cmd(
+    connection: &Connection,
+    name: String,
+    glob: bool,
+    count: bool,
+) -> Result<String> {
+    let matches: i64 = if glob {
+        connection.query_row(
+            "SELECT COUNT(*) FROM data WHERE name GLOB ?",
+            [name],
+            |row| row.get(0),
+        )?
+    } else {
+        connection.query_row(
+            "SELECT COUNT(*) FROM data WHERE name = ?",
+            [resolve(connection, &name)?],
+            |row| row.get(0),
+        )?
+    };
+
+    Ok(if count {
+        matches.to_string()
+    } else {
+        (matches > 0).to_string()
+    })
+}
+
+/// Checks that the current value of an [Entry] is `value` or matches the glob `pattern`
+///
+/// Computed & secret values are checked like `get` prints them. Will return [Error::Mismatch] if
+/// the value differs & [Error::EntryNotFound] if the entry doesn't exist, so scripts can branch on
+/// the exit code alone.
+pub fn check_value_cmd(
+    connection: &Connection,
+    name: String,
+    value: Option<String>,
+    pattern: Option<String>,
+) -> Result<String> {
+    let entry = select(connection, &resolve(connection, &name)?)?;
+    let entry = crate::keyring::reveal(connection, store::compute(connection, entry)?)?;
+
+    let matches = match (value, pattern) {
+        (Some(value), _) => entry.value == value,
+        (None, Some(pattern)) => {
+            connection.query_row("SELECT ? GLOB ?", (&entry.value, pattern), |row| row.get(0))?
+        }
+        (None, None) => true,
+    };
+
+    if matches {
+        Ok("true".to_string())
+    } else {
+        Err(Error::Mismatch)
+    }
 }
 
-/// Delete an [Entry] in the db
+/// Delete an [Entry] in the db, or all entries matching the [Filter] if no name is given
 ///
-/// If the entry doesn't exist, this will do nothing.
-pub fn delete_cmd(connection: &Connection, name: String) -> Result<String> {
-    connection.execute("DELETE FROM data WHERE name = ?", [name])?;
+/// If the entry doesn't exist, this will do nothing. Unless `force` is set, nothing is deleted if
+/// any of the entries is protected.
+pub fn delete_cmd(
+    connection: &Connection,
+    name: Option<String>,
+    filter: Filter,
+    strict: bool,
+    force: bool,
+) -> Result<String> {
+    let (clause, params) = match &name {
+        Some(name) => (
+            "WHERE name = ?".to_string(),
+            vec![store::canonical(connection, name)?.into()],
+        ),
+        None => filter.sql(),
+    };
+    if !force {
+        check_protected(connection, &clause, params.clone())?;
+    }
+
+    let deleted = connection
+        .prepare(&format!(
+            "DELETE FROM data {} RETURNING name, secret",
+            clause
+        ))?
+        .query_map(rusqlite::params_from_iter(params), |row| {
+            Ok((
+                row.get::<_, Option<String>>(0)?,
+                row.get::<_, Option<bool>>(1)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    for (name, _) in deleted.iter().filter(|(_, secret)| *secret == Some(true)) {
+        crate::keyring::forget(connection, name.as_deref().unwrap_or_default());
+    }
+    let deleted = deleted.len();
+
+    if strict && deleted == 0 {
+        return Err(match name {
+            Some(name) => not_found(connection, &name),
+            None => Error::NotFound("matching entries".to_string()),
+        });
+    }
 
     Ok("Ok".to_string())
 }
 
+/// Set the value of an [Entry] to `secret`, stored in the keyring instead of the db (see
+/// [crate::keyring])
+///
+/// The entry keeps an empty value in the db & is marked as secret, so `get` fetches the value from
+/// the keyring. The other fields & the `mode` work like for [set_cmd]. Setting a non-empty value
+/// without the keyring later makes the entry a regular one again.
+pub fn set_secret_cmd(
+    connection: &Connection,
+    name: String,
+    secret: Option<String>,
+    alternate: Option<String>,
+    description: Option<String>,
+    default: Option<String>,
+    mode: SetMode,
+) -> Result<String> {
+    let Some(secret) = secret else {
+        return Err(Error::Invalid(
+            "--keyring needs a value, e.g. with --paste".to_string(),
+        ));
+    };
+
+    transaction(connection, || {
+        set_cmd(
+            connection,
+            name.clone(),
+            Some(String::new()),
+            alternate,
+            description,
+            default,
+            mode,
+        )?;
+
+        let name = resolve(connection, &name)?;
+        connection.execute("UPDATE data SET secret = TRUE WHERE name = ?", [&name])?;
+        // Stored last, so the db is rolled back if the keyring isn't available
+        crate::keyring::store(connection, &name, &secret)?;
+
+        Ok("Ok".to_string())
+    })
+}
+
 /// Return a value (and/or) alternate from the db
+///
+/// See [GetFormat] for the formats the entry can be printed in. With `interpolate`, `{{name}}`
+/// placeholders are replaced by the values of the entries they reference.
 pub fn get_cmd(
     connection: &Connection,
     name: String,
     value_only: bool,
     alternate_only: bool,
-    json_format: bool,
+    format: GetFormat,
+    interpolate: bool,
 ) -> Result<String> {
-    let entry = select(connection, &name)?;
+    let entry = select(connection, &resolve(connection, &name)?)?;
+    let mut entry = crate::keyring::reveal(connection, store::compute(connection, entry)?)?;
+    if interpolate {
+        entry = store::interpolate(connection, entry)?;
+    }
+    record_access(connection, entry._id)?;
 
-    if value_only {
-        return Ok(entry.value);
+    Ok(match format {
+        GetFormat::Plain { .. } | GetFormat::Quoted(_) => format_values(
+            entry.value,
+            entry.alternate,
+            value_only,
+            alternate_only,
+            format,
+        ),
+        GetFormat::Pair(quote) => format_pairs(entry, value_only, alternate_only, quote),
+        GetFormat::Json(kind) => {
+            let kind = json_type(connection, &entry, kind, None)?;
+            let meta = metadata(connection, entry._id)?;
+            entry.json_with_meta(&meta, kind)
+        }
+        GetFormat::Nuon => entry.nuon(),
+    })
+}
+
+/// Copy the value (or the alternate) of an [Entry] to the clipboard, see [crate::clipboard]
+pub fn get_copy_cmd(connection: &Connection, name: String, alternate: bool) -> Result<String> {
+    let entry = select(connection, &resolve(connection, &name)?)?;
+    let entry = crate::keyring::reveal(connection, store::compute(connection, entry)?)?;
+    record_access(connection, entry._id)?;
+
+    crate::clipboard::copy(if alternate {
+        entry.alternate.as_deref().unwrap_or_default()
+    } else {
+        &entry.value
+    })?;
+
+    Ok("Ok".to_string())
+}
+
+/// Return the value (and/or) alternate of every [Entry] whose name matches the glob `pattern`
+///
+/// Each entry is printed on its own line, sorted by name. In the plain & quoted [GetFormat]s the
+/// line starts with the name, json objects & NUON records contain it anyway. Will return
+/// [Error::NotFound] if no entry matches.
+pub fn get_glob_cmd(
+    connection: &Connection,
+    pattern: String,
+    value_only: bool,
+    alternate_only: bool,
+    format: GetFormat,
+) -> Result<String> {
+    let entries = connection
+        .prepare(&format!(
+            "SELECT {} FROM {} WHERE name GLOB ? ORDER BY name",
+            Entry::COLUMNS,
+            layers::source(connection)?
+        ))?
+        .query_map([&pattern], Entry::from_row)?
+        .collect::<rusqlite::Result<Vec<Entry>>>()?;
+
+    if entries.is_empty() {
+        return Err(Error::NotFound(format!("entries matching '{}'", pattern)));
+    }
+
+    let declarations = match format {
+        GetFormat::Json(None) => crate::manifest::declared(connection)?,
+        _ => Vec::new(),
+    };
+    let lines = entries
+        .into_iter()
+        .map(|entry| {
+            record_access(connection, entry._id)?;
+
+            Ok(match format {
+                GetFormat::Plain { color, separator } => format!(
+                    "{}{}{}",
+                    Style::Name.paint(&entry.name, color),
+                    separator,
+                    format_values(
+                        entry.value,
+                        entry.alternate,
+                        value_only,
+                        alternate_only,
+                        format
+                    )
+                ),
+                GetFormat::Pair(quote) => format_pairs(entry, value_only, alternate_only, quote),
+                GetFormat::Quoted(_) => format!(
+                    "{} {}",
+                    shell_quote(&entry.name),
+                    format_values(
+                        entry.value,
+                        entry.alternate,
+                        value_only,
+                        alternate_only,
+                        format
+                    )
+                ),
+                GetFormat::Json(kind) => {
+                    let kind = json_type(connection, &entry, kind, Some(&declarations))?;
+                    let meta = metadata(connection, entry._id)?;
+                    entry.json_with_meta(&meta, kind)
+                }
+                GetFormat::Nuon => entry.nuon(),
+            })
+        })
+        .collect::<Result<Vec<String>>>()?;
+
+    Ok(match format {
+        GetFormat::Nuon => format!("[\n  {}\n]", lines.join(",\n  ")),
+        _ => lines.join("\n"),
+    })
+}
+
+/// Helper function returning the type the value of `entry` is printed as in json
+///
+/// This is `kind` if given, which `entry` has to be of, else the type declared for it in
+/// `declarations` (those of the db if `None`).
+fn json_type(
+    connection: &Connection,
+    entry: &Entry,
+    kind: Option<ValueType>,
+    declarations: Option<&[Declaration]>,
+) -> Result<Option<ValueType>> {
+    if let Some(kind) = kind {
+        kind.check(&entry.name, &entry.value)?;
+        return Ok(Some(kind));
+    }
+
+    let declared = |declarations: &[Declaration]| {
+        declarations
+            .iter()
+            .find(|d| d.name == entry.name)
+            .and_then(|d| d.kind)
+    };
+
+    Ok(match declarations {
+        Some(declarations) => declared(declarations),
+        None => declared(&crate::manifest::declared(connection)?),
+    })
+}
+
+/// Helper function formatting the value and/or alternate printed by `get`
+///
+/// `format` is [GetFormat::Plain] or [GetFormat::Quoted], the others print whole entries.
+fn format_values(
+    value: String,
+    alternate: Option<String>,
+    value_only: bool,
+    alternate_only: bool,
+    format: GetFormat,
+) -> String {
+    // Quoted values are meant for other programs & unambiguous with spaces
+    let (quote, color, separator) = match format {
+        GetFormat::Plain { color, separator } => (None, color, separator),
+        GetFormat::Quoted(quote) => (Some(quote), false, ' '),
+        GetFormat::Pair(quote) => (quote, false, ' '),
+        GetFormat::Json(_) | GetFormat::Nuon => (None, false, ' '),
+    };
+    let quote = |s: String| match quote {
+        Some(Quote::Shell) => shell_quote(&s),
+        None => s,
+    };
+
+    match alternate {
+        _ if value_only => quote(value),
+        alternate if alternate_only => quote(alternate.unwrap_or_default()),
+        // Without an alternate only the value is printed, so it can't be mistaken for an empty one
+        None => Style::Value.paint(&quote(value), color),
+        Some(alternate) => format!(
+            "{}{}{}",
+            Style::Value.paint(&quote(value), color),
+            separator,
+            Style::Alternate.paint(&quote(alternate), color)
+        ),
     }
+}
+
+/// Helper function formatting the value and/or alternate of `entry` as `name=value` &
+/// `name.alt=alternate` lines, see [GetFormat::Pair]
+fn format_pairs(
+    entry: Entry,
+    value_only: bool,
+    alternate_only: bool,
+    quote: Option<Quote>,
+) -> String {
+    let pair = |name: &str, value: &str| match quote {
+        Some(Quote::Shell) => format!("{}={}", name, shell_quote(value)),
+        None => format!("{}={}", name, value),
+    };
+    let alternate_name = format!("{}.alt", entry.name);
 
-    if alternate_only {
-        return Ok(entry.alternate);
+    match entry.alternate {
+        alternate if alternate_only => pair(&alternate_name, &alternate.unwrap_or_default()),
+        Some(alternate) if !value_only => format!(
+            "{}\n{}",
+            pair(&entry.name, &entry.value),
+            pair(&alternate_name, &alternate)
+        ),
+        _ => pair(&entry.name, &entry.value),
     }
+}
+
+/// Get the value & alternate an [Entry] had at the time `as_of`
+///
+/// The value is taken from the last change to the entry before that time (or the first one after
+/// it) in the audit log. Entries which haven't changed since the audit log was added simply have
+/// their current value. Will return [Error::EntryNotFound] if the entry didn't exist at that time.
+pub fn get_as_of_cmd(
+    connection: &Connection,
+    name: String,
+    as_of: String,
+    value_only: bool,
+    alternate_only: bool,
+    format: GetFormat,
+) -> Result<String> {
+    let timestamp = time::parse_datetime(connection, &as_of).map_err(Error::Invalid)?;
+    let name = resolve(connection, &name)?;
 
-    if json_format {
-        return Ok(entry.json());
+    // Past values come from the audit log, which isn't signed
+    if crate::signing::verified(connection)? {
+        return Err(Error::Invalid(
+            "past values can't be checked against the signing key".to_string(),
+        ));
     }
 
-    Ok(format!("{} {}", entry.value, entry.alternate))
+    let before = connection
+        .query_row(
+            "SELECT new_value, new_alternate FROM log WHERE name = ? AND timestamp <= ?
+                ORDER BY id DESC LIMIT 1",
+            (&name, timestamp),
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let after = connection
+        .query_row(
+            "SELECT old_value, old_alternate FROM log WHERE name = ? AND timestamp > ?
+                ORDER BY id LIMIT 1",
+            (&name, timestamp),
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+
+    let (value, alternate): (Option<String>, Option<String>) = match before.or(after) {
+        Some(state) => state,
+        None => {
+            let entry = select(connection, &name)?;
+            if entry.created_at.is_some_and(|c| c > timestamp) {
+                return Err(Error::entry_not_found(name));
+            }
+            (Some(entry.value), entry.alternate)
+        }
+    };
+
+    // A missing value means the entry was deleted (or not created yet) at that time
+    let value = value.ok_or_else(|| Error::entry_not_found(name))?;
+
+    Ok(format_values(
+        value,
+        alternate,
+        value_only,
+        alternate_only,
+        format,
+    ))
 }
 
-/// Creates a new (if not `change_only`) [Entry] in the db or update an existing one
+/// Creates a new [Entry] in the db or update an existing one, depending on the [SetMode]
 ///
-/// Will return [Error::NoEntry] if `change_only == true` and [exists] returns false (aka. the value doesn't exist).
+/// Any of `new_value`, `new_alternate`, `new_description` & `new_default` which are `None` are
+/// left unchanged for existing entries.
+///
+/// Will return [Error::EntryNotFound] for [SetMode::ChangeOnly] & [SetMode::IfValue] if the entry
+/// doesn't exist & [Error::EntryExists] for [SetMode::CreateOnly] if it does. Will return
+/// [Error::Mismatch] for [SetMode::IfValue] if the entry has a different value.
 pub fn set_cmd(
     connection: &Connection,
     name: String,
     new_value: Option<String>,
     new_alternate: Option<String>,
-    change_only: bool,
+    new_description: Option<String>,
+    new_default: Option<String>,
+    mode: SetMode,
 ) -> Result<String> {
-    if exists(connection, &name)? {
-        let entry = select(connection, &name)?;
+    let name = store::canonical(connection, &name)?;
+    crate::naming::check(connection, &name)?;
 
-        connection.execute(
-            "UPDATE data SET value = ?, alternate = ? WHERE name = ?",
-            [
-                new_value.unwrap_or(entry.value),
-                new_alternate.unwrap_or(entry.alternate),
-                name,
-            ],
-        )?;
+    transaction(connection, || {
+        // The conditions are part of the statements, so they can't change in between
+        match &mode {
+            SetMode::CreateOnly => {
+                layers::copy_up(connection, &name)?;
 
-        Ok("Ok".to_string())
-    } else if !change_only {
-        new(
-            connection,
-            name,
-            new_value.unwrap_or_default(),
-            new_alternate.unwrap_or_default(),
-        )
-    } else {
-        Err(Error::NoEntry)
-    }
+                let created = connection
+                    .prepare_cached(&format!(
+                        "{} WHERE NOT EXISTS (SELECT 1 FROM data WHERE name = ?1)",
+                        store::INSERT
+                    ))?
+                    .execute((
+                        &name,
+                        new_value,
+                        new_alternate,
+                        new_description,
+                        new_default,
+                        time::now(),
+                    ))?;
+                if created == 0 {
+                    return Err(Error::EntryExists);
+                }
+
+                evict(connection).map(|_| ())
+            }
+            SetMode::IfValue(current) => {
+                layers::copy_up(connection, &name)?;
+                let secret = crate::keyring::overwritten(connection, &name, new_value.as_deref())?;
+
+                let updated = connection
+                    .prepare_cached(&format!("{} AND value = ?7", store::UPDATE))?
+                    .execute((
+                        &name,
+                        new_value,
+                        new_alternate,
+                        new_description,
+                        new_default,
+                        time::now(),
+                        current,
+                    ))?;
+
+                match updated {
+                    0 if exists(connection, &name)? => Err(Error::Mismatch),
+                    0 => Err(not_found(connection, &name)),
+                    _ => {
+                        if secret {
+                            crate::keyring::forget(connection, &name);
+                        }
+                        Ok(())
+                    }
+                }
+            }
+            SetMode::Upsert | SetMode::ChangeOnly => set(
+                connection,
+                &name,
+                new_value,
+                new_alternate,
+                new_description,
+                new_default,
+                matches!(mode, SetMode::ChangeOnly),
+            ),
+        }?;
+
+        crate::manifest::check(connection, &crate::manifest::declared(connection)?, &name)
+    })?;
+
+    Ok("Ok".to_string())
 }
 
-/// Toggles an [Entry]'s value & alternate returning the new value
-pub fn toggle_cmd(connection: &Connection, name: String) -> Result<String> {
-    let entry = select(connection, &name)?;
+/// Creates or updates the entries described by `json` like [set_cmd]
+///
+/// `json` is an object with the fields `name`, `value`, `alternate`, `description` & `default` or
+/// an array of such objects, or `-` to read it from stdin (see [JsonEntry]). All entries are set
+/// in a single transaction, so nothing is changed if any of them fails.
+pub fn set_json_cmd(connection: &Connection, json: String, mode: SetMode) -> Result<String> {
+    use serde_json::Value;
 
-    connection.execute(
-        "UPDATE data SET value = ?, alternate = ? WHERE name = ?",
-        [entry.alternate.clone(), entry.value, entry.name],
-    )?;
+    let json = if json == "-" {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        json
+    };
+
+    let entries = match serde_json::from_str(&json) {
+        Ok(Value::Array(entries)) => entries,
+        Ok(entry @ Value::Object(_)) => vec![entry],
+        Ok(_) => {
+            return Err(Error::Invalid(
+                "expected a json object or an array of objects".to_string(),
+            ))
+        }
+        Err(error) => return Err(Error::Invalid(format!("invalid json: {}", error))),
+    };
+
+    transaction(connection, || {
+        for entry in entries {
+            let entry = JsonEntry::parse(entry)?;
+
+            set_cmd(
+                connection,
+                entry.name,
+                entry.value,
+                entry.alternate,
+                entry.description,
+                entry.default,
+                mode.clone(),
+            )?;
+        }
 
-    Ok(entry.alternate)
+        Ok("Ok".to_string())
+    })
 }
 
-/// Lists all entries in the db
-pub fn list_cmd(connection: &Connection, json: bool) -> Result<String> {
-    Ok(connection
-        .prepare("SELECT * FROM data")?
-        .query_map([], |row| {
-            Ok(Entry {
-                _id: row.get(0)?,
-                name: row.get(1)?,
-                value: row.get(2)?,
-                alternate: row.get(3)?,
-            })
-        })?
-        .fold(String::new(), |mut acc, e| {
-            let display_string = if json {
-                e.unwrap().json()
-            } else {
-                e.unwrap().to_string()
+/// Set the entries of a file (or stdin with `-`) of `name=value[:alternate]` lines in a single
+/// transaction, a lighter alternative to [set_json_cmd] for quick bulk loads
+///
+/// Empty lines & lines starting with `#` are skipped. The name is trimmed, the value & alternate
+/// are kept as they are. `\:` & `\\` are a literal `:` & `\` in the value & alternate.
+pub fn set_file_cmd(
+    connection: &Connection,
+    path: std::path::PathBuf,
+    mode: SetMode,
+) -> Result<String> {
+    let input = if path.as_os_str() == "-" {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        std::fs::read_to_string(path)?
+    };
+
+    let mut entries = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        let (name, rest) = line
+            .split_once('=')
+            .ok_or_else(|| Error::Invalid(format!("line {}: expected name=value", i + 1)))?;
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(Error::Invalid(format!("line {}: missing name", i + 1)));
+        }
+
+        let (mut value, mut alternate) = (String::new(), None::<String>);
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            let c = match c {
+                '\\' => chars.next().unwrap_or('\\'),
+                ':' if alternate.is_none() => {
+                    alternate = Some(String::new());
+                    continue;
+                }
+                c => c,
             };
+            alternate.as_mut().unwrap_or(&mut value).push(c);
+        }
 
-            writeln!(acc, "{}", display_string).unwrap();
-            acc
-        }))
+        entries.push((name.to_string(), value, alternate));
+    }
+
+    transaction(connection, || {
+        for (name, value, alternate) in entries {
+            set_cmd(
+                connection,
+                name,
+                Some(value),
+                alternate,
+                None,
+                None,
+                mode.clone(),
+            )?;
+        }
+
+        Ok("Ok".to_string())
+    })
 }
 
-/// Drops the `data` table deleting all entries.
+/// An entry given as json to `set --json` & `import`
 ///
-/// This won't actually delete the file on disk.
-pub fn drop_cmd(connection: &Connection) -> Result<String> {
-    connection.execute("DROP TABLE data", [])?;
-
-    Ok("Ok".to_string())
+/// Numbers & booleans are stored as text & `null` is treated like a missing field. The metadata
+/// printed by `list --json` (`_id`, the timestamps, `modified_by` & `meta`) is ignored, so its
+/// output can be used as input. Only `updated_at` is kept for [ConflictPolicy::Newer].
+struct JsonEntry {
+    name: String,
+    value: Option<String>,
+    alternate: Option<String>,
+    description: Option<String>,
+    default: Option<String>,
+    updated_at: Option<String>,
 }
 
-/// Generates shell the completion script
-pub fn completions_cmd(shell: clap_complete::Shell) -> String {
-    let mut cursor_vec: Vec<u8> = vec![];
-    let mut cursor = Cursor::new(&mut cursor_vec);
-
-    clap_complete::generate(
-        shell,
-        &mut crate::Args::command(),
-        crate::Args::command().get_name(),
-        &mut cursor,
-    );
+impl JsonEntry {
+    /// An entry only setting the value of `name`
+    fn value(name: String, value: String) -> JsonEntry {
+        JsonEntry {
+            name,
+            value: Some(value),
+            alternate: None,
+            description: None,
+            default: None,
+            updated_at: None,
+        }
+    }
+
+    /// Parses a json object, returning [Error::Invalid] for missing names & unknown fields
+    fn parse(entry: serde_json::Value) -> Result<JsonEntry> {
+        use serde_json::Value;
+
+        let Value::Object(mut fields) = entry else {
+            return Err(Error::Invalid(format!("expected an object, got {}", entry)));
+        };
+
+        let mut field = |field: &str| match fields.remove(field) {
+            None | Some(Value::Null) => Ok(None),
+            Some(Value::String(s)) => Ok(Some(s)),
+            Some(value @ (Value::Number(_) | Value::Bool(_))) => Ok(Some(value.to_string())),
+            Some(value) => Err(Error::Invalid(format!(
+                "`{}` has to be a string, got {}",
+                field, value
+            ))),
+        };
+
+        let entry = JsonEntry {
+            name: field("name")?
+                .ok_or_else(|| Error::Invalid("an entry is missing its `name`".to_string()))?,
+            value: field("value")?,
+            alternate: field("alternate")?,
+            description: field("description")?,
+            default: field("default")?,
+            updated_at: field("updated_at")?,
+        };
 
-    String::from_utf8(cursor.get_ref().to_vec()).expect("Failed to generate completion String.")
+        for metadata in ["_id", "created_at", "modified_by", "meta"] {
+            fields.remove(metadata);
+        }
+        if let Some(unknown) = fields.keys().next() {
+            return Err(Error::Invalid(format!("unknown field `{}`", unknown)));
+        }
+
+        Ok(entry)
+    }
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    fn create_db() -> Connection {
-        // WARN: Transactions do not work in an in memory db
-        let connection = Connection::open_in_memory().unwrap();
-        connection
-            .execute(
-                "
-            CREATE TABLE IF NOT EXISTS data (
-                id INTEGER PRIMARY KEY,
-                name TEXT,
-                value TEXT,
-                alternate TEXT
-            );",
-                (),
-            )
-            .expect("Failed to create values TABLE");
+/// Creates or updates many entries at once, e.g. to provision a machine
+///
+/// `input` is read from stdin if it's `None`. In the json [ImportFormat] it contains json objects
+/// like those of [set_json_cmd], one per line as printed by `list --ndjson`, or a json array of
+/// them. Other formats only set values. See [ConflictPolicy] for what happens to entries which
+/// already exist, protected ones are only overwritten if `force` is set.
+///
+/// Everything is written in a single transaction with cached statements. With `no_fsync` sqlite
+/// doesn't wait for the data to reach the disk, which is much faster but may corrupt the db if
+/// the system crashes during the import. Returns the number of created & updated entries & the
+/// entries which already existed. Will return [Error::EntryExists] (and import nothing) for
+/// [ConflictPolicy::Fail] if any entry already exists.
+pub fn import_cmd(
+    connection: &Connection,
+    input: Option<std::path::PathBuf>,
+    format: ImportFormat,
+    policy: ConflictPolicy,
+    no_fsync: bool,
+    force: bool,
+) -> Result<String> {
+    use serde_json::Value;
 
-        connection
+    let reader: Box<dyn std::io::Read> = match input {
+        Some(path) => Box::new(std::fs::File::open(path)?),
+        None => Box::new(std::io::stdin().lock()),
+    };
+
+    // The level can't be changed inside a transaction, e.g. the one of `--dry-run`
+    if no_fsync && connection.is_autocommit() {
+        connection.pragma_update(None, "synchronous", "OFF")?;
     }
 
-    #[test]
-    fn insert_and_drop() {
-        let connection = create_db();
+    transaction(connection, || {
+        let mut update = connection.prepare_cached(store::UPDATE)?;
+        let mut insert = connection.prepare_cached(store::INSERT)?;
+        let mut existing = connection
+            .prepare_cached("SELECT protected IS TRUE, updated_at FROM data WHERE name = ?")?;
 
-        new(
-            &connection,
-            "test1".to_string(),
-            "value1".to_string(),
-            "alternate1".to_string(),
-        )
-        .unwrap();
+        let (mut created, mut updated, mut refused) = (0, 0, Vec::new());
+        let mut conflicts = Vec::new();
+        let now = time::now();
 
-        assert_eq!(
-            list_cmd(&connection, false).unwrap(),
-            format!(
-                "{:?}\n",
-                Entry {
-                    _id: 1,
-                    name: "test1".to_string(),
-                    value: "value1".to_string(),
-                    alternate: "alternate1".to_string(),
+        let mut import = |entry: JsonEntry| -> Result<()> {
+            let current = existing
+                .query_row([&entry.name], |row| {
+                    Ok((row.get::<_, bool>(0)?, row.get::<_, Option<i64>>(1)?))
+                })
+                .optional()?;
+
+            if let Some((protected, updated_at)) = current {
+                let replace = match policy {
+                    ConflictPolicy::Skip => false,
+                    ConflictPolicy::Overwrite => true,
+                    ConflictPolicy::Newer => match &entry.updated_at {
+                        Some(changed) => {
+                            Some(
+                                time::parse_datetime(connection, changed)
+                                    .map_err(Error::Invalid)?,
+                            ) > updated_at
+                        }
+                        None => false,
+                    },
+                    ConflictPolicy::Fail => return Err(Error::EntryExists),
+                };
+
+                if !replace {
+                    let outcome = match policy {
+                        ConflictPolicy::Skip => "skipped",
+                        _ => "kept",
+                    };
+                    conflicts.push((entry.name, outcome));
+                    return Ok(());
                 }
-            )
-        );
+                if protected && !force {
+                    refused.push(entry.name);
+                    return Ok(());
+                }
+                conflicts.push((entry.name.clone(), "overwritten"));
+            }
 
-        drop_cmd(&connection).unwrap();
-    }
+            let secret =
+                crate::keyring::overwritten(connection, &entry.name, entry.value.as_deref())?;
+            let params = (
+                &entry.name,
+                &entry.value,
+                &entry.alternate,
+                &entry.description,
+                &entry.default,
+                now,
+            );
+            if update.execute(params)? > 0 {
+                if secret {
+                    crate::keyring::forget(connection, &entry.name);
+                }
+                updated += 1;
+            } else {
+                insert.execute(params)?;
+                created += 1;
+            }
 
-    #[test]
-    fn insert_and_exists() {
-        let connection = create_db();
+            Ok(())
+        };
 
-        assert_eq!(
-            exists_cmd(&connection, "test1".to_string()).unwrap(),
-            "false"
-        );
+        match format {
+            ImportFormat::Json => {
+                for value in serde_json::Deserializer::from_reader(std::io::BufReader::new(reader))
+                    .into_iter::<Value>()
+                {
+                    let entries = match value {
+                        Ok(Value::Array(entries)) => entries,
+                        Ok(entry) => vec![entry],
+                        Err(error) => {
+                            return Err(Error::Invalid(format!("invalid json: {}", error)))
+                        }
+                    };
 
-        new(
-            &connection,
-            "test1".to_string(),
-            "value1".to_string(),
-            "alternate1".to_string(),
-        )
-        .unwrap();
+                    for entry in entries {
+                        import(JsonEntry::parse(entry)?)?;
+                    }
+                }
+            }
+            ImportFormat::Gitconfig | ImportFormat::Dconf => {
+                let input = std::io::read_to_string(reader)?;
+                let entries = match format {
+                    ImportFormat::Gitconfig => crate::ini::gitconfig(&input)?,
+                    _ => crate::ini::dconf(&input)?,
+                };
 
-        assert_eq!(
-            exists_cmd(&connection, "test1".to_string()).unwrap(),
-            "true"
-        );
-    }
+                for (name, value) in entries {
+                    import(JsonEntry::value(name, value))?;
+                }
+            }
+        }
 
-    #[test]
-    fn insert_and_get() {
-        let connection = create_db();
+        if !refused.is_empty() {
+            return Err(Error::Protected(refused.join(", ")));
+        }
 
-        new(
-            &connection,
-            "test1".to_string(),
-            "value1".to_string(),
-            "alternate1".to_string(),
-        )
-        .unwrap();
+        evict(connection)?;
 
-        assert_eq!(
-            get_cmd(&connection, "test1".to_string(), false, false, false).unwrap(),
-            format!("{} {}", "value1", "alternate1")
+        let mut summary = format!("Created {} entries, updated {}", created, updated);
+        if !conflicts.is_empty() {
+            summary += &format!("\n{}", conflict_report(&conflicts));
+        }
+
+        Ok(summary)
+    })
+}
+
+/// The most names of conflicting entries [conflict_report] lists
+const MAX_CONFLICTS_LISTED: usize = 20;
+
+/// Formats the entries [import_cmd] found already existing with what happened to them, e.g.
+/// `2 already existed (1 overwritten, 1 kept): a (kept), b (overwritten)`
+///
+/// Only the first [MAX_CONFLICTS_LISTED] names are listed, followed by how many more there are.
+fn conflict_report(conflicts: &[(String, &str)]) -> String {
+    let counts = ["skipped", "overwritten", "kept"]
+        .into_iter()
+        .filter_map(|outcome| {
+            let count = conflicts.iter().filter(|(_, o)| *o == outcome).count();
+            (count > 0).then(|| format!("{} {}", count, outcome))
+        })
+        .collect::<Vec<String>>();
+    let mut names = conflicts
+        .iter()
+        .take(MAX_CONFLICTS_LISTED)
+        .map(|(name, outcome)| format!("{} ({})", name, outcome))
+        .collect::<Vec<String>>()
+        .join(", ");
+    if conflicts.len() > MAX_CONFLICTS_LISTED {
+        names += &format!(" and {} more", conflicts.len() - MAX_CONFLICTS_LISTED);
+    }
+
+    format!(
+        "{} already existed ({}): {}",
+        conflicts.len(),
+        counts.join(", "),
+        names
+    )
+}
+
+/// Saves the environment variables whose names start with `prefix` as entries, e.g. to capture a
+/// working environment
+///
+/// The entries are named after the variables without `prefix`, in lower case with `lowercase`.
+/// Variables which aren't valid unicode are skipped. Like [import_cmd] protected entries are only
+/// overwritten with `force`. Returns the number of created & updated entries.
+pub fn import_env_cmd(
+    connection: &Connection,
+    prefix: String,
+    lowercase: bool,
+    force: bool,
+) -> Result<String> {
+    let mut variables = std::env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+        .filter_map(|(name, value)| {
+            let name = name.strip_prefix(&prefix).filter(|n| !n.is_empty())?;
+            Some((
+                if lowercase {
+                    name.to_lowercase()
+                } else {
+                    name.to_string()
+                },
+                value,
+            ))
+        })
+        .collect::<Vec<(String, String)>>();
+    variables.sort();
+
+    transaction(connection, || {
+        let mut protected =
+            connection.prepare_cached("SELECT 1 FROM data WHERE name = ? AND protected")?;
+        let (mut created, mut updated, mut refused) = (0, 0, Vec::new());
+
+        for (name, value) in variables {
+            if !force && protected.exists([&name])? {
+                refused.push(name);
+                continue;
+            }
+
+            if exists(connection, &name)? {
+                updated += 1;
+            } else {
+                created += 1;
+            }
+            set(connection, &name, Some(value), None, None, None, false)?;
+        }
+
+        if !refused.is_empty() {
+            return Err(Error::Protected(refused.join(", ")));
+        }
+
+        evict(connection)?;
+
+        Ok(format!("Created {} entries, updated {}", created, updated))
+    })
+}
+
+/// Creates the entries declared in the manifest at `path` which don't exist yet (see
+/// [crate::manifest])
+///
+/// New entries without a value start out with their default. Existing entries (including those of
+/// layers) are left unchanged. Everything is created in a single transaction & the manifest is
+/// checked first, so nothing is created if it's invalid.
+pub fn seed_cmd(connection: &Connection, path: std::path::PathBuf) -> Result<String> {
+    let declarations = crate::manifest::read(&path)?;
+
+    transaction(connection, || {
+        let mut insert = connection.prepare_cached(store::INSERT)?;
+        let (mut created, mut kept) = (0, 0);
+        let now = time::now();
+
+        for declaration in declarations {
+            if exists(connection, &resolve(connection, &declaration.name)?)? {
+                kept += 1;
+                continue;
+            }
+
+            insert.execute((
+                &declaration.name,
+                declaration.value.as_ref().or(declaration.default.as_ref()),
+                &declaration.alternate,
+                &declaration.description,
+                &declaration.default,
+                now,
+            ))?;
+            created += 1;
+        }
+
+        evict(connection)?;
+
+        Ok(format!("Created {} entries, kept {}", created, kept))
+    })
+}
+
+/// Makes the entries match the manifest at `path` (see [crate::manifest]) & returns how many were
+/// created, updated & deleted
+///
+/// Missing entries are created like by [seed_cmd] & existing ones get the declared fields, which
+/// only changes those that differ. With `prune` the entries which aren't declared are deleted as
+/// well, unless any of them is protected. Everything happens in a single transaction.
+pub fn apply_cmd(connection: &Connection, path: std::path::PathBuf, prune: bool) -> Result<String> {
+    let declarations = crate::manifest::read(&path)?;
+
+    transaction(connection, || {
+        let (mut created, mut updated) = (0, 0);
+        let mut declared = Vec::new();
+
+        for declaration in declarations {
+            let name = resolve(connection, &declaration.name)?;
+            declared.push(rusqlite::types::Value::from(name.clone()));
+
+            let value = match select(connection, &name) {
+                Ok(entry) => {
+                    let differs = |new: &Option<String>, old: Option<&String>| {
+                        new.is_some() && new.as_ref() != old
+                    };
+
+                    if !(differs(&declaration.value, Some(&entry.value))
+                        || differs(&declaration.alternate, entry.alternate.as_ref())
+                        || differs(&declaration.description, entry.description.as_ref())
+                        || differs(&declaration.default, entry.default.as_ref()))
+                    {
+                        continue;
+                    }
+
+                    updated += 1;
+                    declaration.value
+                }
+                Err(Error::EntryNotFound { .. }) => {
+                    created += 1;
+                    declaration.value.or(declaration.default.clone())
+                }
+                Err(error) => return Err(error),
+            };
+
+            set(
+                connection,
+                &name,
+                value,
+                declaration.alternate,
+                declaration.description,
+                declaration.default,
+                false,
+            )?;
+        }
+
+        let deleted = if prune {
+            let clause = format!(
+                "WHERE name NOT IN ({})",
+                vec!["?"; declared.len()].join(", ")
+            );
+            check_protected(connection, &clause, declared.clone())?;
+
+            connection.execute(
+                &format!("DELETE FROM data {}", clause),
+                rusqlite::params_from_iter(declared),
+            )?
+        } else {
+            0
+        };
+
+        Ok(format!(
+            "Created {} entries, updated {}, deleted {}",
+            created, updated, deleted
+        ))
+    })
+}
+
+/// Checks the entries against the declarations of the manifest at `path` (see
+/// [crate::manifest])
+///
+/// Every declared entry has to match the constraints of its declaration (see
+/// [crate::manifest::check]) & required entries have to exist. Will return [Error::Invalid]
+/// listing every violation otherwise.
+pub fn validate_cmd(connection: &Connection, path: std::path::PathBuf) -> Result<String> {
+    let mut violations = Vec::new();
+
+    let declarations = crate::manifest::read(&path)?;
+
+    for declaration in &declarations {
+        match crate::manifest::check(connection, &declarations, &declaration.name) {
+            Ok(()) => {}
+            Err(Error::EntryNotFound { .. }) => {
+                if declaration.required {
+                    violations.push(format!("'{}' is missing", declaration.name));
+                }
+            }
+            Err(error @ (Error::Invalid(_) | Error::InvalidType { .. })) => {
+                violations.push(error.to_string());
+            }
+            Err(error) => return Err(error),
+        }
+    }
+
+    if violations.is_empty() {
+        Ok("Ok".to_string())
+    } else {
+        Err(Error::Invalid(format!(
+            "entries don't match the manifest:\n{}",
+            violations.join("\n")
+        )))
+    }
+}
+
+/// Schedules a reset of an [Entry] & returns when it will happen
+///
+/// At that time the value is set to the default, or to the alternate if there is no default (see
+/// [apply_resets](crate::store::apply_resets)). Resets are carried out lazily, the next time the
+/// db is opened after they are due.
+pub fn reset_at_cmd(connection: &Connection, name: String, at: time::At) -> Result<String> {
+    let timestamp = at.timestamp(connection)?;
+
+    let scheduled = connection.execute(
+        "UPDATE data SET reset_at = ?, reset_value = IFNULL(default_value, alternate)
+            WHERE name = ?",
+        (timestamp, resolve(connection, &name)?),
+    )?;
+
+    if scheduled == 0 {
+        return Err(not_found(connection, &name));
+    }
+
+    Ok(format!("Resets at {}", time::format(timestamp)))
+}
+
+/// Sets the value of an [Entry] to the result of an arithmetic expression
+///
+/// The expression is evaluated with `x` set to the current value (see [crate::expr]) & the result
+/// is clamped to `min` & `max`. Reading & writing happen in one transaction, so concurrent calls
+/// don't lose updates. Returns the new value. Will return [Error::InvalidType] if the current value
+/// isn't a number & [Error::Invalid] if the expression is invalid.
+pub fn set_expr_cmd(
+    connection: &Connection,
+    name: String,
+    expr: String,
+    min: Option<f64>,
+    max: Option<f64>,
+) -> Result<String> {
+    transaction(connection, || {
+        let entry = select(connection, &resolve(connection, &name)?)?;
+
+        let x = entry
+            .value
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| Error::InvalidType {
+                name: name.clone(),
+                expected: "a number",
+            })?;
+        let result = crate::expr::eval(&expr, x)
+            .map_err(Error::Invalid)?
+            .max(min.unwrap_or(f64::MIN))
+            .min(max.unwrap_or(f64::MAX));
+
+        // Print whole numbers without a fraction, so integers stay integers
+        let value = if result.fract() == 0.0 && result.abs() < i64::MAX as f64 {
+            (result as i64).to_string()
+        } else {
+            result.to_string()
+        };
+
+        connection.execute(
+            "UPDATE data SET value = ?, updated_at = ? WHERE id = ?",
+            (&value, time::now(), entry._id),
+        )?;
+
+        Ok(value)
+    })
+}
+
+/// Shows or changes the maximum number of entries in the db
+///
+/// When the cap is exceeded the least recently used entries are deleted, see
+/// [evict](crate::store::evict). Lowering the cap below the current number of entries deletes the
+/// excess entries immediately.
+pub fn cap_cmd(connection: &Connection, max: Option<usize>, off: bool) -> Result<String> {
+    if off {
+        settings::set(connection, settings::CAP, None)?;
+        return Ok("Ok".to_string());
+    }
+
+    let Some(max) = max else {
+        return Ok(settings::get(connection, settings::CAP)?.unwrap_or("none".to_string()));
+    };
+
+    transaction(connection, || {
+        settings::set(connection, settings::CAP, Some(&max.to_string()))?;
+        let evicted = evict(connection)?;
+
+        Ok(format!("Evicted {} entries", evicted))
+    })
+}
+
+/// Applies `transform` to the value of an entry & returns the new value
+///
+/// The value is read & written in a single transaction, so concurrent changes can't get lost.
+pub fn transform_cmd(
+    connection: &Connection,
+    name: String,
+    transform: Transform,
+) -> Result<String> {
+    transaction(connection, || {
+        let entry = select(connection, &resolve(connection, &name)?)?;
+        let value = match transform.script() {
+            Some(script) => store::run_script(connection, script, &entry.name, &entry.value)?,
+            None => transform.apply(&entry.value),
+        };
+
+        connection.execute(
+            "UPDATE data SET value = ?, updated_at = ? WHERE id = ?",
+            (&value, time::now(), entry._id),
+        )?;
+
+        Ok(value)
+    })
+}
+
+/// Applies the JSON merge patch (RFC 7386) `patch` to the value of an entry & returns the new value
+///
+/// The value has to be JSON, an empty value is patched like `null`. `patch` may be `-` to read it
+/// from stdin. Like [transform_cmd] this happens in a single transaction.
+pub fn patch_cmd(connection: &Connection, name: String, patch: String) -> Result<String> {
+    use serde_json::Value;
+
+    let patch = if patch == "-" {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        patch
+    };
+    let patch: Value = serde_json::from_str(&patch)
+        .map_err(|error| Error::Invalid(format!("invalid patch: {}", error)))?;
+
+    transaction(connection, || {
+        let entry = select(connection, &resolve(connection, &name)?)?;
+        let mut value = if entry.value.trim().is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_str(&entry.value).map_err(|_| Error::InvalidType {
+                name: name.clone(),
+                expected: "json",
+            })?
+        };
+
+        merge_patch(&mut value, patch.clone());
+        let value = value.to_string();
+
+        connection.execute(
+            "UPDATE data SET value = ?, updated_at = ? WHERE id = ?",
+            (&value, time::now(), entry._id),
+        )?;
+
+        Ok(value)
+    })
+}
+
+/// Helper function applying a JSON merge patch to `target` as described in RFC 7386
+fn merge_patch(target: &mut serde_json::Value, patch: serde_json::Value) {
+    use serde_json::{Map, Value};
+
+    let Value::Object(patch) = patch else {
+        *target = patch;
+        return;
+    };
+
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let Value::Object(fields) = target else {
+        unreachable!()
+    };
+
+    for (key, value) in patch {
+        if value.is_null() {
+            fields.remove(&key);
+        } else {
+            merge_patch(fields.entry(key).or_insert(Value::Null), value);
+        }
+    }
+}
+
+/// Toggles the value & alternate of one or more entries returning the new values
+///
+/// All entries are toggled in a single transaction, so if any of them doesn't exist none are
+/// changed. The new values are returned one per line, in the order the names were given.
+///
+/// Without names, every entry matching `filter` is toggled instead, in the same transaction.
+///
+/// If `create` is set, missing entries are created with that value & alternate first (& then
+/// toggled), so the pair describes the state before the first toggle.
+///
+/// If `if_value` is set, entries are only toggled if that is their current value. Will return
+/// [Error::Mismatch] (& toggle none of them) otherwise.
+///
+/// Entries whose alternate was never set are toggled to an empty value, unless
+/// `require_alternate` is set, which returns [Error::NotFound] for them instead.
+pub fn toggle_cmd(
+    connection: &Connection,
+    names: Vec<String>,
+    filter: Filter,
+    create: Option<(String, String)>,
+    if_value: Option<String>,
+    require_alternate: bool,
+) -> Result<String> {
+    transaction(connection, || {
+        let names = if names.is_empty() {
+            select_filtered(connection, &filter)?
+                .into_iter()
+                .map(|e| e.name)
+                .collect()
+        } else {
+            names
+        };
+
+        let declarations = crate::manifest::declared(connection)?;
+
+        Ok(names
+            .iter()
+            .map(|name| {
+                if let Some((value, alternate)) = &create {
+                    if !exists(connection, &resolve(connection, name)?)? {
+                        crate::naming::check(connection, name)?;
+                        insert(
+                            connection,
+                            name.to_string(),
+                            value.to_string(),
+                            alternate.to_string(),
+                            None,
+                            None,
+                        )?;
+                    }
+                }
+
+                if if_value.is_some() || require_alternate {
+                    let entry = select(connection, &resolve(connection, name)?)?;
+                    if if_value
+                        .as_ref()
+                        .is_some_and(|current| entry.value != *current)
+                    {
+                        return Err(Error::Mismatch);
+                    }
+                    if require_alternate && entry.alternate.is_none() {
+                        return Err(Error::NotFound(format!(
+                            "the alternate of '{}'",
+                            entry.name
+                        )));
+                    }
+                }
+
+                let value = toggle(connection, name)?;
+                crate::manifest::check(connection, &declarations, name)?;
+                Ok(value)
+            })
+            .collect::<Result<Vec<String>>>()?
+            .join("\n"))
+    })
+}
+
+/// Switches one or more boolean-style entries on (or off) returning their new values
+///
+/// Entries which already are in the state are left as they are, those whose alternate is are
+/// toggled, see [Entry::needs_toggle]. Missing entries are created as `true` with the alternate
+/// `false` (or the other way round). All entries are switched in a single transaction.
+pub fn switch_cmd(connection: &Connection, names: Vec<String>, on: bool) -> Result<String> {
+    transaction(connection, || {
+        let declarations = crate::manifest::declared(connection)?;
+
+        Ok(names
+            .iter()
+            .map(|name| {
+                let resolved = resolve(connection, name)?;
+                if !exists(connection, &resolved)? {
+                    crate::naming::check(connection, name)?;
+                    insert(
+                        connection,
+                        name.to_string(),
+                        on.to_string(),
+                        (!on).to_string(),
+                        None,
+                        None,
+                    )?;
+                } else if select(connection, &resolved)?.needs_toggle(on)? {
+                    toggle(connection, name)?;
+                }
+
+                crate::manifest::check(connection, &declarations, name)?;
+                Ok(select(connection, &resolve(connection, name)?)?.value)
+            })
+            .collect::<Result<Vec<String>>>()?
+            .join("\n"))
+    })
+}
+
+/// Updates an [Entry]'s modification timestamp without changing it
+pub fn touch_cmd(connection: &Connection, name: String) -> Result<String> {
+    let changed = connection.execute(
+        "UPDATE data SET updated_at = ? WHERE name = ?",
+        (time::now(), resolve(connection, &name)?),
+    )?;
+
+    if changed == 0 {
+        return Err(not_found(connection, &name));
+    }
+
+    Ok("Ok".to_string())
+}
+
+/// Freezes (or unfreezes) an [Entry]
+///
+/// Changing or deleting a frozen entry fails with [Error::Frozen], no matter which command does
+/// it. Scheduled resets & the cap skip frozen entries.
+pub fn freeze_cmd(connection: &Connection, name: String, frozen: bool) -> Result<String> {
+    let changed = connection.execute(
+        "UPDATE data SET frozen = ? WHERE name = ?",
+        (frozen.then_some(1), resolve(connection, &name)?),
+    )?;
+
+    if changed == 0 {
+        return Err(not_found(connection, &name));
+    }
+
+    Ok("Ok".to_string())
+}
+
+/// Protects (or unprotects) an [Entry]
+///
+/// Protected entries can still be changed, but deleting them with `delete` or `drop` or
+/// overwriting them with `copy-from` fails with [Error::Protected] unless forced. The cap never
+/// evicts them.
+pub fn protect_cmd(connection: &Connection, name: String, protected: bool) -> Result<String> {
+    let changed = connection.execute(
+        "UPDATE data SET protected = ? WHERE name = ?",
+        (protected.then_some(1), resolve(connection, &name)?),
+    )?;
+
+    if changed == 0 {
+        return Err(not_found(connection, &name));
+    }
+
+    Ok("Ok".to_string())
+}
+
+/// Makes an [Entry] computed: its value becomes the output of the shell `command`, which `get`
+/// runs (see [store::compute])
+///
+/// With a `ttl` (in seconds) the output is kept for that long instead of running the command on
+/// every read. With `script` the command is a Rhai script instead (see [crate::store::run_script]).
+/// `command == None` makes the entry a regular one again, keeping its stored value.
+pub fn compute_cmd(
+    connection: &Connection,
+    name: String,
+    command: Option<String>,
+    ttl: Option<i64>,
+    script: bool,
+) -> Result<String> {
+    let changed = connection.execute(
+        "UPDATE data SET command = ?, command_ttl = ?, command_script = ?, computed_at = NULL
+            WHERE name = ?",
+        (
+            command,
+            ttl,
+            script.then_some(true),
+            resolve(connection, &name)?,
+        ),
+    )?;
+
+    if changed == 0 {
+        return Err(not_found(connection, &name));
+    }
+
+    Ok("Ok".to_string())
+}
+
+/// Helper function returning [Error::Protected] if any entry selected by the `WHERE` clause (as
+/// returned by [Filter::sql]) is protected
+fn check_protected(
+    connection: &Connection,
+    clause: &str,
+    params: Vec<rusqlite::types::Value>,
+) -> Result<()> {
+    let protected = connection
+        .prepare(&format!(
+            "SELECT name FROM data {} {} protected ORDER BY name",
+            clause,
+            if clause.is_empty() { "WHERE" } else { "AND" }
+        ))?
+        .query_map(rusqlite::params_from_iter(params), |row| {
+            row.get::<_, String>(0)
+        })?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+
+    if protected.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Protected(protected.join(", ")))
+    }
+}
+
+/// Resets an [Entry]'s value to its default returning the new value
+///
+/// Will return [Error::NoDefault] if the entry has no default.
+pub fn reset_cmd(connection: &Connection, name: String) -> Result<String> {
+    layers::copy_up(connection, &name)?;
+    let entry = select(connection, &name)?;
+
+    let default = entry.default.ok_or(Error::NoDefault)?;
+
+    connection.execute(
+        "UPDATE data SET value = ?, updated_at = ? WHERE name = ?",
+        (&default, time::now(), entry.name),
+    )?;
+
+    Ok(default)
+}
+
+/// Resets all entries matching the [Filter] which have a default
+///
+/// All entries are reset in a single transaction. Returns one line per entry whose value was
+/// changed in the form `name: old -> new`.
+pub fn reset_all_cmd(connection: &Connection, filter: Filter) -> Result<String> {
+    let entries = select_filtered(connection, &filter)?;
+
+    transaction(connection, || {
+        let mut changes = vec![];
+
+        for entry in entries {
+            let Some(default) = entry.default else {
+                continue;
+            };
+
+            if entry.value == default {
+                continue;
+            }
+
+            connection.execute(
+                "UPDATE data SET value = ?, updated_at = ? WHERE id = ?",
+                (&default, time::now(), entry._id),
+            )?;
+
+            changes.push(format!("{}: {} -> {}", entry.name, entry.value, default));
+        }
+
+        Ok(changes.join("\n"))
+    })
+}
+
+/// Creates an alias which resolves to an existing [Entry] for `get`, `toggle` & `check`
+///
+/// Aliasing an alias points the new alias at the same entry. Will return [Error::EntryExists]
+/// if an entry with the alias' name exists.
+pub fn alias_cmd(connection: &Connection, alias: String, target: String) -> Result<String> {
+    let target = resolve(connection, &target)?;
+
+    if !exists(connection, &target)? {
+        return Err(not_found(connection, &target));
+    }
+
+    if exists(connection, &alias)? {
+        return Err(Error::EntryExists);
+    }
+
+    connection.execute(
+        "INSERT OR REPLACE INTO aliases (alias, target) VALUES (?, ?)",
+        [alias, target],
+    )?;
+
+    Ok("Ok".to_string())
+}
+
+/// Removes an alias
+///
+/// This doesn't touch the entry the alias points to. If the alias doesn't exist, this will do
+/// nothing.
+pub fn unalias_cmd(connection: &Connection, alias: String, strict: bool) -> Result<String> {
+    let deleted = connection.execute("DELETE FROM aliases WHERE alias = ?", [&alias])?;
+
+    if strict && deleted == 0 {
+        return Err(Error::NotFound(format!("alias '{}'", alias)));
+    }
+
+    Ok("Ok".to_string())
+}
+
+/// Lists all entries in the db matching the [Filter], writing them to `out`
+///
+/// See [ListFormat] for the formats entries can be printed in. Each entry is written as soon as
+/// it's read from the db, so even huge stores don't need much memory. A reader closing `out`
+/// early (e.g. `head`) isn't an error.
+///
+/// If `recent` is set, only that many entries are listed, starting with the most recently
+/// changed. Entries filtered with [Filter::since] or [Filter::changed_since] are also sorted this
+/// way.
+///
+/// `limit` & `offset` page through the entries, e.g. `limit = 50, offset = 100` lists the third
+/// page of 50 entries.
+pub fn list_cmd(
+    connection: &Connection,
+    out: &mut impl std::io::Write,
+    format: ListFormat,
+    filter: Filter,
+    recent: Option<usize>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> Result<()> {
+    let order = if recent.is_some() || filter.since.is_some() || filter.changed_since.is_some() {
+        "ORDER BY updated_at DESC, id"
+    } else {
+        "ORDER BY id"
+    };
+    let limit = format!(
+        "LIMIT {} OFFSET {}",
+        recent.or(limit).map_or(-1, |n| n as i64),
+        offset.unwrap_or_default()
+    );
+
+    let duplicates: i64 = connection.query_row(
+        &format!("SELECT COUNT(*) FROM data WHERE {}", DUPLICATE),
+        (),
+        |row| row.get(0),
+    )?;
+    if duplicates > 0 {
+        tracing::warn!(
+            "Found {} duplicate entries, run `config-store dedupe` to remove them",
+            duplicates
+        );
+    }
+
+    let (clause, params) = filter.sql();
+
+    let mut statement = connection.prepare(&format!(
+        "SELECT {} FROM {} {} {} {}",
+        Entry::COLUMNS,
+        layers::source(connection)?,
+        clause,
+        order,
+        limit
+    ))?;
+    let entries = statement.query_map(rusqlite::params_from_iter(params), Entry::from_row)?;
+
+    let declarations = match format {
+        ListFormat::Long { .. } | ListFormat::Json => crate::manifest::declared(connection)?,
+        _ => Vec::new(),
+    };
+    let mut kind = connection.prepare_cached("SELECT command, secret FROM data WHERE name = ?")?;
+
+    ignore_broken_pipe((|| {
+        if let ListFormat::Nuon = format {
+            write!(out, "[")?;
+        }
+
+        for (i, e) in entries.enumerate() {
+            let e = e?;
+
+            match format {
+                ListFormat::Plain { color } => writeln!(out, "{}", e.plain(color))?,
+                ListFormat::Long { color } => {
+                    write!(out, "{}", e.long(color))?;
+                    let (command, secret) = kind
+                        .query_row([&e.name], |row| {
+                            Ok((
+                                row.get::<_, Option<String>>(0)?,
+                                row.get::<_, Option<bool>>(1)?,
+                            ))
+                        })
+                        .optional()?
+                        .unwrap_or_default();
+                    if let Some(command) = command {
+                        write!(out, " (computed by `{}`)", command)?;
+                    }
+                    if secret == Some(true) {
+                        write!(out, " (in the keyring)")?;
+                    }
+                    if let Some(declaration) = declarations.iter().find(|d| d.name == e.name) {
+                        write!(out, " ({})", declaration.summary())?;
+                    }
+                    writeln!(out)?
+                }
+                ListFormat::Json => {
+                    let meta = metadata(connection, e._id)?;
+                    let kind = json_type(connection, &e, None, Some(&declarations))?;
+                    writeln!(out, "{}", e.json_with_meta(&meta, kind))?
+                }
+                ListFormat::Quoted(Quote::Shell) => writeln!(
+                    out,
+                    "{}",
+                    [
+                        &e.name,
+                        &e.value,
+                        e.alternate.as_deref().unwrap_or_default()
+                    ]
+                    .map(shell_quote)
+                    .join(" ")
+                )?,
+                ListFormat::Nuon => {
+                    write!(out, "{}\n  {}", if i > 0 { "," } else { "" }, e.nuon())?
+                }
+            }
+        }
+
+        if let ListFormat::Nuon = format {
+            writeln!(out, "\n]")?;
+        }
+
+        Ok(())
+    })())
+}
+
+/// Helper function treating output closed by its reader (e.g. piped into `head`) as success
+fn ignore_broken_pipe(result: Result<()>) -> Result<()> {
+    match result {
+        Err(Error::Io(error)) if error.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
+
+/// Adds tags to an [Entry]
+///
+/// Adding a tag the entry already has does nothing.
+pub fn tag_add_cmd(connection: &Connection, name: String, tags: Vec<String>) -> Result<String> {
+    let entry = select(connection, &name)?;
+
+    for tag in tags {
+        connection.execute(
+            "INSERT OR IGNORE INTO tags (entry_id, tag) VALUES (?, ?)",
+            (entry._id, tag),
+        )?;
+    }
+
+    Ok("Ok".to_string())
+}
+
+/// Removes tags from an [Entry]
+pub fn tag_remove_cmd(
+    connection: &Connection,
+    name: String,
+    tags: Vec<String>,
+    strict: bool,
+) -> Result<String> {
+    let entry = select(connection, &name)?;
+
+    for tag in tags {
+        let removed = connection.execute(
+            "DELETE FROM tags WHERE entry_id = ? AND tag = ?",
+            (entry._id, &tag),
+        )?;
+
+        if strict && removed == 0 {
+            return Err(Error::NotFound(format!("tag '{}' of '{}'", tag, name)));
+        }
+    }
+
+    Ok("Ok".to_string())
+}
+
+/// Lists the tags of an [Entry], or all tags in use if no name is given
+pub fn tag_list_cmd(connection: &Connection, name: Option<String>) -> Result<String> {
+    let tags = match name {
+        Some(name) => {
+            let entry = select(connection, &name)?;
+
+            connection
+                .prepare("SELECT tag FROM tags WHERE entry_id = ? ORDER BY tag")?
+                .query_map([entry._id], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()?
+        }
+        None => connection
+            .prepare("SELECT DISTINCT tag FROM tags ORDER BY tag")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?,
+    };
+
+    Ok(tags.join("\n"))
+}
+
+/// Sets a metadata attribute of an [Entry], replacing its previous value
+pub fn meta_set_cmd(
+    connection: &Connection,
+    name: String,
+    key: String,
+    value: String,
+) -> Result<String> {
+    let entry = select(connection, &name)?;
+
+    connection.execute(
+        "INSERT OR REPLACE INTO meta (entry_id, key, value) VALUES (?, ?, ?)",
+        (entry._id, key, value),
+    )?;
+
+    Ok("Ok".to_string())
+}
+
+/// Returns the value of a metadata attribute of an [Entry]
+///
+/// Will return [Error::NotFound] if the entry doesn't have the attribute.
+pub fn meta_get_cmd(connection: &Connection, name: String, key: String) -> Result<String> {
+    let entry = select(connection, &name)?;
+
+    connection
+        .query_row(
+            "SELECT value FROM meta WHERE entry_id = ? AND key = ?",
+            (entry._id, &key),
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or_else(|| Error::NotFound(format!("metadata '{}' of '{}'", key, name)))
+}
+
+/// Removes metadata attributes from an [Entry]
+pub fn meta_unset_cmd(
+    connection: &Connection,
+    name: String,
+    keys: Vec<String>,
+    strict: bool,
+) -> Result<String> {
+    let entry = select(connection, &name)?;
+
+    for key in keys {
+        let removed = connection.execute(
+            "DELETE FROM meta WHERE entry_id = ? AND key = ?",
+            (entry._id, &key),
+        )?;
+
+        if strict && removed == 0 {
+            return Err(Error::NotFound(format!("metadata '{}' of '{}'", key, name)));
+        }
+    }
+
+    Ok("Ok".to_string())
+}
+
+/// Lists the metadata attributes of an [Entry] as `key=value` lines
+pub fn meta_list_cmd(connection: &Connection, name: String) -> Result<String> {
+    let entry = select(connection, &name)?;
+
+    Ok(metadata(connection, entry._id)?
+        .into_iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<String>>()
+        .join("\n"))
+}
+
+/// Helper function returning the metadata attributes of the entry with the id `id`, sorted by key
+fn metadata(connection: &Connection, id: i32) -> Result<Vec<(String, String)>> {
+    Ok(connection
+        .prepare_cached("SELECT key, value FROM meta WHERE entry_id = ? ORDER BY key")?
+        .query_map([id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?)
+}
+
+/// Saves the current values & alternates of entries as a preset
+///
+/// The entries are either given by name or selected by the [Filter]. An existing preset with the
+/// same name is replaced.
+pub fn preset_save_cmd(
+    connection: &Connection,
+    preset: String,
+    names: Vec<String>,
+    filter: Filter,
+) -> Result<String> {
+    let entries = if names.is_empty() {
+        select_filtered(connection, &filter)?
+    } else {
+        names
+            .iter()
+            .map(|name| select(connection, name))
+            .collect::<Result<Vec<Entry>>>()?
+    };
+
+    transaction(connection, || {
+        connection.execute("DELETE FROM presets WHERE preset = ?", [&preset])?;
+
+        // Presets always set an alternate, an unset one is saved as empty
+        for entry in entries {
+            connection.execute(
+                "INSERT OR REPLACE INTO presets (preset, name, value, alternate) VALUES (?, ?, ?, ?)",
+                (
+                    &preset,
+                    entry.name,
+                    entry.value,
+                    entry.alternate.unwrap_or_default(),
+                ),
+            )?;
+        }
+
+        Ok("Ok".to_string())
+    })
+}
+
+/// Applies a preset, setting all of its entries in a single transaction
+///
+/// Entries which no longer exist are recreated. Will return [Error::NotFound] if the preset
+/// doesn't exist.
+pub fn preset_apply_cmd(connection: &Connection, preset: String) -> Result<String> {
+    let values = connection
+        .prepare("SELECT name, value, alternate FROM presets WHERE preset = ?")?
+        .query_map([&preset], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<rusqlite::Result<Vec<(String, String, String)>>>()?;
+
+    if values.is_empty() {
+        return Err(Error::NotFound(format!("preset '{}'", preset)));
+    }
+
+    transaction(connection, || {
+        for (name, value, alternate) in values {
+            set_cmd(
+                connection,
+                name,
+                Some(value),
+                Some(alternate),
+                None,
+                None,
+                SetMode::Upsert,
+            )?;
+        }
+
+        Ok("Ok".to_string())
+    })
+}
+
+/// Lists the names of all presets
+pub fn preset_list_cmd(connection: &Connection) -> Result<String> {
+    Ok(connection
+        .prepare("SELECT DISTINCT preset FROM presets ORDER BY preset")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?
+        .join("\n"))
+}
+
+/// Deletes a preset
+///
+/// This doesn't touch any entries. If the preset doesn't exist, this will do nothing.
+pub fn preset_delete_cmd(connection: &Connection, preset: String, strict: bool) -> Result<String> {
+    let deleted = connection.execute("DELETE FROM presets WHERE preset = ?", [&preset])?;
+
+    if strict && deleted == 0 {
+        return Err(Error::NotFound(format!("preset '{}'", preset)));
+    }
+
+    Ok("Ok".to_string())
+}
+
+/// Shows the audit log, optionally only for a single [Entry]
+///
+/// If `limit` is set only the most recent changes are shown. The oldest change is always shown
+/// first.
+pub fn log_cmd(
+    connection: &Connection,
+    name: Option<String>,
+    limit: Option<usize>,
+) -> Result<String> {
+    let limit = limit.map_or(-1, |l| l as i64);
+
+    let entries = connection
+        .prepare(&format!(
+            "SELECT {0} FROM (
+                SELECT id, {0} FROM log WHERE ?1 IS NULL OR name = ?1 ORDER BY id DESC LIMIT ?2
+            ) ORDER BY id",
+            LogEntry::COLUMNS
+        ))?
+        .query_map((name, limit), LogEntry::from_row)?
+        .collect::<rusqlite::Result<Vec<LogEntry>>>()?;
+
+    Ok(entries.iter().fold(String::new(), |mut acc, e| {
+        writeln!(acc, "{}", e).unwrap();
+        acc
+    }))
+}
+
+/// Drops the `data` table deleting all entries.
+///
+/// The entries are deleted before dropping the table, so that they are recorded in the audit log.
+///
+/// This won't actually delete the file on disk.
+pub fn drop_cmd(connection: &Connection, force: bool) -> Result<String> {
+    if !force {
+        check_protected(connection, "", vec![])?;
+    }
+
+    connection.execute("DELETE FROM data", [])?;
+    connection.execute("DROP TABLE data", [])?;
+
+    Ok("Ok".to_string())
+}
+
+/// Generates the completion script for `shell`
+///
+/// The script calls config-store on TAB, so the values allowed for typed entries are completed
+/// from the schema of the db, see [crate::completion].
+pub fn completions_cmd(shell: clap_complete::Shell) -> String {
+    let name = crate::cli::Args::command().get_name().to_string();
+    let mut script = Vec::new();
+
+    clap_complete::env::Shells::builtins()
+        .completer(&shell.to_string())
+        .expect("Failed to find the completer of the shell.")
+        .write_registration("COMPLETE", &name, &name, &name, &mut script)
+        .expect("Failed to generate completion String.");
+
+    String::from_utf8(script).expect("Failed to generate completion String.")
+}
+
+/// Installs the completion script for `shell` (or the shell in `$SHELL`) for the current user
+///
+/// See [completions_path] for where it's written to. Missing directories are created.
+pub fn completions_install_cmd(shell: Option<clap_complete::Shell>) -> Result<String> {
+    let shell = shell
+        .or_else(clap_complete::Shell::from_env)
+        .ok_or_else(|| Error::Invalid("can't detect the shell, please name it".to_string()))?;
+    let path = completions_path(shell, |var| std::env::var(var).ok())?;
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, completions_cmd(shell))?;
+
+    let mut result = format!("Installed {} completions to {}", shell, path.display());
+    if shell == clap_complete::Shell::Zsh {
+        write!(
+            result,
+            "\nAdd `fpath=({} $fpath)` before `compinit` in your .zshrc if it isn't there yet",
+            path.parent().unwrap_or(&path).display()
+        )
+        .unwrap();
+    }
+
+    Ok(result)
+}
+
+/// Returns where the completion script for `shell` is installed, reading environment variables
+/// with `var`
+///
+/// These are the user directories the shells (or bash-completion) load completions from:
+/// - bash: `$BASH_COMPLETION_USER_DIR/completions` or `$XDG_DATA_HOME/bash-completion/completions`
+/// - fish: `$XDG_CONFIG_HOME/fish/completions`
+/// - zsh: `$ZDOTDIR/.zfunc`, which has to be in the `fpath`
+///
+/// `XDG_*` default to their usual places in `$HOME` & `ZDOTDIR` to `$HOME`. Will return
+/// [Error::Invalid] for other shells.
+pub fn completions_path(
+    shell: clap_complete::Shell,
+    var: impl Fn(&str) -> Option<String>,
+) -> Result<std::path::PathBuf> {
+    use clap_complete::Shell;
+    use std::path::PathBuf;
+
+    let home = || {
+        var("HOME")
+            .map(PathBuf::from)
+            .ok_or_else(|| Error::Invalid("$HOME isn't set".to_string()))
+    };
+    let xdg = |name: &str, default: &str| match var(name) {
+        Some(dir) if !dir.is_empty() => Ok(PathBuf::from(dir)),
+        _ => Ok::<_, Error>(home()?.join(default)),
+    };
+    let name = crate::cli::Args::command().get_name().to_string();
+
+    Ok(match shell {
+        Shell::Bash => match var("BASH_COMPLETION_USER_DIR") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => xdg("XDG_DATA_HOME", ".local/share")?.join("bash-completion"),
+        }
+        .join("completions")
+        .join(name),
+        Shell::Fish => xdg("XDG_CONFIG_HOME", ".config")?
+            .join("fish/completions")
+            .join(format!("{}.fish", name)),
+        Shell::Zsh => match var("ZDOTDIR") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => home()?,
+        }
+        .join(".zfunc")
+        .join(format!("_{}", name)),
+        shell => {
+            return Err(Error::Invalid(format!(
+                "installing completions isn't supported for {}, print them instead",
+                shell
+            )))
+        }
+    })
+}
+
+/// Generates a cryptographically random value and stores it in a (new) [Entry]
+///
+/// If the entry already exists its value is returned unchanged, unless `force` is set, in which
+/// case the value is replaced by the newly generated one. The alternate is never touched.
+///
+/// `length` is the number of characters in the result and is ignored for [GenerateKind::Uuid].
+pub fn generate_cmd(
+    connection: &Connection,
+    name: String,
+    kind: GenerateKind,
+    length: usize,
+    force: bool,
+) -> Result<String> {
+    if !force && exists(connection, &name)? {
+        return Ok(select(connection, &name)?.value);
+    }
+
+    let value = random_string(kind, length)?;
+
+    set_cmd(
+        connection,
+        name,
+        Some(value.clone()),
+        None,
+        None,
+        None,
+        SetMode::Upsert,
+    )?;
+
+    Ok(value)
+}
+
+/// Lists the entries whose name, value or description match the FTS5 `query`, best match first
+///
+/// The entries are printed like by `list`, highlighted if `color` is set. Will return
+/// [Error::Invalid] for queries FTS5 can't parse.
+pub fn fts_cmd(
+    connection: &Connection,
+    query: String,
+    json: bool,
+    limit: Option<usize>,
+    color: bool,
+) -> Result<String> {
+    // Errors of the fixed SQL would fail every query, so generic errors are caused by the query
+    let invalid = |error: rusqlite::Error| match error {
+        rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error {
+                code: rusqlite::ErrorCode::Unknown,
+                ..
+            },
+            Some(message),
+        ) => Error::Invalid(format!("invalid query: {}", message)),
+        error => error.into(),
+    };
+
+    let entries = connection
+        .prepare(&format!(
+            "SELECT {} FROM data
+                JOIN (SELECT rowid, rank FROM data_fts WHERE data_fts MATCH ?1) AS matches
+                ON matches.rowid = data.id
+            ORDER BY matches.rank LIMIT ?2",
+            Entry::COLUMNS
+        ))?
+        .query_map((&query, limit.map_or(-1, |n| n as i64)), Entry::from_row)?
+        .collect::<rusqlite::Result<Vec<Entry>>>()
+        .map_err(invalid)?;
+
+    entries
+        .into_iter()
+        .map(|entry| {
+            Ok(if json {
+                let meta = metadata(connection, entry._id)?;
+                entry.json_with_meta(&meta, None)
+            } else {
+                entry.plain(color)
+            })
+        })
+        .collect::<Result<Vec<String>>>()
+        .map(|lines| lines.join("\n"))
+}
+
+/// Print every change to entries matching the glob `pattern` as a line of json until interrupted
+///
+/// Other processes can't be observed directly, so the audit log is polled every `interval`
+/// milliseconds. Returns once stdout is closed & [Error::Timeout] once `timeout` seconds passed.
+pub fn listen_cmd(
+    connection: &Connection,
+    pattern: Option<String>,
+    timeout: Option<i64>,
+    interval: u64,
+) -> Result<String> {
+    use std::io::Write as _;
+
+    let deadline =
+        timeout.map(|t| std::time::Instant::now() + std::time::Duration::from_secs(t as u64));
+    let mut last = crate::audit::last_id(connection)?;
+    let mut stdout = std::io::stdout();
+
+    loop {
+        let (newest, changes) = changes_since(connection, last, pattern.as_deref())?;
+        last = newest;
+
+        for change in changes {
+            let line = writeln!(stdout, "{}", change.json()).and_then(|_| stdout.flush());
+
+            match line {
+                Err(error) if error.kind() == std::io::ErrorKind::BrokenPipe => {
+                    return Ok(String::new())
+                }
+                result => result?,
+            }
+        }
+
+        if deadline.is_some_and(|d| std::time::Instant::now() >= d) {
+            return Err(Error::Timeout);
+        }
+
+        std::thread::sleep(std::time::Duration::from_millis(interval));
+    }
+}
+
+/// Helper function returning the id of the newest change & all changes to entries matching the
+/// glob `pattern` since the change with the id `since`
+fn changes_since(
+    connection: &Connection,
+    since: i64,
+    pattern: Option<&str>,
+) -> Result<(i64, Vec<LogEntry>)> {
+    let newest = crate::audit::last_id(connection)?;
+
+    let changes = connection
+        .prepare(&format!(
+            "SELECT {} FROM log WHERE id > ?1 AND id <= ?2 AND (?3 IS NULL OR name GLOB ?3)
+                ORDER BY id",
+            LogEntry::COLUMNS
+        ))?
+        .query_map((since, newest, pattern), LogEntry::from_row)?
+        .collect::<rusqlite::Result<Vec<LogEntry>>>()?;
+
+    Ok((newest, changes))
+}
+
+/// Block until the [Entry] `name` has the given value
+///
+/// The entry doesn't have to exist yet. The value is checked every `interval` milliseconds. Will
+/// return [Error::Timeout] if the value didn't match within `timeout` seconds.
+pub fn wait_for_cmd(
+    connection: &Connection,
+    name: String,
+    value: String,
+    timeout: Option<i64>,
+    interval: u64,
+) -> Result<String> {
+    let deadline =
+        timeout.map(|t| std::time::Instant::now() + std::time::Duration::from_secs(t as u64));
+
+    loop {
+        if current_value(connection, &name)?.as_ref() == Some(&value) {
+            return Ok("Ok".to_string());
+        }
+
+        if deadline.is_some_and(|d| std::time::Instant::now() >= d) {
+            return Err(Error::Timeout);
+        }
+
+        std::thread::sleep(std::time::Duration::from_millis(interval));
+    }
+}
+
+/// Run the [Hook] `exec` each time the value of the [Entry] `name` changes, until interrupted
+///
+/// The new value is in the `CONFIG_STORE_VALUE` env var of shell commands & in `value` for scripts.
+/// Without `exec` the new value is printed instead. Like [wait_for_cmd] the value is checked every
+/// `interval` milliseconds, so changes undone within that time are missed. Will return
+/// [Error::Timeout] once `timeout` seconds passed.
+pub fn watch_cmd(
+    connection: &Connection,
+    name: String,
+    exec: Option<Hook>,
+    timeout: Option<i64>,
+    interval: u64,
+) -> Result<String> {
+    use std::io::Write as _;
+
+    let deadline =
+        timeout.map(|t| std::time::Instant::now() + std::time::Duration::from_secs(t as u64));
+    let mut last = current_value(connection, &name)?;
+    let mut stdout = std::io::stdout();
+
+    loop {
+        let current = current_value(connection, &name)?;
+
+        if current != last {
+            if let Some(value) = &current {
+                match &exec {
+                    Some(exec) => run_hook(connection, exec, &name, value)?,
+                    None => match writeln!(stdout, "{}", value).and_then(|_| stdout.flush()) {
+                        Err(error) if error.kind() == std::io::ErrorKind::BrokenPipe => {
+                            return Ok(String::new())
+                        }
+                        result => result?,
+                    },
+                }
+            }
+            last = current;
+        }
+
+        if deadline.is_some_and(|d| std::time::Instant::now() >= d) {
+            return Err(Error::Timeout);
+        }
+
+        std::thread::sleep(std::time::Duration::from_millis(interval));
+    }
+}
+
+/// Helper function returning the value of the [Entry] `name`, or `None` if it doesn't exist
+fn current_value(connection: &Connection, name: &str) -> Result<Option<String>> {
+    match select(connection, &resolve(connection, name)?) {
+        Ok(entry) => Ok(Some(entry.value)),
+        Err(Error::EntryNotFound { .. }) => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+/// Helper function running the [Hook] `exec` for the new `value` of the entry `name`
+///
+/// A failing command or script only logs a warning, so one bad value doesn't stop `watch`. Hooks
+/// aren't run during a dry run. Will return [Error::Io] if the shell can't be run.
+fn run_hook(connection: &Connection, exec: &Hook, name: &str, value: &str) -> Result<()> {
+    if store::dry_run(connection)? {
+        return Ok(());
+    }
+
+    let exec = match exec {
+        Hook::Shell(exec) => exec,
+        Hook::Script(script) => {
+            if let Err(error) = store::run_script(connection, script, name, value) {
+                tracing::warn!("{} for {}={}", error, name, value);
+            }
+            return Ok(());
+        }
+    };
+
+    let status = std::process::Command::new("sh")
+        .args(["-c", exec])
+        .env("CONFIG_STORE_NAME", name)
+        .env("CONFIG_STORE_VALUE", value)
+        .status()
+        .map_err(|e| std::io::Error::new(e.kind(), format!("failed to run sh: {}", e)))?;
+
+    if !status.success() {
+        tracing::warn!("`{}` failed with {} for {}={}", exec, status, name, value);
+    }
+
+    Ok(())
+}
+
+/// Print the state of an [Entry] for a status bar
+///
+/// For waybar the value is used as the text, class & alt (for `format-icons`), while the tooltip is
+/// the description, or the name & both values if the entry has none.
+pub fn status_cmd(connection: &Connection, name: String, format: StatusFormat) -> Result<String> {
+    let entry = select(connection, &resolve(connection, &name)?)?;
+
+    Ok(match format {
+        StatusFormat::Waybar => {
+            let value = json_escape(&entry.value);
+            let tooltip = entry.description.unwrap_or_else(|| {
+                format!(
+                    "{}: {} ({})",
+                    entry.name,
+                    entry.value,
+                    entry.alternate.unwrap_or_default()
+                )
+            });
+
+            format!(
+                r#"{{"text":"{0}","alt":"{0}","class":"{0}","tooltip":"{1}"}}"#,
+                value,
+                json_escape(&tooltip)
+            )
+        }
+        StatusFormat::Plain => entry.value,
+    })
+}
+
+/// Run a read-only SQL statement against the db & print its rows as a table, or as one json
+/// object per line if `json` is set
+///
+/// The statement sees the same tables as config-store itself, e.g. `data`, `tags` & `log`. It's
+/// run on a connection of its own which sqlite opens read-only & which can't attach other dbs, so
+/// it can't change any file. Will return [Error::Invalid] for in memory dbs & for input which
+/// doesn't compile, would write or contains more than one statement.
+pub fn query_cmd(connection: &Connection, sql: String, json: bool) -> Result<String> {
+    use rusqlite::{limits::Limit, types::ValueRef, Batch, OpenFlags};
+
+    let path = connection
+        .path()
+        .filter(|path| !path.is_empty())
+        .ok_or_else(|| Error::Invalid("in memory dbs can't be queried".to_string()))?;
+    let reader = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    reader.set_limit(Limit::SQLITE_LIMIT_ATTACHED, 0);
+
+    // Errors of sqlite like a missing table or too many attached dbs are mistakes in the query,
+    // while e.g. a locked db is reported as it is
+    let invalid = |e: rusqlite::Error| match e {
+        rusqlite::Error::SqlInputError { .. } => Error::Invalid(format!("invalid query: {}", e)),
+        e if e.sqlite_error_code() == Some(rusqlite::ErrorCode::Unknown) => {
+            Error::Invalid(format!("invalid query: {}", e))
+        }
+        e => e.into(),
+    };
+    let mut batch = Batch::new(&reader, &sql);
+    let mut statement = batch
+        .next()
+        .map_err(invalid)?
+        .ok_or_else(|| Error::Invalid("the query is empty".to_string()))?;
+    if batch.next().map_err(invalid)?.is_some() {
+        return Err(Error::Invalid(
+            "only a single statement can be queried".to_string(),
+        ));
+    }
+    if !statement.readonly() {
+        return Err(Error::Invalid(
+            "only statements which don't change the db can be queried".to_string(),
+        ));
+    }
+
+    let columns = statement
+        .column_names()
+        .into_iter()
+        .map(str::to_string)
+        .collect::<Vec<String>>();
+
+    let rows = statement
+        .query_map((), |row| {
+            (0..columns.len())
+                .map(|i| row.get_ref(i).map(|value| value.into()))
+                .collect::<rusqlite::Result<Vec<rusqlite::types::Value>>>()
+        })
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .map_err(invalid)?;
+
+    if json {
+        return Ok(rows
+            .into_iter()
+            .map(|row| {
+                let fields = columns
+                    .iter()
+                    .zip(row)
+                    .map(|(column, value)| {
+                        let value = match ValueRef::from(&value) {
+                            ValueRef::Null => "null".to_string(),
+                            ValueRef::Integer(i) => i.to_string(),
+                            ValueRef::Real(f) => serde_json::Value::from(f).to_string(),
+                            ValueRef::Text(t) => {
+                                format!(r#""{}""#, json_escape(&String::from_utf8_lossy(t)))
+                            }
+                            ValueRef::Blob(b) => format!(
+                                r#""{}""#,
+                                base64::engine::general_purpose::STANDARD.encode(b)
+                            ),
+                        };
+                        format!(r#""{}":{}"#, json_escape(column), value)
+                    })
+                    .collect::<Vec<String>>();
+
+                format!("{{{}}}", fields.join(","))
+            })
+            .collect::<Vec<String>>()
+            .join("\n"));
+    }
+
+    let cells = rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|value| match ValueRef::from(value) {
+                    ValueRef::Null => String::new(),
+                    ValueRef::Integer(i) => i.to_string(),
+                    ValueRef::Real(f) => f.to_string(),
+                    ValueRef::Text(t) => String::from_utf8_lossy(t).into_owned(),
+                    ValueRef::Blob(b) => format!("<{} bytes>", b.len()),
+                })
+                .collect::<Vec<String>>()
+        })
+        .collect::<Vec<Vec<String>>>();
+
+    let widths = (0..columns.len())
+        .map(|i| {
+            std::iter::once(&columns[i])
+                .chain(cells.iter().map(|row| &row[i]))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect::<Vec<usize>>();
+
+    Ok(std::iter::once(&columns)
+        .chain(&cells)
+        .map(|row| {
+            row.iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect::<Vec<String>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<String>>()
+        .join("\n"))
+}
+
+/// Print an [Entry] as a [Segment](crate::segment::Segment) of a shell prompt
+///
+/// Missing entries are shown as off instead of failing, so a prompt never shows an error.
+pub fn prompt_cmd(
+    connection: &Connection,
+    name: String,
+    segment: crate::segment::Segment,
+    color: bool,
+) -> Result<String> {
+    Ok(segment.render(current_value(connection, &name)?.as_deref(), color))
+}
+
+/// Copy the entries matching the glob `pattern` from the db at `path`
+///
+/// The other db is only read, so it may also be one written by an older version of config-store.
+/// Secret entries are copied with their secrets in the keyring. See [ConflictPolicy] for what
+/// happens to entries which exist in both dbs. Will return [Error::EntryExists] for
+/// [ConflictPolicy::Fail] if any entry already exists.
+pub fn copy_from_cmd(
+    connection: &Connection,
+    path: std::path::PathBuf,
+    pattern: Option<String>,
+    policy: ConflictPolicy,
+    force: bool,
+) -> Result<String> {
+    let other = Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+    let existing = other
+        .prepare("SELECT name FROM pragma_table_info('data')")?
+        .query_map((), |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    // Columns added after the other db was last opened are missing from it
+    let columns = Entry::COLUMNS
+        .split(", ")
+        .map(|c| {
+            if existing.iter().any(|e| e == c) {
+                c.to_string()
+            } else {
+                format!("NULL AS {}", c)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    let entries = other
+        .prepare(&format!(
+            "SELECT {} FROM data WHERE ?1 IS NULL OR name GLOB ?1 ORDER BY id",
+            columns
+        ))?
+        .query_map([&pattern], Entry::from_row)?
+        .collect::<rusqlite::Result<Vec<Entry>>>()?;
+    let secrets = if existing.iter().any(|c| c == "secret") {
+        other
+            .prepare("SELECT name FROM data WHERE (?1 IS NULL OR name GLOB ?1) AND secret")?
+            .query_map([&pattern], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?
+    } else {
+        Vec::new()
+    };
+
+    transaction(connection, || {
+        let (mut copied, mut skipped) = (0, 0);
+
+        for entry in entries {
+            if exists(connection, &entry.name)? {
+                match policy {
+                    ConflictPolicy::Skip => {
+                        skipped += 1;
+                        continue;
+                    }
+                    ConflictPolicy::Newer
+                        if entry.updated_at <= select(connection, &entry.name)?.updated_at =>
+                    {
+                        skipped += 1;
+                        continue;
+                    }
+                    ConflictPolicy::Fail => return Err(Error::EntryExists),
+                    ConflictPolicy::Overwrite | ConflictPolicy::Newer if !force => check_protected(
+                        connection,
+                        "WHERE name = ?",
+                        vec![entry.name.clone().into()],
+                    )?,
+                    ConflictPolicy::Overwrite | ConflictPolicy::Newer => {}
+                }
+            }
+
+            set(
+                connection,
+                &entry.name,
+                Some(entry.value),
+                entry.alternate,
+                entry.description,
+                entry.default,
+                false,
+            )?;
+            // The secrets of the other db are stored under its path
+            if secrets.contains(&entry.name) {
+                let secret = crate::keyring::lookup(&other, &entry.name)?;
+                connection.execute(
+                    "UPDATE data SET secret = TRUE WHERE name = ?",
+                    [&entry.name],
+                )?;
+                crate::keyring::store(connection, &entry.name, &secret)?;
+            }
+            copied += 1;
+        }
+
+        Ok(format!("Copied {} entries, skipped {}", copied, skipped))
+    })
+}
+
+/// Replace the prefix `old` of all entry names with `new`
+///
+/// Aliases pointing at renamed entries & the secrets of secret entries in the keyring are moved as
+/// well. Will return [Error::EntryExists] (and rename nothing) if a new name is already used by an
+/// entry which isn't renamed itself.
+pub fn rename_prefix_cmd(connection: &Connection, old: String, new: String) -> Result<String> {
+    transaction(connection, || {
+        let conflicts: i64 = connection.query_row(
+            "SELECT COUNT(*) FROM data AS renamed WHERE instr(renamed.name, ?1) = 1
+                AND EXISTS (
+                    SELECT 1 FROM data WHERE name = ?2 || substr(renamed.name, length(?1) + 1)
+                        AND instr(name, ?1) != 1
+                )",
+            (&old, &new),
+            |row| row.get(0),
+        )?;
+
+        if conflicts > 0 {
+            return Err(Error::EntryExists);
+        }
+
+        let secrets = connection
+            .prepare("SELECT name FROM data WHERE instr(name, ?) = 1 AND secret")?
+            .query_map([&old], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+
+        let renamed = connection.execute(
+            "UPDATE data SET name = ?2 || substr(name, length(?1) + 1), updated_at = ?3
+                WHERE instr(name, ?1) = 1",
+            (&old, &new, time::now()),
+        )?;
+        connection.execute(
+            "UPDATE aliases SET target = ?2 || substr(target, length(?1) + 1)
+                WHERE instr(target, ?1) = 1",
+            (&old, &new),
+        )?;
+        // Moved last, so the db is rolled back if the keyring isn't available
+        for name in secrets {
+            crate::keyring::rename(connection, &name, &format!("{}{}", new, &name[old.len()..]))?;
+        }
+
+        Ok(format!("Renamed {} entries", renamed))
+    })
+}
+
+/// Apply the regex `substitution` to the values of all entries matching the glob `pattern`
+///
+/// All entries are changed in a single transaction. Entries whose value doesn't change aren't
+/// updated, so they don't show up in the audit log.
+pub fn replace_cmd(
+    connection: &Connection,
+    substitution: Substitution,
+    pattern: Option<String>,
+) -> Result<String> {
+    transaction(connection, || {
+        let entries = connection
+            .prepare("SELECT id, value FROM data WHERE ?1 IS NULL OR name GLOB ?1")?
+            .query_map([pattern], |row| Ok((row.get::<_, i64>(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<(i64, String)>>>()?;
+
+        let mut changed = 0;
+        for (id, value) in entries {
+            let new = substitution.apply(&value);
+
+            if new != value {
+                connection.execute(
+                    "UPDATE data SET value = ?, updated_at = ? WHERE id = ?",
+                    (new, time::now(), id),
+                )?;
+                changed += 1;
+            }
+        }
+
+        Ok(format!("Changed {} entries", changed))
+    })
+}
+
+/// Show all entries matching the [Filter] as a tree, splitting their names at `.`
+///
+/// Branches deeper than `depth` are collapsed into the number of entries they contain.
+pub fn tree_cmd(connection: &Connection, depth: Option<usize>, filter: Filter) -> Result<String> {
+    let mut root = Node::default();
+
+    for entry in select_filtered(connection, &filter)? {
+        let node = entry.name.split('.').fold(&mut root, |node, segment| {
+            node.children.entry(segment.to_string()).or_default()
+        });
+        node.value = Some(entry.value);
+    }
+
+    let mut output = String::new();
+    root.render(0, depth, &mut output);
+
+    Ok(output)
+}
+
+/// A node in the tree built by [tree_cmd]
+#[derive(Default)]
+struct Node {
+    /// The value of the entry ending at this node, if there is one
+    value: Option<String>,
+    children: std::collections::BTreeMap<String, Node>,
+}
+
+impl Node {
+    /// The number of entries in this node & all of its children
+    fn count(&self) -> usize {
+        self.value.iter().count() + self.children.values().map(Node::count).sum::<usize>()
+    }
+
+    /// Helper function writing the children of this node at the given depth to `output`
+    fn render(&self, depth: usize, max_depth: Option<usize>, output: &mut String) {
+        for (segment, child) in &self.children {
+            write!(output, "{}{}", "  ".repeat(depth), segment).unwrap();
+            if let Some(value) = &child.value {
+                write!(output, " = {}", value).unwrap();
+            }
+
+            let collapsed = max_depth.is_some_and(|max| depth + 1 >= max);
+            if collapsed && !child.children.is_empty() {
+                let hidden = child.count() - child.value.iter().count();
+                writeln!(output, " (+{})", hidden).unwrap();
+            } else {
+                output.push('\n');
+                child.render(depth + 1, max_depth, output);
+            }
+        }
+    }
+}
+
+/// Export all entries matching the [Filter] in the given [ExportFormat], writing them to `out`
+///
+/// Like [list_cmd], entries are written as soon as they're read from the db.
+pub fn export_cmd(
+    connection: &Connection,
+    out: &mut impl std::io::Write,
+    format: ExportFormat,
+    filter: Filter,
+) -> Result<()> {
+    let (clause, params) = filter.sql();
+    let mut statement = connection.prepare(&format!(
+        "SELECT {} FROM {} {}",
+        Entry::COLUMNS,
+        layers::source(connection)?,
+        clause
+    ))?;
+    let entries = statement.query_map(rusqlite::params_from_iter(params), Entry::from_row)?;
+
+    ignore_broken_pipe((|| {
+        match format {
+            ExportFormat::Json => {
+                write!(out, "[")?;
+                for (i, entry) in entries.enumerate() {
+                    let entry = entry?;
+                    let meta = metadata(connection, entry._id)?;
+                    write!(
+                        out,
+                        "{}{}",
+                        if i > 0 { "," } else { "" },
+                        entry.json_with_meta(&meta, None)
+                    )?;
+                }
+                writeln!(out, "]")?;
+            }
+            ExportFormat::Script => {
+                write!(out, "#!/bin/sh\nset -e\n")?;
+
+                let mut tags = connection
+                    .prepare_cached("SELECT tag FROM tags WHERE entry_id = ? ORDER BY tag")?;
+
+                for entry in entries {
+                    let entry = entry?;
+
+                    // Values & names starting with `-` mustn't be read as options
+                    write!(
+                        out,
+                        "config-store set --value={}",
+                        shell_quote(&entry.value)
+                    )?;
+                    if let Some(alternate) = &entry.alternate {
+                        write!(out, " --alternate={}", shell_quote(alternate))?;
+                    }
+                    if let Some(description) = &entry.description {
+                        write!(out, " --description={}", shell_quote(description))?;
+                    }
+                    if let Some(default) = &entry.default {
+                        write!(out, " --default={}", shell_quote(default))?;
+                    }
+                    writeln!(out, " -- {}", shell_quote(&entry.name))?;
+
+                    let tags = tags
+                        .query_map([entry._id], |row| row.get::<_, String>(0))?
+                        .collect::<rusqlite::Result<Vec<String>>>()?;
+                    if !tags.is_empty() {
+                        writeln!(
+                            out,
+                            "config-store tag add -- {} {}",
+                            shell_quote(&entry.name),
+                            tags.iter()
+                                .map(|t| shell_quote(t))
+                                .collect::<Vec<String>>()
+                                .join(" ")
+                        )?;
+                    }
+
+                    for (key, value) in metadata(connection, entry._id)? {
+                        writeln!(
+                            out,
+                            "config-store meta set -- {} {} {}",
+                            shell_quote(&entry.name),
+                            shell_quote(&key),
+                            shell_quote(&value)
+                        )?;
+                    }
+                }
+            }
+            ExportFormat::SystemdEnv => {
+                for entry in entries {
+                    let entry = entry?;
+                    writeln!(out, "{}={}", env_name(&entry.name), env_quote(&entry.value))?;
+                }
+            }
+        }
+
+        Ok(())
+    })())
+}
+
+/// Write the whole db to a compressed archive at `path`, see [crate::archive]
+#[cfg(feature = "archive")]
+pub fn export_archive_cmd(connection: &Connection, path: std::path::PathBuf) -> Result<String> {
+    crate::archive::create(connection, &path)?;
+
+    Ok(format!("Exported to {}", path.display()))
+}
+
+/// Replace the whole db with the archive at `path` after checking it, see [crate::archive]
+///
+/// Unless `force` is set, nothing is replaced if any of the entries is protected.
+#[cfg(feature = "archive")]
+pub fn import_archive_cmd(
+    connection: &Connection,
+    path: std::path::PathBuf,
+    force: bool,
+) -> Result<String> {
+    if !force {
+        check_protected(connection, "", vec![])?;
+    }
+
+    crate::archive::restore(connection, &path)?;
+
+    Ok(format!("Imported {}", path.display()))
+}
+
+/// Returns wrapper functions for `shell` to `eval` in its rc file, calling this binary on the db
+/// at `db_path`
+///
+/// `cs-get` prints the value of an entry, `cs-toggle` toggles entries & `cs-env` exports all
+/// entries as environment variables (named like those of [exec_env] behind `prefix`). The exports
+/// are cached in `$XDG_CACHE_HOME/config-store` until the db changes, see [shell_env_cmd].
+pub fn shell_init_cmd(db_path: &str, shell: InitShell, prefix: &str) -> String {
+    let binary = std::env::current_exe().map_or("config-store".to_string(), |b| {
+        b.to_string_lossy().into_owned()
+    });
+    let db_path = crate::plugin::db_path(db_path);
+    let name = shell.to_possible_value().expect("no skipped shells");
+    let name = name.get_name();
+    // One cache per db & prefix
+    let cache = format!(
+        "config-store/env-{}.{}",
+        &blob::hash(format!("{}\0{}", db_path, prefix).as_bytes())[..12],
+        name
+    );
+
+    if shell == InitShell::Fish {
+        let quote = |s: &str| crate::fish::quote(s);
+        let command = format!("{} --db-path {}", quote(&binary), quote(&db_path));
+
+        return format!(
+            "# config-store functions, generated by `config-store shell-init fish`
+function cs-get
+    {0} get -v $argv
+end
+
+function cs-toggle
+    {0} toggle $argv
+end
+
+function cs-env
+    set -l cache (set -q XDG_CACHE_HOME[1]; and echo $XDG_CACHE_HOME; or echo $HOME/.cache)/{1}
+    set -l changed (path mtime -- {2} {3} | sort -n | tail -n 1)
+    if not test -f $cache; or test \"$changed\" -ge (path mtime -- $cache)
+        mkdir -p (path dirname -- $cache)
+        and touch $cache.tmp
+        and chmod 600 $cache.tmp
+        and {0} shell-init fish --env --prefix {4} >$cache.tmp
+        and mv -- $cache.tmp $cache
+        or return
+    end
+    source $cache
+end",
+            command,
+            cache,
+            quote(&db_path),
+            quote(&format!("{}-wal", db_path)),
+            quote(prefix)
+        );
+    }
+
+    let quote = |s: &str| shell_quote(s);
+    let command = format!("{} --db-path {}", quote(&binary), quote(&db_path));
+
+    format!(
+        "# config-store functions, generated by `config-store shell-init {5}`
+cs-get() {{
+    {0} get -v \"$@\"
+}}
+
+cs-toggle() {{
+    {0} toggle \"$@\"
+}}
+
+cs-env() {{
+    local cache=\"${{XDG_CACHE_HOME:-$HOME/.cache}}/{1}\"
+    if [ ! -f \"$cache\" ] || [ {2} -nt \"$cache\" ] || [ {3} -nt \"$cache\" ]; then
+        mkdir -p \"${{cache%/*}}\" &&
+            (umask 077 && {0} shell-init {5} --env --prefix {4} >\"$cache.tmp\") &&
+            mv -- \"$cache.tmp\" \"$cache\" || return
+    fi
+    . \"$cache\"
+}}",
+        command,
+        cache,
+        quote(&db_path),
+        quote(&format!("{}-wal", db_path)),
+        quote(prefix),
+        name
+    )
+}
+
+/// Returns the commands of `shell` exporting all entries as environment variables, which `cs-env`
+/// of [shell_init_cmd] caches
+pub fn shell_env_cmd(connection: &Connection, shell: InitShell, prefix: &str) -> Result<String> {
+    Ok(exec_env(connection, None, prefix)?
+        .into_iter()
+        .map(|(name, value)| match shell {
+            InitShell::Fish => format!("set -gx {} {}", name, crate::fish::quote(&value)),
+            _ => format!("export {}={}", name, shell_quote(&value)),
+        })
+        .collect::<Vec<String>>()
+        .join("\n"))
+}
+
+/// Helper function quoting a string for use as a single argument in a POSIX shell
+fn shell_quote(string: &str) -> String {
+    format!("'{}'", string.replace('\'', r"'\''"))
+}
+
+/// List the entries matching the [Filter] as `name: value` lines for dmenu / rofi, sorted by name
+///
+/// With `toggle` only entries with an alternate are listed, since the others can't be toggled
+/// meaningfully. See [menu_select_cmd] for acting on the selected line.
+pub fn menu_cmd(connection: &Connection, filter: Filter, toggle: bool) -> Result<String> {
+    let mut entries = select_filtered(connection, &filter)?;
+    entries.retain(|e| !toggle || e.alternate.as_ref().is_some_and(|a| !a.is_empty()));
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(entries
+        .iter()
+        .map(|e| format!("{}: {}", e.name, e.value.replace('\n', " ")))
+        .collect::<Vec<String>>()
+        .join("\n"))
+}
+
+/// Act on a line selected from the output of [menu_cmd], `-` reads it from stdin
+///
+/// With `toggle` the entry is toggled, otherwise the text after `: ` becomes its value. An empty
+/// selection (e.g. a closed menu) does nothing. Returns the new value.
+pub fn menu_select_cmd(connection: &Connection, line: String, toggle: bool) -> Result<String> {
+    let line = if line == "-" {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        line
+    };
+    let line = line.trim_end_matches(['\n', '\r']);
+    if line.is_empty() {
+        return Ok(String::new());
+    }
+
+    let (name, value) = line.split_once(": ").unwrap_or((line, ""));
+
+    if toggle {
+        return toggle_cmd(
+            connection,
+            vec![name.to_string()],
+            Filter::default(),
+            None,
+            None,
+            false,
+        );
+    }
+
+    set_cmd(
+        connection,
+        name.to_string(),
+        Some(value.to_string()),
+        None,
+        None,
+        None,
+        SetMode::ChangeOnly,
+    )?;
+
+    Ok(value.to_string())
+}
+
+/// Append the numeric reading `value` to the samples of the [Entry] `name` & make it its value
+///
+/// The entry is created if it doesn't exist. Will return [Error::Invalid] if `value` isn't a
+/// number.
+pub fn record_cmd(connection: &Connection, name: String, value: String) -> Result<String> {
+    let Ok(number) = value.trim().parse::<f64>() else {
+        return Err(Error::Invalid(format!(
+            "the reading '{}' isn't a number",
+            value
+        )));
+    };
+
+    transaction(connection, || {
+        let name = resolve(connection, &name)?;
+        set(connection, &name, Some(value), None, None, None, false)?;
+
+        connection.execute(
+            "INSERT INTO samples (entry_id, recorded_at, value)
+                SELECT id, ?, ? FROM data WHERE name = ?",
+            (time::now(), number, &name),
+        )?;
+
+        Ok("Ok".to_string())
+    })
+}
+
+/// Print the number, minimum, maximum & average of the samples of the [Entry] `name`
+///
+/// With `last`, only the samples of the last `last` seconds are used.
+pub fn record_stats_cmd(
+    connection: &Connection,
+    name: String,
+    last: Option<i64>,
+) -> Result<String> {
+    let entry = select(connection, &resolve(connection, &name)?)?;
+
+    let (count, min, max, avg): (i64, Option<f64>, Option<f64>, Option<f64>) = connection
+        .query_row(
+            "SELECT COUNT(*), MIN(value), MAX(value), AVG(value) FROM samples
+                WHERE entry_id = ? AND recorded_at >= ?",
+            (entry._id, last.map_or(i64::MIN, |last| time::now() - last)),
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )?;
+
+    let mut output = format!("count: {}", count);
+    if let (Some(min), Some(max), Some(avg)) = (min, max, avg) {
+        write!(output, "\nmin: {}\nmax: {}\navg: {}", min, max, avg).unwrap();
+    }
+
+    Ok(output)
+}
+
+/// Print the samples of the [Entry] `name` as CSV with a `timestamp,value` header, oldest first
+///
+/// With `last`, only the samples of the last `last` seconds are printed.
+pub fn record_export_cmd(
+    connection: &Connection,
+    name: String,
+    last: Option<i64>,
+) -> Result<String> {
+    let entry = select(connection, &resolve(connection, &name)?)?;
+
+    let mut statement = connection.prepare(
+        "SELECT recorded_at, value FROM samples WHERE entry_id = ? AND recorded_at >= ?
+            ORDER BY recorded_at, rowid",
+    )?;
+    let mut rows =
+        statement.query((entry._id, last.map_or(i64::MIN, |last| time::now() - last)))?;
+
+    let mut output = "timestamp,value".to_string();
+    while let Some(row) = rows.next()? {
+        write!(
+            output,
+            "\n{},{}",
+            time::format(row.get(0)?),
+            row.get::<_, f64>(1)?
+        )
+        .unwrap();
+    }
+
+    Ok(output)
+}
+
+/// Take a token from the rate limit `name`, which allows `max` calls per `per` seconds
+///
+/// The limit is a token bucket: it starts full & is refilled continuously, so bursts of up to
+/// `max` calls are allowed. Returns the number of tokens left & [Error::RateLimited] if the bucket
+/// is empty, in which case no token is taken.
+pub fn ratelimit_cmd(connection: &Connection, name: String, max: u32, per: i64) -> Result<String> {
+    if per <= 0 {
+        return Err(Error::Invalid(
+            "the interval has to be positive".to_string(),
+        ));
+    }
+    let max = f64::from(max);
+    let millis_per_token = per as f64 * 1000.0 / max;
+
+    transaction(connection, || {
+        let now = time::now_millis();
+
+        // Writing first locks the db, so concurrent calls can't take the same token
+        connection.execute(
+            "INSERT INTO ratelimits (name, tokens, updated_at) VALUES (?, ?, ?)
+                ON CONFLICT (name) DO NOTHING",
+            (&name, max, now),
+        )?;
+        let (tokens, updated_at): (f64, i64) = connection.query_row(
+            "SELECT tokens, updated_at FROM ratelimits WHERE name = ?",
+            [&name],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+
+        let tokens = (tokens + (now - updated_at).max(0) as f64 / millis_per_token).min(max);
+        if tokens < 1.0 {
+            let wait = ((1.0 - tokens) * millis_per_token / 1000.0).ceil() as i64;
+            return Err(Error::RateLimited(wait.max(1)));
+        }
+
+        connection.execute(
+            "UPDATE ratelimits SET tokens = ?, updated_at = ? WHERE name = ?",
+            (tokens - 1.0, now, &name),
+        )?;
+
+        Ok(((tokens - 1.0).floor() as i64).to_string())
+    })
+}
+
+/// Starts the stopwatch `name` from zero, restarting it if it's already running, see `timer`
+///
+/// Stopwatches are independent of the entries & measured in milliseconds.
+pub fn timer_start_cmd(connection: &Connection, name: String) -> Result<String> {
+    connection.execute(
+        "INSERT INTO timers (name, started_at, elapsed) VALUES (?1, ?2, 0)
+            ON CONFLICT (name) DO UPDATE SET started_at = ?2, elapsed = 0",
+        (&name, time::now_millis()),
+    )?;
+
+    Ok("Ok".to_string())
+}
+
+/// Stops the stopwatch `name` & prints how long it ran like [timer_show_cmd]
+///
+/// Stopping a stopped stopwatch leaves it as it is.
+pub fn timer_stop_cmd(connection: &Connection, name: String, seconds: bool) -> Result<String> {
+    transaction(connection, || {
+        connection.execute(
+            "UPDATE timers SET elapsed = elapsed + ?2 - started_at, started_at = NULL
+                WHERE name = ?1 AND started_at IS NOT NULL",
+            (&name, time::now_millis()),
+        )?;
+
+        timer_show_cmd(connection, name, seconds)
+    })
+}
+
+/// Prints how long the stopwatch `name` has run (until now if it's still running), e.g. `25m3s`
+/// or with `seconds` only the number of whole seconds
+///
+/// Will return [Error::NotFound] if it was never started.
+pub fn timer_show_cmd(connection: &Connection, name: String, seconds: bool) -> Result<String> {
+    let elapsed: i64 = connection
+        .query_row(
+            "SELECT elapsed + IFNULL(?2 - started_at, 0) FROM timers WHERE name = ?1",
+            (&name, time::now_millis()),
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or_else(|| Error::NotFound(format!("timer '{}'", name)))?;
+    let elapsed = elapsed.max(0) / 1000;
+
+    Ok(if seconds {
+        elapsed.to_string()
+    } else {
+        time::format_duration(elapsed)
+    })
+}
+
+/// Binds an [Entry] to the session `token`, or the current login session, see [crate::session]
+pub fn session_bind_cmd(
+    connection: &Connection,
+    name: String,
+    token: Option<String>,
+) -> Result<String> {
+    crate::session::bind(connection, &name, token)?;
+
+    Ok("Ok".to_string())
+}
+
+/// Lists the sessions entries are bound to, with the number of their entries
+pub fn session_list_cmd(connection: &Connection) -> Result<String> {
+    let mut statement = connection.prepare(
+        "SELECT session, COUNT(*) FROM data WHERE session IS NOT NULL
+            GROUP BY session ORDER BY session",
+    )?;
+    let mut rows = statement.query(())?;
+
+    let mut output = String::new();
+    while let Some(row) = rows.next()? {
+        writeln!(
+            output,
+            "{}: {} entries",
+            row.get::<_, String>(0)?,
+            row.get::<_, i64>(1)?
+        )
+        .unwrap();
+    }
+
+    Ok(output.trim_end().to_string())
+}
+
+/// Ends the session `token` (or the current login session), deleting its entries
+pub fn session_end_cmd(connection: &Connection, token: Option<String>) -> Result<String> {
+    let token = match token {
+        Some(token) => token,
+        None => crate::session::current()?,
+    };
+
+    Ok(format!(
+        "Deleted {} entries",
+        crate::session::end(connection, &token)?
+    ))
+}
+
+/// Binds an [Entry] to the file at `path`, syncing them in `direction`, see [crate::mirror]
+pub fn bind_cmd(
+    connection: &Connection,
+    name: String,
+    path: std::path::PathBuf,
+    direction: crate::mirror::Direction,
+) -> Result<String> {
+    crate::mirror::bind(connection, &name, &path, direction)?;
+
+    Ok("Ok".to_string())
+}
+
+/// Removes the binding of an [Entry] to a file, keeping the file
+pub fn unbind_cmd(connection: &Connection, name: String) -> Result<String> {
+    if crate::mirror::unbind(connection, &name)? {
+        Ok("Ok".to_string())
+    } else {
+        Err(Error::Invalid(format!("'{}' isn't bound to a file", name)))
+    }
+}
+
+/// Lists the bindings of entries to files as `name -> path (direction)` lines
+pub fn bindings_cmd(connection: &Connection) -> Result<String> {
+    Ok(crate::mirror::list(connection)?
+        .iter()
+        .map(|b| {
+            format!(
+                "{} -> {} ({})",
+                b.name,
+                b.path.display(),
+                b.direction.name()
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n"))
+}
+
+/// Syncs the bindings of entries to files every `interval` milliseconds until interrupted (or
+/// `timeout` seconds have passed)
+pub fn bind_watch_cmd(
+    connection: &Connection,
+    interval: u64,
+    timeout: Option<i64>,
+) -> Result<String> {
+    let deadline =
+        timeout.map(|t| std::time::Instant::now() + std::time::Duration::from_secs(t as u64));
+
+    loop {
+        crate::mirror::sync(connection)?;
+
+        if deadline.is_some_and(|d| std::time::Instant::now() >= d) {
+            return Err(Error::Timeout);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(interval));
+    }
+}
+
+/// Stores the contents of `file` (stdin for `-`) as a blob & returns its hash, see [crate::blob]
+///
+/// With `name` the entry is set to reference the blob as well, creating it if needed.
+pub fn blob_put_cmd(
+    connection: &Connection,
+    file: std::path::PathBuf,
+    name: Option<String>,
+) -> Result<String> {
+    let data = if file.as_os_str() == "-" {
+        let mut data = Vec::new();
+        std::io::Read::read_to_end(&mut std::io::stdin().lock(), &mut data)?;
+        data
+    } else {
+        std::fs::read(&file)?
+    };
+
+    transaction(connection, || {
+        let hash = blob::put(connection, &data)?;
+        if let Some(name) = &name {
+            let value = format!("{}{}", blob::PREFIX, hash);
+            set(connection, name, Some(value), None, None, None, false)?;
+        }
+
+        Ok(hash)
+    })
+}
+
+/// Writes the blob `hash` to `output` or stdout
+///
+/// `hash` may also be a reference like `blob:HASH` or the name of an entry with such a value. Will
+/// return [Error::NotFound] if there is no such blob.
+pub fn blob_get_cmd(
+    connection: &Connection,
+    hash: String,
+    output: Option<std::path::PathBuf>,
+) -> Result<String> {
+    let name = resolve(connection, &hash)?;
+    let data = match blob::get(connection, &hash) {
+        Err(Error::NotFound(_)) if exists(connection, &name)? => {
+            let entry = select(connection, &name)?;
+            if !entry.value.starts_with(blob::PREFIX) {
+                return Err(Error::Invalid(format!(
+                    "'{}' doesn't reference a blob",
+                    entry.name
+                )));
+            }
+            blob::get(connection, &entry.value)?
+        }
+        result => result?,
+    };
+
+    match output {
+        Some(path) => {
+            std::fs::write(&path, data)?;
+            Ok(format!("Written to {}", path.display()))
+        }
+        None => {
+            ignore_broken_pipe(
+                std::io::Write::write_all(&mut std::io::stdout(), &data).map_err(Error::from),
+            )?;
+            Ok(String::new())
+        }
+    }
+}
+
+/// Deletes the blobs no entry references anymore, see [blob::gc]
+pub fn blob_gc_cmd(connection: &Connection) -> Result<String> {
+    Ok(format!("Deleted {} blobs", blob::gc(connection)?))
+}
+
+/// Runs `command` with the values of the entries matching the glob `pattern` (or all entries) as
+/// environment variables, see [exec_env]
+///
+/// On unix config-store is replaced by the command, so its exit code & signals are those of the
+/// command. Will return [Error::Io] if the command can't be run.
+pub fn exec_cmd(
+    connection: &Connection,
+    pattern: Option<String>,
+    prefix: String,
+    command: Vec<String>,
+) -> Result<String> {
+    let Some((program, args)) = command.split_first() else {
+        return Err(Error::Invalid("no command given".to_string()));
+    };
+
+    let mut child = std::process::Command::new(program);
+    child
+        .args(args)
+        .envs(exec_env(connection, pattern, &prefix)?);
+
+    #[cfg(unix)]
+    let error = std::os::unix::process::CommandExt::exec(&mut child);
+    #[cfg(not(unix))]
+    let error = match child.status() {
+        Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+        Err(error) => error,
+    };
+
+    Err(std::io::Error::new(
+        error.kind(),
+        format!("failed to run {}: {}", program, error),
+    )
+    .into())
+}
+
+/// Mirror the entries matching the glob `pattern` (or all entries) into universal variables of
+/// fish (named like those of [exec_env]), or with `back` the variables into the entries
+///
+/// Only variables whose value differs are set, so fish's event handlers only run for changes. With
+/// a `prefix`, variables with it whose entry doesn't match (anymore) are erased. With `watch` the
+/// variables are updated every `interval` milliseconds after changes until `timeout` (seconds), see
+/// [listen_cmd]. Going back only changes existing entries, variables which aren't set are skipped.
+/// Fish isn't changed during a dry run.
+pub fn fish_sync_cmd(
+    connection: &Connection,
+    pattern: Option<String>,
+    prefix: String,
+    back: bool,
+    watch: bool,
+    interval: u64,
+    timeout: Option<i64>,
+) -> Result<String> {
+    let filter = Filter {
+        glob: pattern.clone(),
+        ..Default::default()
+    };
+
+    if back {
+        let entries = select_filtered(connection, &filter)?;
+        let names = entries
+            .iter()
+            .map(|e| format!("{}{}", prefix, env_name(&e.name)))
+            .collect::<Vec<String>>();
+        let values = crate::fish::get(&names)?;
+
+        return transaction(connection, || {
+            let mut updated = 0;
+            for (entry, value) in entries.into_iter().zip(values) {
+                match value {
+                    Some(value) if value != entry.value => {
+                        let mode = SetMode::IfValue(entry.value);
+                        set_cmd(connection, entry.name, Some(value), None, None, None, mode)?;
+                        updated += 1;
+                    }
+                    _ => {}
+                }
+            }
+
+            Ok(format!("Updated {} entries", updated))
+        });
+    }
+
+    let deadline =
+        timeout.map(|t| std::time::Instant::now() + std::time::Duration::from_secs(t as u64));
+    let mut last = crate::audit::last_id(connection)?;
+
+    let sync = || -> Result<String> {
+        let vars = exec_env(connection, pattern.clone(), &prefix)?;
+        let names = vars.iter().map(|(n, _)| n.clone()).collect::<Vec<String>>();
+        let current = crate::fish::get(&names)?;
+
+        let changed = vars
+            .into_iter()
+            .zip(current)
+            .filter(|((_, value), current)| current.as_ref() != Some(value))
+            .map(|(var, _)| var)
+            .collect::<Vec<(String, String)>>();
+        let erase = if prefix.is_empty() {
+            Vec::new()
+        } else {
+            crate::fish::names(&prefix)?
+                .into_iter()
+                .filter(|name| !names.contains(name))
+                .collect()
+        };
+        if !store::dry_run(connection)? {
+            crate::fish::set(&changed, &erase)?;
+        }
+
+        Ok(format!(
+            "Set {} & erased {} variables",
+            changed.len(),
+            erase.len()
+        ))
+    };
+
+    let result = sync()?;
+    if !watch {
+        return Ok(result);
+    }
+
+    loop {
+        if deadline.is_some_and(|d| std::time::Instant::now() >= d) {
+            return Err(Error::Timeout);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(interval));
+
+        let (newest, changes) = changes_since(connection, last, None)?;
+        last = newest;
+        if !changes.is_empty() {
+            sync()?;
+        }
+    }
+}
+
+/// Mirror the entries matching the glob `pattern` (or all entries) into the global environment of
+/// tmux (named like the variables of [exec_env]), or with `options` into user options named
+/// `@` & `prefix` & the name of the entry
+///
+/// With a `prefix`, variables (or options) with it whose entry doesn't match (anymore) are unset.
+/// With `watch` they are updated every `interval` milliseconds after changes until `timeout`
+/// (seconds), see [listen_cmd]. Tmux isn't changed during a dry run.
+pub fn tmux_sync_cmd(
+    connection: &Connection,
+    pattern: Option<String>,
+    prefix: String,
+    options: bool,
+    watch: bool,
+    interval: u64,
+    timeout: Option<i64>,
+) -> Result<String> {
+    let deadline =
+        timeout.map(|t| std::time::Instant::now() + std::time::Duration::from_secs(t as u64));
+    let mut last = crate::audit::last_id(connection)?;
+
+    let sync = || -> Result<String> {
+        let vars = if options {
+            let filter = Filter {
+                glob: pattern.clone(),
+                ..Default::default()
+            };
+            select_filtered(connection, &filter)?
+                .into_iter()
+                .map(|entry| (format!("@{}{}", prefix, entry.name), entry.value))
+                .collect()
+        } else {
+            exec_env(connection, pattern.clone(), &prefix)?
+        };
+        let unset = if prefix.is_empty() {
+            Vec::new()
+        } else {
+            let prefix = format!("{}{}", if options { "@" } else { "" }, prefix);
+            crate::tmux::names(&prefix, options)?
+                .into_iter()
+                .filter(|name| !vars.iter().any(|(n, _)| n == name))
+                .collect()
+        };
+        if !store::dry_run(connection)? {
+            crate::tmux::set(&vars, &unset, options)?;
+        }
+
+        Ok(format!(
+            "Set {} & unset {} {}",
+            vars.len(),
+            unset.len(),
+            if options { "options" } else { "variables" }
+        ))
+    };
+
+    let result = sync()?;
+    if !watch {
+        return Ok(result);
+    }
+
+    loop {
+        if deadline.is_some_and(|d| std::time::Instant::now() >= d) {
+            return Err(Error::Timeout);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(interval));
+
+        let (newest, changes) = changes_since(connection, last, None)?;
+        last = newest;
+        if !changes.is_empty() {
+            sync()?;
+        }
+    }
+}
+
+/// Returns the environment variables `exec` sets for the entries matching the glob `pattern`
+///
+/// The variables are named after the entries in upper case behind `prefix`, with characters other
+/// than letters, digits & `_` replaced by `_`, e.g. `vpn.home` becomes `CS_VPN_HOME`.
+pub fn exec_env(
+    connection: &Connection,
+    pattern: Option<String>,
+    prefix: &str,
+) -> Result<Vec<(String, String)>> {
+    let filter = Filter {
+        glob: pattern,
+        ..Default::default()
+    };
+
+    Ok(select_filtered(connection, &filter)?
+        .into_iter()
+        .map(|entry| {
+            let name = env_name(&entry.name);
+
+            (format!("{}{}", prefix, name), entry.value)
+        })
+        .collect())
+}
+
+/// Helper function returning the name of the environment variable for the entry `name`, see
+/// [exec_env]
+fn env_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => c.to_ascii_uppercase(),
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Helper function quoting `value` for systemd environment files, if needed
+///
+/// Inside double quotes `\`, `"`, `$` & `` ` `` are escaped, the latter two since `environment.d`
+/// expands variables.
+fn env_quote(value: &str) -> String {
+    if !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-.,:/@+%".contains(c))
+    {
+        return value.to_string();
+    }
+
+    let mut quoted = String::from('"');
+    for c in value.chars() {
+        if matches!(c, '\\' | '"' | '$' | '`') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+/// Create a backup of the db, see [crate::backup]
+///
+/// The backups are copied to the configured remote afterwards, see [crate::remote].
+pub fn backup_create_cmd(connection: &Connection, keep: usize) -> Result<String> {
+    let Some(path) = crate::backup::create(connection, keep.max(1))? else {
+        return Ok("In memory dbs can't be backed up".to_string());
+    };
+
+    crate::backup::push(connection)?;
+
+    Ok(path.display().to_string())
+}
+
+/// Copy the backups to the configured remote, see [crate::remote]
+pub fn backup_push_cmd(connection: &Connection) -> Result<String> {
+    Ok(match crate::backup::push(connection)? {
+        Some(remote) => format!("Copied backups to {}", remote),
+        None => "Nothing to copy".to_string(),
+    })
+}
+
+/// Show or change the remote the backups are copied to
+pub fn backup_remote_cmd(
+    connection: &Connection,
+    remote: Option<crate::remote::Remote>,
+    off: bool,
+) -> Result<String> {
+    if off {
+        settings::set(connection, settings::BACKUP_REMOTE, None)?;
+        return Ok("Ok".to_string());
+    }
+
+    let Some(remote) = remote else {
+        return Ok(
+            settings::get(connection, settings::BACKUP_REMOTE)?.unwrap_or("none".to_string())
+        );
+    };
+
+    settings::set(
+        connection,
+        settings::BACKUP_REMOTE,
+        Some(&remote.to_string()),
+    )?;
+
+    Ok("Ok".to_string())
+}
+
+/// List all backups of the db, oldest first
+pub fn backup_list_cmd(connection: &Connection) -> Result<String> {
+    let Some(dir) = crate::backup::dir(connection) else {
+        return Ok(String::new());
+    };
+
+    Ok(crate::backup::list(&dir)?
+        .iter()
+        .fold(String::new(), |mut acc, p| {
+            let made = crate::backup::timestamp(p).map_or("unknown".to_string(), time::format);
+            writeln!(acc, "{} {}", made, p.display()).unwrap();
+            acc
+        }))
+}
+
+/// Replace all entries with those of a backup
+///
+/// Restores the most recent backup if `backup` is `None`. Will return [Error::NotFound] if there
+/// is no such backup. The current state is backed up first (keeping `keep` backups), so a restore
+/// can be undone by restoring again.
+pub fn backup_restore_cmd(
+    connection: &Connection,
+    backup: Option<std::path::PathBuf>,
+    keep: usize,
+) -> Result<String> {
+    let path = match backup {
+        Some(path) => path,
+        None => crate::backup::dir(connection)
+            .map(|dir| crate::backup::list(&dir))
+            .transpose()?
+            .and_then(|mut backups| backups.pop())
+            .ok_or(Error::NotFound("backup".to_string()))?,
+    };
+
+    crate::backup::create(connection, keep)?;
+    crate::backup::restore(connection, &path)?;
+
+    Ok(format!("Restored {}", path.display()))
+}
+
+/// Create a snapshot of the db labeled `label` (the current time by default), see [crate::backup]
+pub fn snapshot_create_cmd(connection: &Connection, label: Option<String>) -> Result<String> {
+    let label = label.unwrap_or_else(|| time::now().to_string());
+
+    crate::backup::snapshot(connection, &label)?;
+
+    Ok(label)
+}
+
+/// List all snapshots of the db, oldest first
+pub fn snapshot_list_cmd(connection: &Connection) -> Result<String> {
+    Ok(crate::backup::snapshots(connection)?.into_iter().fold(
+        String::new(),
+        |mut acc, (label, created)| {
+            writeln!(acc, "{} {}", time::format(created), label).unwrap();
+            acc
+        },
+    ))
+}
+
+/// Show the differences between the snapshots `from` & `to` (or the current state of the db)
+///
+/// The changes are described like in the audit log, see [LogEntry::summary], & highlighted if
+/// `color` is set. Will return
+/// [Error::NotFound] if either snapshot doesn't exist.
+pub fn snapshot_diff_cmd(
+    connection: &Connection,
+    from: String,
+    to: Option<String>,
+    json: bool,
+    color: bool,
+) -> Result<String> {
+    let open = |label: &str| -> Result<Connection> {
+        let path = crate::backup::snapshot_dir(connection)
+            .ok_or_else(|| Error::NotFound(format!("snapshot '{}'", label)))?
+            .join(format!("{}.db", label));
+
+        if !path.exists() {
+            return Err(Error::NotFound(format!("snapshot '{}'", label)));
+        }
+
+        Ok(Connection::open_with_flags(
+            path,
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+        )?)
+    };
+    let entries =
+        |connection: &Connection| -> Result<std::collections::BTreeMap<String, (String, String)>> {
+            Ok(connection
+                .prepare(&format!(
+                    "SELECT name, IFNULL(value, ''), IFNULL(alternate, '') FROM data
+                    WHERE name IS NOT NULL AND NOT {}",
+                    DUPLICATE
+                ))?
+                .query_map((), |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))?
+                .collect::<rusqlite::Result<_>>()?)
+        };
+
+    let old = entries(&open(&from)?)?;
+    let new = match to {
+        Some(to) => entries(&open(&to)?)?,
+        None => entries(connection)?,
+    };
+
+    let changes = old
+        .keys()
+        .chain(new.keys())
+        .collect::<std::collections::BTreeSet<&String>>()
+        .into_iter()
+        .filter(|name| old.get(*name) != new.get(*name))
+        .map(|name| {
+            let (old_value, old_alternate) = old.get(name).cloned().unzip();
+            let (new_value, new_alternate) = new.get(name).cloned().unzip();
+
+            LogEntry {
+                timestamp: time::now(),
+                command: None,
+                user: None,
+                process: None,
+                name: Some(name.clone()),
+                old_value,
+                old_alternate,
+                new_value,
+                new_alternate,
+            }
+        });
+
+    Ok(if json {
+        format!(
+            "[{}]",
+            changes
+                .map(LogEntry::json)
+                .collect::<Vec<String>>()
+                .join(",")
+        )
+    } else {
+        changes
+            .map(|c| c.summary(color))
+            .collect::<Vec<String>>()
+            .join("\n")
+    })
+}
+
+/// Replace all entries with those of the snapshot labeled `label`
+///
+/// Will return [Error::NotFound] if there is no such snapshot. Like [backup_restore_cmd], the
+/// current state is backed up first.
+pub fn snapshot_restore_cmd(connection: &Connection, label: String, keep: usize) -> Result<String> {
+    let path = crate::backup::snapshot_dir(connection)
+        .ok_or_else(|| Error::NotFound(format!("snapshot '{}'", label)))?
+        .join(format!("{}.db", label));
+
+    if !path.exists() {
+        return Err(Error::NotFound(format!("snapshot '{}'", label)));
+    }
+
+    crate::backup::create(connection, keep)?;
+    crate::backup::restore(connection, &path)?;
+
+    Ok(format!("Restored {}", label))
+}
+
+/// Show where the db is & what it contains
+///
+/// This is mostly useful for figuring out which db a script actually used.
+pub fn info_cmd(connection: &Connection, json: bool) -> Result<String> {
+    let path = connection
+        .path()
+        .filter(|p| !p.is_empty())
+        .unwrap_or(":memory:");
+    let schema_version: i32 = connection.pragma_query_value(None, "user_version", |r| r.get(0))?;
+    let journal_mode: String = connection.pragma_query_value(None, "journal_mode", |r| r.get(0))?;
+    let entries: i64 = connection.query_row("SELECT COUNT(*) FROM data", (), |r| r.get(0))?;
+    let size = std::fs::metadata(path).map_or(0, |m| m.len());
+    let version = env!("CARGO_PKG_VERSION");
+
+    Ok(if json {
+        format!(
+            r#"{{"path":"{}","schema_version":{},"entries":{},"size":{},"journal_mode":"{}","version":"{}"}}"#,
+            json_escape(path),
+            schema_version,
+            entries,
+            size,
+            journal_mode,
+            version
+        )
+    } else {
+        format!(
+            "path: {}\nschema version: {}\nentries: {}\nsize: {} bytes\njournal mode: {}\nversion: {}",
+            path, schema_version, entries, size, journal_mode, version
+        )
+    })
+}
+
+/// Show or change the git repository changes are committed to, see [crate::git]
+///
+/// Will return [Error::Invalid] if `repo` isn't a git repository.
+pub fn git_cmd(
+    connection: &Connection,
+    repo: Option<std::path::PathBuf>,
+    off: bool,
+) -> Result<String> {
+    if off {
+        settings::set(connection, settings::GIT_REPO, None)?;
+        return Ok("Ok".to_string());
+    }
+
+    let Some(repo) = repo else {
+        return Ok(settings::get(connection, settings::GIT_REPO)?.unwrap_or("none".to_string()));
+    };
+
+    crate::git::check(&repo)?;
+    let repo = std::path::absolute(repo)?;
+    settings::set(
+        connection,
+        settings::GIT_REPO,
+        Some(&repo.to_string_lossy()),
+    )?;
+
+    Ok("Ok".to_string())
+}
+
+/// Show or change the schema of the db, the manifest declaring the known entries (see
+/// [crate::manifest::declared])
+///
+/// The manifest is checked when it's set & has to match the existing entries (see
+/// [validate_cmd]).
+pub fn schema_cmd(
+    connection: &Connection,
+    file: Option<std::path::PathBuf>,
+    off: bool,
+) -> Result<String> {
+    if off {
+        settings::set(connection, settings::SCHEMA, None)?;
+        return Ok("Ok".to_string());
+    }
+
+    let Some(file) = file else {
+        return Ok(settings::get(connection, settings::SCHEMA)?.unwrap_or("none".to_string()));
+    };
+
+    let file = std::path::absolute(file)?;
+    validate_cmd(connection, file.clone())?;
+    settings::set(connection, settings::SCHEMA, Some(&file.to_string_lossy()))?;
+
+    Ok("Ok".to_string())
+}
+
+/// Turns signing entries on or off, see [crate::signing]
+pub fn sign_cmd(connection: &Connection, on: bool) -> Result<String> {
+    crate::signing::enable(connection, on)?;
+
+    Ok("Ok".to_string())
+}
+
+/// Checks the values of the entries matching the glob `pattern` (or all) against their
+/// checksums, or turns keeping checksums on or off, see [crate::checksum]
+///
+/// Will return [Error::Corrupted] naming the entries whose value doesn't match & [Error::Invalid]
+/// if checksums are off.
+pub fn verify_cmd(
+    connection: &Connection,
+    pattern: Option<String>,
+    checksums: Option<bool>,
+) -> Result<String> {
+    if let Some(on) = checksums {
+        checksum::enable(connection, on)?;
+        return Ok("Ok".to_string());
+    }
+
+    if !checksum::enabled(connection)? {
+        return Err(Error::Invalid(
+            "checksums are off, turn them on with `verify --checksums on`".to_string(),
+        ));
+    }
+
+    let filter = Filter {
+        glob: pattern,
+        ..Default::default()
+    };
+    let (mismatched, checked) = checksum::verify(connection, &filter)?;
+    if !mismatched.is_empty() {
+        return Err(Error::Corrupted(mismatched.join(", ")));
+    }
+
+    Ok(format!("Verified {} entries", checked))
+}
+
+/// Shows whether names are case-insensitive, or turns that on or off
+///
+/// While it's on, names refer to the existing entry with the same name in any case, see
+/// [canonical](crate::store::canonical). Entries whose names only differ in case are kept, but
+/// only the exact name reaches the others, see [case_duplicates_cmd].
+pub fn ignore_case_cmd(connection: &Connection, on: Option<bool>) -> Result<String> {
+    let Some(on) = on else {
+        let on = settings::get(connection, settings::IGNORE_CASE)?.is_some();
+        return Ok(if on { "on" } else { "off" }.to_string());
+    };
+
+    settings::set(connection, settings::IGNORE_CASE, on.then_some("on"))?;
+
+    Ok("Ok".to_string())
+}
+
+/// Shows the naming policy of the db, or changes the rules given in `policy`, see
+/// [crate::naming]
+///
+/// Will return [Error::Invalid] if the charset isn't a valid character class.
+pub fn naming_cmd(connection: &Connection, policy: naming::Policy, off: bool) -> Result<String> {
+    if off {
+        naming::Policy::clear(connection)?;
+        return Ok("Ok".to_string());
+    }
+    if policy == naming::Policy::default() {
+        return Ok(naming::Policy::get(connection)?.to_string());
+    }
+
+    policy.update(connection)?;
+
+    Ok("Ok".to_string())
+}
+
+/// Shows the size limits of values & alternates, or changes them, see [crate::limits]
+pub fn limits_cmd(
+    connection: &Connection,
+    value: Option<usize>,
+    alternate: Option<usize>,
+    off: bool,
+) -> Result<String> {
+    if off {
+        limits::set(connection, false, None)?;
+        limits::set(connection, true, None)?;
+        return Ok("Ok".to_string());
+    }
+    if value.is_none() && alternate.is_none() {
+        let show = |max: Option<usize>| max.map_or("none".to_string(), |m| format!("{} bytes", m));
+        let (value, alternate) = limits::get(connection)?;
+        return Ok(format!(
+            "value: {}\nalternate: {}",
+            show(value),
+            show(alternate)
+        ));
+    }
+
+    if value.is_some() {
+        limits::set(connection, false, value)?;
+    }
+    if alternate.is_some() {
+        limits::set(connection, true, alternate)?;
+    }
+
+    Ok("Ok".to_string())
+}
+
+/// Lists the names of entries which only differ in case, one group per line
+pub fn case_duplicates_cmd(connection: &Connection) -> Result<String> {
+    Ok(store::case_duplicates(connection)?
+        .iter()
+        .map(|names| names.join(" "))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// Show how often entries are read, or turn counting reads on or off
+///
+/// Reads are only counted while tracking is on, see
+/// [record_access](crate::store::record_access). With `entries` the number of reads & the time
+/// of the last read are shown for every entry, most read first.
+pub fn stats_cmd(connection: &Connection, entries: bool, track: Option<bool>) -> Result<String> {
+    if let Some(track) = track {
+        settings::set(connection, settings::TRACK_ACCESS, track.then_some("on"))?;
+        return Ok("Ok".to_string());
+    }
+
+    if entries {
+        let mut statement = connection.prepare(
+            "SELECT name, IFNULL(reads, 0), accessed_at FROM data
+                ORDER BY IFNULL(reads, 0) DESC, name",
+        )?;
+        let mut rows = statement.query(())?;
+
+        let mut output = String::new();
+        while let Some(row) = rows.next()? {
+            let name: String = row.get(0)?;
+            let reads: i64 = row.get(1)?;
+            let accessed_at: Option<i64> = row.get(2)?;
+
+            writeln!(
+                output,
+                "{} {} {}",
+                name,
+                reads,
+                accessed_at.map_or("never".to_string(), time::format)
+            )
+            .unwrap();
+        }
+
+        return Ok(output.trim_end().to_string());
+    }
+
+    let tracking = settings::get(connection, settings::TRACK_ACCESS)?.is_some();
+    let (reads, unread): (i64, i64) = connection.query_row(
+        "SELECT IFNULL(SUM(reads), 0), COUNT(*) FILTER (WHERE accessed_at IS NULL) FROM data",
+        (),
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+
+    Ok(format!(
+        "tracking: {}\nreads: {}\nunread entries: {}",
+        if tracking { "on" } else { "off" },
+        reads,
+        unread
+    ))
+}
+
+/// List the stores in the db with their number of entries
+///
+/// The default store comes first as `(default)`, followed by the stores created with `--table`,
+/// see [TableStore](crate::table_store::TableStore).
+pub fn tables_cmd(connection: &Connection) -> Result<String> {
+    let count = |table: &str| -> Result<i64> {
+        Ok(
+            connection.query_row(&format!("SELECT COUNT(*) FROM {}", table), (), |row| {
+                row.get(0)
+            })?,
+        )
+    };
+
+    let mut output = format!("(default) {}", count("data")?);
+    for name in crate::table_store::names(connection)? {
+        write!(
+            output,
+            "\n{} {}",
+            name,
+            count(&format!("{}{}", crate::table_store::PREFIX, name))?
+        )
+        .unwrap();
+    }
+
+    Ok(output)
+}
+
+/// Remove entries sharing a name with another entry
+///
+/// See [DedupeStrategy] for which entry is kept.
+pub fn dedupe_cmd(connection: &Connection, strategy: DedupeStrategy) -> Result<String> {
+    let names = connection
+        .prepare("SELECT name FROM data WHERE name IS NOT NULL GROUP BY name HAVING COUNT(*) > 1")?
+        .query_map((), |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+
+    transaction(connection, || {
+        names
+            .iter()
+            .try_fold(String::new(), |mut acc, name| {
+                let entries = connection
+                    .prepare(&format!(
+                        "SELECT {} FROM data WHERE name = ?
+                        ORDER BY IFNULL(updated_at, 0) DESC, id DESC",
+                        Entry::COLUMNS
+                    ))?
+                    .query_map([name], Entry::from_row)?
+                    .collect::<rusqlite::Result<Vec<Entry>>>()?;
+
+                let keep = match strategy {
+                    DedupeStrategy::Newest | DedupeStrategy::Merge => &entries[0],
+                    DedupeStrategy::Interactive => &entries[choose(&entries)?],
+                };
+
+                if let DedupeStrategy::Merge = strategy {
+                    for other in entries.iter().filter(|e| e._id != keep._id) {
+                        connection.execute(
+                            "UPDATE OR IGNORE tags SET entry_id = ?1 WHERE entry_id = ?2",
+                            (keep._id, other._id),
+                        )?;
+                        connection.execute(
+                            "UPDATE OR IGNORE meta SET entry_id = ?1 WHERE entry_id = ?2",
+                            (keep._id, other._id),
+                        )?;
+                        connection.execute(
+                            "UPDATE data SET description = IFNULL(description, ?2),
+                            default_value = IFNULL(default_value, ?3) WHERE id = ?1",
+                            (keep._id, &other.description, &other.default),
+                        )?;
+                    }
+                }
+
+                let removed = connection.execute(
+                    "DELETE FROM data WHERE name = ?1 AND id != ?2",
+                    (name, keep._id),
+                )?;
+
+                writeln!(acc, "{}: kept {:?} (removed {})", name, keep.value, removed).unwrap();
+                Ok(acc)
+            })
+            .and_then(|report| {
+                crate::schema::index_names(connection)?;
+                Ok(report)
+            })
+    })
+}
+
+/// Helper function asking the user which of the duplicate `entries` to keep
+///
+/// Returns the index of the chosen entry, defaulting to the first one.
+fn choose(entries: &[Entry]) -> Result<usize> {
+    loop {
+        for (i, entry) in entries.iter().enumerate() {
+            eprintln!("{}) {}", i + 1, entry.long(false));
+        }
+        eprint!("Keep which entry? [1] ");
+
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+
+        match answer.trim() {
+            "" => return Ok(0),
+            answer => match answer.parse::<usize>() {
+                Ok(n) if (1..=entries.len()).contains(&n) => return Ok(n - 1),
+                _ => continue,
+            },
+        }
+    }
+}
+
+/// The consistency checks run by [doctor_cmd]
+///
+/// Each check is a description, a table, the condition selecting the broken rows of that table
+/// and the statement repairing them. Checks without a repair statement are fixed by deleting the
+/// broken rows.
+const CHECKS: &[(&str, &str, &str, Option<&str>)] = &[
+    ("entries without a name", "data", "name IS NULL", None),
+    // Alternates may be missing, they are `NULL` until set
+    (
+        "entries with a missing value",
+        "data",
+        "value IS NULL",
+        Some("UPDATE data SET value = '' WHERE value IS NULL"),
+    ),
+    ("duplicate entries", "data", DUPLICATE, None),
+    (
+        "tags of missing entries",
+        "tags",
+        "entry_id NOT IN (SELECT id FROM data)",
+        None,
+    ),
+    (
+        "metadata of missing entries",
+        "meta",
+        "entry_id NOT IN (SELECT id FROM data)",
+        None,
+    ),
+    (
+        "aliases of missing entries",
+        "aliases",
+        "target NOT IN (SELECT name FROM data WHERE name IS NOT NULL)",
+        None,
+    ),
+];
+
+/// Check the db for corruption & inconsistencies left behind by older versions
+///
+/// Runs `PRAGMA integrity_check` & all [CHECKS]. If `fix == true` the problems found by the checks
+/// are repaired, keeping the most recently updated of duplicate entries. Corruption reported by
+/// the integrity check can't be repaired by config-store.
+pub fn doctor_cmd(connection: &Connection, fix: bool) -> Result<String> {
+    let mut output = String::new();
+
+    let integrity = connection
+        .prepare("PRAGMA integrity_check")?
+        .query_map((), |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    writeln!(output, "integrity check: {}", integrity.join("; ")).unwrap();
+
+    let mut problems = integrity != ["ok"];
+
+    transaction(connection, || {
+        for (description, table, condition, repair) in CHECKS {
+            let count: i64 = connection.query_row(
+                &format!("SELECT COUNT(*) FROM {} WHERE {}", table, condition),
+                (),
+                |row| row.get(0),
+            )?;
+
+            if count > 0 && fix {
+                match repair {
+                    Some(repair) => connection.execute(repair, ())?,
+                    None => connection
+                        .execute(&format!("DELETE FROM {} WHERE {}", table, condition), ())?,
+                };
+                writeln!(output, "{}: {} (fixed)", description, count).unwrap();
+            } else {
+                problems |= count > 0;
+                writeln!(output, "{}: {}", description, count).unwrap();
+            }
+        }
+
+        // Without duplicates the names can be indexed as unique
+        if fix {
+            crate::schema::index_names(connection)?;
+        }
+
+        Ok(())
+    })?;
+
+    if problems && !fix {
+        output.push_str("Run with --fix to repair these problems\n");
+    }
+
+    Ok(output)
+}
+
+/// Benchmark the common operations against a temporary db
+///
+/// Runs `set`, `get` & `toggle` `iterations` times each through [Store](crate::Store) and invokes
+/// the current binary `invocations` times to measure the end-to-end latency of a single command,
+/// including process startup & opening the db. The temporary db is removed afterwards.
+pub fn bench_cmd(iterations: usize, invocations: usize) -> Result<String> {
+    let path = std::env::temp_dir().join(format!("config-store-bench-{}.db", std::process::id()));
+    let result = bench(&path, iterations, invocations);
+
+    // The db may not exist if opening it failed, in which case there is nothing to clean up
+    let _ = std::fs::remove_file(&path);
+
+    result
+}
+
+/// Helper function running the benchmarks of [bench_cmd] against the db at `path`
+fn bench(path: &std::path::Path, iterations: usize, invocations: usize) -> Result<String> {
+    let store = crate::Store::open(path)?;
+    let mut output = String::new();
+
+    let mut measure = |operation: &str, f: &dyn Fn(usize) -> Result<()>| -> Result<()> {
+        let start = std::time::Instant::now();
+        for i in 0..iterations {
+            f(i)?;
+        }
+        let elapsed = start.elapsed();
+
+        writeln!(
+            output,
+            "{:<8} {:>10.0} ops/s {:>10.1?}/op",
+            operation,
+            iterations as f64 / elapsed.as_secs_f64(),
+            elapsed / iterations.max(1) as u32
+        )
+        .unwrap();
+        Ok(())
+    };
+
+    measure("set", &|i| {
+        store.set(&format!("bench{}", i), Some("on"), Some("off"))?;
+        Ok(())
+    })?;
+    measure("get", &|i| {
+        store.get(&format!("bench{}", i))?;
+        Ok(())
+    })?;
+    measure("toggle", &|i| {
+        store.toggle(&format!("bench{}", i))?;
+        Ok(())
+    })?;
+
+    if invocations > 0 {
+        let exe = std::env::current_exe()?;
+        let start = std::time::Instant::now();
+
+        for _ in 0..invocations {
+            std::process::Command::new(&exe)
+                .arg("--db-path")
+                .arg(path)
+                .args(["get", "bench0", "--value-only"])
+                .output()?;
+        }
+
+        writeln!(
+            output,
+            "{:<8} {:>10.1?}/invocation",
+            "process",
+            start.elapsed() / invocations as u32
+        )
+        .unwrap();
+    }
+
+    Ok(output)
+}
+
+/// Helper function creating a random string of the given [GenerateKind]
+fn random_string(kind: GenerateKind, length: usize) -> Result<String> {
+    let mut value = match kind {
+        GenerateKind::Uuid => {
+            let mut bytes = [0u8; 16];
+            getrandom::getrandom(&mut bytes)?;
+
+            // Set the version (4) and variant (RFC 4122) bits
+            bytes[6] = (bytes[6] & 0x0f) | 0x40;
+            bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+            let hex = to_hex(&bytes);
+            return Ok(format!(
+                "{}-{}-{}-{}-{}",
+                &hex[0..8],
+                &hex[8..12],
+                &hex[12..16],
+                &hex[16..20],
+                &hex[20..32]
+            ));
+        }
+        GenerateKind::Hex => {
+            let mut bytes = vec![0u8; length.div_ceil(2)];
+            getrandom::getrandom(&mut bytes)?;
+
+            to_hex(&bytes)
+        }
+        GenerateKind::Base64 => {
+            let mut bytes = vec![0u8; (length * 3).div_ceil(4)];
+            getrandom::getrandom(&mut bytes)?;
+
+            base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+        }
+    };
+
+    value.truncate(length);
+
+    Ok(value)
+}
+
+/// Helper function to encode bytes as a lowercase hex string
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut acc, b| {
+        write!(acc, "{:02x}", b).unwrap();
+        acc
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    fn create_db() -> Connection {
+        // WARN: Transactions do not work in an in memory db
+        let connection = Connection::open_in_memory().unwrap();
+        crate::schema::init(&connection).expect("Failed to create values TABLE");
+
+        connection
+    }
+
+    /// Runs [list_cmd] & returns what it wrote
+    fn listed(
+        connection: &Connection,
+        format: ListFormat,
+        filter: Filter,
+        recent: Option<usize>,
+        limit: Option<usize>,
+        offset: Option<usize>,
+    ) -> Result<String> {
+        let mut out = Vec::new();
+        list_cmd(connection, &mut out, format, filter, recent, limit, offset)?;
+
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn insert_and_drop() {
+        let connection = create_db();
+
+        crate::store::insert(
+            &connection,
+            "test1".to_string(),
+            "value1".to_string(),
+            "alternate1".to_string(),
+            None,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            listed(
+                &connection,
+                ListFormat::Plain { color: false },
+                Filter::default(),
+                None,
+                None,
+                None
+            )
+            .unwrap(),
+            "Entry { _id: 1, name: \"test1\", value: \"value1\", alternate: \"alternate1\" }\n"
+        );
+
+        // An alternate which was never set is left out
+        set(&connection, "test2", None, None, None, None, false).unwrap();
+        assert_eq!(
+            select(&connection, "test2").unwrap().to_string(),
+            "Entry { _id: 2, name: \"test2\", value: \"\" }"
+        );
+
+        drop_cmd(&connection, false).unwrap();
+    }
+
+    #[test]
+    fn list_ndjson() {
+        let connection = create_db();
+        assert_eq!(
+            listed(
+                &connection,
+                ListFormat::Json,
+                Filter::default(),
+                None,
+                None,
+                None
+            )
+            .unwrap(),
+            ""
+        );
+
+        for (name, value) in [("motd", "line 1\nline \"2\""), ("theme", "dark")] {
+            set(
+                &connection,
+                name,
+                Some(value.to_string()),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+        }
+        let ndjson = listed(
+            &connection,
+            ListFormat::Json,
+            Filter::default(),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+
+        // One object per line, even for values spanning several lines
+        let lines = ndjson.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 2);
+        let entry: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(entry["name"], "motd");
+        assert_eq!(entry["value"], "line 1\nline \"2\"");
+        assert_eq!(entry["alternate"], serde_json::Value::Null);
+
+        // The output can be imported again
+        let copy = create_db();
+        let path = std::env::temp_dir().join(format!("config-store-ndjson-{}", std::process::id()));
+        std::fs::write(&path, &ndjson).unwrap();
+        assert_eq!(
+            import_cmd(
+                &copy,
+                Some(path.clone()),
+                ImportFormat::Json,
+                ConflictPolicy::Fail,
+                false,
+                false
+            )
+            .unwrap(),
+            "Created 2 entries, updated 0"
+        );
+        assert_eq!(select(&copy, "motd").unwrap().value, "line 1\nline \"2\"");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn insert_and_exists() {
+        let connection = create_db();
+
+        assert_eq!(
+            exists_cmd(&connection, "test1".to_string(), false, false).unwrap(),
+            "false"
+        );
+
+        crate::store::insert(
+            &connection,
+            "test1".to_string(),
+            "value1".to_string(),
+            "alternate1".to_string(),
+            None,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            exists_cmd(&connection, "test1".to_string(), false, false).unwrap(),
+            "true"
+        );
+    }
+
+    #[test]
+    fn exists_glob() {
+        let connection = create_db();
+
+        for name in ["vpn.home", "vpn.work", "theme"] {
+            set(&connection, name, None, None, None, None, false).unwrap();
+        }
+
+        assert_eq!(
+            exists_cmd(&connection, "vpn.*".to_string(), true, false).unwrap(),
+            "true"
+        );
+        assert_eq!(
+            exists_cmd(&connection, "vpn.*".to_string(), true, true).unwrap(),
+            "2"
+        );
+        assert_eq!(
+            exists_cmd(&connection, "vpn.*".to_string(), false, true).unwrap(),
+            "0"
+        );
+    }
+
+    #[test]
+    fn check_value() {
+        let connection = create_db();
+        set(
+            &connection,
+            "theme",
+            Some("dark-blue".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        let check = |name: &str, value: Option<&str>, pattern: Option<&str>| {
+            check_value_cmd(
+                &connection,
+                name.to_string(),
+                value.map(str::to_string),
+                pattern.map(str::to_string),
+            )
+        };
+
+        assert_eq!(check("theme", Some("dark-blue"), None).unwrap(), "true");
+        assert_eq!(check("theme", None, Some("dark*")).unwrap(), "true");
+        assert!(matches!(
+            check("theme", Some("dark"), None),
+            Err(Error::Mismatch)
+        ));
+        assert!(matches!(
+            check("theme", None, Some("light*")),
+            Err(Error::Mismatch)
+        ));
+        assert!(matches!(
+            check("missing", Some("dark"), None),
+            Err(Error::EntryNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn set_expr() {
+        let connection = create_db();
+        set(
+            &connection,
+            "volume",
+            Some("50".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        let step = |expr: &str| {
+            set_expr_cmd(
+                &connection,
+                "volume".to_string(),
+                expr.to_string(),
+                Some(0.0),
+                Some(100.0),
+            )
+        };
+
+        assert_eq!(step("x + 10").unwrap(), "60");
+        assert_eq!(step("x * 2").unwrap(), "100");
+        assert_eq!(step("x / 8").unwrap(), "12.5");
+        assert!(matches!(step("x +"), Err(Error::Invalid(_))));
+
+        set(
+            &connection,
+            "volume",
+            Some("loud".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        assert!(matches!(step("x + 1"), Err(Error::InvalidType { .. })));
+    }
+
+    #[test]
+    fn transform() {
+        let connection = create_db();
+        set(
+            &connection,
+            "greeting",
+            Some("  hello world ".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        let transform = |transform: &str| {
+            let transform = Transform {
+                regex: Some(Substitution::parse(transform).unwrap()),
+                ..Default::default()
+            };
+            transform_cmd(&connection, "greeting".to_string(), transform)
+        };
+
+        assert_eq!(transform("s/o/0/g").unwrap(), "  hell0 w0rld ");
+        assert_eq!(transform("s/^ +//").unwrap(), "hell0 w0rld ");
+        assert_eq!(
+            select(&connection, "greeting").unwrap().value,
+            "hell0 w0rld "
+        );
+        assert!(matches!(
+            transform_cmd(
+                &connection,
+                "missing".to_string(),
+                Transform {
+                    upper: true,
+                    ..Default::default()
+                }
+            ),
+            Err(Error::EntryNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn cap() {
+        let connection = create_db();
+        for name in ["a", "b", "c"] {
+            set(&connection, name, None, None, None, None, false).unwrap();
+        }
+        // Pretend `a` was read after the others were written
+        connection
+            .execute(
+                "UPDATE data SET accessed_at = ? WHERE name = 'a'",
+                [time::now() + 10],
+            )
+            .unwrap();
+
+        assert_eq!(cap_cmd(&connection, None, false).unwrap(), "none");
+        assert_eq!(
+            cap_cmd(&connection, Some(2), false).unwrap(),
+            "Evicted 1 entries"
+        );
+        assert!(!exists(&connection, "b").unwrap());
+
+        set(&connection, "d", None, None, None, None, false).unwrap();
+        assert!(exists(&connection, "a").unwrap());
+        assert!(!exists(&connection, "c").unwrap());
+        assert!(exists(&connection, "d").unwrap());
+
+        cap_cmd(&connection, None, true).unwrap();
+        set(&connection, "e", None, None, None, None, false).unwrap();
+        assert!(exists(&connection, "d").unwrap());
+    }
+
+    #[test]
+    fn stats() {
+        let connection = create_db();
+        for name in ["a", "b"] {
+            set(&connection, name, None, None, None, None, false).unwrap();
+        }
+
+        get_cmd(
+            &connection,
+            "a".to_string(),
+            false,
+            false,
+            GetFormat::Plain {
+                color: false,
+                separator: ' ',
+            },
+            false,
+        )
+        .unwrap();
+        stats_cmd(&connection, false, Some(true)).unwrap();
+        get_cmd(
+            &connection,
+            "a".to_string(),
+            false,
+            false,
+            GetFormat::Plain {
+                color: false,
+                separator: ' ',
+            },
+            false,
+        )
+        .unwrap();
+        get_cmd(
+            &connection,
+            "a".to_string(),
+            false,
+            false,
+            GetFormat::Plain {
+                color: false,
+                separator: ' ',
+            },
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(
+            stats_cmd(&connection, false, None).unwrap(),
+            "tracking: on\nreads: 2\nunread entries: 1"
+        );
+        let entries = stats_cmd(&connection, true, None).unwrap();
+        assert!(entries.starts_with("a 2 "));
+        assert!(entries.ends_with("\nb 0 never"));
+
+        let unused = Filter {
+            unused: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            listed(
+                &connection,
+                ListFormat::Plain { color: false },
+                unused,
+                None,
+                None,
+                None
+            )
+            .unwrap()
+            .lines()
+            .count(),
+            1
+        );
+        let unused_recently = Filter {
+            since: Some(3600),
+            unused: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            listed(
+                &connection,
+                ListFormat::Plain { color: false },
+                unused_recently,
+                None,
+                None,
+                None
+            )
+            .unwrap()
+            .lines()
+            .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn reset_at() {
+        let connection = create_db();
+        set(
+            &connection,
+            "dnd",
+            Some("on".to_string()),
+            Some("off".to_string()),
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        reset_at_cmd(&connection, "dnd".to_string(), time::At::After(3600)).unwrap();
+        assert_eq!(crate::store::apply_resets(&connection).unwrap(), 0);
+
+        reset_at_cmd(&connection, "dnd".to_string(), time::At::After(0)).unwrap();
+        assert_eq!(crate::store::apply_resets(&connection).unwrap(), 1);
+        let entry = select(&connection, "dnd").unwrap();
+        assert_eq!(
+            (entry.value, entry.alternate),
+            ("off".into(), Some("on".into()))
+        );
+
+        // Toggling back manually before the reset is due doesn't get undone
+        toggle(&connection, "dnd").unwrap();
+        reset_at_cmd(&connection, "dnd".to_string(), time::At::After(0)).unwrap();
+        toggle(&connection, "dnd").unwrap();
+        assert_eq!(crate::store::apply_resets(&connection).unwrap(), 0);
+        assert_eq!(select(&connection, "dnd").unwrap().value, "off");
+
+        assert!(matches!(
+            reset_at_cmd(&connection, "missing".to_string(), time::At::After(0)),
+            Err(Error::EntryNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn snapshots() {
+        let dir =
+            std::env::temp_dir().join(format!("config-store-snapshots-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test.db");
+        let store = crate::Store::open(&path).unwrap();
+        let connection = store.connection();
+
+        assert_eq!(snapshot_list_cmd(connection).unwrap(), "");
+        store.set("theme", Some("dark"), None).unwrap();
+        assert_eq!(
+            snapshot_create_cmd(connection, Some("dark".to_string())).unwrap(),
+            "dark"
+        );
+        assert!(matches!(
+            snapshot_create_cmd(connection, Some("dark".to_string())),
+            Err(Error::EntryExists)
+        ));
+        for label in ["", ".hidden", "a/b", "..\\x"] {
+            assert!(matches!(
+                snapshot_create_cmd(connection, Some(label.to_string())),
+                Err(Error::Invalid(_))
+            ));
+        }
+        // Without a label the current time is used
+        let label = snapshot_create_cmd(connection, None).unwrap();
+        assert!(label.parse::<i64>().is_ok());
+
+        let list = snapshot_list_cmd(connection).unwrap();
+        assert_eq!(list.lines().count(), 2);
+        assert!(list.lines().any(|l| l.ends_with(" dark")));
+
+        store.set("theme", Some("light"), None).unwrap();
+        store.set("font", Some("mono"), None).unwrap();
+        assert_eq!(
+            snapshot_restore_cmd(connection, "dark".to_string(), 1).unwrap(),
+            "Restored dark"
+        );
+        assert_eq!(store.get("theme").unwrap().value, "dark");
+        assert!(!store.exists("font").unwrap());
+        // The state before restoring is backed up
+        assert_eq!(
+            crate::backup::list(&crate::backup::dir_of(&path))
+                .unwrap()
+                .len(),
+            1
+        );
+
+        assert!(matches!(
+            snapshot_restore_cmd(connection, "missing".to_string(), 1),
+            Err(Error::NotFound(_))
+        ));
+        let memory = create_db();
+        assert!(matches!(
+            snapshot_create_cmd(&memory, None),
+            Err(Error::Invalid(_))
+        ));
+        assert!(matches!(
+            snapshot_restore_cmd(&memory, "dark".to_string(), 1),
+            Err(Error::NotFound(_))
+        ));
+
+        drop(store);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn snapshot_diff() {
+        let dir = std::env::temp_dir().join(format!("config-store-diff-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let store = crate::Store::open(dir.join("test.db")).unwrap();
+        let connection = store.connection();
+
+        store.set("kept", Some("1"), None).unwrap();
+        store.set("changed", Some("a"), Some("b")).unwrap();
+        store.set("deleted", Some("x"), None).unwrap();
+        snapshot_create_cmd(connection, Some("before".to_string())).unwrap();
+
+        store.toggle("changed").unwrap();
+        store.delete("deleted").unwrap();
+        store.set("created", Some("y"), None).unwrap();
+
+        assert_eq!(
+            snapshot_diff_cmd(connection, "before".to_string(), None, false, false).unwrap(),
+            "update changed: \"a\" \"b\" -> \"b\" \"a\"\n\
+            create created: \"y\" \"\"\n\
+            delete deleted: \"x\" \"\""
+        );
+
+        snapshot_create_cmd(connection, Some("after".to_string())).unwrap();
+        let json = snapshot_diff_cmd(
+            connection,
+            "before".to_string(),
+            Some("after".to_string()),
+            true,
+            false,
+        )
+        .unwrap();
+        assert!(json.starts_with(r#"[{"event":"update""#));
+        assert_eq!(json.matches("event").count(), 3);
+
+        assert!(matches!(
+            snapshot_diff_cmd(connection, "missing".to_string(), None, false, false),
+            Err(Error::NotFound(_))
+        ));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn get_as_of() {
+        let connection = create_db();
+        let as_of = |time: &str| {
+            get_as_of_cmd(
+                &connection,
+                "a".to_string(),
+                time.to_string(),
+                true,
+                false,
+                GetFormat::Plain {
+                    color: false,
+                    separator: ' ',
+                },
+            )
+        };
+
+        set(&connection, "a", Some("1".into()), None, None, None, false).unwrap();
+        connection
+            .execute("UPDATE log SET timestamp = 1000", ())
+            .unwrap();
+        set(&connection, "a", Some("2".into()), None, None, None, false).unwrap();
+        connection
+            .execute(
+                "UPDATE log SET timestamp = 2000 WHERE timestamp != 1000",
+                (),
+            )
+            .unwrap();
+
+        assert!(matches!(
+            as_of("1970-01-01 00:05"),
+            Err(Error::EntryNotFound { .. })
+        ));
+        assert_eq!(as_of("1970-01-01 00:25").unwrap(), "1");
+        assert_eq!(as_of("1970-01-01 00:40").unwrap(), "2");
+        assert!(matches!(as_of("yesterday"), Err(Error::Invalid(_))));
+
+        // Without any changes in the log the current value is used
+        connection.execute("DELETE FROM log", ()).unwrap();
+        assert_eq!(as_of("0s").unwrap(), "2");
+        assert!(matches!(
+            as_of("1970-01-01 00:40"),
+            Err(Error::EntryNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn replace() {
+        let connection = create_db();
+        for (name, value) in [
+            ("net.host", "old.example.com"),
+            ("net.mirror", "https://old.example.com/mirror"),
+            ("net.port", "8080"),
+            ("web.host", "old.example.com"),
+        ] {
+            set(
+                &connection,
+                name,
+                Some(value.to_string()),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+        }
+
+        let substitution = Substitution::parse("s/old/new/").unwrap();
+        assert_eq!(
+            replace_cmd(&connection, substitution, Some("net.*".to_string())).unwrap(),
+            "Changed 2 entries"
+        );
+        assert_eq!(
+            select(&connection, "net.mirror").unwrap().value,
+            "https://new.example.com/mirror"
+        );
+        assert_eq!(
+            select(&connection, "web.host").unwrap().value,
+            "old.example.com"
+        );
+    }
+
+    #[test]
+    fn toggle_create() {
+        let connection = create_db();
+        let initial = Some(("light".to_string(), "dark".to_string()));
+
+        assert!(matches!(
+            toggle_cmd(
+                &connection,
+                vec!["theme".to_string()],
+                Filter::default(),
+                None,
+                None,
+                false
+            ),
+            Err(Error::EntryNotFound { .. })
+        ));
+        assert_eq!(
+            toggle_cmd(
+                &connection,
+                vec!["theme".to_string()],
+                Filter::default(),
+                initial.clone(),
+                None,
+                false
+            )
+            .unwrap(),
+            "dark"
+        );
+        assert_eq!(
+            toggle_cmd(
+                &connection,
+                vec!["theme".to_string()],
+                Filter::default(),
+                initial,
+                None,
+                false
+            )
+            .unwrap(),
+            "light"
+        );
+
+        naming::Policy {
+            prefixes: vec!["ui.".to_string()],
+            ..Default::default()
+        }
+        .update(&connection)
+        .unwrap();
+        assert!(matches!(
+            toggle_cmd(
+                &connection,
+                vec!["mode".to_string()],
+                Filter::default(),
+                Some(("light".to_string(), "dark".to_string())),
+                None,
+                false
+            ),
+            Err(Error::Invalid(_))
+        ));
+        assert!(!exists(&connection, "mode").unwrap());
+    }
+
+    #[test]
+    fn toggle_if_value() {
+        let connection = create_db();
+        set(
+            &connection,
+            "vpn",
+            Some("on".to_string()),
+            Some("off".to_string()),
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        let toggle = |current: &str| {
+            toggle_cmd(
+                &connection,
+                vec!["vpn".to_string()],
+                Filter::default(),
+                None,
+                Some(current.to_string()),
+                false,
+            )
+        };
+
+        assert!(matches!(toggle("off"), Err(Error::Mismatch)));
+        assert_eq!(toggle("on").unwrap(), "off");
+        assert!(matches!(toggle("on"), Err(Error::Mismatch)));
+    }
+
+    #[test]
+    fn enable_disable() {
+        let connection = create_db();
+        set(
+            &connection,
+            "vpn",
+            Some("off".to_string()),
+            Some("on".to_string()),
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        set(
+            &connection,
+            "theme",
+            Some("dark".to_string()),
+            Some("light".to_string()),
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        let switch = |name: &str, on| switch_cmd(&connection, vec![name.to_string()], on);
+
+        assert_eq!(switch("vpn", true).unwrap(), "on");
+        assert_eq!(switch("vpn", true).unwrap(), "on");
+        assert_eq!(switch("vpn", false).unwrap(), "off");
+        assert_eq!(switch("gaming", false).unwrap(), "false");
+        assert_eq!(switch("gaming", true).unwrap(), "true");
+        assert!(matches!(switch("theme", true), Err(Error::Invalid(_))));
+    }
+
+    #[test]
+    fn set_modes() {
+        let connection = create_db();
+        let set = |mode| {
+            set_cmd(
+                &connection,
+                "init".to_string(),
+                Some("1".to_string()),
+                None,
+                None,
+                None,
+                mode,
+            )
+        };
+
+        assert!(matches!(
+            set(SetMode::ChangeOnly),
+            Err(Error::EntryNotFound { .. })
+        ));
+        assert_eq!(set(SetMode::CreateOnly).unwrap(), "Ok");
+        assert!(matches!(set(SetMode::CreateOnly), Err(Error::EntryExists)));
+        assert_eq!(set(SetMode::ChangeOnly).unwrap(), "Ok");
+
+        assert!(matches!(
+            set(SetMode::IfValue("0".to_string())),
+            Err(Error::Mismatch)
+        ));
+        assert_eq!(set(SetMode::IfValue("1".to_string())).unwrap(), "Ok");
+    }
+
+    #[test]
+    fn set_if_value() {
+        let connection = create_db();
+        let set_if = |name: &str, value: &str, alternate: &str, current: &str| {
+            set_cmd(
+                &connection,
+                name.to_string(),
+                Some(value.to_string()),
+                Some(alternate.to_string()),
+                None,
+                None,
+                SetMode::IfValue(current.to_string()),
+            )
+        };
+
+        let error = set_if("lock", "b", "-", "a").unwrap_err();
+        assert!(matches!(error, Error::EntryNotFound { .. }));
+        assert!(!exists(&connection, "lock").unwrap());
+
+        set(
+            &connection,
+            "lock",
+            Some("a".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        let error = set_if("lock", "c", "x", "b").unwrap_err();
+        assert!(matches!(error, Error::Mismatch));
+        assert_eq!(error.exit_code(), 7);
+        // Nothing is changed on a mismatch, not even the alternate
+        let entry = select(&connection, "lock").unwrap();
+        assert_eq!((entry.value.as_str(), entry.alternate), ("a", None));
+
+        assert_eq!(set_if("lock", "b", "a", "a").unwrap(), "Ok");
+        let entry = select(&connection, "lock").unwrap();
+        assert_eq!(
+            (entry.value.as_str(), entry.alternate.as_deref()),
+            ("b", Some("a"))
+        );
+        // The condition compares the whole value
+        assert!(matches!(
+            set_if("lock", "c", "b", "B"),
+            Err(Error::Mismatch)
+        ));
+        assert!(matches!(set_if("lock", "c", "b", ""), Err(Error::Mismatch)));
+    }
+
+    #[test]
+    fn strict_delete() {
+        let connection = create_db();
+
+        assert_eq!(
+            delete_cmd(
+                &connection,
+                Some("missing".to_string()),
+                Filter::default(),
+                false,
+                false,
+            )
+            .unwrap(),
+            "Ok"
+        );
+        assert!(matches!(
+            delete_cmd(
+                &connection,
+                Some("missing".to_string()),
+                Filter::default(),
+                true,
+                false,
+            ),
+            Err(Error::EntryNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn insert_and_get() {
+        let connection = create_db();
+
+        crate::store::insert(
+            &connection,
+            "test1".to_string(),
+            "value1".to_string(),
+            "alternate1".to_string(),
+            None,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            get_cmd(
+                &connection,
+                "test1".to_string(),
+                false,
+                false,
+                GetFormat::Plain {
+                    color: false,
+                    separator: ' '
+                },
+                false,
+            )
+            .unwrap(),
+            format!("{} {}", "value1", "alternate1")
+        );
+    }
+
+    #[test]
+    fn info() {
+        let connection = create_db();
+        crate::store::insert(
+            &connection,
+            "test1".to_string(),
+            "a".to_string(),
+            "b".to_string(),
+            None,
+            None,
+        )
+        .unwrap();
+
+        let info = info_cmd(&connection, false).unwrap();
+        assert!(info.contains("path: :memory:\n"));
+        assert!(info.contains(&format!("schema version: {}\n", crate::schema::VERSION)));
+        assert!(info.contains("entries: 1\n"));
+
+        let info = info_cmd(&connection, true).unwrap();
+        assert!(info.contains(r#""entries":1"#));
+    }
+
+    #[test]
+    fn get_glob() {
+        let connection = create_db();
+        for (name, value) in [("vpn.work", "off"), ("vpn.home", "on"), ("theme", "dark")] {
+            set(
+                &connection,
+                name,
+                Some(value.to_string()),
+                Some("it's".to_string()),
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+        }
+        let get = |value_only, format| {
+            get_glob_cmd(&connection, "vpn.*".to_string(), value_only, false, format)
+        };
+
+        assert_eq!(
+            get(
+                false,
+                GetFormat::Plain {
+                    color: false,
+                    separator: ' '
+                }
+            )
+            .unwrap(),
+            "vpn.home on it's\nvpn.work off it's"
+        );
+        // Values with spaces stay unambiguous with another separator
+        assert_eq!(
+            get(
+                false,
+                GetFormat::Plain {
+                    color: false,
+                    separator: '\t'
+                }
+            )
+            .unwrap(),
+            "vpn.home\ton\tit's\nvpn.work\toff\tit's"
+        );
+        assert_eq!(
+            get(false, GetFormat::Pair(None)).unwrap(),
+            "vpn.home=on\nvpn.home.alt=it's\nvpn.work=off\nvpn.work.alt=it's"
+        );
+        assert_eq!(
+            get(true, GetFormat::Pair(Some(Quote::Shell))).unwrap(),
+            "vpn.home='on'\nvpn.work='off'"
+        );
+        assert_eq!(
+            get(true, GetFormat::Quoted(Quote::Shell)).unwrap(),
+            "'vpn.home' 'on'\n'vpn.work' 'off'"
+        );
+        assert_eq!(
+            get(false, GetFormat::Json(None)).unwrap().lines().count(),
+            2
+        );
+
+        assert!(matches!(
+            get_glob_cmd(
+                &connection,
+                "wifi.*".to_string(),
+                false,
+                false,
+                GetFormat::Json(None)
+            ),
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn get_separator() {
+        let connection = create_db();
+        set(
+            &connection,
+            "greeting",
+            Some("hello world".to_string()),
+            Some("good bye".to_string()),
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        set(
+            &connection,
+            "name",
+            Some("Jane Doe".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        let get = |name: &str, value_only, separator| {
+            get_cmd(
+                &connection,
+                name.to_string(),
+                value_only,
+                false,
+                GetFormat::Plain {
+                    color: false,
+                    separator,
+                },
+                false,
+            )
+            .unwrap()
+        };
+
+        assert_eq!(get("greeting", false, '\0'), "hello world\0good bye");
+        assert_eq!(get("greeting", false, '\n'), "hello world\ngood bye");
+        // The separator is only printed between fields
+        assert_eq!(get("greeting", true, '\0'), "hello world");
+        assert_eq!(get("name", false, '\0'), "Jane Doe");
+    }
+
+    #[test]
+    fn get_pair() {
+        let connection = create_db();
+        set(
+            &connection,
+            "greeting",
+            Some("it's a=b".to_string()),
+            Some("bye".to_string()),
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        set(
+            &connection,
+            "theme",
+            Some("dark".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        let get = |name: &str, value_only, alternate_only, quote| {
+            get_cmd(
+                &connection,
+                name.to_string(),
+                value_only,
+                alternate_only,
+                GetFormat::Pair(quote),
+                false,
+            )
+            .unwrap()
+        };
+
+        assert_eq!(
+            get("greeting", false, false, None),
+            "greeting=it's a=b\ngreeting.alt=bye"
+        );
+        assert_eq!(
+            get("greeting", false, false, Some(Quote::Shell)),
+            "greeting='it'\\''s a=b'\ngreeting.alt='bye'"
+        );
+        assert_eq!(get("greeting", true, false, None), "greeting=it's a=b");
+        assert_eq!(get("greeting", false, true, None), "greeting.alt=bye");
+        // Without an alternate only the value is printed, unless the alternate is asked for
+        assert_eq!(get("theme", false, false, None), "theme=dark");
+        assert_eq!(
+            get("theme", false, true, Some(Quote::Shell)),
+            "theme.alt=''"
+        );
+    }
+
+    #[test]
+    fn did_you_mean() {
+        let connection = create_db();
+        set(&connection, "theme", None, None, None, None, false).unwrap();
+        alias_cmd(&connection, "colors".to_string(), "theme".to_string()).unwrap();
+
+        let Err(Error::EntryNotFound { suggestions, .. }) = toggle_cmd(
+            &connection,
+            vec!["colours".to_string()],
+            Filter::default(),
+            None,
+            None,
+            false,
+        ) else {
+            panic!("expected EntryNotFound");
+        };
+        assert_eq!(suggestions, ["colors"]);
+
+        let Err(error) = get_cmd(
+            &connection,
+            "thme".to_string(),
+            false,
+            false,
+            GetFormat::Json(None),
+            false,
+        ) else {
+            panic!("expected an error");
+        };
+        assert_eq!(
+            error.to_string(),
+            "no entry named 'thme', did you mean 'theme'?"
+        );
+    }
+
+    #[test]
+    fn fts() {
+        let connection = create_db();
+        let fts = |query: &str| {
+            fts_cmd(&connection, query.to_string(), false, None, false).map(|found| {
+                found
+                    .lines()
+                    .map(|l| l.split('"').nth(1).unwrap().to_string())
+                    .collect::<Vec<String>>()
+            })
+        };
+
+        set(
+            &connection,
+            "vpn.home",
+            Some("wireguard tunnel".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        set(
+            &connection,
+            "theme",
+            Some("dark".to_string()),
+            None,
+            Some("The colour scheme of the desktop".to_string()),
+            None,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(fts("tunnel").unwrap(), ["vpn.home"]);
+        assert_eq!(fts("desk*").unwrap(), ["theme"]);
+
+        set(
+            &connection,
+            "theme",
+            Some("tunnel".into()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        assert_eq!(fts("tunnel").unwrap().len(), 2);
+        delete_cmd(
+            &connection,
+            Some("vpn.home".to_string()),
+            Filter::default(),
+            false,
+            false,
+        )
+        .unwrap();
+        assert_eq!(fts("tunnel").unwrap(), ["theme"]);
+
+        assert!(matches!(fts("\"unclosed"), Err(Error::Invalid(_))));
+
+        // Dbs created before the index are indexed on open
+        connection
+            .execute_batch("DROP TABLE data_fts; DROP TRIGGER data_fts_insert;")
+            .unwrap();
+        crate::schema::init(&connection).unwrap();
+        assert_eq!(fts("desktop").unwrap(), ["theme"]);
+
+        // The triggers are dropped with the entries & created again when the db is opened
+        drop_cmd(&connection, false).unwrap();
+        crate::schema::init(&connection).unwrap();
+        assert!(fts("desktop").unwrap().is_empty());
+        set(
+            &connection,
+            "vpn.work",
+            Some("tunnel".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        assert_eq!(fts("tunnel").unwrap(), ["vpn.work"]);
+    }
+
+    #[test]
+    fn listen_changes() {
+        let connection = create_db();
+
+        set(
+            &connection,
+            "vpn.home",
+            Some("on".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        let (first, _) = changes_since(&connection, 0, None).unwrap();
+
+        set(
+            &connection,
+            "theme",
+            Some("dark".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        toggle(&connection, "vpn.home").unwrap();
+
+        let (newest, changes) = changes_since(&connection, first, Some("vpn.*")).unwrap();
+        assert_eq!(newest, first + 2);
+        assert_eq!(changes.len(), 1);
+        assert!(changes
+            .into_iter()
+            .next()
+            .unwrap()
+            .json()
+            .starts_with(r#"{"event":"update","#));
+
+        assert!(matches!(
+            listen_cmd(&connection, None, Some(0), 1),
+            Err(Error::Timeout)
+        ));
+    }
+
+    #[test]
+    fn wait_for() {
+        let connection = create_db();
+
+        assert!(matches!(
+            wait_for_cmd(
+                &connection,
+                "flag".to_string(),
+                "on".to_string(),
+                Some(0),
+                1
+            ),
+            Err(Error::Timeout)
+        ));
+
+        set(
+            &connection,
+            "flag",
+            Some("on".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            wait_for_cmd(
+                &connection,
+                "flag".to_string(),
+                "on".to_string(),
+                Some(0),
+                1
+            )
+            .unwrap(),
+            "Ok"
+        );
+    }
+
+    #[test]
+    fn watch() {
+        let connection = create_db();
+
+        assert!(matches!(
+            watch_cmd(&connection, "flag".to_string(), None, Some(0), 1),
+            Err(Error::Timeout)
+        ));
+
+        let path = std::env::temp_dir().join(format!("config-store-watch-{}", std::process::id()));
+        run_hook(
+            &connection,
+            &Hook::Shell(format!(
+                "printf %s \"$CONFIG_STORE_NAME=$CONFIG_STORE_VALUE\" > '{}'",
+                path.display()
+            )),
+            "theme",
+            "dark",
+        )
+        .unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "theme=dark");
+        std::fs::remove_file(path).unwrap();
+
+        assert!(run_hook(
+            &connection,
+            &Hook::Shell("exit 1".to_string()),
+            "theme",
+            "dark"
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn list_pages() {
+        let connection = create_db();
+
+        for name in ["a", "b", "c"] {
+            set(&connection, name, None, None, None, None, false).unwrap();
+        }
+
+        let page = listed(
+            &connection,
+            ListFormat::Plain { color: false },
+            Filter::default(),
+            None,
+            Some(1),
+            Some(1),
+        )
+        .unwrap();
+        assert_eq!(page.lines().count(), 1);
+        assert!(page.contains(r#"name: "b""#));
+
+        let rest = listed(
+            &connection,
+            ListFormat::Plain { color: false },
+            Filter::default(),
+            None,
+            None,
+            Some(1),
+        )
+        .unwrap();
+        assert_eq!(rest.lines().count(), 2);
+    }
+
+    #[test]
+    fn copy_from() {
+        let dir = std::env::temp_dir().join(format!("config-store-copy-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("other.db");
+        let other = crate::Store::open(&path).unwrap();
+        other.set("vpn.home", Some("on"), None).unwrap();
+        other.set("vpn.work", Some("off"), None).unwrap();
+        other.set("theme", Some("dark"), None).unwrap();
+
+        let connection = create_db();
+        set(
+            &connection,
+            "vpn.home",
+            Some("keep".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        let copy = |policy| {
+            copy_from_cmd(
+                &connection,
+                path.clone(),
+                Some("vpn.*".to_string()),
+                policy,
+                false,
+            )
+        };
+
+        assert!(matches!(
+            copy(ConflictPolicy::Fail),
+            Err(Error::EntryExists)
+        ));
+        assert!(!exists(&connection, "vpn.work").unwrap());
+
+        assert_eq!(
+            copy(ConflictPolicy::Skip).unwrap(),
+            "Copied 1 entries, skipped 1"
+        );
+        assert_eq!(select(&connection, "vpn.home").unwrap().value, "keep");
+        assert!(!exists(&connection, "theme").unwrap());
+
+        // The entries of the other db weren't changed after those copied or set here
+        assert_eq!(
+            copy(ConflictPolicy::Newer).unwrap(),
+            "Copied 0 entries, skipped 2"
+        );
+        assert_eq!(select(&connection, "vpn.home").unwrap().value, "keep");
+
+        assert_eq!(
+            copy(ConflictPolicy::Overwrite).unwrap(),
+            "Copied 2 entries, skipped 0"
+        );
+        assert_eq!(select(&connection, "vpn.home").unwrap().value, "on");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rename_prefix() {
+        let connection = create_db();
+
+        for name in ["net.home", "net.work", "network", "vpn.home"] {
+            set(&connection, name, None, None, None, None, false).unwrap();
+        }
+        alias_cmd(&connection, "home".to_string(), "net.home".to_string()).unwrap();
+
+        assert!(matches!(
+            rename_prefix_cmd(&connection, "net.".to_string(), "vpn.".to_string()),
+            Err(Error::EntryExists)
+        ));
+
+        assert_eq!(
+            rename_prefix_cmd(&connection, "net.".to_string(), "lan.".to_string()).unwrap(),
+            "Renamed 2 entries"
+        );
+        assert!(exists(&connection, "lan.work").unwrap());
+        assert!(exists(&connection, "network").unwrap());
+        assert_eq!(resolve(&connection, "home").unwrap(), "lan.home");
+
+        // Secrets move in the keyring as well, which in memory dbs don't have
+        connection
+            .execute("UPDATE data SET secret = TRUE WHERE name = 'lan.work'", ())
+            .unwrap();
+        assert!(matches!(
+            rename_prefix_cmd(&connection, "lan.".to_string(), "wifi.".to_string()),
+            Err(Error::Invalid(_))
+        ));
+        assert!(exists(&connection, "lan.work").unwrap());
+
+        // A dry run doesn't touch the keyring
+        store::begin_dry_run(&connection).unwrap();
+        assert_eq!(
+            rename_prefix_cmd(&connection, "lan.".to_string(), "wifi.".to_string()).unwrap(),
+            "Renamed 2 entries"
+        );
+        store::end_dry_run(&connection).unwrap();
+        assert!(exists(&connection, "lan.work").unwrap());
+    }
+
+    #[test]
+    fn tree() {
+        let connection = create_db();
+
+        for (name, value) in [
+            ("vpn", "on"),
+            ("vpn.home.ip", "1"),
+            ("vpn.work", "2"),
+            ("theme", "dark"),
+        ] {
+            set(
+                &connection,
+                name,
+                Some(value.to_string()),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            tree_cmd(&connection, None, Filter::default()).unwrap(),
+            "theme = dark\nvpn = on\n  home\n    ip = 1\n  work = 2\n"
+        );
+        assert_eq!(
+            tree_cmd(&connection, Some(1), Filter::default()).unwrap(),
+            "theme = dark\nvpn = on (+2)\n"
+        );
+    }
+
+    #[test]
+    fn status() {
+        let connection = create_db();
+
+        set(
+            &connection,
+            "vpn",
+            Some("on".to_string()),
+            Some("off".to_string()),
+            Some("The \"VPN\"".to_string()),
+            None,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(
+            status_cmd(&connection, "vpn".to_string(), StatusFormat::Waybar).unwrap(),
+            r#"{"text":"on","alt":"on","class":"on","tooltip":"The \"VPN\""}"#
+        );
+        assert_eq!(
+            status_cmd(&connection, "vpn".to_string(), StatusFormat::Plain).unwrap(),
+            "on"
+        );
+    }
+
+    #[test]
+    fn export_script() {
+        let connection = create_db();
+
+        set(
+            &connection,
+            "it's",
+            Some("a b".to_string()),
+            Some("$HOME".to_string()),
+            Some("quoted".to_string()),
+            None,
+            false,
+        )
+        .unwrap();
+        tag_add_cmd(&connection, "it's".to_string(), vec!["t".to_string()]).unwrap();
+
+        let mut script = Vec::new();
+        export_cmd(
+            &connection,
+            &mut script,
+            ExportFormat::Script,
+            Filter::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(script).unwrap(),
+            "#!/bin/sh\nset -e\n\
+            config-store set --value='a b' --alternate='$HOME' --description='quoted' -- 'it'\\''s'\n\
+            config-store tag add -- 'it'\\''s' 't'\n"
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn export_script_round_trip() {
+        use crate::cli::{Action, Args, MetaAction, SetArgs, TagAction};
+        use clap::Parser as _;
+
+        let connection = create_db();
+        set(
+            &connection,
+            "-x",
+            Some("-v".to_string()),
+            Some("--alternate".to_string()),
+            Some("-d".to_string()),
+            Some("-".to_string()),
+            false,
+        )
+        .unwrap();
+        tag_add_cmd(&connection, "-x".to_string(), vec!["-t".to_string()]).unwrap();
+        meta_set_cmd(
+            &connection,
+            "-x".to_string(),
+            "-k".to_string(),
+            "-m".to_string(),
+        )
+        .unwrap();
+
+        let mut script = Vec::new();
+        export_cmd(
+            &connection,
+            &mut script,
+            ExportFormat::Script,
+            Filter::default(),
+        )
+        .unwrap();
+
+        // Run the script with a stand-in printing the arguments it's called with
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!(
+                "config_store() {{ printf '%s\\0' config-store \"$@\"; printf '\\n'; }}\n{}",
+                String::from_utf8(script)
+                    .unwrap()
+                    .replace("config-store ", "config_store ")
+            ))
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let calls: Vec<Action> = String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                Args::try_parse_from(line.trim_end_matches('\0').split('\0'))
+                    .unwrap()
+                    .command
+            })
+            .collect();
+
+        assert!(matches!(
+            &calls[..],
+            [
+                Action::Set(SetArgs {
+                    name: Some(name),
+                    value: Some(value),
+                    alternate: Some(alternate),
+                    description: Some(description),
+                    default: Some(default),
+                    ..
+                }),
+                Action::Tag {
+                    action: TagAction::Add { tags, .. },
+                },
+                Action::Meta {
+                    action: MetaAction::Set { key, value: meta, .. },
+                },
+            ] if name == "-x"
+                && value == "-v"
+                && alternate == "--alternate"
+                && description == "-d"
+                && default == "-"
+                && tags == &["-t"]
+                && key == "-k"
+                && meta == "-m"
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "archive")]
+    fn import_archive_protected() {
+        let dir = std::env::temp_dir().join(format!(
+            "config-store-archive-protected-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let archive = dir.join("out.tar.zst");
+        let store = crate::Store::open(dir.join("a.db")).unwrap();
+        store.set("theme", Some("dark"), None).unwrap();
+        export_archive_cmd(store.connection(), archive.clone()).unwrap();
+
+        let connection = create_db();
+        set(
+            &connection,
+            "token",
+            Some("secret".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        protect_cmd(&connection, "token".to_string(), true).unwrap();
+
+        assert!(matches!(
+            import_archive_cmd(&connection, archive.clone(), false),
+            Err(Error::Protected(names)) if names == "token"
+        ));
+        assert!(exists(&connection, "token").unwrap());
+        assert!(!exists(&connection, "theme").unwrap());
+
+        import_archive_cmd(&connection, archive, true).unwrap();
+        assert!(!exists(&connection, "token").unwrap());
+        assert!(exists(&connection, "theme").unwrap());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn export_systemd_env() {
+        let connection = create_db();
+        set(
+            &connection,
+            "vpn.home",
+            Some("on".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        set(
+            &connection,
+            "greeting",
+            Some("say \"hi\" to $USER".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        let mut file = Vec::new();
+        export_cmd(
+            &connection,
+            &mut file,
+            ExportFormat::SystemdEnv,
+            Filter::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(file).unwrap(),
+            "VPN_HOME=on\nGREETING=\"say \\\"hi\\\" to \\$USER\"\n"
+        );
+    }
+
+    #[test]
+    fn export_filtered() {
+        let connection = create_db();
+        for name in ["foot.theme", "foot.font", "sway.gaps", "token"] {
+            set(
+                &connection,
+                name,
+                Some("1".to_string()),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+        }
+        tag_add_cmd(
+            &connection,
+            "sway.gaps".to_string(),
+            vec!["share".to_string()],
+        )
+        .unwrap();
+
+        let export = |filter: Filter| {
+            let mut file = Vec::new();
+            export_cmd(&connection, &mut file, ExportFormat::SystemdEnv, filter).unwrap();
+            String::from_utf8(file).unwrap()
+        };
+        assert_eq!(
+            export(Filter {
+                prefix: Some("foot.".to_string()),
+                ..Default::default()
+            }),
+            "FOOT_THEME=1\nFOOT_FONT=1\n"
+        );
+        assert_eq!(
+            export(Filter {
+                glob: Some("*.font".to_string()),
+                ..Default::default()
+            }),
+            "FOOT_FONT=1\n"
+        );
+        assert_eq!(
+            export(Filter {
+                tag: Some("share".to_string()),
+                ..Default::default()
+            }),
+            "SWAY_GAPS=1\n"
+        );
+    }
+
+    /// A writer failing with `kind` once more than `capacity` bytes are written, like a closed pipe
+    struct Failing {
+        kind: std::io::ErrorKind,
+        capacity: usize,
+        written: Vec<u8>,
+    }
+
+    impl std::io::Write for Failing {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.written.len() + buf.len() > self.capacity {
+                return Err(self.kind.into());
+            }
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn stream_errors() {
+        let connection = create_db();
+        for name in ["a", "b", "c"] {
+            set(
+                &connection,
+                name,
+                Some("1".to_string()),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+        }
+        let failing = |kind| Failing {
+            kind,
+            capacity: 50,
+            written: Vec::new(),
+        };
+
+        // A reader closing the output early isn't an error
+        let mut out = failing(std::io::ErrorKind::BrokenPipe);
+        list_cmd(
+            &connection,
+            &mut out,
+            ListFormat::Plain { color: false },
+            Filter::default(),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        assert!(!out.written.is_empty());
+        let mut out = failing(std::io::ErrorKind::BrokenPipe);
+        export_cmd(&connection, &mut out, ExportFormat::Json, Filter::default()).unwrap();
+
+        // Any other failure to write is
+        let mut out = failing(std::io::ErrorKind::StorageFull);
+        let error = list_cmd(
+            &connection,
+            &mut out,
+            ListFormat::Plain { color: false },
+            Filter::default(),
+            None,
+            None,
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(error, Error::Io(e) if e.kind() == std::io::ErrorKind::StorageFull));
+        let mut out = failing(std::io::ErrorKind::StorageFull);
+        let error = export_cmd(
+            &connection,
+            &mut out,
+            ExportFormat::Script,
+            Filter::default(),
+        )
+        .unwrap_err();
+        assert!(matches!(error, Error::Io(e) if e.kind() == std::io::ErrorKind::StorageFull));
+
+        // As is an entry which can't be read, after the ones before it were written
+        connection
+            .execute("UPDATE data SET value = X'00ff' WHERE name = 'b'", ())
+            .unwrap();
+        let mut out = Vec::new();
+        let error = list_cmd(
+            &connection,
+            &mut out,
+            ListFormat::Plain { color: false },
+            Filter::default(),
+            None,
+            None,
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(error, Error::Sqlite(_)));
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Entry { _id: 1, name: \"a\", value: \"1\" }\n"
+        );
+        let error = export_cmd(
+            &connection,
+            &mut Vec::new(),
+            ExportFormat::Json,
+            Filter::default(),
+        )
+        .unwrap_err();
+        assert!(matches!(error, Error::Sqlite(_)));
+    }
+
+    #[test]
+    fn dedupe() {
+        let connection = create_db();
+
+        // Duplicates only exist in dbs without the unique index, e.g. from old versions
+        connection
+            .execute_batch(
+                "DROP INDEX data_name;
+                INSERT INTO data (name, value, alternate, updated_at, description) VALUES
+                    ('a', 'old', 'x', 1, 'described'), ('a', 'new', 'x', 2, NULL), ('b', 'v', 'w', 1, NULL);",
+            )
+            .unwrap();
+        connection
+            .execute("INSERT INTO tags VALUES (1, 'old')", ())
+            .unwrap();
+
+        // Without the unique index `set` can't upsert, but still works
+        for name in ["b", "c"] {
+            set(
+                &connection,
+                name,
+                Some("v2".to_string()),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+            assert_eq!(select(&connection, name).unwrap().value, "v2");
+        }
+
+        assert_eq!(
+            dedupe_cmd(&connection, DedupeStrategy::Merge).unwrap(),
+            "a: kept \"new\" (removed 1)\n"
+        );
+
+        let entry = select(&connection, "a").unwrap();
+        assert_eq!(entry.value, "new");
+        assert_eq!(entry.description, Some("described".to_string()));
+        assert_eq!(
+            tag_list_cmd(&connection, Some("a".to_string())).unwrap(),
+            "old"
+        );
+
+        assert_eq!(dedupe_cmd(&connection, DedupeStrategy::Newest).unwrap(), "");
+        assert!(matches!(
+            connection.execute("INSERT INTO data (name) VALUES ('a')", ()),
+            Err(rusqlite::Error::SqliteFailure(_, _))
+        ));
+    }
+
+    #[test]
+    fn doctor() {
+        let connection = create_db();
+
+        connection
+            .execute_batch(
+                "DROP INDEX data_name;
+                INSERT INTO data (name, value, alternate, updated_at) VALUES
+                    ('a', 'old', 'x', 1), ('a', 'new', 'x', 2), ('b', NULL, 'y', 1);
+                INSERT INTO aliases VALUES ('c', 'missing');",
+            )
+            .unwrap();
+
+        let report = doctor_cmd(&connection, false).unwrap();
+        assert!(report.contains("integrity check: ok\n"));
+        assert!(report.contains("duplicate entries: 1\n"));
+        assert!(report.contains("entries with a missing value: 1\n"));
+        assert!(report.contains("aliases of missing entries: 1\n"));
+        assert!(report.contains("--fix"));
+
+        let report = doctor_cmd(&connection, true).unwrap();
+        assert!(report.contains("duplicate entries: 1 (fixed)\n"));
+        assert_eq!(select(&connection, "a").unwrap().value, "new".to_string());
+        assert_eq!(select(&connection, "b").unwrap().value, "");
+
+        assert!(!doctor_cmd(&connection, false).unwrap().contains("--fix"));
+    }
+
+    #[test]
+    fn bench() {
+        let output = bench_cmd(10, 0).unwrap();
+
+        assert_eq!(output.lines().count(), 3);
+        assert!(output.starts_with("set "));
+    }
+
+    #[test]
+    fn generate_kinds() {
+        let uuid = random_string(GenerateKind::Uuid, 0).unwrap();
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(uuid.chars().nth(14), Some('4'));
+
+        let hex = random_string(GenerateKind::Hex, 7).unwrap();
+        assert_eq!(hex.len(), 7);
+        assert!(hex.chars().all(|c| c.is_ascii_hexdigit()));
+
+        assert_eq!(random_string(GenerateKind::Base64, 10).unwrap().len(), 10);
+    }
+
+    #[test]
+    fn generate_keeps_existing() {
+        let connection = create_db();
+
+        let first =
+            generate_cmd(&connection, "id".to_string(), GenerateKind::Hex, 16, false).unwrap();
+        let second =
+            generate_cmd(&connection, "id".to_string(), GenerateKind::Hex, 16, false).unwrap();
+        assert_eq!(first, second);
+
+        let forced =
+            generate_cmd(&connection, "id".to_string(), GenerateKind::Hex, 16, true).unwrap();
+        assert_ne!(first, forced);
+        assert_eq!(select(&connection, "id").unwrap().value, forced);
+    }
+
+    #[test]
+    fn timestamps() {
+        let connection = create_db();
+
+        set_cmd(
+            &connection,
+            "test1".to_string(),
+            None,
+            None,
+            None,
+            None,
+            SetMode::Upsert,
+        )
+        .unwrap();
+        let created = select(&connection, "test1").unwrap();
+        assert!(created.created_at.is_some());
+        assert_eq!(created.created_at, created.updated_at);
+
+        connection
+            .execute("UPDATE data SET updated_at = 0 WHERE name = 'test1'", [])
+            .unwrap();
+        toggle_cmd(
+            &connection,
+            vec!["test1".to_string()],
+            Filter::default(),
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        let toggled = select(&connection, "test1").unwrap();
+        assert_eq!(toggled.created_at, created.created_at);
+        assert!(toggled.updated_at.unwrap() > 0);
+    }
+
+    #[test]
+    fn migrate_legacy_table() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute(
+                "CREATE TABLE data (id INTEGER PRIMARY KEY, name TEXT, value TEXT, alternate TEXT)",
+                [],
+            )
+            .unwrap();
+        connection
+            .execute(
+                "INSERT INTO data (name, value, alternate) VALUES ('old', 'a', 'b')",
+                [],
+            )
+            .unwrap();
+
+        crate::schema::init(&connection).unwrap();
+
+        let entry = select(&connection, "old").unwrap();
+        assert_eq!(entry.value, "a");
+        assert_eq!(entry.created_at, None);
+    }
+
+    #[test]
+    fn description() {
+        let connection = create_db();
+
+        set_cmd(
+            &connection,
+            "flag".to_string(),
+            Some("on".to_string()),
+            None,
+            Some("Whether the flag is set".to_string()),
+            None,
+            SetMode::Upsert,
+        )
+        .unwrap();
+        set_cmd(
+            &connection,
+            "flag".to_string(),
+            Some("off".to_string()),
+            None,
+            None,
+            None,
+            SetMode::Upsert,
+        )
+        .unwrap();
+
+        let entry = select(&connection, "flag").unwrap();
+        assert_eq!(entry.value, "off");
+        assert_eq!(
+            entry.description.as_deref(),
+            Some("Whether the flag is set")
+        );
+    }
+
+    #[test]
+    fn tags() {
+        let connection = create_db();
+
+        for name in ["bar", "gaps", "theme"] {
+            set_cmd(
+                &connection,
+                name.to_string(),
+                None,
+                None,
+                None,
+                None,
+                SetMode::Upsert,
+            )
+            .unwrap();
+        }
+        tag_add_cmd(&connection, "bar".to_string(), vec!["gaming".to_string()]).unwrap();
+        tag_add_cmd(
+            &connection,
+            "gaps".to_string(),
+            vec!["gaming".to_string(), "layout".to_string()],
+        )
+        .unwrap();
+
+        assert_eq!(
+            tag_list_cmd(&connection, Some("gaps".to_string())).unwrap(),
+            "gaming\nlayout"
+        );
+        assert_eq!(tag_list_cmd(&connection, None).unwrap(), "gaming\nlayout");
+
+        let gaming = Filter {
+            tag: Some("gaming".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            listed(
+                &connection,
+                ListFormat::Plain { color: false },
+                gaming.clone(),
+                None,
+                None,
+                None
+            )
+            .unwrap()
+            .lines()
+            .count(),
+            2
+        );
+
+        delete_cmd(&connection, None, gaming, false, false).unwrap();
+        assert!(exists(&connection, "theme").unwrap());
+        assert!(!exists(&connection, "bar").unwrap());
+        assert_eq!(tag_list_cmd(&connection, None).unwrap(), "");
+    }
+
+    #[test]
+    fn meta() {
+        let connection = create_db();
+        let meta_set = |key: &str, value: &str| {
+            meta_set_cmd(
+                &connection,
+                "bar".to_string(),
+                key.to_string(),
+                value.to_string(),
+            )
+        };
+
+        assert!(matches!(
+            meta_set("color", "#ff0000"),
+            Err(Error::EntryNotFound { .. })
+        ));
+
+        set(&connection, "bar", None, None, None, None, false).unwrap();
+        meta_set("color", "#00ff00").unwrap();
+        meta_set("color", "#ff0000").unwrap();
+        meta_set("icon", "\"bar\"").unwrap();
+
+        assert_eq!(
+            meta_get_cmd(&connection, "bar".to_string(), "color".to_string()).unwrap(),
+            "#ff0000"
+        );
+        assert_eq!(
+            meta_list_cmd(&connection, "bar".to_string()).unwrap(),
+            "color=#ff0000\nicon=\"bar\""
+        );
+        assert!(get_cmd(
+            &connection,
+            "bar".to_string(),
+            false,
+            false,
+            GetFormat::Json(None),
+            false
+        )
+        .unwrap()
+        .ends_with(r##""meta": {"color": "#ff0000", "icon": "\"bar\""} }"##));
+
+        meta_unset_cmd(
+            &connection,
+            "bar".to_string(),
+            vec!["icon".to_string()],
+            true,
+        )
+        .unwrap();
+        assert!(matches!(
+            meta_get_cmd(&connection, "bar".to_string(), "icon".to_string()),
+            Err(Error::NotFound(_))
+        ));
+        assert!(matches!(
+            meta_unset_cmd(
+                &connection,
+                "bar".to_string(),
+                vec!["icon".to_string()],
+                true
+            ),
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn reset() {
+        let connection = create_db();
+
+        set_cmd(
+            &connection,
+            "volume".to_string(),
+            Some("80".to_string()),
+            None,
+            None,
+            Some("50".to_string()),
+            SetMode::Upsert,
+        )
+        .unwrap();
+        set_cmd(
+            &connection,
+            "other".to_string(),
+            None,
+            None,
+            None,
+            None,
+            SetMode::Upsert,
+        )
+        .unwrap();
+
+        assert_eq!(reset_cmd(&connection, "volume".to_string()).unwrap(), "50");
+        assert_eq!(select(&connection, "volume").unwrap().value, "50");
+        assert!(matches!(
+            reset_cmd(&connection, "other".to_string()),
+            Err(Error::NoDefault)
+        ));
+    }
+
+    #[test]
+    fn reset_all() {
+        let connection = create_db();
+
+        for (name, value, default) in [
+            ("ui.bar", "off", Some("on")),
+            ("ui.gaps", "10", Some("10")),
+            ("ui.theme", "light", None),
+            ("net.vpn", "on", Some("off")),
+        ] {
+            set_cmd(
+                &connection,
+                name.to_string(),
+                Some(value.to_string()),
+                None,
+                None,
+                default.map(str::to_string),
+                SetMode::Upsert,
+            )
+            .unwrap();
+        }
+
+        let filter = Filter {
+            prefix: Some("ui.".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            reset_all_cmd(&connection, filter).unwrap(),
+            "ui.bar: off -> on"
+        );
+        assert_eq!(select(&connection, "net.vpn").unwrap().value, "on");
+
+        assert_eq!(
+            reset_all_cmd(&connection, Filter::default()).unwrap(),
+            "net.vpn: on -> off"
+        );
+    }
+
+    #[test]
+    fn presets() {
+        let connection = create_db();
+
+        for name in ["bar", "gaps"] {
+            set_cmd(
+                &connection,
+                name.to_string(),
+                Some("on".to_string()),
+                Some("off".to_string()),
+                None,
+                None,
+                SetMode::Upsert,
+            )
+            .unwrap();
+        }
+
+        preset_save_cmd(
+            &connection,
+            "normal".to_string(),
+            vec!["bar".to_string(), "gaps".to_string()],
+            Filter::default(),
+        )
+        .unwrap();
+        assert_eq!(preset_list_cmd(&connection).unwrap(), "normal");
+
+        toggle_cmd(
+            &connection,
+            vec!["bar".to_string()],
+            Filter::default(),
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        delete_cmd(
+            &connection,
+            Some("gaps".to_string()),
+            Filter::default(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        preset_apply_cmd(&connection, "normal".to_string()).unwrap();
+        assert_eq!(select(&connection, "bar").unwrap().value, "on");
+        assert_eq!(
+            select(&connection, "gaps").unwrap().alternate.as_deref(),
+            Some("off")
+        );
+
+        assert!(matches!(
+            preset_apply_cmd(&connection, "missing".to_string()),
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn toggle_multiple() {
+        let connection = create_db();
+
+        for (name, value, alternate) in [("bar", "on", "off"), ("gaps", "0", "10")] {
+            set_cmd(
+                &connection,
+                name.to_string(),
+                Some(value.to_string()),
+                Some(alternate.to_string()),
+                None,
+                None,
+                SetMode::Upsert,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            toggle_cmd(
+                &connection,
+                vec!["bar".to_string(), "gaps".to_string()],
+                Filter::default(),
+                None,
+                None,
+                false
+            )
+            .unwrap(),
+            "off\n10"
+        );
+
+        assert!(matches!(
+            toggle_cmd(
+                &connection,
+                vec!["bar".to_string(), "missing".to_string()],
+                Filter::default(),
+                None,
+                None,
+                false
+            ),
+            Err(Error::EntryNotFound { .. })
+        ));
+        assert_eq!(select(&connection, "bar").unwrap().value, "off");
+    }
+
+    #[test]
+    fn shell_init() {
+        let connection = create_db();
+        set_cmd(
+            &connection,
+            "vpn.home".to_string(),
+            Some("it's $HOME \\o/".to_string()),
+            None,
+            None,
+            None,
+            SetMode::Upsert,
+        )
+        .unwrap();
+
+        assert_eq!(
+            shell_env_cmd(&connection, InitShell::Bash, "CS_").unwrap(),
+            r"export CS_VPN_HOME='it'\''s $HOME \o/'"
+        );
+        assert_eq!(
+            shell_env_cmd(&connection, InitShell::Fish, "").unwrap(),
+            r"set -gx VPN_HOME 'it\'s $HOME \\o/'"
+        );
+
+        for shell in [InitShell::Bash, InitShell::Zsh, InitShell::Fish] {
+            let functions = shell_init_cmd("/tmp/test.db", shell, "CS_");
+            for function in ["cs-get", "cs-toggle", "cs-env"] {
+                assert!(functions.contains(function));
+            }
+            assert!(functions.contains("--db-path '/tmp/test.db'"));
+        }
+
+        // Relative paths are resolved, so the functions work in any directory
+        let canonical = std::fs::canonicalize("Cargo.toml").unwrap();
+        let functions = shell_init_cmd("src/../Cargo.toml", InitShell::Bash, "CS_");
+        assert!(functions.contains(&format!("--db-path '{}'", canonical.display())));
+    }
+
+    #[test]
+    fn exec() {
+        let connection = create_db();
+
+        for (name, value) in [
+            ("vpn.home", "on"),
+            ("vpn.work-laptop", "off"),
+            ("theme", "dark"),
+        ] {
+            set_cmd(
+                &connection,
+                name.to_string(),
+                Some(value.to_string()),
+                None,
+                None,
+                None,
+                SetMode::Upsert,
+            )
+            .unwrap();
+        }
+
+        let mut env = exec_env(&connection, Some("vpn.*".to_string()), "CS_").unwrap();
+        env.sort();
+        assert_eq!(
+            env,
+            [
+                ("CS_VPN_HOME".to_string(), "on".to_string()),
+                ("CS_VPN_WORK_LAPTOP".to_string(), "off".to_string())
+            ]
+        );
+        assert_eq!(exec_env(&connection, None, "").unwrap().len(), 3);
+        assert!(matches!(
+            exec_cmd(&connection, None, String::new(), vec![]),
+            Err(Error::Invalid(_))
+        ));
+        assert!(matches!(
+            exec_cmd(
+                &connection,
+                None,
+                String::new(),
+                vec!["config-store-missing-command".to_string()]
+            ),
+            Err(Error::Io(_))
+        ));
+    }
+
+    #[test]
+    fn seed() {
+        let connection = create_db();
+        let path =
+            std::env::temp_dir().join(format!("config-store-seed-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "[theme]\nvalue = \"light\"\nalternate = \"dark\"\n\n[gaps]\ndefault = \"10\"\n",
+        )
+        .unwrap();
+
+        set(
+            &connection,
+            "theme",
+            Some("dark".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(
+            seed_cmd(&connection, path.clone()).unwrap(),
+            "Created 1 entries, kept 1"
+        );
+        assert_eq!(select(&connection, "theme").unwrap().value, "dark");
+        assert_eq!(select(&connection, "gaps").unwrap().value, "10");
+        assert_eq!(
+            seed_cmd(&connection, path.clone()).unwrap(),
+            "Created 0 entries, kept 2"
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn apply() {
+        let connection = create_db();
+        let path =
+            std::env::temp_dir().join(format!("config-store-apply-{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"[{"name": "theme", "value": "light"}, {"name": "gaps", "default": "10"}]"#,
+        )
+        .unwrap();
+
+        for (name, value) in [("theme", "dark"), ("stale", "1")] {
+            set(
+                &connection,
+                name,
+                Some(value.to_string()),
+                Some("x".to_string()),
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            apply_cmd(&connection, path.clone(), false).unwrap(),
+            "Created 1 entries, updated 1, deleted 0"
+        );
+        assert_eq!(select(&connection, "theme").unwrap().value, "light");
+        assert_eq!(
+            select(&connection, "theme").unwrap().alternate.as_deref(),
+            Some("x")
+        );
+        assert_eq!(select(&connection, "gaps").unwrap().value, "10");
+
+        connection
+            .execute("UPDATE data SET protected = true WHERE name = 'stale'", ())
+            .unwrap();
+        assert!(matches!(
+            apply_cmd(&connection, path.clone(), true),
+            Err(Error::Protected(_))
+        ));
+        connection
+            .execute("UPDATE data SET protected = false", ())
+            .unwrap();
+        assert_eq!(
+            apply_cmd(&connection, path.clone(), true).unwrap(),
+            "Created 0 entries, updated 0, deleted 1"
+        );
+        assert!(!exists(&connection, "stale").unwrap());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn validate() {
+        let connection = create_db();
+        let path =
+            std::env::temp_dir().join(format!("config-store-validate-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "[theme]\nvalues = [\"light\", \"dark\"]\n\n[gaps]\ntype = \"int\"\n\n\
+             [host]\npattern = \"[a-z]+\\\\.lan\"\nrequired = true\n",
+        )
+        .unwrap();
+
+        for (name, value) in [("theme", "dark"), ("gaps", "10")] {
+            set(
+                &connection,
+                name,
+                Some(value.to_string()),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+        }
+
+        let Err(Error::Invalid(violations)) = validate_cmd(&connection, path.clone()) else {
+            panic!("expected violations");
+        };
+        assert!(violations.ends_with("'host' is missing"));
+
+        for (name, value) in [("theme", "blue"), ("host", "nas.lan.example")] {
+            set(
+                &connection,
+                name,
+                Some(value.to_string()),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+        }
+        let Err(Error::Invalid(violations)) = validate_cmd(&connection, path.clone()) else {
+            panic!("expected violations");
+        };
+        assert_eq!(
+            violations,
+            "entries don't match the manifest:\n\
+             'nas.lan.example' of 'host' doesn't match `[a-z]+\\.lan`\n\
+             'blue' of 'theme' isn't one of light, dark"
+        );
+
+        set(
+            &connection,
+            "theme",
+            Some("light".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        set(
+            &connection,
+            "host",
+            Some("nas.lan".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        assert_eq!(validate_cmd(&connection, path.clone()).unwrap(), "Ok");
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn schema() {
+        let connection = create_db();
+        let path =
+            std::env::temp_dir().join(format!("config-store-schema-{}.toml", std::process::id()));
+        std::fs::write(&path, "[mute]\ntype = \"bool\"\n").unwrap();
+
+        set(
+            &connection,
+            "mute",
+            Some("yes".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        assert!(matches!(
+            schema_cmd(&connection, Some(path.clone()), false),
+            Err(Error::Invalid(_))
+        ));
+
+        let set_mute = |value: &str, alternate: &str| {
+            set_cmd(
+                &connection,
+                "mute".to_string(),
+                Some(value.to_string()),
+                Some(alternate.to_string()),
+                None,
+                None,
+                SetMode::Upsert,
+            )
+        };
+        set_mute("true", "").unwrap();
+        schema_cmd(&connection, Some(path.clone()), false).unwrap();
+
+        assert!(matches!(
+            set_mute("yes", "no"),
+            Err(Error::InvalidType { .. })
+        ));
+        set_mute("true", "maybe").unwrap_err();
+        assert_eq!(select(&connection, "mute").unwrap().value, "true");
+
+        set_mute("true", "false").unwrap();
+        assert_eq!(
+            toggle_cmd(
+                &connection,
+                vec!["mute".to_string()],
+                Filter::default(),
+                None,
+                None,
+                false
+            )
+            .unwrap(),
+            "false"
+        );
+        assert!(listed(
+            &connection,
+            ListFormat::Long { color: false },
+            Filter::default(),
+            None,
+            None,
+            None
+        )
+        .unwrap()
+        .ends_with("(bool)\n"));
+        // Declared types are kept in json
+        assert!(listed(
+            &connection,
+            ListFormat::Json,
+            Filter::default(),
+            None,
+            None,
+            None
+        )
+        .unwrap()
+        .contains(r#""value": false, "alternate": true,"#));
+        let get_as = |kind: &str| {
+            get_cmd(
+                &connection,
+                "mute".to_string(),
+                false,
+                false,
+                GetFormat::Json(Some(ValueType::parse(kind).unwrap())),
+                false,
+            )
+        };
+        assert!(get_as("string")
+            .unwrap()
+            .contains(r#""value": "false", "alternate": "true","#));
+        assert!(matches!(get_as("int"), Err(Error::InvalidType { .. })));
+
+        schema_cmd(&connection, None, true).unwrap();
+        set_mute("yes", "no").unwrap();
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn query() {
+        let path =
+            std::env::temp_dir().join(format!("config-store-query-{}.db", std::process::id()));
+        let connection = Connection::open(&path).unwrap();
+        crate::schema::init(&connection).unwrap();
+
+        for (name, value) in [("theme", "dark"), ("vpn", "on")] {
+            set(
+                &connection,
+                name,
+                Some(value.to_string()),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+        }
+
+        let sql = "SELECT name, value, NULL AS missing FROM data ORDER BY name".to_string();
+        assert_eq!(
+            query_cmd(&connection, sql.clone(), false).unwrap(),
+            "name   value  missing\ntheme  dark\nvpn    on"
+        );
+        assert_eq!(
+            query_cmd(&connection, sql, true).unwrap(),
+            r#"{"name":"theme","value":"dark","missing":null}
+{"name":"vpn","value":"on","missing":null}"#
+        );
+
+        let attached = path.with_extension("attached");
+        for sql in [
+            "DELETE FROM data".to_string(),
+            "SELEC 1".to_string(),
+            " ".to_string(),
+            "SELECT 1; DELETE FROM data".to_string(),
+            format!("ATTACH '{}' AS other", attached.display()),
+            format!("VACUUM INTO '{}'", attached.display()),
+        ] {
+            assert!(matches!(
+                query_cmd(&connection, sql, false),
+                Err(Error::Invalid(_))
+            ));
+        }
+        assert!(!attached.exists());
+        assert_eq!(
+            query_cmd(&connection, "SELECT count(*) FROM data;".to_string(), false).unwrap(),
+            "count(*)\n2"
+        );
+        set(&connection, "mute", None, None, None, None, false).unwrap();
+
+        assert!(matches!(
+            query_cmd(&create_db(), "SELECT 1".to_string(), false),
+            Err(Error::Invalid(_))
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn toggle_filtered() {
+        let connection = create_db();
+
+        for name in ["vpn.home", "vpn.work", "gamemode"] {
+            set_cmd(
+                &connection,
+                name.to_string(),
+                Some("on".to_string()),
+                Some("off".to_string()),
+                None,
+                None,
+                SetMode::Upsert,
+            )
+            .unwrap();
+        }
+        tag_add_cmd(
+            &connection,
+            "gamemode".to_string(),
+            vec!["gaming".to_string()],
+        )
+        .unwrap();
+
+        let toggle = |filter| toggle_cmd(&connection, vec![], filter, None, None, false).unwrap();
+
+        assert_eq!(
+            toggle(Filter {
+                glob: Some("vpn.*".to_string()),
+                ..Default::default()
+            }),
+            "off\noff"
+        );
+        assert_eq!(
+            toggle(Filter {
+                tag: Some("gaming".to_string()),
+                ..Default::default()
+            }),
+            "off"
+        );
+        assert_eq!(select(&connection, "vpn.work").unwrap().value, "off");
+    }
+
+    #[test]
+    fn unset_alternates() {
+        let connection = create_db();
+        let set = |alternate: Option<&str>| {
+            set_cmd(
+                &connection,
+                "theme".to_string(),
+                Some("dark".to_string()),
+                alternate.map(str::to_string),
+                None,
+                None,
+                SetMode::Upsert,
+            )
+            .unwrap()
+        };
+        let get = || {
+            get_cmd(
+                &connection,
+                "theme".to_string(),
+                false,
+                false,
+                GetFormat::Plain {
+                    color: false,
+                    separator: ' ',
+                },
+                false,
+            )
+            .unwrap()
+        };
+        let toggle = || {
+            toggle_cmd(
+                &connection,
+                vec!["theme".to_string()],
+                Filter::default(),
+                None,
+                None,
+                true,
+            )
+        };
+
+        set(None);
+        assert_eq!(get(), "dark");
+        assert!(select(&connection, "theme")
+            .unwrap()
+            .json()
+            .contains(r#""alternate": null"#));
+        assert!(matches!(toggle(), Err(Error::NotFound(_))));
+
+        set(Some(""));
+        assert_eq!(get(), "dark ");
+        assert_eq!(toggle().unwrap(), "");
+    }
+
+    #[test]
+    fn ignore_case() {
+        let connection = create_db();
+        let set = |name: &str, value: &str| {
+            set_cmd(
+                &connection,
+                name.to_string(),
+                Some(value.to_string()),
+                None,
+                None,
+                None,
+                SetMode::Upsert,
+            )
+        };
+
+        set("Theme", "dark").unwrap();
+        set("theme", "light").unwrap();
+        assert_eq!(ignore_case_cmd(&connection, None).unwrap(), "off");
+        assert_eq!(case_duplicates_cmd(&connection).unwrap(), "Theme theme");
+
+        ignore_case_cmd(&connection, Some(true)).unwrap();
+        set("THEME", "auto").unwrap();
+        set("Font", "mono").unwrap();
+        set("FONT", "sans").unwrap();
+        // Without an exact match the first name wins
+        assert_eq!(select(&connection, "Theme").unwrap().value, "auto");
+        assert_eq!(select(&connection, "theme").unwrap().value, "light");
+        assert_eq!(select(&connection, "Font").unwrap().value, "sans");
+        assert!(!exists(&connection, "FONT").unwrap());
+        assert_eq!(resolve(&connection, "fOnT").unwrap(), "Font");
+
+        delete_cmd(
+            &connection,
+            Some("font".to_string()),
+            Filter::default(),
+            true,
+            false,
+        )
+        .unwrap();
+        assert!(!exists(&connection, "Font").unwrap());
+    }
+
+    #[test]
+    fn aliases() {
+        let connection = create_db();
+
+        set_cmd(
+            &connection,
+            "theme.dark".to_string(),
+            Some("on".to_string()),
+            Some("off".to_string()),
+            None,
+            None,
+            SetMode::Upsert,
+        )
+        .unwrap();
+
+        alias_cmd(&connection, "dark".to_string(), "theme.dark".to_string()).unwrap();
+        alias_cmd(&connection, "dm".to_string(), "dark".to_string()).unwrap();
+
+        assert_eq!(
+            exists_cmd(&connection, "dm".to_string(), false, false).unwrap(),
+            "true"
+        );
+        assert_eq!(
+            toggle_cmd(
+                &connection,
+                vec!["dark".to_string()],
+                Filter::default(),
+                None,
+                None,
+                false
+            )
+            .unwrap(),
+            "off"
+        );
+        assert_eq!(
+            get_cmd(
+                &connection,
+                "dm".to_string(),
+                true,
+                false,
+                GetFormat::Plain {
+                    color: false,
+                    separator: ' '
+                },
+                false,
+            )
+            .unwrap(),
+            "off"
+        );
+
+        assert!(matches!(
+            alias_cmd(&connection, "theme.dark".to_string(), "dark".to_string()),
+            Err(Error::EntryExists)
+        ));
+        assert!(matches!(
+            alias_cmd(&connection, "x".to_string(), "missing".to_string()),
+            Err(Error::EntryNotFound { .. })
+        ));
+
+        unalias_cmd(&connection, "dark".to_string(), false).unwrap();
+        assert!(matches!(
+            unalias_cmd(&connection, "dark".to_string(), true),
+            Err(Error::NotFound(_))
+        ));
+        assert_eq!(
+            exists_cmd(&connection, "dark".to_string(), false, false).unwrap(),
+            "false"
+        );
+    }
+
+    #[test]
+    fn touch_and_recent() {
+        let connection = create_db();
+
+        for name in ["a", "b", "c"] {
+            set_cmd(
+                &connection,
+                name.to_string(),
+                None,
+                None,
+                None,
+                None,
+                SetMode::Upsert,
+            )
+            .unwrap();
+        }
+        connection
+            .execute("UPDATE data SET updated_at = 0", [])
+            .unwrap();
+
+        alias_cmd(&connection, "bee".to_string(), "b".to_string()).unwrap();
+        touch_cmd(&connection, "bee".to_string()).unwrap();
+        assert!(matches!(
+            touch_cmd(&connection, "missing".to_string()),
+            Err(Error::EntryNotFound { .. })
+        ));
+
+        let recent = listed(
+            &connection,
+            ListFormat::Plain { color: false },
+            Filter::default(),
+            Some(1),
+            None,
+            None,
+        )
+        .unwrap();
+        assert!(recent.starts_with("Entry { _id: 2, name: \"b\""));
+        assert_eq!(recent.lines().count(), 1);
+
+        let since = Filter {
+            since: Some(3600),
+            ..Default::default()
+        };
+        assert_eq!(
+            listed(
+                &connection,
+                ListFormat::Plain { color: false },
+                since,
+                None,
+                None,
+                None
+            )
+            .unwrap()
+            .lines()
+            .count(),
+            1
+        );
+
+        let changed_since = Filter {
+            changed_since: Some(time::parse_timestamp("1h").unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(
+            listed(
+                &connection,
+                ListFormat::Plain { color: false },
+                changed_since,
+                None,
+                None,
+                None
+            )
+            .unwrap()
+            .lines()
+            .count(),
+            1
+        );
+
+        // Stale entries are cleared in one call
+        let older_than = Filter {
+            older_than: Some(time::parse_duration("7d").unwrap()),
+            ..Default::default()
+        };
+        delete_cmd(&connection, None, older_than, true, false).unwrap();
+        assert!(!exists(&connection, "a").unwrap());
+        assert!(exists(&connection, "b").unwrap());
+    }
+
+    #[test]
+    fn delete_older_than() {
+        let connection = create_db();
+        for name in ["tmp.old", "tmp.new", "tmp.locked", "theme"] {
+            set(
+                &connection,
+                name,
+                Some("1".to_string()),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+        }
+        connection
+            .execute("UPDATE data SET updated_at = 0 WHERE name != 'tmp.new'", ())
+            .unwrap();
+        protect_cmd(&connection, "tmp.locked".to_string(), true).unwrap();
+        let stale = |prefix: &str| Filter {
+            prefix: Some(prefix.to_string()),
+            older_than: Some(time::parse_duration("7d").unwrap()),
+            ..Default::default()
+        };
+
+        // Nothing is deleted while a stale entry is protected
+        assert!(matches!(
+            delete_cmd(&connection, None, stale("tmp."), false, false),
+            Err(Error::Protected(names)) if names == "tmp.locked"
+        ));
+        assert!(exists(&connection, "tmp.old").unwrap());
+
+        delete_cmd(&connection, None, stale("tmp."), false, true).unwrap();
+        assert!(!exists(&connection, "tmp.old").unwrap());
+        assert!(!exists(&connection, "tmp.locked").unwrap());
+        assert!(exists(&connection, "tmp.new").unwrap());
+        assert!(exists(&connection, "theme").unwrap());
+
+        assert!(matches!(
+            delete_cmd(&connection, None, stale("tmp."), true, false),
+            Err(Error::NotFound(_))
+        ));
+        delete_cmd(&connection, None, stale("tmp."), false, false).unwrap();
+    }
+
+    #[test]
+    fn changed_since() {
+        let connection = create_db();
+        for (name, updated_at) in [("a", 100), ("b", 300), ("c", 200)] {
+            set(
+                &connection,
+                name,
+                Some("1".to_string()),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+            connection
+                .execute(
+                    "UPDATE data SET updated_at = ? WHERE name = ?",
+                    (updated_at, name),
+                )
+                .unwrap();
+        }
+        let changed_since = |timestamp| Filter {
+            changed_since: Some(timestamp),
+            ..Default::default()
+        };
+
+        // Inclusive & sorted by the most recent change
+        let list = listed(
+            &connection,
+            ListFormat::Plain { color: false },
+            changed_since(200),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        let lines: Vec<&str> = list.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("Entry { _id: 2, name: \"b\""));
+        assert!(lines[1].starts_with("Entry { _id: 3, name: \"c\""));
+        assert_eq!(
+            listed(
+                &connection,
+                ListFormat::Plain { color: false },
+                changed_since(301),
+                None,
+                None,
+                None
+            )
+            .unwrap(),
+            ""
+        );
+    }
+
+    #[test]
+    fn freeze() {
+        let connection = create_db();
+        set(
+            &connection,
+            "pi",
+            Some("3.14".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        freeze_cmd(&connection, "pi".to_string(), true).unwrap();
+        assert!(matches!(
+            set(
+                &connection,
+                "pi",
+                Some("3".to_string()),
+                None,
+                None,
+                None,
+                false
+            ),
+            Err(Error::Frozen)
+        ));
+        assert!(matches!(toggle(&connection, "pi"), Err(Error::Frozen)));
+        assert!(matches!(
+            delete_cmd(
+                &connection,
+                Some("pi".to_string()),
+                Filter::default(),
+                false,
+                false,
+            ),
+            Err(Error::Frozen)
+        ));
+        touch_cmd(&connection, "pi".to_string()).unwrap();
+        assert!(matches!(
+            freeze_cmd(&connection, "e".to_string(), true),
+            Err(Error::EntryNotFound { .. })
+        ));
+
+        freeze_cmd(&connection, "pi".to_string(), false).unwrap();
+        assert_eq!(toggle(&connection, "pi").unwrap(), "");
+    }
+
+    #[test]
+    fn protect() {
+        let connection = create_db();
+        for name in ["a", "b"] {
+            set(&connection, name, None, None, None, None, false).unwrap();
+        }
+        protect_cmd(&connection, "a".to_string(), true).unwrap();
+
+        let all = Filter::default();
+        assert!(matches!(
+            delete_cmd(&connection, None, all.clone(), false, false),
+            Err(Error::Protected(names)) if names == "a"
+        ));
+        assert!(matches!(
+            drop_cmd(&connection, false),
+            Err(Error::Protected(_))
+        ));
+        assert!(exists(&connection, "b").unwrap());
+
+        set(
+            &connection,
+            "a",
+            Some("changed".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        delete_cmd(&connection, Some("a".to_string()), all.clone(), false, true).unwrap();
+        assert!(!exists(&connection, "a").unwrap());
+
+        protect_cmd(&connection, "b".to_string(), true).unwrap();
+        protect_cmd(&connection, "b".to_string(), false).unwrap();
+        delete_cmd(&connection, None, all, false, false).unwrap();
+    }
+
+    #[test]
+    fn compute() {
+        let connection = create_db();
+        set(&connection, "a", None, None, None, None, false).unwrap();
+        compute_cmd(
+            &connection,
+            "a".to_string(),
+            Some("echo $((1 + 1))".to_string()),
+            None,
+            false,
+        )
+        .unwrap();
+
+        let get = |connection| {
+            get_cmd(
+                connection,
+                "a".to_string(),
+                true,
+                false,
+                GetFormat::Plain {
+                    color: false,
+                    separator: ' ',
+                },
+                false,
+            )
+        };
+        assert_eq!(get(&connection).unwrap(), "2");
+
+        compute_cmd(
+            &connection,
+            "a".to_string(),
+            Some("date +%N".to_string()),
+            Some(60),
+            false,
+        )
+        .unwrap();
+        let cached = get(&connection).unwrap();
+        assert_eq!(get(&connection).unwrap(), cached);
+
+        compute_cmd(
+            &connection,
+            "a".to_string(),
+            Some("exit 1".to_string()),
+            None,
+            false,
+        )
+        .unwrap();
+        assert!(matches!(get(&connection), Err(Error::Io(_))));
+
+        compute_cmd(&connection, "a".to_string(), None, None, false).unwrap();
+        assert_eq!(get(&connection).unwrap(), cached);
+        assert!(compute_cmd(&connection, "b".to_string(), None, None, false).is_err());
+    }
+
+    #[test]
+    fn interpolate() {
+        let connection = create_db();
+        let set_value = |name: &str, value: &str| {
+            set(
+                &connection,
+                name,
+                Some(value.to_string()),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap()
+        };
+        set_value("host", "example.org");
+        set_value("port", "{{ default-port }}");
+        set_value("default-port", "8080");
+        set_value("url", "https://{{host}}:{{port}}/{{host}}");
+
+        let get = |name: &str, resolve| {
+            get_cmd(
+                &connection,
+                name.to_string(),
+                true,
+                false,
+                GetFormat::Plain {
+                    color: false,
+                    separator: ' ',
+                },
+                resolve,
+            )
+        };
+        assert_eq!(
+            get("url", false).unwrap(),
+            "https://{{host}}:{{port}}/{{host}}"
+        );
+        assert_eq!(
+            get("url", true).unwrap(),
+            "https://example.org:8080/example.org"
+        );
+
+        set_value("default-port", "{{url}}");
+        assert!(matches!(
+            get("url", true),
+            Err(Error::Invalid(message)) if message.ends_with("url -> port -> default-port -> url")
+        ));
+        set_value("host", "{{missing}}");
+        assert!(matches!(
+            get("host", true),
+            Err(Error::EntryNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn keyring() {
+        let connection = create_db();
+        assert!(matches!(
+            set_secret_cmd(
+                &connection,
+                "token".to_string(),
+                None,
+                None,
+                None,
+                None,
+                SetMode::Upsert
+            ),
+            Err(Error::Invalid(_))
+        ));
+
+        // Marked by hand, since the tests can't rely on a keyring
+        set(&connection, "token", None, None, None, None, false).unwrap();
+        connection
+            .execute("UPDATE data SET secret = TRUE WHERE name = 'token'", ())
+            .unwrap();
+        let secret = || -> Option<bool> {
+            connection
+                .query_row("SELECT secret FROM data", (), |row| row.get(0))
+                .unwrap()
+        };
+        toggle_cmd(
+            &connection,
+            vec!["token".to_string()],
+            Filter::default(),
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        assert_eq!(secret(), Some(true));
+
+        let overwritten = |value| crate::keyring::overwritten(&connection, "token", value).unwrap();
+        assert!(!overwritten(None));
+        assert!(!overwritten(Some("")));
+        assert!(overwritten(Some("plain")));
+        // The orphaned secret can't be removed from an in memory db, which is only a warning
+        set(
+            &connection,
+            "token",
+            Some("plain".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        assert_eq!(secret(), None);
+        assert!(!overwritten(Some("other")));
+    }
+
+    #[test]
+    fn menu() {
+        let connection = create_db();
+        set(
+            &connection,
+            "theme",
+            Some("dark".to_string()),
+            Some("light".to_string()),
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        set(
+            &connection,
+            "host",
+            Some("a.org".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        let all = Filter::default();
+        assert_eq!(
+            menu_cmd(&connection, all.clone(), false).unwrap(),
+            "host: a.org\ntheme: dark"
+        );
+        let lines = menu_cmd(&connection, all, true).unwrap();
+        assert_eq!(lines, "theme: dark");
+
+        assert_eq!(menu_select_cmd(&connection, lines, true).unwrap(), "light");
+        assert_eq!(
+            menu_select_cmd(&connection, "host: b.org".to_string(), false).unwrap(),
+            "b.org"
+        );
+        assert_eq!(select(&connection, "host").unwrap().value, "b.org");
+        assert_eq!(
+            menu_select_cmd(&connection, "\n".to_string(), false).unwrap(),
+            ""
+        );
+        assert!(menu_select_cmd(&connection, "missing: x".to_string(), false).is_err());
+    }
+
+    #[test]
+    fn record() {
+        let connection = create_db();
+        for value in ["80", "90", "70.5"] {
+            record_cmd(&connection, "battery".to_string(), value.to_string()).unwrap();
+        }
+        assert_eq!(
+            record_cmd(&connection, "battery".to_string(), "full".to_string())
+                .unwrap_err()
+                .to_string(),
+            "the reading 'full' isn't a number"
+        );
+        assert_eq!(select(&connection, "battery").unwrap().value, "70.5");
+
+        connection
+            .execute(
+                "UPDATE samples SET recorded_at = recorded_at - 7200 WHERE value = 90",
+                (),
+            )
+            .unwrap();
+        assert_eq!(
+            record_stats_cmd(&connection, "battery".to_string(), None).unwrap(),
+            "count: 3\nmin: 70.5\nmax: 90\navg: 80.16666666666667"
+        );
+        assert_eq!(
+            record_stats_cmd(&connection, "battery".to_string(), Some(3600)).unwrap(),
+            "count: 2\nmin: 70.5\nmax: 80\navg: 75.25"
+        );
+
+        let csv = record_export_cmd(&connection, "battery".to_string(), None).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "timestamp,value");
+        assert!(lines[1].ends_with(",90"));
+
+        delete_cmd(
+            &connection,
+            Some("battery".to_string()),
+            Filter::default(),
+            false,
+            true,
+        )
+        .unwrap();
+        let samples: i64 = connection
+            .query_row("SELECT COUNT(*) FROM samples", (), |row| row.get(0))
+            .unwrap();
+        assert_eq!(samples, 0);
+    }
+
+    #[test]
+    fn timers() {
+        let connection = create_db();
+        let show = |seconds| timer_show_cmd(&connection, "tea".to_string(), seconds);
+
+        assert!(matches!(show(false), Err(Error::NotFound(_))));
+        timer_start_cmd(&connection, "tea".to_string()).unwrap();
+        assert_eq!(show(false).unwrap(), "0s");
+
+        connection
+            .execute("UPDATE timers SET started_at = started_at - 90500", ())
+            .unwrap();
+        assert_eq!(show(false).unwrap(), "1m30s");
+        assert_eq!(
+            timer_stop_cmd(&connection, "tea".to_string(), true).unwrap(),
+            "90"
+        );
+
+        // Stopped stopwatches don't run on
+        connection
+            .execute("UPDATE timers SET elapsed = elapsed - 1000", ())
+            .unwrap();
+        assert_eq!(show(true).unwrap(), "89");
+        assert_eq!(
+            timer_stop_cmd(&connection, "tea".to_string(), true).unwrap(),
+            "89"
+        );
+
+        timer_start_cmd(&connection, "tea".to_string()).unwrap();
+        assert_eq!(show(true).unwrap(), "0");
+    }
+
+    #[test]
+    fn ratelimit() {
+        let connection = create_db();
+        let take = |name: &str| ratelimit_cmd(&connection, name.to_string(), 2, 3600);
+
+        assert_eq!(take("api").unwrap(), "1");
+        assert_eq!(take("api").unwrap(), "0");
+        assert!(matches!(take("api"), Err(Error::RateLimited(wait)) if wait > 1000));
+        assert_eq!(take("other").unwrap(), "1");
+
+        connection
+            .execute(
+                "UPDATE ratelimits SET updated_at = updated_at - 1800000",
+                (),
+            )
+            .unwrap();
+        assert_eq!(take("api").unwrap(), "0");
+        assert!(ratelimit_cmd(&connection, "api".to_string(), 2, 0).is_err());
+    }
+
+    #[test]
+    fn completion_paths() {
+        use clap_complete::Shell;
+
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+        let home = env(&[("HOME", "/home/u")]);
+
+        assert_eq!(
+            completions_path(Shell::Bash, home).unwrap(),
+            std::path::Path::new("/home/u/.local/share/bash-completion/completions/config-store")
+        );
+        assert_eq!(
+            completions_path(Shell::Fish, env(&[("XDG_CONFIG_HOME", "/cfg")])).unwrap(),
+            std::path::Path::new("/cfg/fish/completions/config-store.fish")
+        );
+        assert_eq!(
+            completions_path(Shell::Zsh, home).unwrap(),
+            std::path::Path::new("/home/u/.zfunc/_config-store")
+        );
+        assert!(matches!(
+            completions_path(Shell::Zsh, env(&[])),
+            Err(Error::Invalid(_))
+        ));
+        assert!(matches!(
+            completions_path(Shell::PowerShell, home),
+            Err(Error::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn quote() {
+        let connection = create_db();
+        set(
+            &connection,
+            "greeting",
+            Some("it's $HOME".to_string()),
+            Some("a b".to_string()),
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        let get = |value_only| {
+            get_cmd(
+                &connection,
+                "greeting".to_string(),
+                value_only,
+                false,
+                GetFormat::Quoted(Quote::Shell),
+                false,
+            )
+            .unwrap()
+        };
+        assert_eq!(get(true), r"'it'\''s $HOME'");
+        assert_eq!(get(false), r"'it'\''s $HOME' 'a b'");
+
+        assert_eq!(
+            listed(
+                &connection,
+                ListFormat::Quoted(Quote::Shell),
+                Filter::default(),
+                None,
+                None,
+                None
+            )
+            .unwrap(),
+            "'greeting' 'it'\\''s $HOME' 'a b'\n"
+        );
+    }
+
+    #[test]
+    fn nuon() {
+        let connection = create_db();
+        set(
+            &connection,
+            "greeting",
+            Some("say \"hi\"\u{7}".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        let record = get_cmd(
+            &connection,
+            "greeting".to_string(),
+            false,
+            false,
+            GetFormat::Nuon,
+            false,
+        )
+        .unwrap();
+        assert!(record.starts_with(r#"{_id: 1, name: "greeting", value: "say \"hi\"\u{7}", "#));
+        assert!(record.ends_with("description: null, default: null, modified_by: null}"));
+
+        assert_eq!(
+            listed(
+                &connection,
+                ListFormat::Nuon,
+                Filter::default(),
+                None,
+                None,
+                None
+            )
+            .unwrap(),
+            format!("[\n  {}\n]\n", record)
+        );
+    }
+
+    #[test]
+    fn color() {
+        let connection = create_db();
+        set(
+            &connection,
+            "theme",
+            Some("dark".to_string()),
+            Some("light".to_string()),
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        let get = |format| {
+            get_cmd(
+                &connection,
+                "theme".to_string(),
+                false,
+                false,
+                format,
+                false,
+            )
+        };
+        assert_eq!(
+            get(GetFormat::Plain {
+                color: true,
+                separator: ' '
+            })
+            .unwrap(),
+            "\x1b[32mdark\x1b[0m \x1b[2mlight\x1b[0m"
+        );
+        assert_eq!(
+            get(GetFormat::Quoted(Quote::Shell)).unwrap(),
+            "'dark' 'light'"
+        );
+
+        let list = |color| {
+            listed(
+                &connection,
+                ListFormat::Plain { color },
+                Filter::default(),
+                None,
+                None,
+                None,
+            )
+            .unwrap()
+        };
+        assert!(list(true).contains("name: \x1b[1;34m\"theme\"\x1b[0m"));
+        assert!(!list(false).contains('\x1b'));
+    }
+
+    #[test]
+    fn patch() {
+        let connection = create_db();
+        set(
+            &connection,
+            "settings",
+            Some(r#"{"a": "b", "c": {"d": "e", "f": "g"}}"#.to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        let patch =
+            |name: &str, patch: &str| patch_cmd(&connection, name.to_string(), patch.to_string());
+
+        assert_eq!(
+            patch("settings", r#"{"a": "z", "c": {"f": null}}"#).unwrap(),
+            r#"{"a":"z","c":{"d":"e"}}"#
+        );
+        assert_eq!(
+            patch("settings", r#"{"c": [1], "x": {"y": true}}"#).unwrap(),
+            r#"{"a":"z","c":[1],"x":{"y":true}}"#
+        );
+        assert_eq!(
+            select(&connection, "settings").unwrap().value,
+            r#"{"a":"z","c":[1],"x":{"y":true}}"#
+        );
+
+        set(
+            &connection,
+            "theme",
+            Some("dark".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        assert!(matches!(
+            patch("theme", "{}"),
+            Err(Error::InvalidType { .. })
+        ));
+        assert!(matches!(patch("settings", "{"), Err(Error::Invalid(_))));
+        assert!(matches!(
+            patch("missing", "{}"),
+            Err(Error::EntryNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn import_env() {
+        let connection = create_db();
+        std::env::set_var("CS_IMPORT_ENV_PORT", "8080");
+        std::env::set_var("CS_IMPORT_ENV_HOST", "a.org");
+
+        assert_eq!(
+            import_env_cmd(&connection, "CS_IMPORT_ENV_".to_string(), true, false).unwrap(),
+            "Created 2 entries, updated 0"
+        );
+        assert_eq!(select(&connection, "port").unwrap().value, "8080");
+
+        protect_cmd(&connection, "host".to_string(), true).unwrap();
+        assert!(matches!(
+            import_env_cmd(&connection, "CS_IMPORT_ENV_".to_string(), true, false),
+            Err(Error::Protected(_))
+        ));
+        assert_eq!(
+            import_env_cmd(&connection, "CS_IMPORT_ENV_".to_string(), false, false).unwrap(),
+            "Created 2 entries, updated 0"
+        );
+        assert_eq!(select(&connection, "HOST").unwrap().value, "a.org");
+    }
+
+    #[test]
+    fn import() {
+        let connection = create_db();
+        set(
+            &connection,
+            "theme",
+            Some("dark".to_string()),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        protect_cmd(&connection, "theme".to_string(), true).unwrap();
+
+        let path = std::env::temp_dir().join(format!("config-store-import-{}", std::process::id()));
+        let import_with = |input: &str, policy, force| {
+            std::fs::write(&path, input).unwrap();
+            import_cmd(
+                &connection,
+                Some(path.clone()),
+                ImportFormat::Json,
+                policy,
+                true,
+                force,
+            )
+        };
+        let import = |input: &str, force| import_with(input, ConflictPolicy::Overwrite, force);
+
+        assert_eq!(
+            import(
+                "{\"name\": \"a\", \"value\": \"1\"}\n[{\"name\": \"b\", \"_id\": \"7\"}]\n",
+                false
+            )
+            .unwrap(),
+            "Created 2 entries, updated 0"
+        );
+        assert!(matches!(
+            import(r#"{"name": "a", "value": "2"} {"name": "theme"}"#, false),
+            Err(Error::Protected(_))
+        ));
+        assert_eq!(select(&connection, "a").unwrap().value, "1");
+
+        assert_eq!(
+            import(
+                r#"{"name": "a", "value": "2"} {"name": "theme", "alternate": "light"}"#,
+                true
+            )
+            .unwrap(),
+            "Created 0 entries, updated 2\n2 already existed (2 overwritten): a (overwritten), \
+             theme (overwritten)"
+        );
+        assert_eq!(select(&connection, "a").unwrap().value, "2");
+        assert_eq!(
+            select(&connection, "theme").unwrap().alternate.as_deref(),
+            Some("light")
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    /// Imports `input` with `policy` into a db containing `a` (updated in 2020) & `b`
+    fn import_conflicting(input: &str, policy: ConflictPolicy) -> (Connection, Result<String>) {
+        let connection = create_db();
+        for name in ["a", "b"] {
+            set(
+                &connection,
+                name,
+                Some("old".to_string()),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+        }
+        connection
+            .execute("UPDATE data SET updated_at = 1577836800", ())
+            .unwrap();
+
+        let path = std::env::temp_dir().join(format!(
+            "config-store-import-{:?}-{}",
+            policy,
+            std::process::id()
+        ));
+        std::fs::write(&path, input).unwrap();
+        let result = import_cmd(
+            &connection,
+            Some(path.clone()),
+            ImportFormat::Json,
+            policy,
+            true,
+            false,
+        );
+        std::fs::remove_file(path).unwrap();
+
+        (connection, result)
+    }
+
+    const CONFLICTING: &str = r#"{"name": "a", "value": "new", "updated_at": "2000-01-01T00:00:00Z"}
+        {"name": "b", "value": "new", "updated_at": "2999-01-01T00:00:00Z"}
+        {"name": "c", "value": "new"}"#;
+
+    #[test]
+    fn import_skip() {
+        let (connection, result) = import_conflicting(CONFLICTING, ConflictPolicy::Skip);
+        assert_eq!(
+            result.unwrap(),
+            "Created 1 entries, updated 0\n2 already existed (2 skipped): a (skipped), b (skipped)"
+        );
+        assert_eq!(select(&connection, "a").unwrap().value, "old");
+        assert_eq!(select(&connection, "b").unwrap().value, "old");
+        assert_eq!(select(&connection, "c").unwrap().value, "new");
+    }
+
+    #[test]
+    fn import_overwrite() {
+        let (connection, result) = import_conflicting(CONFLICTING, ConflictPolicy::Overwrite);
+        assert_eq!(
+            result.unwrap(),
+            "Created 1 entries, updated 2\n2 already existed (2 overwritten): a (overwritten), \
+             b (overwritten)"
+        );
+        assert_eq!(select(&connection, "a").unwrap().value, "new");
+        assert_eq!(select(&connection, "b").unwrap().value, "new");
+    }
+
+    #[test]
+    fn import_newer() {
+        let (connection, result) = import_conflicting(CONFLICTING, ConflictPolicy::Newer);
+        assert_eq!(
+            result.unwrap(),
+            "Created 1 entries, updated 1\n2 already existed (1 overwritten, 1 kept): a (kept), \
+             b (overwritten)"
+        );
+        assert_eq!(select(&connection, "a").unwrap().value, "old");
+        assert_eq!(select(&connection, "b").unwrap().value, "new");
+
+        // A timestamp which can't be parsed is rejected instead of guessed
+        let (_, result) = import_conflicting(
+            r#"{"name": "a", "updated_at": "yesterday-ish"}"#,
+            ConflictPolicy::Newer,
+        );
+        assert!(matches!(result, Err(Error::Invalid(_))));
+    }
+
+    #[test]
+    fn import_fail() {
+        let (connection, result) = import_conflicting(CONFLICTING, ConflictPolicy::Fail);
+        assert!(matches!(result, Err(Error::EntryExists)));
+        assert!(!exists(&connection, "c").unwrap());
+        assert_eq!(select(&connection, "a").unwrap().value, "old");
+
+        let (_, result) = import_conflicting(r#"{"name": "c"}"#, ConflictPolicy::Fail);
+        assert_eq!(result.unwrap(), "Created 1 entries, updated 0");
+    }
+
+    #[test]
+    fn conflict_reports() {
+        let conflicts = (0..25)
+            .map(|n| (format!("e{}", n), if n < 5 { "kept" } else { "skipped" }))
+            .collect::<Vec<_>>();
+        let report = conflict_report(&conflicts);
+        assert!(report.starts_with("25 already existed (20 skipped, 5 kept): e0 (kept), "));
+        assert!(report.ends_with(", e19 (skipped) and 5 more"));
+        assert!(!report.contains("e20"));
+    }
+
+    #[test]
+    fn set_json() {
+        let connection = create_db();
+        let set = |json: &str| set_json_cmd(&connection, json.to_string(), SetMode::Upsert);
+
+        set(r#"{"name": "theme", "value": "dark", "alternate": "light"}"#).unwrap();
+        set(r#"[{"name": "volume", "value": 30, "default": 50}, {"name": "theme", "value": null, "alternate": "solarized"}]"#).unwrap();
+        assert_eq!(
+            select(&connection, "theme").unwrap().alternate.as_deref(),
+            Some("solarized")
+        );
+        assert_eq!(select(&connection, "volume").unwrap().value, "30");
+        assert_eq!(
+            select(&connection, "volume").unwrap().default.as_deref(),
+            Some("50")
+        );
+
+        assert!(matches!(set(r#"{"value": "1"}"#), Err(Error::Invalid(_))));
+        assert!(matches!(
+            set(r#"[{"name": "mode", "value": "a"}, {"name": "x", "colour": "red"}]"#),
+            Err(Error::Invalid(_))
+        ));
+        assert!(matches!(
+            select(&connection, "mode"),
+            Err(Error::EntryNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn set_file() {
+        let connection = create_db();
+        let path = std::env::temp_dir().join(format!("config-store-set-{}", std::process::id()));
+        let set = |lines: &str| {
+            std::fs::write(&path, lines).unwrap();
+            set_file_cmd(&connection, path.clone(), SetMode::Upsert)
+        };
+
+        set("# comment\ntheme=dark:light\n\n url = https\\://example.com\nempty=\n").unwrap();
+        let theme = select(&connection, "theme").unwrap();
+        assert_eq!(
+            (theme.value.as_str(), theme.alternate.as_deref()),
+            ("dark", Some("light"))
+        );
+        let url = select(&connection, "url").unwrap();
+        assert_eq!(
+            (url.value.as_str(), url.alternate),
+            (" https://example.com", None)
+        );
+        assert_eq!(select(&connection, "empty").unwrap().value, "");
+
+        assert!(matches!(set("mode=a\nbroken\n"), Err(Error::Invalid(_))));
+        assert!(matches!(
+            select(&connection, "mode"),
+            Err(Error::EntryNotFound { .. })
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn audit_log() {
+        let connection = create_db();
+        crate::audit::set_context(&connection, "test").unwrap();
+
+        set_cmd(
+            &connection,
+            "theme".to_string(),
+            Some("dark".to_string()),
+            Some("light".to_string()),
+            None,
+            None,
+            SetMode::Upsert,
+        )
+        .unwrap();
+        toggle_cmd(
+            &connection,
+            vec!["theme".to_string()],
+            Filter::default(),
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            select(&connection, "theme").unwrap().modified_by,
+            Some(crate::audit::modifier())
+        );
+        touch_cmd(&connection, "theme".to_string()).unwrap();
+        set_cmd(
+            &connection,
+            "other".to_string(),
+            None,
+            None,
+            None,
+            None,
+            SetMode::Upsert,
+        )
+        .unwrap();
+        drop_cmd(&connection, false).unwrap();
+
+        let log = log_cmd(&connection, Some("theme".to_string()), None).unwrap();
+        let lines = log.lines().collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with(r#" test theme: - -> "dark" "light""#));
+        assert!(lines[1].ends_with(r#" test theme: "dark" "light" -> "light" "dark""#));
+        assert!(lines[2].ends_with(r#" test theme: "light" "dark" -> -"#));
+
+        assert_eq!(
+            log_cmd(&connection, None, Some(2)).unwrap().lines().count(),
+            2
         );
     }
 }
@@ -0,0 +1,121 @@
+//! Module completing the values of typed entries, see `completions`
+//!
+//! The completion scripts call config-store itself on TAB (with `$COMPLETE` set), so `set NAME
+//! --value <TAB>` offers the values the schema of the db allows for `NAME`, like `true` & `false`
+//! for booleans. The entry is found by parsing the words typed so far, as completers only get the
+//! word being completed.
+use std::ffi::{OsStr, OsString};
+
+use clap::Parser;
+use clap_complete::engine::CompletionCandidate;
+use rusqlite::{Connection, OpenFlags};
+
+use crate::{
+    cli::{Action, Args, SetArgs},
+    manifest,
+    store::resolve,
+};
+
+/// Completes the value of the entry on the command line, for `ArgValueCompleter`
+pub fn values(current: &OsStr) -> Vec<CompletionCandidate> {
+    let current = current.to_string_lossy();
+    let mut words = std::env::args_os().skip_while(|word| word != "--").skip(1);
+    let words: Vec<OsString> = match std::env::var("_CLAP_COMPLETE_INDEX")
+        .ok()
+        .and_then(|index| index.parse().ok())
+    {
+        Some(index) => words.by_ref().take(index).collect(),
+        None => {
+            let mut words: Vec<OsString> = words.collect();
+            words.pop();
+            words
+        }
+    };
+
+    allowed(words)
+        .into_iter()
+        .filter(|value| value.starts_with(current.as_ref()))
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+/// Helper function returning the values allowed for the entry of the `set` command `words`, the
+/// words before the one being completed
+///
+/// Nothing is offered if the words don't parse, the db can't be read or the entry takes any value.
+fn allowed(mut words: Vec<OsString>) -> Vec<String> {
+    // A placeholder for the value being completed, so the options before it parse
+    words.push(OsString::from("_"));
+    let Ok(args) = Args::try_parse_from(words) else {
+        return Vec::new();
+    };
+    let Action::Set(SetArgs {
+        name: Some(name), ..
+    }) = args.command
+    else {
+        return Vec::new();
+    };
+
+    let declared = || {
+        let connection = Connection::open_with_flags(
+            &args.db_path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI,
+        )?;
+        let name = resolve(&connection, &name).unwrap_or(name);
+
+        Ok::<_, crate::Error>(
+            manifest::declared(&connection)?
+                .into_iter()
+                .find(|declaration| declaration.name == name),
+        )
+    };
+
+    declared()
+        .ok()
+        .flatten()
+        .map(|declaration| declaration.allowed())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn allowed_values() {
+        let dir =
+            std::env::temp_dir().join(format!("config-store-completion-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let schema = dir.join("schema.toml");
+        std::fs::write(
+            &schema,
+            "[dark]\ntype = \"bool\"\n\n[theme]\nvalues = [\"light\", \"dark\"]\n\n[gaps]\ntype = \"int\"\n",
+        )
+        .unwrap();
+        let db = dir.join("db.sqlite");
+        let store = crate::Store::open(&db).unwrap();
+        crate::settings::set(
+            store.connection(),
+            crate::settings::SCHEMA,
+            Some(schema.to_str().unwrap()),
+        )
+        .unwrap();
+
+        let words = |words: &[&str]| {
+            ["config-store", "--db-path", db.to_str().unwrap()]
+                .iter()
+                .chain(words)
+                .map(OsString::from)
+                .collect::<Vec<OsString>>()
+        };
+        assert_eq!(allowed(words(&["set", "dark", "-v"])), ["true", "false"]);
+        assert_eq!(
+            allowed(words(&["set", "theme", "-v", "light", "--alternate"])),
+            ["light", "dark"]
+        );
+        assert!(allowed(words(&["set", "gaps", "--value"])).is_empty());
+        assert!(allowed(words(&["get", "dark", "-v"])).is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,6 +1,10 @@
 //! Module containing the [Entry] struct
 use std::fmt::Display;
 
+use rusqlite::Row;
+
+use crate::{color::Style, manifest::ValueType, Error, Result};
+
 /// Representation an entry in the db
 #[derive(Debug)]
 pub struct Entry {
@@ -14,21 +18,242 @@ pub struct Entry {
     pub value: String,
     /// An additional value that can be toggled to
     ///
-    /// This is particularly useful for true / false toggles
-    pub alternate: String,
+    /// This is particularly useful for true / false toggles. It's `None` if it was never set, as
+    /// opposed to set to an empty string.
+    pub alternate: Option<String>,
+    /// When the entry was created (seconds since the unix epoch)
+    ///
+    /// This is `None` for entries created before timestamps were tracked.
+    pub created_at: Option<i64>,
+    /// When the entry was last changed (seconds since the unix epoch)
+    ///
+    /// This is `None` for entries which haven't been changed since timestamps were tracked.
+    pub updated_at: Option<i64>,
+    /// A free-text description of what the entry is used for
+    pub description: Option<String>,
+    /// The value the entry is restored to by `reset`
+    pub default: Option<String>,
+    /// Who last changed the entry: the user & the process which ran config-store (e.g. a script)
+    ///
+    /// This is `None` for entries which haven't been changed since this was tracked.
+    pub modified_by: Option<String>,
 }
 
 impl Entry {
+    /// The columns to select to be able to construct an [Entry] via [Entry::from_row]
+    pub const COLUMNS: &'static str =
+        "id, name, value, alternate, created_at, updated_at, description, default_value, modified_by";
+
+    /// Constructs an [Entry] from a row selected with [Entry::COLUMNS]
+    pub fn from_row(row: &Row) -> rusqlite::Result<Entry> {
+        Ok(Entry {
+            _id: row.get(0)?,
+            name: row.get(1)?,
+            value: row.get(2)?,
+            alternate: row.get(3)?,
+            created_at: row.get(4)?,
+            updated_at: row.get(5)?,
+            description: row.get(6)?,
+            default: row.get(7)?,
+            modified_by: row.get(8)?,
+        })
+    }
+
     pub fn json(self) -> String {
+        format!("{{ {} }}", self.json_fields(None))
+    }
+
+    /// Like [Entry::json], with the metadata attributes of the entry (see `meta`) in a `meta`
+    /// object
+    ///
+    /// With `kind`, the value, alternate & default are literals of that type where they are of it,
+    /// e.g. `"value": 30` for an int.
+    pub fn json_with_meta(self, meta: &[(String, String)], kind: Option<ValueType>) -> String {
+        format!(
+            r#"{{ {}, "meta": {{{}}} }}"#,
+            self.json_fields(kind),
+            meta.iter()
+                .map(|(key, value)| format!(r#""{}": "{}""#, json_escape(key), json_escape(value)))
+                .collect::<Vec<String>>()
+                .join(", ")
+        )
+    }
+
+    /// Helper function formatting the fields of the json object of the entry, see
+    /// [Entry::json_with_meta] for `kind`
+    fn json_fields(self, kind: Option<ValueType>) -> String {
+        let literal = |value: Option<String>| match (value, kind) {
+            (Some(value), Some(kind)) => kind
+                .json(&value)
+                .unwrap_or_else(|| json_string(Some(value))),
+            (value, _) => json_string(value),
+        };
+
+        format!(
+            r#""_id": "{}", "name": "{}", "value": {}, "alternate": {}, "created_at": {}, "updated_at": {}, "description": {}, "default": {}, "modified_by": {}"#,
+            self._id,
+            json_escape(&self.name),
+            literal(Some(self.value)),
+            literal(self.alternate),
+            json_timestamp(self.created_at),
+            json_timestamp(self.updated_at),
+            json_string(self.description),
+            literal(self.default),
+            json_string(self.modified_by)
+        )
+    }
+
+    /// A nushell record of the entry in NUON, the format of `from nuon`
+    ///
+    /// Timestamps are nushell datetimes & missing metadata is `null`.
+    pub fn nuon(&self) -> String {
+        let string = |s: &Option<String>| s.as_deref().map_or("null".to_string(), nuon_string);
+        let timestamp = |t: Option<i64>| t.map_or("null".to_string(), crate::time::format);
+
+        format!(
+            "{{_id: {}, name: {}, value: {}, alternate: {}, created_at: {}, updated_at: {}, description: {}, default: {}, modified_by: {}}}",
+            self._id,
+            nuon_string(&self.name),
+            nuon_string(&self.value),
+            string(&self.alternate),
+            timestamp(self.created_at),
+            timestamp(self.updated_at),
+            string(&self.description),
+            string(&self.default),
+            string(&self.modified_by)
+        )
+    }
+
+    /// The format of [Display], with the name, value & alternate highlighted if `color` is set
+    pub fn plain(&self, color: bool) -> String {
+        let [name, value, alternate] = self.highlighted(color);
+
         format!(
-            r#"{{ "_id": "{}", "name": "{}", "value": "{}", "alternate": "{}" }}"#,
-            self._id, self.name, self.value, self.alternate
+            "Entry {{ _id: {}, name: {}, value: {}{} }}",
+            self._id, name, value, alternate
         )
     }
+
+    /// A representation of the entry including all of its metadata
+    ///
+    /// Used by `list --long`. The name, value & alternate are highlighted if `color` is set.
+    pub fn long(&self, color: bool) -> String {
+        let [name, value, alternate] = self.highlighted(color);
+
+        format!(
+            "Entry {{ _id: {}, name: {}, value: {}{}, created_at: {}, updated_at: {}, description: {:?}, default: {:?}, modified_by: {:?} }}",
+            self._id,
+            name,
+            value,
+            alternate,
+            display_timestamp(self.created_at),
+            display_timestamp(self.updated_at),
+            self.description,
+            self.default,
+            self.modified_by
+        )
+    }
+
+    /// Returns whether the entry has to be toggled to be switched on (or off), see `enable`
+    ///
+    /// `true`, `on`, `yes`, `1` & `enabled` mean on, `false`, `off`, `no`, `0` & `disabled` off,
+    /// ignoring case. Will return [Error::Invalid] if neither the value nor the alternate is in
+    /// the state.
+    pub fn needs_toggle(&self, on: bool) -> Result<bool> {
+        let state = |value: &str| match value.to_lowercase().as_str() {
+            "true" | "on" | "yes" | "1" | "enabled" => Some(true),
+            "false" | "off" | "no" | "0" | "disabled" => Some(false),
+            _ => None,
+        };
+
+        if state(&self.value) == Some(on) {
+            Ok(false)
+        } else if self.alternate.as_deref().and_then(state) == Some(on) {
+            Ok(true)
+        } else {
+            Err(Error::Invalid(format!(
+                "'{}' can't be switched {}, neither its value nor its alternate is {}",
+                self.name,
+                if on { "on" } else { "off" },
+                on
+            )))
+        }
+    }
+
+    /// Helper function quoting the name, value & alternate, highlighted if `color` is set
+    ///
+    /// The alternate comes with its `, alternate: ` label, it's left out if it was never set.
+    fn highlighted(&self, color: bool) -> [String; 3] {
+        let [name, value] = [(&self.name, Style::Name), (&self.value, Style::Value)]
+            .map(|(field, style)| style.paint(&format!("{:?}", field), color));
+        let alternate = self.alternate.as_ref().map_or(String::new(), |alternate| {
+            format!(
+                ", alternate: {}",
+                Style::Alternate.paint(&format!("{:?}", alternate), color)
+            )
+        });
+
+        [name, value, alternate]
+    }
 }
 
 impl Display for Entry {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        write!(f, "{}", self.plain(false))
     }
 }
+
+/// Formats an optional timestamp for use in json
+fn json_timestamp(timestamp: Option<i64>) -> String {
+    timestamp.map_or("null".to_string(), |t| {
+        format!(r#""{}""#, crate::time::format(t))
+    })
+}
+
+/// Formats an optional string for use in json
+pub(crate) fn json_string(string: Option<String>) -> String {
+    string.map_or("null".to_string(), |s| format!(r#""{}""#, json_escape(&s)))
+}
+
+/// Escapes a string for use inside a json string literal
+pub(crate) fn json_escape(string: &str) -> String {
+    string.chars().fold(String::new(), |mut acc, c| {
+        match c {
+            '"' => acc.push_str(r#"\""#),
+            '\\' => acc.push_str(r"\\"),
+            '\n' => acc.push_str(r"\n"),
+            '\r' => acc.push_str(r"\r"),
+            '\t' => acc.push_str(r"\t"),
+            c if c.is_control() => acc.push_str(&format!(r"\u{:04x}", c as u32)),
+            c => acc.push(c),
+        }
+        acc
+    })
+}
+
+/// Quotes a string for use in NUON
+///
+/// Nushell's escapes are like json's, except for the `\u{..}` form of unicode escapes.
+fn nuon_string(string: &str) -> String {
+    let mut quoted = String::from('"');
+
+    for c in string.chars() {
+        match c {
+            '"' => quoted.push_str(r#"\""#),
+            '\\' => quoted.push_str(r"\\"),
+            '\n' => quoted.push_str(r"\n"),
+            '\r' => quoted.push_str(r"\r"),
+            '\t' => quoted.push_str(r"\t"),
+            c if c.is_control() => quoted.push_str(&format!(r"\u{{{:x}}}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+/// Formats an optional timestamp for use in human readable output
+fn display_timestamp(timestamp: Option<i64>) -> String {
+    timestamp.map_or("unknown".to_string(), crate::time::format)
+}
@@ -0,0 +1,264 @@
+//! Module containing the [Error] type used throughout config-store
+use crate::entry::json_string;
+
+/// The error type of config-store
+///
+/// Wraps the errors of the libraries used (e.g. [rusqlite::Error]) & adds specific variants like
+/// [Error::EntryNotFound] to make errors clearer to users. The [Display](std::fmt::Display)
+/// implementation is meant to be shown to users, see [Error::exit_code] for the exit codes.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// A wrapper around an [rusqlite::Error]
+    #[error("db error: {0}")]
+    Sqlite(rusqlite::Error),
+    /// Error for trying to operate on an [Entry](crate::entry::Entry) that doesn't exist
+    ///
+    /// `suggestions` are similar names which do exist, see [crate::suggest].
+    #[error("no entry named '{name}'{}", did_you_mean(suggestions))]
+    EntryNotFound {
+        name: String,
+        suggestions: Vec<String>,
+    },
+    /// Error for something other than an entry that doesn't exist, e.g. a preset or a backup
+    #[error("{0} not found")]
+    NotFound(String),
+    /// Error for trying to reset an [Entry](crate::entry::Entry) which doesn't have a default
+    #[error("the entry has no default")]
+    NoDefault,
+    /// Error for trying to create something with a name that is already in use
+    #[error("an entry with this name already exists")]
+    EntryExists,
+    /// The OS failed to provide random bytes
+    #[error("failed to get random bytes: {0}")]
+    Random(getrandom::Error),
+    /// A wrapper around an [std::io::Error], e.g. from accessing files other than the db
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    /// Error for a blocking operation which didn't finish in time
+    #[error("timed out")]
+    Timeout,
+    /// Error for a db which another process keeps locked for too long
+    #[error("the db is locked by another process")]
+    Locked,
+    /// Error for a `ratelimit` without tokens left, holding the seconds until the next one
+    #[error("rate limit reached, try again in {0}s")]
+    RateLimited(i64),
+    /// Error for a conditional change whose condition wasn't met, e.g. `set --if-value`
+    #[error("the current value doesn't match")]
+    Mismatch,
+    /// Error for trying to change or delete a frozen [Entry](crate::entry::Entry), see `freeze`
+    #[error("the entry is frozen, see `unfreeze`")]
+    Frozen,
+    /// Error for trying to delete or overwrite protected entries (named in the error) without
+    /// `--force`, see `protect`
+    #[error("refusing to change protected entries without --force: {0}")]
+    Protected(String),
+    /// Error for a destructive command the user didn't confirm, see `--yes`
+    #[error("aborted")]
+    Aborted,
+    /// Error for a value which can't be used by a command, e.g. `set --expr` on a non-numeric one
+    #[error("the value of '{name}' isn't {expected}")]
+    InvalidType {
+        name: String,
+        expected: &'static str,
+    },
+    /// Error for values which don't match their checksum (the entries are named in the error), see
+    /// `verify`
+    #[error("values changed outside of config-store: {0}")]
+    Corrupted(String),
+    /// Error for an entry whose signature doesn't match the key, see `sign`
+    #[error("the value of '{0}' wasn't signed with the key, it may have been tampered with")]
+    Tampered(String),
+    /// Error for a value or alternate larger than the limit of the db, see `limits`
+    #[error("{0}")]
+    TooLarge(String),
+    /// Error for input which can't be used, e.g. an invalid expression
+    #[error("{0}")]
+    Invalid(String),
+    #[cfg(feature = "redis")]
+    /// A wrapper around a [redis::RedisError], see [RedisStore](crate::redis_store::RedisStore)
+    #[error("redis error: {0}")]
+    Redis(#[from] redis::RedisError),
+}
+
+impl Error {
+    /// The exit code for invalid input, including invalid command lines
+    pub const INVALID_INPUT: i32 = 3;
+
+    /// An [Error::EntryNotFound] without suggestions
+    pub fn entry_not_found(name: impl Into<String>) -> Error {
+        Error::EntryNotFound {
+            name: name.into(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    /// Formats the error as a json object like
+    /// `{"error": "no entry named 'thme'", "code": 2, "suggestions": ["theme"]}`
+    ///
+    /// `code` is the [exit code](Error::exit_code) & `suggestions` are only non-empty for
+    /// [Error::EntryNotFound].
+    pub fn json(&self) -> String {
+        let suggestions = match self {
+            Error::EntryNotFound { suggestions, .. } => suggestions.as_slice(),
+            _ => &[],
+        };
+
+        format!(
+            r#"{{"error":{},"code":{},"suggestions":[{}]}}"#,
+            json_string(Some(self.to_string())),
+            self.exit_code(),
+            suggestions
+                .iter()
+                .map(|s| json_string(Some(s.clone())))
+                .collect::<Vec<String>>()
+                .join(",")
+        )
+    }
+
+    /// The exit code of the binary when a command fails with this error
+    ///
+    /// The codes are stable, so scripts can tell the categories of errors apart:
+    ///
+    /// | Code | Category                                                                      |
+    /// |------|-------------------------------------------------------------------------------|
+    /// | 0    | Success                                                                       |
+    /// | 1    | Other failures, e.g. a corrupt db                                             |
+    /// | 2    | Something doesn't exist, e.g. [Error::EntryNotFound]                          |
+    /// | 3    | Invalid input: [Error::Invalid], [Error::InvalidType] & [Error::TooLarge]     |
+    /// | 4    | Try again later: [Error::Timeout], [Error::Locked] & [Error::RateLimited]     |
+    /// | 5    | Something already exists: [Error::EntryExists]                                |
+    /// | 6    | The change is refused: [Error::Frozen], [Error::Protected] & [Error::Aborted] |
+    /// | 7    | A condition wasn't met: [Error::Mismatch]                                     |
+    /// | 8    | Files other than the db can't be accessed: [Error::Io]                        |
+    ///
+    /// Invalid command lines are rejected with code 3 as well (see [Error::INVALID_INPUT]) before
+    /// any command runs, so 4 always means that trying again may help.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::EntryNotFound { .. } | Error::NotFound(_) | Error::NoDefault => 2,
+            Error::Invalid(_) | Error::InvalidType { .. } | Error::TooLarge(_) => {
+                Error::INVALID_INPUT
+            }
+            Error::Timeout | Error::Locked | Error::RateLimited(_) => 4,
+            Error::EntryExists => 5,
+            Error::Frozen | Error::Protected(_) | Error::Aborted => 6,
+            Error::Mismatch => 7,
+            Error::Io(_) => 8,
+            _ => 1,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(error: rusqlite::Error) -> Self {
+        use rusqlite::ErrorCode;
+
+        match error {
+            rusqlite::Error::QueryReturnedNoRows => Error::NotFound("entry".to_string()),
+            // Raised by the triggers protecting frozen entries
+            rusqlite::Error::SqliteFailure(_, Some(ref message)) if message == "frozen" => {
+                Error::Frozen
+            }
+            // Raised by the triggers checking the sizes of values, see `limits`
+            rusqlite::Error::SqliteFailure(_, Some(ref message))
+                if message.starts_with(crate::limits::TOO_LARGE) =>
+            {
+                Error::TooLarge(message[crate::limits::TOO_LARGE.len()..].to_string())
+            }
+            // Raised when reading entries whose signature doesn't match, see `sign`
+            rusqlite::Error::SqliteFailure(_, Some(ref message))
+                if message.starts_with(crate::signing::TAMPERED) =>
+            {
+                Error::Tampered(message[crate::signing::TAMPERED.len()..].to_string())
+            }
+            rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error {
+                    code: ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked,
+                    ..
+                },
+                _,
+            ) => Error::Locked,
+            // Raised by the unique index of the names, for commands which don't check first
+            rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error {
+                    extended_code: rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE,
+                    ..
+                },
+                Some(ref message),
+            ) if message.ends_with(" data.name") => Error::EntryExists,
+            _ => Error::Sqlite(error),
+        }
+    }
+}
+
+/// Helper function formatting the suggestions of [Error::EntryNotFound]
+fn did_you_mean(suggestions: &[String]) -> String {
+    match suggestions {
+        [] => String::new(),
+        suggestions => format!(
+            ", did you mean {}?",
+            suggestions
+                .iter()
+                .map(|s| format!("'{}'", s))
+                .collect::<Vec<String>>()
+                .join(" or ")
+        ),
+    }
+}
+
+// getrandom's error only implements std::error::Error with its `std` feature, so no `#[from]`
+impl From<getrandom::Error> for Error {
+    fn from(error: getrandom::Error) -> Self {
+        Error::Random(error)
+    }
+}
+
+/// A [std::result::Result] using [Error]
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let error = Error::entry_not_found("theme");
+        assert_eq!(error.to_string(), "no entry named 'theme'");
+        assert_eq!(error.exit_code(), 2);
+
+        let error = Error::EntryNotFound {
+            name: "thme".to_string(),
+            suggestions: vec!["theme".to_string(), "them".to_string()],
+        };
+        assert_eq!(
+            error.to_string(),
+            "no entry named 'thme', did you mean 'theme' or 'them'?"
+        );
+        let json: serde_json::Value = serde_json::from_str(&error.json()).unwrap();
+        assert_eq!(json["code"], 2);
+        assert_eq!(json["suggestions"], serde_json::json!(["theme", "them"]));
+
+        let locked = rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+            None,
+        );
+        assert!(matches!(Error::from(locked), Error::Locked));
+        assert_eq!(Error::Locked.exit_code(), 4);
+
+        let connection = rusqlite::Connection::open_in_memory().unwrap();
+        crate::schema::init(&connection).unwrap();
+        let insert = || connection.execute("INSERT INTO data (name, value) VALUES ('a', '')", ());
+        insert().unwrap();
+        let error = Error::from(insert().unwrap_err());
+        assert!(matches!(error, Error::EntryExists));
+        assert_eq!(error.exit_code(), 5);
+        assert_eq!(
+            error.json(),
+            r#"{"error":"an entry with this name already exists","code":5,"suggestions":[]}"#
+        );
+
+        assert_eq!(Error::Invalid("x".to_string()).exit_code(), 3);
+        assert_eq!(Error::Mismatch.exit_code(), 7);
+    }
+}
@@ -0,0 +1,119 @@
+//! Module containing a tiny evaluator for arithmetic expressions used by `set --expr`
+//!
+//! Expressions consist of numbers, the variable `x` (the current value of the entry), the
+//! operators `+`, `-`, `*`, `/` & `%` with the usual precedence, unary minus & parentheses.
+
+/// Evaluates `expr` with the variable `x` set to the given value
+pub fn eval(expr: &str, x: f64) -> Result<f64, String> {
+    let mut parser = Parser {
+        chars: expr.chars().filter(|c| !c.is_whitespace()).collect(),
+        position: 0,
+        x,
+    };
+
+    let result = parser.sum()?;
+
+    match parser.peek() {
+        None => Ok(result),
+        Some(c) => Err(format!("unexpected '{}' in expression '{}'", c, expr)),
+    }
+}
+
+/// A recursive descent parser evaluating the expression while parsing it
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    x: f64,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.position += 1;
+        c
+    }
+
+    /// `sum := product (('+' | '-') product)*`
+    fn sum(&mut self) -> Result<f64, String> {
+        let mut result = self.product()?;
+
+        while let Some(c @ ('+' | '-')) = self.peek() {
+            self.next();
+            let rhs = self.product()?;
+            result = if c == '+' { result + rhs } else { result - rhs };
+        }
+
+        Ok(result)
+    }
+
+    /// `product := factor (('*' | '/' | '%') factor)*`
+    fn product(&mut self) -> Result<f64, String> {
+        let mut result = self.factor()?;
+
+        while let Some(c @ ('*' | '/' | '%')) = self.peek() {
+            self.next();
+            let rhs = self.factor()?;
+
+            if c != '*' && rhs == 0.0 {
+                return Err("division by zero".to_string());
+            }
+
+            result = match c {
+                '*' => result * rhs,
+                '/' => result / rhs,
+                _ => result % rhs,
+            };
+        }
+
+        Ok(result)
+    }
+
+    /// `factor := '-' factor | number | 'x' | '(' sum ')'`
+    fn factor(&mut self) -> Result<f64, String> {
+        match self.next() {
+            Some('-') => Ok(-self.factor()?),
+            Some('x') => Ok(self.x),
+            Some('(') => {
+                let result = self.sum()?;
+                match self.next() {
+                    Some(')') => Ok(result),
+                    _ => Err("missing ')'".to_string()),
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let start = self.position - 1;
+                while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+                    self.next();
+                }
+
+                let number = self.chars[start..self.position].iter().collect::<String>();
+                number
+                    .parse()
+                    .map_err(|_| format!("invalid number '{}'", number))
+            }
+            Some(c) => Err(format!("unexpected '{}'", c)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn evaluate() {
+        assert_eq!(eval("x + 10", 5.0), Ok(15.0));
+        assert_eq!(eval("2 + 3 * x", 2.0), Ok(8.0));
+        assert_eq!(eval("(2 + 3) * -x", 2.0), Ok(-10.0));
+        assert_eq!(eval("x % 3 / 2", 7.0), Ok(0.5));
+        assert!(eval("x / 0", 1.0).is_err());
+        assert!(eval("x +", 1.0).is_err());
+        assert!(eval("(x", 1.0).is_err());
+        assert!(eval("x y", 1.0).is_err());
+    }
+}
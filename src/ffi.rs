@@ -0,0 +1,219 @@
+//! Module containing the C API
+//!
+//! Only available with the `ffi` feature. The matching header is `include/config_store.h`.
+//!
+//! All strings passed in must be valid, nul-terminated UTF-8. All strings returned are owned by
+//! the caller and must be freed with [cs_free].
+use std::{
+    ffi::{c_char, c_int, CStr, CString},
+    ptr,
+};
+
+use crate::{Error, Store};
+
+/// Returned on success
+pub const CS_OK: c_int = 0;
+/// Returned if the entry doesn't exist
+pub const CS_NO_ENTRY: c_int = 1;
+/// Returned for any other error
+pub const CS_ERROR: c_int = -1;
+
+/// Helper function to convert a C string into a `&str`
+///
+/// # Safety
+///
+/// `string` must be null or a valid, nul-terminated C string.
+unsafe fn to_str<'a>(string: *const c_char) -> Option<&'a str> {
+    if string.is_null() {
+        return None;
+    }
+
+    CStr::from_ptr(string).to_str().ok()
+}
+
+/// Helper function to convert a C string which may be null into an `Option<&str>`
+///
+/// Returns `None` if the string isn't valid UTF-8, unlike [to_str] which also does so for null.
+///
+/// # Safety
+///
+/// `string` must be null or a valid, nul-terminated C string.
+unsafe fn to_optional_str<'a>(string: *const c_char) -> Option<Option<&'a str>> {
+    if string.is_null() {
+        return Some(None);
+    }
+
+    CStr::from_ptr(string).to_str().ok().map(Some)
+}
+
+/// Helper function to hand a string over to the caller
+fn to_c_string(string: String) -> *mut c_char {
+    CString::new(string).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// Opens (or creates) the db at `path`
+///
+/// Returns null on failure. The store must be closed with [cs_close].
+///
+/// # Safety
+///
+/// `path` must be a valid, nul-terminated C string.
+#[no_mangle]
+pub unsafe extern "C" fn cs_open(path: *const c_char) -> *mut Store {
+    match to_str(path).map(Store::open) {
+        Some(Ok(store)) => Box::into_raw(Box::new(store)),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Closes a store opened with [cs_open]
+///
+/// # Safety
+///
+/// `store` must be null or a pointer returned by [cs_open], which hasn't been closed yet.
+#[no_mangle]
+pub unsafe extern "C" fn cs_close(store: *mut Store) {
+    if !store.is_null() {
+        drop(Box::from_raw(store));
+    }
+}
+
+/// Gets the value of an entry
+///
+/// Returns null if the entry doesn't exist or on failure. The result must be freed with [cs_free].
+///
+/// # Safety
+///
+/// `store` must be a pointer returned by [cs_open] & `name` a valid, nul-terminated C string.
+#[no_mangle]
+pub unsafe extern "C" fn cs_get(store: *const Store, name: *const c_char) -> *mut c_char {
+    match (store.as_ref(), to_str(name)) {
+        (Some(store), Some(name)) => store
+            .get(name)
+            .map_or(ptr::null_mut(), |entry| to_c_string(entry.value)),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Creates or updates an entry
+///
+/// `value` & `alternate` may be null to leave them unchanged (or empty & unset for new entries).
+/// Returns [CS_ERROR] if any string isn't valid UTF-8.
+///
+/// # Safety
+///
+/// `store` must be a pointer returned by [cs_open] & all strings must be null or valid,
+/// nul-terminated C strings.
+#[no_mangle]
+pub unsafe extern "C" fn cs_set(
+    store: *const Store,
+    name: *const c_char,
+    value: *const c_char,
+    alternate: *const c_char,
+) -> c_int {
+    let (Some(store), Some(name), Some(value), Some(alternate)) = (
+        store.as_ref(),
+        to_str(name),
+        to_optional_str(value),
+        to_optional_str(alternate),
+    ) else {
+        return CS_ERROR;
+    };
+
+    match store.set(name, value, alternate) {
+        Ok(_) => CS_OK,
+        Err(_) => CS_ERROR,
+    }
+}
+
+/// Toggles an entry between its value & alternate
+///
+/// The new value is written to `new_value` if it isn't null and must be freed with [cs_free].
+///
+/// # Safety
+///
+/// `store` must be a pointer returned by [cs_open], `name` a valid, nul-terminated C string &
+/// `new_value` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cs_toggle(
+    store: *const Store,
+    name: *const c_char,
+    new_value: *mut *mut c_char,
+) -> c_int {
+    let (Some(store), Some(name)) = (store.as_ref(), to_str(name)) else {
+        return CS_ERROR;
+    };
+
+    match store.toggle(name) {
+        Ok(value) => {
+            if let Some(new_value) = new_value.as_mut() {
+                *new_value = to_c_string(value);
+            }
+            CS_OK
+        }
+        Err(Error::EntryNotFound { .. }) => CS_NO_ENTRY,
+        Err(_) => CS_ERROR,
+    }
+}
+
+/// Frees a string returned by this API
+///
+/// # Safety
+///
+/// `string` must be null or a string returned by this API, which hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn cs_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn c_api() {
+        unsafe {
+            let store = cs_open(c":memory:".as_ptr());
+            assert!(!store.is_null());
+
+            assert_eq!(
+                cs_set(
+                    store,
+                    c"theme".as_ptr(),
+                    c"dark".as_ptr(),
+                    c"light".as_ptr()
+                ),
+                CS_OK
+            );
+
+            // Invalid UTF-8 is refused instead of leaving the value unchanged
+            assert_eq!(
+                cs_set(store, c"theme".as_ptr(), c"\xff".as_ptr(), ptr::null()),
+                CS_ERROR
+            );
+            assert_eq!(
+                cs_set(store, c"theme".as_ptr(), ptr::null(), c"\xff".as_ptr()),
+                CS_ERROR
+            );
+
+            let mut value = ptr::null_mut();
+            assert_eq!(cs_toggle(store, c"theme".as_ptr(), &mut value), CS_OK);
+            assert_eq!(CStr::from_ptr(value).to_str(), Ok("light"));
+            cs_free(value);
+
+            let value = cs_get(store, c"theme".as_ptr());
+            assert_eq!(CStr::from_ptr(value).to_str(), Ok("light"));
+            cs_free(value);
+
+            assert!(cs_get(store, c"missing".as_ptr()).is_null());
+            assert_eq!(
+                cs_toggle(store, c"missing".as_ptr(), ptr::null_mut()),
+                CS_NO_ENTRY
+            );
+
+            cs_close(store);
+        }
+    }
+}
@@ -0,0 +1,293 @@
+//! Module containing the [Filter] used by commands operating on multiple entries at once
+use clap::Args;
+use rusqlite::types::Value;
+
+use crate::{time, Entry, Error};
+
+/// Criteria selecting a set of entries
+///
+/// An empty filter matches every entry. Otherwise an entry has to match all given criteria.
+#[derive(Debug, Default, Clone, Args)]
+#[group(id = "filter", multiple = true)]
+pub struct Filter {
+    /// Only include entries with this tag
+    #[arg(short, long)]
+    pub tag: Option<String>,
+    /// Only include entries whose name starts with this prefix
+    #[arg(short, long)]
+    pub prefix: Option<String>,
+    /// Only include entries whose name matches this glob pattern, e.g. `vpn.*`
+    #[arg(long, value_name = "PATTERN")]
+    pub glob: Option<String>,
+    /// Only include entries changed within this duration, e.g. `30m`, `1h` or `7d`
+    ///
+    /// Together with `--unused` only entries which haven't been read within this duration are
+    /// included instead.
+    #[arg(long, value_parser = time::parse_duration)]
+    pub since: Option<i64>,
+    /// Only include entries changed since this date & time (e.g. `2024-05-01 10:00`) or this long
+    /// ago (e.g. `1h`), for syncing incrementally
+    #[arg(long, value_name = "WHEN", value_parser = time::parse_timestamp)]
+    pub changed_since: Option<i64>,
+    /// Only include entries which haven't been changed within this duration, e.g. `7d` for stale
+    /// transient entries
+    #[arg(long, value_name = "DURATION", value_parser = time::parse_duration)]
+    pub older_than: Option<i64>,
+    /// Only include entries which have never been read (or not within `--since`), reads are only
+    /// recorded with `stats --track on` or a `cap`
+    #[arg(long)]
+    pub unused: bool,
+    /// Only include entries where a field matches, e.g. `value=on` or `alternate!=""`
+    ///
+    /// The fields are name, value, alternate, description & default. Can be given multiple times.
+    #[arg(short, long = "where", value_name = "CONDITION", value_parser = Condition::parse)]
+    pub r#where: Vec<Condition>,
+}
+
+/// A comparison of a field of an entry with a value, used by [Filter::where]
+#[derive(Debug, Clone)]
+pub struct Condition {
+    /// The column in the db the field is stored in
+    column: &'static str,
+    /// Whether the field must not equal the value
+    negate: bool,
+    value: String,
+}
+
+impl Condition {
+    /// Parses a condition like `value=on` or `alternate!=""`
+    ///
+    /// Surrounding double quotes are removed from the value, so `""` can be used for the empty
+    /// string.
+    pub fn parse(condition: &str) -> Result<Condition, String> {
+        let Some((field, value)) = condition.split_once('=') else {
+            return Err("expected FIELD=VALUE or FIELD!=VALUE".to_string());
+        };
+        let (field, negate) = match field.strip_suffix('!') {
+            Some(field) => (field, true),
+            None => (field, false),
+        };
+
+        let column = match field.trim() {
+            "name" => "name",
+            "value" => "value",
+            "alternate" => "alternate",
+            "description" => "description",
+            "default" => "default_value",
+            field => return Err(format!("unknown field `{}`", field)),
+        };
+
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(value);
+
+        Ok(Condition {
+            column,
+            negate,
+            value: value.to_string(),
+        })
+    }
+}
+
+impl Filter {
+    /// Builds the `WHERE` clause (including the keyword) & its parameters
+    ///
+    /// If no criteria are set the clause is an empty string.
+    pub fn sql(&self) -> (String, Vec<Value>) {
+        let mut conditions = vec![];
+        let mut params = vec![];
+
+        if let Some(tag) = &self.tag {
+            conditions.push("id IN (SELECT entry_id FROM tags WHERE tag = ?)".to_string());
+            params.push(tag.clone().into());
+        }
+
+        if let Some(prefix) = &self.prefix {
+            conditions.push("instr(name, ?) = 1".to_string());
+            params.push(prefix.clone().into());
+        }
+
+        if let Some(glob) = &self.glob {
+            conditions.push("name GLOB ?".to_string());
+            params.push(glob.clone().into());
+        }
+
+        match (self.since, self.unused) {
+            (Some(since), false) => {
+                conditions.push("updated_at >= ?".to_string());
+                params.push((time::now() - since).into());
+            }
+            (Some(since), true) => {
+                conditions.push("(accessed_at IS NULL OR accessed_at < ?)".to_string());
+                params.push((time::now() - since).into());
+            }
+            (None, true) => conditions.push("accessed_at IS NULL".to_string()),
+            (None, false) => {}
+        }
+
+        if let Some(timestamp) = self.changed_since {
+            conditions.push("updated_at >= ?".to_string());
+            params.push(timestamp.into());
+        }
+
+        if let Some(older_than) = self.older_than {
+            conditions.push("updated_at < ?".to_string());
+            params.push((time::now() - older_than).into());
+        }
+
+        for condition in &self.r#where {
+            let operator = if condition.negate { "IS NOT" } else { "IS" };
+            conditions.push(format!("{} {} ?", condition.column, operator));
+            params.push(condition.value.clone().into());
+        }
+
+        if conditions.is_empty() {
+            (String::new(), params)
+        } else {
+            (format!("WHERE {}", conditions.join(" AND ")), params)
+        }
+    }
+
+    /// Checks if `entry` matches the filter, for backends other than sqlite (see [crate::backend])
+    ///
+    /// Will return [Error::Invalid] for tags & `--unused`, which only the sqlite db keeps track of,
+    /// & for globs, which are matched by sqlite.
+    pub fn matches(&self, entry: &Entry) -> crate::Result<bool> {
+        if self.tag.is_some() || self.glob.is_some() || self.unused {
+            return Err(Error::Invalid(
+                "tags, --glob & --unused are only supported by sqlite dbs".to_string(),
+            ));
+        }
+
+        let field = |column| match column {
+            "name" => Some(&entry.name),
+            "value" => Some(&entry.value),
+            "alternate" => entry.alternate.as_ref(),
+            "description" => entry.description.as_ref(),
+            _ => entry.default.as_ref(),
+        };
+
+        Ok(self
+            .prefix
+            .as_ref()
+            .is_none_or(|p| entry.name.starts_with(p))
+            && self
+                .since
+                .is_none_or(|since| entry.updated_at >= Some(time::now() - since))
+            && self
+                .changed_since
+                .is_none_or(|timestamp| entry.updated_at >= Some(timestamp))
+            && self
+                .older_than
+                .is_none_or(|older| entry.updated_at.is_some_and(|t| t < time::now() - older))
+            && self
+                .r#where
+                .iter()
+                .all(|c| (field(c.column) == Some(&c.value)) != c.negate))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn where_conditions() {
+        let filter = Filter {
+            r#where: vec![
+                Condition::parse("value=on").unwrap(),
+                Condition::parse(r#"default!="""#).unwrap(),
+            ],
+            ..Default::default()
+        };
+
+        let (clause, params) = filter.sql();
+        assert_eq!(clause, "WHERE value IS ? AND default_value IS NOT ?");
+        assert_eq!(
+            params,
+            vec![Value::from("on".to_string()), Value::from(String::new())]
+        );
+
+        let entry = Entry {
+            _id: 0,
+            name: "a".to_string(),
+            value: "on".to_string(),
+            alternate: None,
+            created_at: None,
+            updated_at: None,
+            description: None,
+            default: Some("off".to_string()),
+            modified_by: None,
+        };
+        assert!(filter.matches(&entry).unwrap());
+        assert!(!Filter {
+            prefix: Some("b".to_string()),
+            ..filter
+        }
+        .matches(&entry)
+        .unwrap());
+
+        assert!(Condition::parse("colour=red").is_err());
+        assert!(Condition::parse("value").is_err());
+    }
+
+    #[test]
+    fn changed_since() {
+        let filter = Filter {
+            changed_since: Some(1_714_557_600),
+            ..Default::default()
+        };
+
+        let (clause, params) = filter.sql();
+        assert_eq!(clause, "WHERE updated_at >= ?");
+        assert_eq!(params, vec![Value::from(1_714_557_600)]);
+
+        let entry = |updated_at| Entry {
+            _id: 0,
+            name: "a".to_string(),
+            value: "on".to_string(),
+            alternate: None,
+            created_at: None,
+            updated_at,
+            description: None,
+            default: None,
+            modified_by: None,
+        };
+        assert!(filter.matches(&entry(Some(1_714_557_600))).unwrap());
+        assert!(!filter.matches(&entry(Some(1_714_557_599))).unwrap());
+        // Entries never changed can't have changed since
+        assert!(!filter.matches(&entry(None)).unwrap());
+    }
+
+    #[test]
+    fn older_than() {
+        let filter = Filter {
+            older_than: Some(3600),
+            ..Default::default()
+        };
+
+        let (clause, params) = filter.sql();
+        assert_eq!(clause, "WHERE updated_at < ?");
+        let Value::Integer(cutoff) = params[0] else {
+            panic!("{:?}", params);
+        };
+        assert!((time::now() - 3600 - cutoff).abs() <= 1);
+
+        let entry = |updated_at| Entry {
+            _id: 0,
+            name: "a".to_string(),
+            value: "on".to_string(),
+            alternate: None,
+            created_at: None,
+            updated_at,
+            description: None,
+            default: None,
+            modified_by: None,
+        };
+        assert!(filter.matches(&entry(Some(time::now() - 3700))).unwrap());
+        assert!(!filter.matches(&entry(Some(time::now() - 60))).unwrap());
+        // Entries without a timestamp aren't known to be stale
+        assert!(!filter.matches(&entry(None)).unwrap());
+    }
+}
@@ -0,0 +1,125 @@
+//! Module mirroring entries into universal variables of fish, see `fish-sync`
+//!
+//! Universal variables are shared by all fish shells of the user & changes reach running shells at
+//! once, so prompts & abbreviations can use entries without running config-store. They are read &
+//! written by running `fish` itself, which stores them in `~/.config/fish/fish_variables`.
+use std::process::Command;
+
+use crate::{Error, Result};
+
+/// Returns the values of the universal variables `names`, `None` for those which aren't set
+///
+/// Variables with several elements are joined by spaces.
+pub fn get(names: &[String]) -> Result<Vec<Option<String>>> {
+    if names.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let output = run(&get_script(names))?;
+    let values = output
+        .split(|b| *b == 0)
+        .take(names.len())
+        .map(|value| match value.split_first() {
+            Some((b'1', value)) => Some(String::from_utf8_lossy(value).into_owned()),
+            _ => None,
+        })
+        .collect::<Vec<Option<String>>>();
+
+    if values.len() != names.len() {
+        return Err(Error::Invalid("unexpected output of fish".to_string()));
+    }
+
+    Ok(values)
+}
+
+/// Sets the universal variables `vars` to their values & erases those named in `erase`
+pub fn set(vars: &[(String, String)], erase: &[String]) -> Result<()> {
+    if vars.is_empty() && erase.is_empty() {
+        return Ok(());
+    }
+
+    run(&set_script(vars, erase)).map(|_| ())
+}
+
+/// Returns the names of the universal variables starting with `prefix`
+pub fn names(prefix: &str) -> Result<Vec<String>> {
+    Ok(String::from_utf8_lossy(&run("set --universal --names")?)
+        .lines()
+        .filter(|name| name.starts_with(prefix))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Quotes a string for use as a single argument in fish
+pub fn quote(string: &str) -> String {
+    format!("'{}'", string.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+/// Helper function returning the script printing the variables `names`, each as `1` & its value or
+/// `0` if it isn't set, followed by a NUL byte
+fn get_script(names: &[String]) -> String {
+    names
+        .iter()
+        .map(|name| {
+            format!(
+                "if set --query --universal {0}; printf '1%s\\0' \"${0}\"; else; printf '0\\0'; end",
+                name
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Helper function returning the script setting `vars` & erasing `erase`
+fn set_script(vars: &[(String, String)], erase: &[String]) -> String {
+    vars.iter()
+        .map(|(name, value)| format!("set --universal {} {}", name, quote(value)))
+        .chain(
+            erase
+                .iter()
+                .map(|name| format!("set --erase --universal {}", name)),
+        )
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Helper function running `script` with fish & returning its output
+fn run(script: &str) -> Result<Vec<u8>> {
+    let output = Command::new("fish")
+        .args(["--no-config", "--command", script])
+        .output()
+        .map_err(|error| {
+            std::io::Error::new(error.kind(), format!("failed to run fish: {}", error))
+        })?;
+
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!(
+            "fish failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+        .into());
+    }
+
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn scripts() {
+        assert_eq!(quote(r"it's \o/"), r"'it\'s \\o/'");
+        assert_eq!(
+            get_script(&["CS_THEME".to_string()]),
+            "if set --query --universal CS_THEME; printf '1%s\\0' \"$CS_THEME\"; else; printf '0\\0'; end"
+        );
+        assert_eq!(
+            set_script(
+                &[("CS_THEME".to_string(), "dark mode".to_string())],
+                &["CS_OLD".to_string()]
+            ),
+            "set --universal CS_THEME 'dark mode'\nset --erase --universal CS_OLD"
+        );
+    }
+}
@@ -0,0 +1,146 @@
+//! Module containing the change tracking in a git repository
+//!
+//! When a repository is configured (see [settings::GIT_REPO]), every command changing entries
+//! writes all entries as a script (see [ExportFormat::Script]) to [FILE] in the repository &
+//! commits it with the command line as the message. This keeps the history of the db next to
+//! e.g. the dotfiles using it.
+use std::{path::Path, process::Command};
+
+use rusqlite::Connection;
+
+use crate::{
+    audit,
+    commands::{export_cmd, ExportFormat},
+    settings, Error, Filter, Result,
+};
+
+/// The file in the repository the entries are written to
+pub const FILE: &str = "config-store.sh";
+
+/// Checks that `repo` is a git repository
+///
+/// Will return [Error::Invalid] if it isn't (or git isn't installed).
+pub fn check(repo: &Path) -> Result<()> {
+    let inside = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["rev-parse", "--is-inside-work-tree"])
+        .output()
+        .is_ok_and(|o| o.status.success());
+
+    if inside {
+        Ok(())
+    } else {
+        Err(Error::Invalid(format!(
+            "'{}' is not a git repository",
+            repo.display()
+        )))
+    }
+}
+
+/// Commits the entries to the configured repository, if there were changes after the change with
+/// the id `since`
+///
+/// Failing to commit (e.g. because the repository was removed) is only logged, since the changes
+/// to the db have already been made.
+pub fn commit(connection: &Connection, since: i64, message: &str) -> Result<()> {
+    let Some(repo) = settings::get(connection, settings::GIT_REPO)? else {
+        return Ok(());
+    };
+
+    if audit::since(connection, since)?.is_empty() {
+        return Ok(());
+    }
+
+    // The hooks of the repository run as the current user, so others mustn't choose it
+    if let Err(error) = crate::permissions::trusted(connection, "commit to the git repository") {
+        tracing::warn!("{}", error);
+        return Ok(());
+    }
+
+    let exported = std::fs::File::create(Path::new(&repo).join(FILE))
+        .map_err(Error::from)
+        .and_then(|mut export| {
+            export_cmd(
+                connection,
+                &mut export,
+                ExportFormat::Script,
+                Filter::default(),
+            )
+        });
+    if let Err(error) = exported {
+        tracing::warn!("Failed to export the entries to {}: {}", repo, error);
+        return Ok(());
+    }
+
+    let git = |args: &[&str]| Command::new("git").arg("-C").arg(&repo).args(args).output();
+
+    let result = git(&["add", FILE]).and_then(|_| {
+        // The export doesn't change for every change to the db, e.g. for `touch`
+        if git(&["diff", "--cached", "--quiet", "--", FILE])?
+            .status
+            .success()
+        {
+            return Ok(None);
+        }
+
+        git(&["commit", "-q", "-m", message, "--", FILE]).map(Some)
+    });
+
+    match result {
+        Ok(Some(output)) if output.status.success() => {
+            tracing::info!("Committed changes to {}", repo)
+        }
+        Ok(Some(output)) => tracing::warn!(
+            "Failed to commit changes to {}: {}",
+            repo,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+        Ok(None) => tracing::debug!("Nothing to commit to {}", repo),
+        Err(error) => tracing::warn!("Failed to run git in {}: {}", repo, error),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn commit_changes() {
+        let repo = std::env::temp_dir().join(format!("config-store-git-{}", std::process::id()));
+        std::fs::create_dir_all(&repo).unwrap();
+        let git = |args: &[&str]| {
+            let output = Command::new("git").arg("-C").arg(&repo).args(args).output();
+            String::from_utf8(output.unwrap().stdout).unwrap()
+        };
+        git(&["init", "-q"]);
+        git(&["config", "user.name", "test"]);
+        git(&["config", "user.email", "test@example.com"]);
+
+        let store = crate::Store::open_in_memory().unwrap();
+        let connection = store.connection();
+        assert!(check(&std::env::temp_dir().join("config-store-no-repo")).is_err());
+        check(&repo).unwrap();
+        settings::set(connection, settings::GIT_REPO, repo.to_str()).unwrap();
+
+        let since = audit::last_id(connection).unwrap();
+        commit(connection, since, "nothing").unwrap();
+        store.set("theme", Some("dark"), None).unwrap();
+        commit(connection, since, "config-store set theme -v dark").unwrap();
+
+        assert_eq!(
+            git(&["log", "--format=%s"]).trim(),
+            "config-store set theme -v dark"
+        );
+        assert!(std::fs::read_to_string(repo.join(FILE))
+            .unwrap()
+            .contains("dark"));
+
+        // A removed repository doesn't fail the command which changed the db
+        std::fs::remove_dir_all(&repo).unwrap();
+        store.set("theme", Some("light"), None).unwrap();
+        commit(connection, since, "config-store set theme -v light").unwrap();
+    }
+}
@@ -0,0 +1,280 @@
+//! Module parsing the INI-like config files of other tools for `import --format`
+//!
+//! Each key becomes an entry named after its section & the key, e.g. `core.editor` for `editor`
+//! in the `[core]` section of a git config or `org.gnome.desktop.interface.color-scheme` in a
+//! `dconf dump`.
+use crate::{Error, Result};
+
+/// Parses a git config file (like `~/.gitconfig`) into the names & values of entries
+///
+/// Subsections are part of the names, so `url` in `[remote "origin"]` becomes
+/// `remote.origin.url`. Section & key names are case-insensitive & returned in lower case. Keys
+/// without a value are booleans & become `true`. Includes aren't followed.
+pub fn gitconfig(input: &str) -> Result<Vec<(String, String)>> {
+    let mut entries = Vec::new();
+    let mut section: Option<String> = None;
+    let mut lines = input.lines().enumerate();
+
+    while let Some((i, line)) = lines.next() {
+        let invalid = |message: &str| Error::Invalid(format!("line {}: {}", i + 1, message));
+        let line = line.trim_start();
+
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+
+        let line = if let Some(header) = line.strip_prefix('[') {
+            let (header, rest) = header
+                .split_once(']')
+                .ok_or_else(|| invalid("unterminated section header"))?;
+            section = Some(match header.split_once(char::is_whitespace) {
+                Some((name, subsection)) => {
+                    let subsection = subsection
+                        .trim()
+                        .strip_prefix('"')
+                        .and_then(|s| s.strip_suffix('"'))
+                        .ok_or_else(|| invalid("expected a quoted subsection"))?
+                        .replace("\\\"", "\"")
+                        .replace("\\\\", "\\");
+                    format!("{}.{}", name.to_lowercase(), subsection)
+                }
+                None => header.to_lowercase(),
+            });
+
+            // A key may follow the header on the same line
+            match rest.trim_start() {
+                "" => continue,
+                rest if rest.starts_with(['#', ';']) => continue,
+                rest => rest,
+            }
+        } else {
+            line
+        };
+
+        let Some(section) = &section else {
+            return Err(invalid("key outside of a section"));
+        };
+
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), Some(value)),
+            None => (
+                line.split([' ', '\t', '#', ';']).next().unwrap_or(line),
+                None,
+            ),
+        };
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(invalid(&format!("invalid key `{}`", key)));
+        }
+
+        let value = match value {
+            Some(value) => {
+                let mut value = value.to_string();
+                // A trailing backslash continues the value on the next line
+                while value.trim_end().ends_with('\\') && !value.trim_end().ends_with("\\\\") {
+                    value.truncate(value.trim_end().len() - 1);
+                    let (_, next) = lines
+                        .next()
+                        .ok_or_else(|| invalid("continuation at the end of the file"))?;
+                    value.push_str(next);
+                }
+                git_value(&value).map_err(invalid)?
+            }
+            None => "true".to_string(),
+        };
+
+        entries.push((format!("{}.{}", section, key.to_lowercase()), value));
+    }
+
+    Ok(entries)
+}
+
+/// Helper function unquoting & unescaping the value of a git config key
+///
+/// Whitespace outside of quotes is trimmed at the ends & comments are removed.
+fn git_value(raw: &str) -> std::result::Result<String, &'static str> {
+    let mut value = String::new();
+    // The length of `value` up to its last quoted or escaped character, which isn't trimmed
+    let mut kept = 0;
+    let mut quoted = false;
+    let mut chars = raw.trim_start().chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' | ';' if !quoted => break,
+            '\\' => {
+                value.push(match chars.next() {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('b') => '\u{8}',
+                    Some(c @ ('"' | '\\')) => c,
+                    _ => return Err("invalid escape sequence"),
+                });
+                kept = value.len();
+                continue;
+            }
+            c => value.push(c),
+        }
+
+        if quoted {
+            kept = value.len();
+        }
+    }
+
+    if quoted {
+        return Err("unterminated quote");
+    }
+
+    let trimmed = value.trim_end().len().max(kept);
+    value.truncate(trimmed);
+
+    Ok(value)
+}
+
+/// Parses the output of `dconf dump` into the names & values of entries
+///
+/// The paths of the sections become dotted names, e.g. `color-scheme` in
+/// `[org/gnome/desktop/interface]` becomes `org.gnome.desktop.interface.color-scheme`. Strings
+/// are unquoted, other values are kept in the GVariant text format (e.g. `true`, `uint32 5` or
+/// `['a', 'b']`), so they can be written back with `dconf write`.
+pub fn dconf(input: &str) -> Result<Vec<(String, String)>> {
+    let mut entries = Vec::new();
+    let mut section: Option<String> = None;
+
+    for (i, line) in input.lines().enumerate() {
+        let invalid = |message: &str| Error::Invalid(format!("line {}: {}", i + 1, message));
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(path) = line.strip_prefix('[') {
+            let path = path
+                .strip_suffix(']')
+                .ok_or_else(|| invalid("unterminated section header"))?;
+            section = Some(path.trim_matches('/').replace('/', "."));
+            continue;
+        }
+
+        let Some(section) = &section else {
+            return Err(invalid("key outside of a section"));
+        };
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| invalid("expected key=value"))?;
+        let name = match section.as_str() {
+            "" => key.to_string(),
+            section => format!("{}.{}", section, key),
+        };
+
+        entries.push((
+            name,
+            gvariant_string(value).unwrap_or_else(|| value.to_string()),
+        ));
+    }
+
+    Ok(entries)
+}
+
+/// Helper function returning the contents of a GVariant string like `'it\'s'`, or `None` if
+/// `value` is something else
+fn gvariant_string(value: &str) -> Option<String> {
+    let quote = value.chars().next().filter(|c| matches!(c, '\'' | '"'))?;
+    let mut string = String::new();
+    let mut chars = value[1..].chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => string.push(match chars.next()? {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                'u' => {
+                    let code = chars.by_ref().take(4).collect::<String>();
+                    char::from_u32(u32::from_str_radix(&code, 16).ok()?)?
+                }
+                c => c,
+            }),
+            // The string has to end with its closing quote
+            c if c == quote => return chars.next().is_none().then_some(string),
+            c => string.push(c),
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn gitconfig_files() {
+        let config = r#"
+# comment
+[user]
+    name = Jane Doe ; the full name
+    email=jane@example.org
+[core]
+    editor = "vim -p" # quoted
+    autocrlf
+[remote "origin"]
+    url = https://example.org/a.git
+[Alias] lg = log --graph \
+  --oneline
+    msg = "say \"hi\"\t"
+"#;
+
+        assert_eq!(
+            gitconfig(config).unwrap(),
+            [
+                ("user.name", "Jane Doe"),
+                ("user.email", "jane@example.org"),
+                ("core.editor", "vim -p"),
+                ("core.autocrlf", "true"),
+                ("remote.origin.url", "https://example.org/a.git"),
+                ("alias.lg", "log --graph   --oneline"),
+                ("alias.msg", "say \"hi\"\t"),
+            ]
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+        );
+
+        assert!(matches!(gitconfig("key = 1"), Err(Error::Invalid(_))));
+        assert!(matches!(gitconfig("[a]\nb = \"c"), Err(Error::Invalid(_))));
+        assert!(matches!(gitconfig("[a\nb = c"), Err(Error::Invalid(_))));
+    }
+
+    #[test]
+    fn dconf_dumps() {
+        let dump = r#"[/]
+version=3
+
+[org/gnome/desktop/interface]
+color-scheme='prefer-dark'
+font-name="Ubuntu's Sans 11"
+text-scaling-factor=1.25
+enabled-extensions=['a@b', 'c']
+greeting='it\'s \u00e9'
+"#;
+
+        assert_eq!(
+            dconf(dump).unwrap(),
+            [
+                ("version", "3"),
+                ("org.gnome.desktop.interface.color-scheme", "prefer-dark"),
+                ("org.gnome.desktop.interface.font-name", "Ubuntu's Sans 11"),
+                ("org.gnome.desktop.interface.text-scaling-factor", "1.25"),
+                (
+                    "org.gnome.desktop.interface.enabled-extensions",
+                    "['a@b', 'c']"
+                ),
+                ("org.gnome.desktop.interface.greeting", "it's \u{e9}"),
+            ]
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+        );
+
+        assert!(matches!(dconf("a=1"), Err(Error::Invalid(_))));
+        assert!(matches!(dconf("[a]\nb"), Err(Error::Invalid(_))));
+    }
+}
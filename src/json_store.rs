@@ -0,0 +1,229 @@
+//! Module containing [JsonStore], a [Backend] storing entries in a single json file
+//!
+//! The file is an object mapping the names of the entries to objects with their fields, sorted by
+//! name & pretty printed, so it can be edited by hand & diffed. Every change rewrites the whole
+//! file to a temporary file next to it, which then replaces the old one. So the file is never
+//! left half written, but concurrent changes by several processes may overwrite each other.
+use std::path::PathBuf;
+
+use serde_json::{json, Map, Value};
+
+use crate::{backend::Backend, time, Entry, Error, Filter, Result};
+
+/// A [Backend] using a json file
+#[derive(Debug)]
+pub struct JsonStore {
+    path: PathBuf,
+}
+
+impl JsonStore {
+    /// Uses the file at `path`, which is created on the first change if it doesn't exist
+    pub fn open(path: impl Into<PathBuf>) -> JsonStore {
+        JsonStore { path: path.into() }
+    }
+
+    /// Helper function reading all entries from the file
+    ///
+    /// Will return [Error::Invalid] if the file isn't a json object.
+    fn load(&self) -> Result<Map<String, Value>> {
+        if !self.path.exists() {
+            return Ok(Map::new());
+        }
+
+        match serde_json::from_slice(&std::fs::read(&self.path)?) {
+            Ok(Value::Object(entries)) => Ok(entries),
+            Ok(_) => Err(Error::Invalid(format!(
+                "{} doesn't contain a json object",
+                self.path.display()
+            ))),
+            Err(error) => Err(Error::Invalid(format!(
+                "{} isn't valid json: {}",
+                self.path.display(),
+                error
+            ))),
+        }
+    }
+
+    /// Helper function replacing the file with `entries`
+    fn save(&self, entries: Map<String, Value>) -> Result<()> {
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+
+        let mut json = serde_json::to_string_pretty(&Value::Object(entries))
+            .map_err(|e| Error::Invalid(e.to_string()))?;
+        json.push('\n');
+
+        std::fs::write(&temporary, json)?;
+        std::fs::rename(&temporary, &self.path)?;
+
+        Ok(())
+    }
+
+    /// Helper function returning the fields of the entry `name`
+    ///
+    /// Will return [Error::Invalid] if they aren't a json object, e.g. in a hand edited file.
+    fn fields<'a>(&self, name: &str, fields: &'a mut Value) -> Result<&'a mut Map<String, Value>> {
+        fields.as_object_mut().ok_or_else(|| self.not_object(name))
+    }
+
+    /// Helper function creating the error of [JsonStore::fields]
+    fn not_object(&self, name: &str) -> Error {
+        Error::Invalid(format!(
+            "the entry '{}' in {} isn't a json object",
+            name,
+            self.path.display()
+        ))
+    }
+
+    /// Helper function converting a json object into an [Entry], see [JsonStore::fields]
+    fn entry(&self, name: &str, fields: &Value) -> Result<Entry> {
+        let fields = fields.as_object().ok_or_else(|| self.not_object(name))?;
+        let string = |field: &str| {
+            fields
+                .get(field)
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        };
+
+        Ok(Entry {
+            _id: 0,
+            name: name.to_string(),
+            value: string("value").unwrap_or_default(),
+            alternate: string("alternate"),
+            created_at: fields.get("created_at").and_then(Value::as_i64),
+            updated_at: fields.get("updated_at").and_then(Value::as_i64),
+            description: string("description"),
+            default: string("default"),
+            modified_by: None,
+        })
+    }
+}
+
+impl Backend for JsonStore {
+    fn get(&self, name: &str) -> Result<Entry> {
+        let entries = self.load()?;
+
+        self.entry(
+            name,
+            entries.get(name).ok_or_else(|| Error::EntryNotFound {
+                name: name.to_string(),
+                suggestions: crate::suggest::similar(name, entries.keys()),
+            })?,
+        )
+    }
+
+    fn exists(&self, name: &str) -> Result<bool> {
+        Ok(self.load()?.contains_key(name))
+    }
+
+    fn set(&self, name: &str, value: Option<&str>, alternate: Option<&str>) -> Result<Entry> {
+        let mut entries = self.load()?;
+        let now = time::now();
+
+        let fields = self.fields(
+            name,
+            entries
+                .entry(name)
+                .or_insert_with(|| json!({ "value": "", "created_at": now })),
+        )?;
+        if let Some(value) = value {
+            fields.insert("value".to_string(), value.into());
+        }
+        if let Some(alternate) = alternate {
+            fields.insert("alternate".to_string(), alternate.into());
+        }
+        fields.insert("updated_at".to_string(), now.into());
+
+        let entry = self.entry(name, &entries[name])?;
+        self.save(entries)?;
+
+        Ok(entry)
+    }
+
+    fn toggle(&self, name: &str) -> Result<String> {
+        let mut entries = self.load()?;
+        let entry = self.get(name)?;
+
+        let fields = self.fields(name, &mut entries[name])?;
+        fields.insert(
+            "value".to_string(),
+            entry.alternate.as_deref().unwrap_or_default().into(),
+        );
+        fields.insert("alternate".to_string(), entry.value.into());
+        fields.insert("updated_at".to_string(), time::now().into());
+
+        self.save(entries)?;
+
+        Ok(entry.alternate.unwrap_or_default())
+    }
+
+    fn delete(&self, name: &str) -> Result<bool> {
+        let mut entries = self.load()?;
+
+        if entries.remove(name).is_none() {
+            return Ok(false);
+        }
+
+        self.save(entries)?;
+
+        Ok(true)
+    }
+
+    fn list(&self, filter: &Filter) -> Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+
+        for (name, fields) in self.load()? {
+            let entry = self.entry(&name, &fields)?;
+
+            if filter.matches(&entry)? {
+                entries.push(entry);
+            }
+        }
+
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn json_file() {
+        let path = std::env::temp_dir().join(format!("config-store-{}.json", std::process::id()));
+        let store = JsonStore::open(&path);
+
+        store.set("volume", Some("50"), None).unwrap();
+        store.set("theme", Some("dark"), Some("light")).unwrap();
+        assert_eq!(store.toggle("theme").unwrap(), "light");
+        assert_eq!(
+            store.get("theme").unwrap().alternate.as_deref(),
+            Some("dark")
+        );
+        assert_eq!(store.list(&Filter::default()).unwrap().len(), 2);
+
+        let json = std::fs::read_to_string(&path).unwrap();
+        assert!(json.starts_with("{\n  \"theme\": {"));
+
+        assert!(store.delete("theme").unwrap());
+        assert!(!store.delete("theme").unwrap());
+        assert!(matches!(
+            store.toggle("theme"),
+            Err(Error::EntryNotFound { .. })
+        ));
+
+        std::fs::write(&path, r#"{"theme": "dark"}"#).unwrap();
+        for result in [
+            store.get("theme").map(|_| ()),
+            store.set("theme", Some("light"), None).map(|_| ()),
+            store.toggle("theme").map(|_| ()),
+        ] {
+            assert!(matches!(result, Err(Error::Invalid(message)) if message.contains("'theme'")));
+        }
+
+        std::fs::write(&path, "[]").unwrap();
+        assert!(matches!(store.get("volume"), Err(Error::Invalid(_))));
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
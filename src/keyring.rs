@@ -0,0 +1,179 @@
+//! Module keeping the values of secret entries in the keyring of the desktop, see `set --keyring`
+//!
+//! The values are stored through the Secret Service (GNOME Keyring, KWallet, KeePassXC, ...) with
+//! `secret-tool` from libsecret, so they are never written to the db in plaintext & can be read
+//! without a passphrase while the keyring is unlocked. The db only marks the entries as secret &
+//! keeps an empty value, `get` fetches the real one.
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::{entry::Entry, Error, Result};
+
+/// Stores `secret` as the value of the entry `name` of the db of `connection`
+///
+/// Nothing is stored during a dry run, see [crate::store::dry_run].
+pub fn store(connection: &Connection, name: &str, secret: &str) -> Result<()> {
+    if crate::store::dry_run(connection)? {
+        return Ok(());
+    }
+
+    let mut child = secret_tool(connection, name, &["store", "--label", name])?
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(missing)?;
+
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(secret.as_bytes())?;
+    let output = child.wait_with_output()?;
+
+    if !output.status.success() {
+        return Err(failed("store", &output.stderr));
+    }
+
+    Ok(())
+}
+
+/// Returns the value of the entry `name` of the db of `connection` from the keyring
+///
+/// Will return [Error::NotFound] if the keyring doesn't contain it (anymore).
+pub fn lookup(connection: &Connection, name: &str) -> Result<String> {
+    let output = secret_tool(connection, name, &["lookup"])?
+        .output()
+        .map_err(missing)?;
+
+    if !output.status.success() {
+        if output.stderr.is_empty() {
+            return Err(Error::NotFound(format!(
+                "secret of '{}' in the keyring",
+                name
+            )));
+        }
+        return Err(failed("lookup", &output.stderr));
+    }
+
+    String::from_utf8(output.stdout)
+        .map_err(|_| Error::Invalid(format!("the secret of '{}' isn't text", name)))
+}
+
+/// Removes the value of the entry `name` of the db of `connection` from the keyring
+///
+/// Nothing is removed during a dry run, see [crate::store::dry_run].
+pub fn remove(connection: &Connection, name: &str) -> Result<()> {
+    if crate::store::dry_run(connection)? {
+        return Ok(());
+    }
+
+    let output = secret_tool(connection, name, &["clear"])?
+        .output()
+        .map_err(missing)?;
+
+    if !output.status.success() {
+        return Err(failed("clear", &output.stderr));
+    }
+
+    Ok(())
+}
+
+/// Moves the value of the entry `old` of the db of `connection` to the entry `new`, e.g. after
+/// renaming it
+///
+/// Nothing is moved during a dry run, see [crate::store::dry_run].
+pub fn rename(connection: &Connection, old: &str, new: &str) -> Result<()> {
+    if crate::store::dry_run(connection)? {
+        return Ok(());
+    }
+
+    store(connection, new, &lookup(connection, old)?)?;
+    remove(connection, old)
+}
+
+/// Whether writing `value` to the entry `name` turns a secret entry into a regular one
+///
+/// Setting a non-empty value does that, see `set --keyring`. The secret left in the keyring has
+/// to be removed with [forget] then.
+pub fn overwritten(connection: &Connection, name: &str, value: Option<&str>) -> Result<bool> {
+    if !matches!(value, Some(value) if !value.is_empty()) {
+        return Ok(false);
+    }
+
+    Ok(connection
+        .query_row(
+            "SELECT secret IS TRUE FROM data WHERE name = ?",
+            [name],
+            |row| row.get(0),
+        )
+        .optional()?
+        .unwrap_or(false))
+}
+
+/// Removes the value of the entry `name`, which was deleted or overwritten, from the keyring
+///
+/// The entry is gone either way, so a secret left in the keyring is only worth a warning.
+pub fn forget(connection: &Connection, name: &str) {
+    if let Err(error) = remove(connection, name) {
+        tracing::warn!(
+            "Failed to remove the secret of '{}' from the keyring: {}",
+            name,
+            error
+        );
+    }
+}
+
+/// Gives a secret [Entry] its value from the keyring, other entries are returned unchanged
+pub fn reveal(connection: &Connection, mut entry: Entry) -> Result<Entry> {
+    let secret: Option<bool> =
+        connection.query_row("SELECT secret FROM data WHERE id = ?", [entry._id], |row| {
+            row.get(0)
+        })?;
+
+    if secret == Some(true) {
+        entry.value = lookup(connection, &entry.name)?;
+    }
+
+    Ok(entry)
+}
+
+/// Helper function preparing a `secret-tool` command for the entry `name`
+///
+/// The attributes include the path of the db, so entries of different dbs don't collide.
+fn secret_tool(connection: &Connection, name: &str, args: &[&str]) -> Result<Command> {
+    let db = connection
+        .path()
+        .filter(|p| !p.is_empty())
+        .ok_or_else(|| Error::Invalid("in memory dbs can't store secrets".to_string()))?;
+
+    let mut command = Command::new("secret-tool");
+    command
+        .args(args)
+        .args(["application", "config-store", "db", db, "entry", name]);
+
+    Ok(command)
+}
+
+/// Helper function for a `secret-tool` which can't be started
+fn missing(error: std::io::Error) -> Error {
+    std::io::Error::new(
+        error.kind(),
+        format!("failed to run secret-tool (from libsecret): {}", error),
+    )
+    .into()
+}
+
+/// Helper function for a failed `secret-tool` command
+fn failed(action: &str, stderr: &[u8]) -> Error {
+    std::io::Error::other(format!(
+        "secret-tool {} failed: {}",
+        action,
+        String::from_utf8_lossy(stderr).trim()
+    ))
+    .into()
+}
@@ -0,0 +1,176 @@
+//! Module containing layered stores, where entries missing from the db are read from other dbs
+//!
+//! Layers are other config-store dbs attached read-only with `--layer` (e.g. machine-wide
+//! defaults in `/etc`). When reading, an entry is taken from the db itself if it's there and
+//! otherwise from the first layer containing it. Changes always go to the db itself: changing an
+//! entry which only exists in a layer first copies it from the layer, so the layer is never
+//! modified & the entry overrides it from then on.
+//!
+//! Only the entries themselves are layered, tags, aliases & presets of the layers are ignored.
+use std::path::Path;
+
+use rusqlite::Connection;
+
+use crate::{entry::Entry, Result};
+
+/// The prefix of the schema names the layers are attached as
+const SCHEMA: &str = "layer";
+
+/// Attaches the db at `path` as the lowest layer so far
+///
+/// Returns `false` if there is no config-store db at `path`, in which case nothing is attached.
+pub fn attach(connection: &Connection, path: &Path) -> Result<bool> {
+    if !path.is_file() {
+        tracing::debug!("Skipping missing layer {}", path.display());
+        return Ok(false);
+    }
+
+    let schema = format!("{}{}", SCHEMA, schemas(connection)?.len() + 1);
+
+    // URIs separate directories with `/`, so absolute Windows paths become `/C:/...`
+    #[cfg(windows)]
+    let uri_path = format!(
+        "/{}",
+        std::path::absolute(path)?
+            .to_string_lossy()
+            .replace('\\', "/")
+    );
+    #[cfg(not(windows))]
+    let uri_path = path.to_string_lossy();
+
+    // Characters with a meaning in URIs have to be escaped in the path
+    let mut uri = String::from("file:");
+    for c in uri_path.chars() {
+        match c {
+            '%' | '?' | '#' => uri.push_str(&format!("%{:02X}", c as u32)),
+            c => uri.push(c),
+        }
+    }
+    uri.push_str("?mode=ro");
+
+    connection.execute(&format!("ATTACH DATABASE ? AS {}", schema), [uri])?;
+
+    let has_entries = connection
+        .prepare(&format!(
+            "SELECT 1 FROM {}.sqlite_master WHERE type = 'table' AND name = 'data'",
+            schema
+        ))?
+        .exists(())?;
+    if !has_entries {
+        tracing::warn!("{} isn't a config-store db, ignoring it", path.display());
+        connection.execute(&format!("DETACH DATABASE {}", schema), ())?;
+    }
+
+    Ok(has_entries)
+}
+
+/// Helper function returning the schema names of the attached layers in priority order
+fn schemas(connection: &Connection) -> Result<Vec<String>> {
+    Ok(connection
+        .prepare("SELECT name FROM pragma_database_list WHERE instr(name, ?) = 1 ORDER BY seq")?
+        .query_map([SCHEMA], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?)
+}
+
+/// Helper function returning what to select entries from instead of the `data` table
+///
+/// Without layers this is simply `data`. Otherwise it's a subquery combining `data` with the
+/// entries of the layers which aren't overridden by the db or a higher layer. It's named `data`
+/// & has the columns of [Entry::COLUMNS] & `accessed_at`, so filters work unchanged. Entries
+/// from layers have the id `0`, which no entry in the db has, & were never accessed or modified
+/// by anyone known.
+///
+/// With a signing key the entries of the db are checked while they are read, see
+/// [crate::signing::verified].
+pub(crate) fn source(connection: &Connection) -> Result<String> {
+    let main = if crate::signing::verified(connection)? {
+        "(SELECT * FROM main.data WHERE hmac_verify(name, value, alternate, hmac))"
+    } else {
+        "main.data"
+    };
+
+    let schemas = schemas(connection)?;
+    if schemas.is_empty() {
+        return Ok(match main {
+            "main.data" => "data".to_string(),
+            main => format!("{} AS data", main),
+        });
+    }
+
+    let mut source = format!("SELECT {}, accessed_at FROM {}", Entry::COLUMNS, main);
+    let mut higher = vec!["main".to_string()];
+
+    for schema in schemas {
+        source.push_str(&format!(
+            " UNION ALL SELECT 0, name, value, alternate, created_at, updated_at, description,
+                default_value, NULL, NULL FROM {}.data WHERE {}",
+            schema,
+            higher
+                .iter()
+                .map(|h| format!("name NOT IN (SELECT name FROM {}.data)", h))
+                .collect::<Vec<String>>()
+                .join(" AND ")
+        ));
+        higher.push(schema);
+    }
+
+    Ok(format!("({}) AS data", source))
+}
+
+/// Helper function copying an [Entry] which only exists in a layer into the db before changing it
+///
+/// Does nothing if the entry exists in the db or in no layer.
+pub(crate) fn copy_up(connection: &Connection, name: &str) -> Result<()> {
+    if schemas(connection)?.is_empty() {
+        return Ok(());
+    }
+    let source = source(connection)?;
+
+    connection.execute(
+        &format!(
+            "INSERT INTO main.data
+                (name, value, alternate, created_at, updated_at, description, default_value)
+            SELECT name, value, alternate, created_at, updated_at, description, default_value
+                FROM {} WHERE id = 0 AND name = ? LIMIT 1",
+            source
+        ),
+        [name],
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Filter, Store};
+
+    #[test]
+    fn fall_through() {
+        let dir = std::env::temp_dir().join(format!("config-store-layers-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let system = Store::open(dir.join("system.db")).unwrap();
+        system.set("theme", Some("light"), Some("dark")).unwrap();
+        system.set("volume", Some("50"), None).unwrap();
+        let site = Store::open(dir.join("site.db")).unwrap();
+        site.set("volume", Some("30"), None).unwrap();
+
+        let store = Store::open_in_memory().unwrap();
+        assert!(store.add_layer(dir.join("site.db")).unwrap());
+        assert!(store.add_layer(dir.join("system.db")).unwrap());
+        assert!(!store.add_layer(dir.join("missing.db")).unwrap());
+
+        assert_eq!(store.get("volume").unwrap().value, "30");
+        assert_eq!(store.list(&Filter::default()).unwrap().len(), 2);
+
+        assert_eq!(store.toggle("theme").unwrap(), "dark");
+        assert_eq!(
+            store.get("theme").unwrap().alternate.as_deref(),
+            Some("light")
+        );
+        assert_eq!(system.get("theme").unwrap().value, "light");
+        assert_eq!(store.list(&Filter::default()).unwrap().len(), 2);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
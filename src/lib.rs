@@ -0,0 +1,77 @@
+//! config-store is a simple and lightweight key-value store designed for easy use from shell
+//! scripts
+//!
+//! It uses a sqlite3 db named `config-store.db` in the temporary directory of the platform (e.g.
+//! `/tmp` on Linux) by default to save values. This means that all values persist until reboot.
+//! Should `config-store.db` be deleted for any reason, config-store will simply create a new one
+//! on the next invocation. <b> Please note that this only applies to release builds. For
+//! debug builds the db is located at `./test.db`. </b>
+//!
+//! See [commands] for more information on how individual commands work.
+//!
+//! Besides the `config-store` binary, this crate can be used as a library through the typed
+//! [Store] API, which operates on the same db as the command line.
+#[cfg(feature = "archive")]
+pub mod archive;
+#[cfg(feature = "async")]
+pub mod async_store;
+pub mod audit;
+pub mod backend;
+pub mod backup;
+pub mod blob;
+pub mod checksum;
+pub mod cli;
+pub mod clipboard;
+pub mod color;
+pub mod commands;
+pub mod completion;
+pub mod entry;
+pub mod error;
+pub mod expr;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod filter;
+pub mod fish;
+pub mod git;
+pub mod ini;
+pub mod json_store;
+pub mod keyring;
+pub mod layers;
+pub mod limits;
+pub mod manifest;
+pub mod mirror;
+pub mod naming;
+#[cfg(feature = "notify")]
+pub mod notify;
+pub mod permissions;
+pub mod plugin;
+pub mod prompt;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod recovery;
+#[cfg(feature = "redis")]
+pub mod redis_store;
+pub mod remote;
+pub mod schema;
+#[cfg(feature = "script")]
+pub mod script;
+pub mod segment;
+pub mod session;
+pub mod settings;
+pub mod shortcut;
+pub mod signing;
+pub mod store;
+pub mod suggest;
+pub mod table_store;
+pub mod time;
+pub mod tmux;
+pub mod transform;
+pub mod tuning;
+
+#[cfg(feature = "async")]
+pub use async_store::AsyncStore;
+pub use backend::Backend;
+pub use entry::Entry;
+pub use error::{Error, Result};
+pub use filter::Filter;
+pub use store::Store;
@@ -0,0 +1,154 @@
+//! Module limiting the size of values & alternates, so a runaway script can't fill the db, see
+//! `limits`
+//!
+//! The limits are settings of the db in bytes. Temporary triggers of the connection check every
+//! new value & alternate against them, so every command (and [Store](crate::Store)) is covered.
+use rusqlite::{
+    functions::{Context, FunctionFlags},
+    types::Null,
+    Connection,
+};
+
+use crate::{settings, Result};
+
+/// The prefix of the errors raised by the triggers, see [Error::TooLarge](crate::Error::TooLarge)
+pub const TOO_LARGE: &str = "too large: ";
+
+/// Registers the `size_check` SQL function & creates the triggers checking the sizes
+pub fn init(connection: &Connection) -> rusqlite::Result<()> {
+    connection.create_scalar_function(
+        "size_check",
+        4,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        size_check,
+    )?;
+
+    // Updates only check the fields they change, so existing entries stay usable
+    let checks = |update: bool| {
+        [
+            ("value", settings::MAX_VALUE_SIZE),
+            ("alternate", settings::MAX_ALTERNATE_SIZE),
+        ]
+        .map(|(field, key)| {
+            format!(
+                "SELECT size_check(NEW.name, '{0}', length(CAST(NEW.{0} AS BLOB)),
+                    (SELECT CAST(value AS INTEGER) FROM settings WHERE key = '{1}'))
+                {2};",
+                field,
+                key,
+                if update {
+                    format!("WHERE NEW.{0} IS NOT OLD.{0}", field)
+                } else {
+                    String::new()
+                }
+            )
+        })
+        .join("\n")
+    };
+
+    connection.execute_batch(&format!(
+        "
+        CREATE TEMP TRIGGER IF NOT EXISTS limits_insert BEFORE INSERT ON data
+        WHEN EXISTS (SELECT 1 FROM settings WHERE key IN ('{0}', '{1}'))
+        BEGIN
+            {2}
+        END;
+
+        CREATE TEMP TRIGGER IF NOT EXISTS limits_update BEFORE UPDATE OF value, alternate ON data
+        WHEN EXISTS (SELECT 1 FROM settings WHERE key IN ('{0}', '{1}'))
+        BEGIN
+            {3}
+        END;",
+        settings::MAX_VALUE_SIZE,
+        settings::MAX_ALTERNATE_SIZE,
+        checks(false),
+        checks(true)
+    ))
+}
+
+/// Returns the limits of values & alternates in bytes
+pub fn get(connection: &Connection) -> Result<(Option<usize>, Option<usize>)> {
+    let limit = |key| -> Result<Option<usize>> {
+        Ok(settings::get(connection, key)?.and_then(|max| max.parse().ok()))
+    };
+
+    Ok((
+        limit(settings::MAX_VALUE_SIZE)?,
+        limit(settings::MAX_ALTERNATE_SIZE)?,
+    ))
+}
+
+/// Changes the limit of values (or alternates if `alternate`), removing it if `max` is `None`
+///
+/// Existing entries are kept even if they are larger, only new values are checked.
+pub fn set(connection: &Connection, alternate: bool, max: Option<usize>) -> Result<()> {
+    let key = if alternate {
+        settings::MAX_ALTERNATE_SIZE
+    } else {
+        settings::MAX_VALUE_SIZE
+    };
+
+    settings::set(connection, key, max.map(|max| max.to_string()).as_deref())
+}
+
+/// Helper function implementing `size_check(name, field, size, max)`, which fails if `size` is
+/// larger than `max`
+fn size_check(context: &Context) -> rusqlite::Result<Null> {
+    let (Some(size), Some(max)) = (
+        context.get::<Option<i64>>(2)?,
+        context.get::<Option<i64>>(3)?,
+    ) else {
+        return Ok(Null);
+    };
+
+    if size <= max {
+        return Ok(Null);
+    }
+
+    Err(rusqlite::Error::UserFunctionError(
+        format!(
+            "{}the {} of '{}' has {} bytes, more than the limit of {} bytes",
+            TOO_LARGE,
+            context.get::<String>(1)?,
+            context.get::<String>(0)?,
+            size,
+            max
+        )
+        .into(),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Error;
+
+    #[test]
+    fn limits() {
+        let store = crate::Store::open_in_memory().unwrap();
+        let connection = store.connection();
+        store.set("large", Some("0123456789"), None).unwrap();
+
+        set(connection, false, Some(4)).unwrap();
+        set(connection, true, Some(2)).unwrap();
+        assert_eq!(get(connection).unwrap(), (Some(4), Some(2)));
+
+        store.set("small", Some("ab"), Some("c")).unwrap();
+        assert!(matches!(
+            store.set("small", Some("abcde"), None),
+            Err(Error::TooLarge(_))
+        ));
+        assert!(matches!(
+            store.set("new", Some("a"), Some("abc")),
+            Err(Error::TooLarge(_))
+        ));
+        // Multi-byte characters count with all their bytes
+        assert!(store.set("small", Some("äää"), None).is_err());
+        // Existing entries keep their values
+        assert_eq!(store.toggle("small").unwrap(), "c");
+        store.set("large", None, Some("d")).unwrap();
+
+        set(connection, false, None).unwrap();
+        store.set("small", Some("0123456789"), None).unwrap();
+    }
+}
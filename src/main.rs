@@ -1,147 +1,1024 @@
-//! config-store is a simple and lightweight key-value store designed for easy use from shell
-//! scripts
-//!
-//! It uses a sqlite3 db in `/tmp/config-store.db` by default to save values. This means that all values persist
-//! until reboot. Should `config-store.db` be deleted for any reason, config-store will simply create
-//! a new one on the next invocation. <b> Please note that this only applies to release builds. For
-//! debug builds the db is located at `./test.db`. </b>
-//!
-//! See [commands] for more information on how individual commands work.
+//! The `config-store` binary
 //!
+//! See the library documentation for how config-store works.
 use core::panic;
+use std::{ffi::OsString, io::Write};
 
-use clap::{Parser, Subcommand};
+use clap::{error::ErrorKind, CommandFactory, FromArgMatches};
+use config_store::{
+    audit,
+    backend::{self, BackendKind},
+    backup,
+    cli::{
+        Action, Args, BackupAction, BindArgs, BlobAction, CheckArgs, ComputeArgs, CopyFromArgs,
+        DeleteArgs, ExecArgs, ExportArgs, FishSyncArgs, FtsArgs, GenerateArgs, GetArgs, ImportArgs,
+        ImportEnvArgs, LimitsArgs, ListArgs, MenuArgs, MetaAction, NamingArgs, PresetAction,
+        RatelimitArgs, RecordAction, RecordArgs, SessionAction, SetArgs, ShellInitArgs,
+        SnapshotAction, TagAction, TimerAction, TmuxSyncArgs, ToggleArgs, WaitForArgs, WatchArgs,
+    },
+    clipboard,
+    commands::{self, ListFormat, SetMode},
+    json_store::JsonStore,
+    mirror, permissions, plugin, prompt, recovery, session, shortcut, signing, store,
+    table_store::TableStore,
+    Backend, Store,
+};
 use rusqlite::Connection;
 
-mod commands;
-mod entry;
+fn main() {
+    // Completion scripts call the binary with `$COMPLETE` set on TAB
+    clap_complete::CompleteEnv::with_factory(Args::command).complete();
+
+    let arguments: Vec<OsString> = std::env::args_os().collect();
+    let (args, command_name) = parse(arguments.clone());
+    let json = args.json;
+
+    if let Err(error) = run(args, command_name, arguments) {
+        if json {
+            eprintln!("{}", error.json());
+        } else {
+            eprintln!("Error: {}", error);
+        }
+        std::process::exit(error.exit_code());
+    }
+}
+
+/// Runs the command parsed from the command line `arguments` & prints its result
+fn run(mut args: Args, mut command_name: String, arguments: Vec<OsString>) -> commands::Result<()> {
+    // Aliases replace the unknown command with their words, keeping the arguments around it
+    if let Action::External(command) = &args.command {
+        if let Some(words) = shortcut::expand(&command[0])? {
+            let mut arguments = arguments.clone();
+            let at = arguments.len() - command.len();
+            arguments.splice(at..=at, words.into_iter().map(OsString::from));
+            (args, command_name) = parse(arguments);
+        }
+    }
+
+    init_logging(args.verbose);
 
-fn main() -> commands::Result<()> {
-    let args = Args::parse();
+    // Plugins open the db themselves, with the options passed on to them
+    if let Action::External(command) = &args.command {
+        let (name, arguments) = command.split_first().expect("clap requires a subcommand");
+        let Some(program) = plugin::find(name) else {
+            exit_usage(Args::command().error(ErrorKind::InvalidSubcommand, plugin::unknown(name)))
+        };
+        return plugin::run(&args, &program, arguments);
+    }
+
+    let start = std::time::Instant::now();
 
     let path = &args.db_path;
 
-    let connection =
-        Connection::open(path).unwrap_or_else(|_| panic!("Failed to open sqlite3 DB at {}", path));
-
-    connection
-        .execute(
-            "
-            CREATE TABLE IF NOT EXISTS data (
-                id INTEGER PRIMARY KEY,
-                name TEXT,
-                value TEXT,
-                alternate TEXT
-            );",
-            (),
-        )
-        .expect("Failed to create data TABLE");
+    let kind = args.backend.unwrap_or_else(|| BackendKind::detect(path));
+    if args.table.is_some() && kind != BackendKind::Sqlite {
+        return Err(config_store::Error::Invalid(
+            "--table is only supported by sqlite dbs".to_string(),
+        ));
+    }
+    if !args.layers.is_empty() && (args.table.is_some() || kind != BackendKind::Sqlite) {
+        return Err(config_store::Error::Invalid(
+            "--layer is only supported by the default store of sqlite dbs".to_string(),
+        ));
+    }
+
+    match kind {
+        BackendKind::Sqlite => {
+            if let Some(table) = &args.table {
+                let connection = Connection::open(path)?;
+                set_busy_timeout(&connection, args.timeout)?;
+                args.tuning.apply(&connection)?;
+                return run_backend(&TableStore::from_connection(connection, table)?, args);
+            }
+        }
+        BackendKind::Json => return run_backend(&JsonStore::open(path), args),
+        #[cfg(feature = "redis")]
+        BackendKind::Redis => {
+            let store = config_store::redis_store::RedisStore::open(path)?;
+            return run_backend(&store, args);
+        }
+    }
+
+    // URIs & `:memory:` aren't paths
+    let is_path = !path.starts_with("file:") && path != ":memory:";
+    tracing::debug!(
+        "Opening db at {}",
+        std::path::absolute(path)
+            .ok()
+            .filter(|_| is_path)
+            .map_or(path.to_string(), |p| p.display().to_string())
+    );
+
+    if is_path {
+        permissions::prepare(std::path::Path::new(path), args.file_mode)?;
+    }
+
+    let mut connection = Connection::open_with_flags(path, store::open_flags(path))?;
+    set_busy_timeout(&connection, args.timeout)?;
+
+    if is_path {
+        if let Some(problem) = recovery::check(&connection)? {
+            if args.dry_run {
+                return Err(config_store::Error::Invalid(format!(
+                    "the db is corrupt ({}), run without --dry-run to recover it",
+                    problem
+                )));
+            }
+            if !args.yes {
+                prompt::confirm("The db is corrupt, replace it with its latest backup?")?;
+            }
+
+            drop(connection);
+            let report = recovery::recover(std::path::Path::new(path), problem)?;
+            tracing::warn!("Recovered: {}", report);
+
+            connection = Connection::open_with_flags(path, store::open_flags(path))?;
+            set_busy_timeout(&connection, args.timeout)?;
+        }
+    }
+    args.tuning.apply(&connection)?;
+
+    connection.profile(Some(
+        |sql, duration| tracing::debug!(target: "config_store::sql", ?duration, "{}", sql.trim()),
+    ));
+
+    let store = Store::from_connection(connection)?;
+    let connection = store.connection();
+
+    for layer in &args.layers {
+        store.add_layer(layer)?;
+    }
+
+    if let Some(key) = &args.hmac_key {
+        let key = std::fs::read(key).map_err(|e| {
+            std::io::Error::new(e.kind(), format!("failed to read {}: {}", key.display(), e))
+        })?;
+        signing::init(connection, key)?;
+    }
+
+    // Housekeeping is part of the dry run as well, nothing may be written
+    if args.dry_run {
+        store::begin_dry_run(connection)?;
+    }
+
+    // Scheduled resets show up in the audit log as their own command
+    audit::set_context(connection, "reset-at").expect("Failed to set audit log context");
+    store.apply_resets()?;
+
+    audit::set_context(connection, "session-end").expect("Failed to set audit log context");
+    session::end_stale(connection)?;
+
+    // Only edited files are copied, reads don't write to the db or to bound files
+    audit::set_context(connection, "bind").expect("Failed to set audit log context");
+    sync_bindings(connection, mirror::pull);
+
+    audit::set_context(connection, &command_name).expect("Failed to set audit log context");
+
+    if let Some(question) = args.command.confirmation() {
+        if !args.yes && !args.dry_run {
+            prompt::confirm(question)?;
+        }
+    }
+
+    if !args.dry_run && args.command.is_destructive() {
+        backup::create(connection, args.keep_backups)?;
+
+        // The command shouldn't fail just because e.g. the network is down
+        if let Err(error) = backup::push(connection) {
+            tracing::warn!("Failed to copy backups: {:?}", error);
+        }
+    }
+
+    let last_change = audit::last_id(connection)?;
+
+    let streaming = args.command.is_streaming();
+    let color = args.color.enabled();
 
+    let get_format = args.command.get_format(color);
     let result = match args.command {
-        Action::Set {
-            name,
+        Action::Set(SetArgs {
+            json: Some(json),
+            change_only,
+            create_only,
+            if_value,
+            ..
+        }) => commands::set_json_cmd(
+            connection,
+            json,
+            set_mode(change_only, create_only, if_value),
+        )?,
+        Action::Set(SetArgs {
+            from_file: Some(path),
+            change_only,
+            create_only,
+            if_value,
+            ..
+        }) => commands::set_file_cmd(
+            connection,
+            path,
+            set_mode(change_only, create_only, if_value),
+        )?,
+        Action::Set(SetArgs {
+            name: Some(name),
+            expr: Some(expr),
+            min,
+            max,
+            ..
+        }) => commands::set_expr_cmd(connection, name, expr, min, max)?,
+        Action::Set(SetArgs {
+            name: Some(name),
             value,
             alternate,
+            description,
+            default,
+            paste,
+            prompt,
+            prompt_hidden,
+            keyring,
             change_only,
-        } => commands::set_cmd(&connection, name, value, alternate, change_only)?,
-        Action::Get {
+            create_only,
+            if_value,
+            reset_at,
+            session,
+            ..
+        }) => {
+            let value = if paste {
+                Some(clipboard::paste()?)
+            } else if prompt || prompt_hidden {
+                Some(prompt::value(&name, prompt_hidden)?)
+            } else {
+                value
+            };
+            let mode = set_mode(change_only, create_only, if_value);
+            let result = if keyring {
+                commands::set_secret_cmd(
+                    connection,
+                    name.clone(),
+                    value,
+                    alternate,
+                    description,
+                    default,
+                    mode,
+                )?
+            } else {
+                commands::set_cmd(
+                    connection,
+                    name.clone(),
+                    value,
+                    alternate,
+                    description,
+                    default,
+                    mode,
+                )?
+            };
+
+            let result = match reset_at {
+                Some(at) => commands::reset_at_cmd(connection, name.clone(), at)?,
+                None => result,
+            };
+
+            match session {
+                Some(token) => {
+                    let token = (!token.is_empty()).then_some(token);
+                    commands::session_bind_cmd(connection, name, token)?
+                }
+                None => result,
+            }
+        }
+        Action::Set(SetArgs { name: None, .. }) => {
+            unreachable!("clap requires a name without --json")
+        }
+        Action::Get(GetArgs {
+            name,
+            value_only,
+            alternate_only,
+            glob: true,
+            ..
+        }) => commands::get_glob_cmd(connection, name, value_only, alternate_only, get_format)?,
+        Action::Get(GetArgs {
+            name,
+            alternate_only,
+            copy: true,
+            ..
+        }) => commands::get_copy_cmd(connection, name, alternate_only)?,
+        Action::Get(GetArgs {
+            name,
+            value_only,
+            alternate_only,
+            as_of: Some(as_of),
+            ..
+        }) => commands::get_as_of_cmd(
+            connection,
+            name,
+            as_of,
+            value_only,
+            alternate_only,
+            get_format,
+        )?,
+        Action::Get(GetArgs {
             name,
             value_only,
             alternate_only,
+            resolve,
+            ..
+        }) => commands::get_cmd(
+            connection,
+            name,
+            value_only,
+            alternate_only,
+            get_format,
+            resolve,
+        )?,
+        Action::Toggle(ToggleArgs {
+            names,
+            filter,
+            create,
+            value,
+            alternate,
+            if_value,
+            require_alternate,
+        }) => commands::toggle_cmd(
+            connection,
+            names,
+            filter,
+            create.then_some((value, alternate)),
+            if_value,
+            require_alternate,
+        )?,
+        Action::Enable { names } => commands::switch_cmd(connection, names, true)?,
+        Action::Disable { names } => commands::switch_cmd(connection, names, false)?,
+        Action::Touch { name } => commands::touch_cmd(connection, name)?,
+        Action::Freeze { name } => commands::freeze_cmd(connection, name, true)?,
+        Action::Unfreeze { name } => commands::freeze_cmd(connection, name, false)?,
+        Action::Protect { name } => commands::protect_cmd(connection, name, true)?,
+        Action::Unprotect { name } => commands::protect_cmd(connection, name, false)?,
+        #[cfg(feature = "script")]
+        Action::Compute(ComputeArgs {
+            name,
+            command,
+            ttl,
+            script,
+            ..
+        }) => commands::compute_cmd(connection, name, command, ttl, script)?,
+        #[cfg(not(feature = "script"))]
+        Action::Compute(ComputeArgs {
+            name, command, ttl, ..
+        }) => commands::compute_cmd(connection, name, command, ttl, false)?,
+        Action::Reset { name } => commands::reset_cmd(connection, name)?,
+        Action::ResetAll { filter } => commands::reset_all_cmd(connection, filter)?,
+        Action::Delete(DeleteArgs {
+            name,
+            filter,
+            force,
+        }) => commands::delete_cmd(connection, name, filter, args.strict, force)?,
+        Action::Check(CheckArgs {
+            name,
+            value,
+            matches,
+            ..
+        }) if value.is_some() || matches.is_some() => {
+            commands::check_value_cmd(connection, name, value, matches)?
+        }
+        Action::Check(CheckArgs {
+            name, glob, count, ..
+        }) => commands::exists_cmd(connection, name, glob, count)?,
+        Action::List(ListArgs {
+            case_duplicates: true,
+            ..
+        }) => commands::case_duplicates_cmd(connection)?,
+        Action::List(ListArgs {
+            json_format,
+            long,
+            ndjson,
+            quote,
+            nuon,
+            filter,
+            recent,
+            limit,
+            offset,
+            case_duplicates: false,
+        }) => {
+            let format = match (json_format, long, ndjson, quote, nuon) {
+                (true, _, _, _, _) => ListFormat::Json,
+                (_, true, _, _, _) => ListFormat::Long { color },
+                (_, _, true, _, _) => ListFormat::Json,
+                (_, _, _, Some(quote), _) => ListFormat::Quoted(quote),
+                (_, _, _, _, true) => ListFormat::Nuon,
+                _ => ListFormat::Plain { color },
+            };
+            commands::list_cmd(
+                connection,
+                &mut std::io::stdout().lock(),
+                format,
+                filter,
+                recent,
+                limit,
+                offset,
+            )?;
+            String::new()
+        }
+        Action::Log { name, limit } => commands::log_cmd(connection, name, limit)?,
+        Action::Drop { force } => commands::drop_cmd(connection, force)?,
+        Action::Completions {
+            shell: Some(shell),
+            install: false,
+        } => commands::completions_cmd(shell),
+        Action::Completions { shell, .. } => commands::completions_install_cmd(shell)?,
+        Action::ShellInit(ShellInitArgs {
+            shell,
+            prefix,
+            env: false,
+        }) => commands::shell_init_cmd(&args.db_path, shell, &prefix),
+        Action::ShellInit(ShellInitArgs { shell, prefix, .. }) => {
+            commands::shell_env_cmd(connection, shell, &prefix)?
+        }
+        Action::Alias { alias, target } => commands::alias_cmd(connection, alias, target)?,
+        Action::Unalias { alias } => commands::unalias_cmd(connection, alias, args.strict)?,
+        Action::Preset { action } => match action {
+            PresetAction::Save {
+                preset,
+                names,
+                filter,
+            } => commands::preset_save_cmd(connection, preset, names, filter)?,
+            PresetAction::Apply { preset } => commands::preset_apply_cmd(connection, preset)?,
+            PresetAction::List => commands::preset_list_cmd(connection)?,
+            PresetAction::Delete { preset } => {
+                commands::preset_delete_cmd(connection, preset, args.strict)?
+            }
+        },
+        Action::Tag { action } => match action {
+            TagAction::Add { name, tags } => commands::tag_add_cmd(connection, name, tags)?,
+            TagAction::Remove { name, tags } => {
+                commands::tag_remove_cmd(connection, name, tags, args.strict)?
+            }
+            TagAction::List { name } => commands::tag_list_cmd(connection, name)?,
+        },
+        Action::Meta { action } => match action {
+            MetaAction::Set { name, key, value } => {
+                commands::meta_set_cmd(connection, name, key, value)?
+            }
+            MetaAction::Get { name, key } => commands::meta_get_cmd(connection, name, key)?,
+            MetaAction::Unset { name, keys } => {
+                commands::meta_unset_cmd(connection, name, keys, args.strict)?
+            }
+            MetaAction::List { name } => commands::meta_list_cmd(connection, name)?,
+        },
+        Action::Generate(GenerateArgs {
+            name,
+            kind,
+            length,
+            force,
+        }) => commands::generate_cmd(connection, name, kind, length, force)?,
+        Action::Bench {
+            iterations,
+            invocations,
+        } => commands::bench_cmd(iterations, invocations)?,
+        Action::Dedupe { strategy } => commands::dedupe_cmd(connection, strategy)?,
+        Action::Doctor { fix } => commands::doctor_cmd(connection, fix)?,
+        #[cfg(feature = "archive")]
+        Action::Import(ImportArgs {
+            input: Some(input),
+            archive: true,
+            force,
+            ..
+        }) => commands::import_archive_cmd(connection, input, force)?,
+        Action::Import(ImportArgs {
+            input,
+            format,
+            on_conflict,
+            no_fsync,
+            force,
+            ..
+        }) => commands::import_cmd(connection, input, format, on_conflict, no_fsync, force)?,
+        Action::ImportEnv(ImportEnvArgs {
+            prefix,
+            lowercase,
+            force,
+        }) => commands::import_env_cmd(connection, prefix, lowercase, force)?,
+        Action::Seed { file } => commands::seed_cmd(connection, file)?,
+        Action::Apply { file, prune } => commands::apply_cmd(connection, file, prune)?,
+        Action::Validate { file } => commands::validate_cmd(connection, file)?,
+        Action::CopyFrom(CopyFromArgs {
+            path,
+            pattern,
+            on_conflict,
+            force,
+        }) => commands::copy_from_cmd(connection, path, pattern, on_conflict, force)?,
+        Action::RenamePrefix { old, new } => commands::rename_prefix_cmd(connection, old, new)?,
+        Action::Replace {
+            substitution,
+            pattern,
+        } => commands::replace_cmd(connection, substitution, pattern)?,
+        Action::Tree { depth, filter } => commands::tree_cmd(connection, depth, filter)?,
+        Action::Fts(FtsArgs {
+            query,
             json_format,
-        } => commands::get_cmd(&connection, name, value_only, alternate_only, json_format)?,
-        Action::Toggle { name } => commands::toggle_cmd(&connection, name)?,
-        Action::Delete { name } => commands::delete_cmd(&connection, name)?,
-        Action::Check { name } => commands::exists_cmd(&connection, name)?,
-        Action::List { json_format } => commands::list_cmd(&connection, json_format)?,
-        Action::Drop => commands::drop_cmd(&connection)?,
-        Action::Completions { shell } => commands::completions_cmd(shell),
+            limit,
+        }) => commands::fts_cmd(connection, query, json_format, limit, color)?,
+        Action::Listen { pattern, interval } => {
+            commands::listen_cmd(connection, pattern, args.timeout, interval)?
+        }
+        Action::WaitFor(WaitForArgs {
+            name,
+            value,
+            interval,
+        }) => commands::wait_for_cmd(connection, name, value, args.timeout, interval)?,
+        #[cfg(feature = "script")]
+        Action::Watch(WatchArgs {
+            name,
+            script: Some(script),
+            interval,
+            ..
+        }) => commands::watch_cmd(
+            connection,
+            name,
+            Some(commands::Hook::Script(script)),
+            args.timeout,
+            interval,
+        )?,
+        Action::Watch(WatchArgs {
+            name,
+            exec,
+            interval,
+            ..
+        }) => commands::watch_cmd(
+            connection,
+            name,
+            exec.map(commands::Hook::Shell),
+            args.timeout,
+            interval,
+        )?,
+        Action::Status { name, format } => commands::status_cmd(connection, name, format)?,
+        Action::Query { sql, json_format } => commands::query_cmd(connection, sql, json_format)?,
+        Action::Prompt { name, segment } => {
+            commands::prompt_cmd(connection, name, segment, args.color.allowed())?
+        }
+        #[cfg(feature = "archive")]
+        Action::Export(ExportArgs {
+            archive: Some(archive),
+            ..
+        }) => commands::export_archive_cmd(connection, archive)?,
+        Action::Export(ExportArgs {
+            format,
+            pattern,
+            mut filter,
+            ..
+        }) => {
+            filter.glob = pattern.or(filter.glob);
+            commands::export_cmd(connection, &mut std::io::stdout().lock(), format, filter)?;
+            String::new()
+        }
+        Action::Exec(ExecArgs {
+            pattern,
+            prefix,
+            command,
+        }) => commands::exec_cmd(connection, pattern, prefix, command)?,
+        Action::Bind(BindArgs { list: true, .. }) => commands::bindings_cmd(connection)?,
+        Action::Bind(BindArgs {
+            watch: true,
+            interval,
+            ..
+        }) => commands::bind_watch_cmd(connection, interval, args.timeout)?,
+        Action::Bind(BindArgs {
+            name: Some(name),
+            remove: true,
+            ..
+        }) => commands::unbind_cmd(connection, name)?,
+        Action::Bind(BindArgs {
+            name: Some(name),
+            file: Some(file),
+            direction,
+            ..
+        }) => commands::bind_cmd(connection, name, file, direction)?,
+        Action::Bind(BindArgs { .. }) => unreachable!("clap requires a name & a file"),
+        Action::FishSync(FishSyncArgs {
+            pattern,
+            prefix,
+            back,
+            watch,
+            interval,
+        }) => commands::fish_sync_cmd(
+            connection,
+            pattern,
+            prefix,
+            back,
+            watch,
+            interval,
+            args.timeout,
+        )?,
+        Action::TmuxSync(TmuxSyncArgs {
+            pattern,
+            prefix,
+            options,
+            watch,
+            interval,
+        }) => commands::tmux_sync_cmd(
+            connection,
+            pattern,
+            prefix,
+            options,
+            watch,
+            interval,
+            args.timeout,
+        )?,
+        Action::Menu(MenuArgs {
+            toggle,
+            select: Some(line),
+            ..
+        }) => commands::menu_select_cmd(connection, line, toggle)?,
+        Action::Menu(MenuArgs { toggle, filter, .. }) => {
+            commands::menu_cmd(connection, filter, toggle)?
+        }
+        Action::Record(RecordArgs {
+            action: Some(RecordAction::Stats { name, last }),
+            ..
+        }) => commands::record_stats_cmd(connection, name, last)?,
+        Action::Record(RecordArgs {
+            action: Some(RecordAction::Export { name, last }),
+            ..
+        }) => commands::record_export_cmd(connection, name, last)?,
+        Action::Record(RecordArgs {
+            name: Some(name),
+            value: Some(value),
+            ..
+        }) => commands::record_cmd(connection, name, value)?,
+        Action::Record(RecordArgs { .. }) => unreachable!("clap requires a name & value"),
+        Action::Ratelimit(RatelimitArgs { name, max, per }) => {
+            commands::ratelimit_cmd(connection, name, max, per)?
+        }
+        Action::Timer { action } => match action {
+            TimerAction::Start { name } => commands::timer_start_cmd(connection, name)?,
+            TimerAction::Stop { name, seconds } => {
+                commands::timer_stop_cmd(connection, name, seconds)?
+            }
+            TimerAction::Show { name, seconds } => {
+                commands::timer_show_cmd(connection, name, seconds)?
+            }
+        },
+        Action::Sign { off } => commands::sign_cmd(connection, !off)?,
+        Action::Verify { pattern, checksums } => {
+            commands::verify_cmd(connection, pattern, checksums)?
+        }
+        Action::Session { action } => match action {
+            SessionAction::List => commands::session_list_cmd(connection)?,
+            SessionAction::End { token } => commands::session_end_cmd(connection, token)?,
+        },
+        Action::Blob { action } => match action {
+            BlobAction::Put { file, name } => commands::blob_put_cmd(connection, file, name)?,
+            BlobAction::Get { hash, output } => commands::blob_get_cmd(connection, hash, output)?,
+            BlobAction::Gc => commands::blob_gc_cmd(connection)?,
+        },
+        Action::Backup { action } => match action {
+            BackupAction::Create => commands::backup_create_cmd(connection, args.keep_backups)?,
+            BackupAction::List => commands::backup_list_cmd(connection)?,
+            BackupAction::Push => commands::backup_push_cmd(connection)?,
+            BackupAction::Remote { remote, off } => {
+                commands::backup_remote_cmd(connection, remote, off)?
+            }
+            BackupAction::Restore { backup } => commands::backup_restore_cmd(
+                connection,
+                backup,
+                if args.dry_run { 0 } else { args.keep_backups },
+            )?,
+        },
+        Action::Transform { name, transform } => {
+            commands::transform_cmd(connection, name, transform)?
+        }
+        Action::Patch { name, patch } => commands::patch_cmd(connection, name, patch)?,
+        Action::Naming(NamingArgs {
+            charset,
+            max_length,
+            prefix,
+            off,
+        }) => commands::naming_cmd(
+            connection,
+            config_store::naming::Policy {
+                charset,
+                max_length,
+                prefixes: prefix,
+            },
+            off,
+        )?,
+        Action::Limits(LimitsArgs {
+            value,
+            alternate,
+            off,
+        }) => commands::limits_cmd(connection, value, alternate, off)?,
+        Action::IgnoreCase { on } => commands::ignore_case_cmd(connection, on)?,
+        Action::Cap { max, off } => commands::cap_cmd(connection, max, off)?,
+        Action::Git { repo, off } => commands::git_cmd(connection, repo, off)?,
+        Action::Schema { file, off } => commands::schema_cmd(connection, file, off)?,
+        Action::Stats { entries, track } => commands::stats_cmd(connection, entries, track)?,
+        Action::Tables => commands::tables_cmd(connection)?,
+        Action::Snapshot { action } => match action {
+            SnapshotAction::Create { label } => commands::snapshot_create_cmd(connection, label)?,
+            SnapshotAction::List => commands::snapshot_list_cmd(connection)?,
+            SnapshotAction::Diff {
+                from,
+                to,
+                json_format,
+            } => commands::snapshot_diff_cmd(connection, from, to, json_format, color)?,
+            SnapshotAction::Restore { label } => commands::snapshot_restore_cmd(
+                connection,
+                label,
+                if args.dry_run { 0 } else { args.keep_backups },
+            )?,
+        },
+        Action::Info { json_format } => commands::info_cmd(connection, json_format)?,
+        Action::External(_) => unreachable!("plugins are run before opening the db"),
+    };
+
+    let result = if args.json {
+        let changes = audit::since(connection, last_change)?;
+        if args.dry_run {
+            store::end_dry_run(connection)?;
+        }
+
+        audit::json_report(&result, changes, args.dry_run)
+    } else if args.dry_run {
+        let changes = audit::since(connection, last_change)?;
+        store::end_dry_run(connection)?;
+
+        if changes.is_empty() {
+            "No changes".to_string()
+        } else {
+            changes
+                .iter()
+                .map(|c| format!("would {}", c.summary(color)))
+                .collect::<Vec<String>>()
+                .join("\n")
+        }
+    } else {
+        result
     };
 
-    println!("{}", result);
+    if !args.dry_run {
+        let message = arguments
+            .iter()
+            .skip(1)
+            .map(|a| a.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ");
+        config_store::git::commit(
+            connection,
+            last_change,
+            &format!("config-store {}", message),
+        )?;
+    }
+
+    // Only entries changed by the command can make bound files outdated
+    if !args.dry_run && audit::last_id(connection)? != last_change {
+        audit::set_context(connection, "bind").expect("Failed to set audit log context");
+        sync_bindings(connection, mirror::sync);
+    }
+
+    #[cfg(feature = "notify")]
+    if !args.dry_run {
+        config_store::notify::send(connection, last_change, args.notify)?;
+    }
+
+    if !streaming {
+        print(&result);
+    }
+
+    tracing::debug!("Finished in {:?}", start.elapsed());
 
     Ok(())
 }
 
-/// Struct containing all command line options
-/// For more information, see [clap documentation](https://docs.rs/clap/latest/clap/index.html)
-#[derive(Debug, Parser)]
-#[command(
-    version,
-    about = "config-store is a simple key-value store designed for use from shell scripts",
-    author
-)]
-struct Args {
-    /// What you want to do
-    #[command(subcommand)]
-    command: Action,
-    /// Set an alternate path for the db
-    #[arg(long, default_value = if cfg!(debug_assertions) { "test.db" } else { "/tmp/config-store.db" })]
-    db_path: String,
+/// Syncs the entries bound to files, see [config_store::mirror]
+///
+/// `sync` is [mirror::sync] or [mirror::pull]. The command shouldn't fail just because e.g. the
+/// directory of a file was removed.
+fn sync_bindings(connection: &Connection, sync: fn(&Connection) -> commands::Result<usize>) {
+    if let Err(error) = sync(connection) {
+        tracing::warn!("Failed to sync bound files: {:?}", error);
+    }
 }
 
-/// The different (sub-)commands that are available
-#[derive(Debug, Subcommand)]
-enum Action {
-    /// Set / Change a value & it's alternate
-    Set {
-        /// The name of the Entry
-        name: String,
-        /// The value
-        #[arg(short, long)]
-        value: Option<String>,
-        /// The alternate
-        #[arg(short, long)]
-        alternate: Option<String>,
-        /// Only change entries; don't create new ones
-        #[arg(short, long)]
-        change_only: bool,
-    },
-    /// Get a value & it's alternate
-    Get {
-        /// The name of the entry to get
-        name: String,
-        /// Only get the value
-        #[arg(short, long, conflicts_with = "alternate_only")]
-        value_only: bool,
-        /// Only get the alternate
-        #[arg(short, long, conflicts_with = "value_only")]
-        alternate_only: bool,
-        /// Return the entire entry as a json object
-        #[arg(
-            short,
-            long,
-            conflicts_with = "value_only",
-            conflicts_with = "alternate_only"
-        )]
-        json_format: bool,
-    },
-    /// Toggle an entry between its value & its alternate
-    Toggle {
-        /// The name of the entry to toggle
-        name: String,
-    },
-    /// Delete an entry
-    Delete {
-        /// The name of the entry to delete
-        name: String,
-    },
-    /// Check if an entry exists
-    Check {
-        /// The name of the entry to check
-        name: String,
-    },
-    /// List all entries
-    List {
-        #[arg(short, long)]
-        json_format: bool,
-    },
-    /// Delete all entries <span style="color: red;">!! BE VERY CAREFUL WITH THIS !!</span>
-    #[command(about = "Delete all entries !! BE VERY CAREFUL WITH THIS !!")]
-    Drop,
-    /// Generate shell completions
-    Completions {
-        /// The shell to generate completions for
-        shell: clap_complete::Shell,
-    },
+/// Prints an error about the command line & exits, with [Error::INVALID_INPUT] unless help or the
+/// version were requested
+///
+/// [Error::INVALID_INPUT]: config_store::Error::INVALID_INPUT
+fn exit_usage(error: clap::Error) -> ! {
+    if !error.use_stderr() {
+        error.exit()
+    }
+
+    let _ = error.print();
+    std::process::exit(config_store::Error::INVALID_INPUT)
+}
+
+/// Runs the command against a [Backend] other than the sqlite db & prints its result
+///
+/// See [backend::run] for the supported commands.
+fn run_backend(backend: &dyn Backend, args: Args) -> commands::Result<()> {
+    if args.dry_run || args.json {
+        return Err(config_store::Error::Invalid(
+            "--dry-run & --json are only supported by sqlite dbs".to_string(),
+        ));
+    }
+
+    print(&backend::run(backend, args.command, args.color.enabled())?);
+
+    Ok(())
+}
+
+/// Parses the command line `arguments` into the options & the name of the command, exiting on
+/// invalid ones
+fn parse(arguments: Vec<OsString>) -> (Args, String) {
+    let matches = Args::command()
+        .try_get_matches_from(arguments)
+        .unwrap_or_else(|e| exit_usage(e));
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| exit_usage(e));
+
+    (
+        args,
+        matches.subcommand_name().unwrap_or_default().to_string(),
+    )
+}
+
+/// Chooses the [SetMode] for the options of `set`
+fn set_mode(change_only: bool, create_only: bool, if_value: Option<String>) -> SetMode {
+    match (change_only, create_only, if_value) {
+        (_, _, Some(current)) => SetMode::IfValue(current),
+        (true, _, _) => SetMode::ChangeOnly,
+        (_, true, _) => SetMode::CreateOnly,
+        _ => SetMode::Upsert,
+    }
+}
+
+/// Waits up to `timeout` seconds for a db locked by another process, keeping sqlite's default
+/// otherwise
+fn set_busy_timeout(connection: &Connection, timeout: Option<i64>) -> rusqlite::Result<()> {
+    match timeout {
+        Some(timeout) => connection.busy_timeout(std::time::Duration::from_secs(timeout as u64)),
+        None => Ok(()),
+    }
+}
+
+/// Prints the result of a command
+fn print(result: &str) {
+    // Output piped into e.g. `head` may be closed early, which is not an error
+    if let Err(error) = writeln!(std::io::stdout(), "{}", result) {
+        if error.kind() != std::io::ErrorKind::BrokenPipe {
+            panic!("Failed to print result: {}", error);
+        }
+    }
+}
+
+/// Sets up logging to stderr
+///
+/// If `RUST_LOG` is set it takes precedence, otherwise the level is chosen by the number of
+/// `--verbose` flags. Only the first call sets it up.
+fn init_logging(verbose: u8) {
+    let _ = tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(log_level(verbose))),
+        )
+        .try_init();
+}
+
+/// The level logged with `verbose` `--verbose` flags
+fn log_level(verbose: u8) -> &'static str {
+    match verbose {
+        0 => "warn",
+        1 => "debug",
+        _ => "trace",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Runs config-store with `arguments` on the db at `db`
+    fn config_store(db: &std::path::Path, arguments: &[&str]) -> commands::Result<()> {
+        let db = db.to_str().unwrap();
+        let arguments: Vec<OsString> = ["config-store", "--db-path", db]
+            .iter()
+            .chain(arguments)
+            .map(OsString::from)
+            .collect();
+        let (args, command_name) = parse(arguments.clone());
+        run(args, command_name, arguments)
+    }
+
+    #[test]
+    fn dry_run() {
+        let dir = std::env::temp_dir().join(format!("config-store-dry-run-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("test.db");
+        let theme = dir.join("theme");
+        let font = dir.join("font");
+        let config_store = |arguments: &[&str]| config_store(&db, arguments);
+
+        config_store(&["set", "theme", "-v", "dark", "-a", "light"]).unwrap();
+        config_store(&["set", "font", "-v", "mono", "-D", "serif"]).unwrap();
+        config_store(&["bind", "theme", "-f", theme.to_str().unwrap()]).unwrap();
+        std::fs::write(&font, "mono\n").unwrap();
+        config_store(&[
+            "bind",
+            "font",
+            "-f",
+            font.to_str().unwrap(),
+            "-d",
+            "from-file",
+        ])
+        .unwrap();
+
+        // A due reset & an edited bound file would both change the db before the command
+        Connection::open(&db)
+            .unwrap()
+            .execute("UPDATE data SET reset_at = 1, reset_value = 'serif'", ())
+            .unwrap();
+        std::fs::write(&font, "sans\n").unwrap();
+        let before = std::fs::read(&db).unwrap();
+
+        config_store(&["--dry-run", "toggle", "theme"]).unwrap();
+        config_store(&["--dry-run", "delete", "font"]).unwrap();
+        assert!(std::fs::read(&db).unwrap() == before);
+        assert_eq!(std::fs::read_to_string(&theme).unwrap(), "dark\n");
+        assert_eq!(std::fs::read_to_string(&font).unwrap(), "sans\n");
+
+        config_store(&["toggle", "theme"]).unwrap();
+        assert!(std::fs::read(&db).unwrap() != before);
+        assert_eq!(std::fs::read_to_string(&theme).unwrap(), "light\n");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// A log shared with the test reading it
+    #[derive(Clone, Default)]
+    struct Log(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl Write for Log {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn verbose() {
+        let dir = std::env::temp_dir().join(format!("config-store-verbose-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("test.db");
+
+        let log = Log::default();
+        let writer = log.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_env_filter(log_level(1))
+            .with_ansi(false)
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            config_store(&db, &["set", "theme", "-v", "dark", "--verbose"]).unwrap()
+        });
+
+        let log = String::from_utf8(log.0.lock().unwrap().clone()).unwrap();
+        assert!(log.contains(&format!("Opening db at {}", db.display())));
+        assert!(log.contains("config_store::sql"));
+        assert!(log.contains("INSERT INTO data"));
+
+        let (args, _) = parse(
+            ["config-store", "get", "theme", "--verbose", "--verbose"]
+                .map(OsString::from)
+                .to_vec(),
+        );
+        assert_eq!(log_level(args.verbose), "trace");
+        assert_eq!(log_level(0), "warn");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn timeout() {
+        let dir = std::env::temp_dir().join(format!("config-store-timeout-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("test.db");
+        config_store(&db, &["set", "theme", "-v", "dark"]).unwrap();
+
+        // Waiting for the value gives up once the timeout is reached
+        let error = config_store(&db, &["wait-for", "theme", "light", "--timeout", "0"]);
+        assert!(matches!(error, Err(commands::Error::Timeout)));
+        config_store(&db, &["wait-for", "theme", "dark", "--timeout", "0"]).unwrap();
+
+        // As does waiting for a db locked by another process, instead of sqlite's default
+        let other = Connection::open(&db).unwrap();
+        other.execute_batch("BEGIN EXCLUSIVE").unwrap();
+        let start = std::time::Instant::now();
+        let error = config_store(&db, &["--timeout", "0", "set", "theme", "-v", "light"]);
+        assert_eq!(error.unwrap_err().exit_code(), 4);
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+        other.execute_batch("ROLLBACK").unwrap();
+        config_store(&db, &["--timeout", "1", "set", "theme", "-v", "light"]).unwrap();
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
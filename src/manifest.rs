@@ -0,0 +1,425 @@
+//! Module reading manifests, which declare entries in a file (e.g. for `seed`)
+//!
+//! A manifest is a json file in the format of `set --json` (an object or an array of objects) or,
+//! if its name ends in `.toml`, a toml file with a table per entry named after it:
+//!
+//! ```toml
+//! [theme]
+//! value = "light"
+//! alternate = "dark"
+//! type = "string"
+//! values = ["light", "dark"]
+//! required = true
+//! ```
+//!
+//! Besides the fields of an entry, a declaration can constrain the values of the entry with a
+//! `type`, a `pattern` (a regex the whole value has to match) & a list of allowed `values`, see
+//! [Declaration::check_value]. `validate` reports entries which break these constraints & missing
+//! `required` ones.
+//!
+//! A manifest can also be made the schema of the db with `schema FILE`, so `set` & `toggle` refuse
+//! values breaking the declarations & `list --long` shows them.
+use std::path::Path;
+
+use regex::Regex;
+use rusqlite::Connection;
+use serde_json::Value;
+
+use crate::{
+    settings,
+    store::{resolve, select},
+    Error, Result,
+};
+
+/// The type of the values of an entry declared in a manifest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    /// Any text
+    String,
+    /// `true` or `false`
+    Bool,
+    /// A whole number
+    Int,
+    /// Any number
+    Float,
+    /// A json document
+    Json,
+}
+
+impl ValueType {
+    /// Parses the name of a type, e.g. `bool`
+    pub fn parse(name: &str) -> Result<ValueType> {
+        match name {
+            "string" => Ok(ValueType::String),
+            "bool" => Ok(ValueType::Bool),
+            "int" => Ok(ValueType::Int),
+            "float" => Ok(ValueType::Float),
+            "json" => Ok(ValueType::Json),
+            _ => Err(Error::Invalid(format!(
+                "unknown type `{}`, expected string, bool, int, float or json",
+                name
+            ))),
+        }
+    }
+
+    /// The name of the type, as accepted by [ValueType::parse]
+    pub fn name(self) -> &'static str {
+        match self {
+            ValueType::String => "string",
+            ValueType::Bool => "bool",
+            ValueType::Int => "int",
+            ValueType::Float => "float",
+            ValueType::Json => "json",
+        }
+    }
+
+    /// Returns `value` as a json literal of this type (e.g. `true` instead of `"true"`), `None` if
+    /// it isn't of this type
+    pub fn json(self, value: &str) -> Option<String> {
+        match self {
+            ValueType::String => Some(format!(r#""{}""#, crate::entry::json_escape(value))),
+            ValueType::Bool => matches!(value, "true" | "false").then(|| value.to_string()),
+            ValueType::Int => value.trim().parse::<i64>().ok().map(|i| i.to_string()),
+            ValueType::Float => value
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .and_then(serde_json::Number::from_f64)
+                .map(|n| n.to_string()),
+            ValueType::Json => serde_json::from_str::<Value>(value)
+                .ok()
+                .map(|v| v.to_string()),
+        }
+    }
+
+    /// Returns [Error::InvalidType] if `value` of the entry `name` isn't of this type
+    pub fn check(self, name: &str, value: &str) -> Result<()> {
+        let (valid, expected) = match self {
+            ValueType::String => (true, "a string"),
+            ValueType::Bool => (matches!(value, "true" | "false"), "a boolean"),
+            ValueType::Int => (value.trim().parse::<i64>().is_ok(), "an integer"),
+            ValueType::Float => (value.trim().parse::<f64>().is_ok(), "a number"),
+            ValueType::Json => (serde_json::from_str::<Value>(value).is_ok(), "json"),
+        };
+
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::InvalidType {
+                name: name.to_string(),
+                expected,
+            })
+        }
+    }
+}
+
+/// An entry declared in a manifest
+///
+/// Fields which aren't given are `None`, like for `set --json`.
+#[derive(Debug, Clone)]
+pub struct Declaration {
+    pub name: String,
+    pub value: Option<String>,
+    pub alternate: Option<String>,
+    pub description: Option<String>,
+    pub default: Option<String>,
+    /// The type of the value, alternate & default
+    pub kind: Option<ValueType>,
+    /// A regex the whole value, alternate & default have to match
+    pub pattern: Option<Regex>,
+    /// The values allowed for the value, alternate & default, any if empty
+    pub values: Vec<String>,
+    /// Whether the entry has to exist
+    pub required: bool,
+}
+
+impl Declaration {
+    /// Returns an error if the value, alternate or default break the constraints, see
+    /// [Declaration::check_value]
+    pub fn check(&self) -> Result<()> {
+        [&self.value, &self.alternate, &self.default]
+            .into_iter()
+            .flatten()
+            .try_for_each(|value| self.check_value(value))
+    }
+
+    /// Returns [Error::InvalidType] if `value` doesn't have the declared type & [Error::Invalid] if
+    /// it doesn't match the pattern or isn't one of the allowed values
+    pub fn check_value(&self, value: &str) -> Result<()> {
+        if let Some(kind) = self.kind {
+            kind.check(&self.name, value)?;
+        }
+
+        if let Some(pattern) = &self.pattern {
+            if !pattern.is_match(value) {
+                return Err(Error::Invalid(format!(
+                    "'{}' of '{}' doesn't match `{}`",
+                    value,
+                    self.name,
+                    pattern_source(pattern)
+                )));
+            }
+        }
+
+        if !self.values.is_empty() && !self.values.iter().any(|v| v == value) {
+            return Err(Error::Invalid(format!(
+                "'{}' of '{}' isn't one of {}",
+                value,
+                self.name,
+                self.values.join(", ")
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// A short description of the constraints, e.g. `int, one of 0, 5, 10, required`
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+
+        if let Some(kind) = self.kind {
+            parts.push(kind.name().to_string());
+        }
+        if let Some(pattern) = &self.pattern {
+            parts.push(format!("matching `{}`", pattern_source(pattern)));
+        }
+        if !self.values.is_empty() {
+            parts.push(format!("one of {}", self.values.join(", ")));
+        }
+        if self.required {
+            parts.push("required".to_string());
+        }
+
+        parts.join(", ")
+    }
+
+    /// The values the entry can take, offered by shell completion, empty if it can take any
+    pub fn allowed(&self) -> Vec<String> {
+        match (self.values.as_slice(), self.kind) {
+            ([], Some(ValueType::Bool)) => vec!["true".to_string(), "false".to_string()],
+            (values, _) => values.to_vec(),
+        }
+    }
+
+    /// Parses a json object, returning [Error::Invalid] for missing names & unknown fields
+    fn parse(declaration: Value) -> Result<Declaration> {
+        let Value::Object(mut fields) = declaration else {
+            return Err(Error::Invalid(format!(
+                "expected an object, got {}",
+                declaration
+            )));
+        };
+
+        let values = match fields.remove("values") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Array(values)) => values
+                .into_iter()
+                .map(|value| match value {
+                    Value::String(s) => Ok(s),
+                    value @ (Value::Number(_) | Value::Bool(_)) => Ok(value.to_string()),
+                    value => Err(Error::Invalid(format!(
+                        "`values` has to contain strings, got {}",
+                        value
+                    ))),
+                })
+                .collect::<Result<Vec<String>>>()?,
+            Some(value) => {
+                return Err(Error::Invalid(format!(
+                    "`values` has to be an array, got {}",
+                    value
+                )))
+            }
+        };
+        let required = match fields.remove("required") {
+            None | Some(Value::Null) => false,
+            Some(Value::Bool(required)) => required,
+            Some(value) => {
+                return Err(Error::Invalid(format!(
+                    "`required` has to be a boolean, got {}",
+                    value
+                )))
+            }
+        };
+
+        let mut field = |field: &str| match fields.remove(field) {
+            None | Some(Value::Null) => Ok(None),
+            Some(Value::String(s)) => Ok(Some(s)),
+            Some(value @ (Value::Number(_) | Value::Bool(_))) => Ok(Some(value.to_string())),
+            Some(value) => Err(Error::Invalid(format!(
+                "`{}` has to be a string, got {}",
+                field, value
+            ))),
+        };
+
+        let declaration = Declaration {
+            name: field("name")?
+                .ok_or_else(|| Error::Invalid("an entry is missing its `name`".to_string()))?,
+            value: field("value")?,
+            alternate: field("alternate")?,
+            description: field("description")?,
+            default: field("default")?,
+            kind: field("type")?
+                .as_deref()
+                .map(ValueType::parse)
+                .transpose()?,
+            pattern: field("pattern")?
+                .map(|p| Regex::new(&format!("^(?:{})$", p)))
+                .transpose()
+                .map_err(|e| Error::Invalid(format!("invalid pattern: {}", e)))?,
+            values,
+            required,
+        };
+
+        if let Some(unknown) = fields.keys().next() {
+            return Err(Error::Invalid(format!(
+                "unknown field `{}` of '{}'",
+                unknown, declaration.name
+            )));
+        }
+
+        declaration.check()?;
+
+        Ok(declaration)
+    }
+}
+
+/// Helper function returning a pattern without the anchors added by [Declaration::parse]
+fn pattern_source(pattern: &Regex) -> &str {
+    pattern
+        .as_str()
+        .strip_prefix("^(?:")
+        .and_then(|p| p.strip_suffix(")$"))
+        .unwrap_or_default()
+}
+
+/// Returns the entries declared in the schema of the db (see [settings::SCHEMA]), none if it has
+/// no schema
+pub fn declared(connection: &Connection) -> Result<Vec<Declaration>> {
+    match settings::get(connection, settings::SCHEMA)? {
+        Some(path) => read(Path::new(&path)),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Returns an error if the entry `name` breaks its declaration in `declarations`, see
+/// [Declaration::check_value]
+///
+/// The alternate is only checked if it isn't empty, since it's optional.
+pub fn check(connection: &Connection, declarations: &[Declaration], name: &str) -> Result<()> {
+    let name = resolve(connection, name)?;
+    let Some(declaration) = declarations.iter().find(|d| d.name == name) else {
+        return Ok(());
+    };
+
+    let entry = select(connection, &name)?;
+    let alternate = entry.alternate.as_ref().filter(|a| !a.is_empty());
+
+    for value in [Some(&entry.value), alternate, entry.default.as_ref()]
+        .into_iter()
+        .flatten()
+    {
+        declaration.check_value(value)?;
+    }
+
+    Ok(())
+}
+
+/// Reads the entries declared in the manifest at `path`
+///
+/// Will return [Error::Invalid] for manifests which can't be parsed & [Error::InvalidType] for
+/// values which don't have their declared type.
+pub fn read(path: &Path) -> Result<Vec<Declaration>> {
+    let text = std::fs::read_to_string(path)?;
+
+    let declarations = if path.extension().is_some_and(|e| e == "toml") {
+        let table = text
+            .parse::<toml::Table>()
+            .map_err(|e| Error::Invalid(format!("invalid toml: {}", e)))?;
+
+        table
+            .into_iter()
+            .map(|(name, fields)| {
+                let mut fields = serde_json::to_value(fields)
+                    .map_err(|e| Error::Invalid(format!("invalid toml: {}", e)))?;
+                if let Value::Object(fields) = &mut fields {
+                    fields.insert("name".to_string(), Value::String(name));
+                }
+                Ok(fields)
+            })
+            .collect::<Result<Vec<Value>>>()?
+    } else {
+        match serde_json::from_str(&text) {
+            Ok(Value::Array(declarations)) => declarations,
+            Ok(declaration @ Value::Object(_)) => vec![declaration],
+            Ok(_) => {
+                return Err(Error::Invalid(
+                    "expected a json object or an array of objects".to_string(),
+                ))
+            }
+            Err(error) => return Err(Error::Invalid(format!("invalid json: {}", error))),
+        }
+    };
+
+    declarations.into_iter().map(Declaration::parse).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn read_manifests() {
+        let dir =
+            std::env::temp_dir().join(format!("config-store-manifest-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let toml = dir.join("entries.toml");
+        std::fs::write(
+            &toml,
+            "[theme]\nvalue = \"light\"\nalternate = \"dark\"\n\n[gaps]\ndefault = 10\ntype = \"int\"\n",
+        )
+        .unwrap();
+        let declarations = read(&toml).unwrap();
+        assert_eq!(declarations.len(), 2);
+        assert_eq!(declarations[0].name, "gaps");
+        assert_eq!(declarations[0].default.as_deref(), Some("10"));
+        assert_eq!(declarations[0].kind, Some(ValueType::Int));
+
+        let json = dir.join("entries.json");
+        std::fs::write(
+            &json,
+            r#"[{"name": "mute", "value": "maybe", "type": "bool"}]"#,
+        )
+        .unwrap();
+        assert!(matches!(read(&json), Err(Error::InvalidType { .. })));
+        std::fs::write(&json, r#"{"name": "mute", "colour": "red"}"#).unwrap();
+        assert!(matches!(read(&json), Err(Error::Invalid(_))));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn json_literals() {
+        let json = |kind: &str, value: &str| ValueType::parse(kind).unwrap().json(value);
+
+        assert_eq!(json("bool", "true").as_deref(), Some("true"));
+        assert_eq!(json("int", " 30 ").as_deref(), Some("30"));
+        assert_eq!(json("float", "1.5").as_deref(), Some("1.5"));
+        assert_eq!(
+            json("json", r#"{ "a": [1] }"#).as_deref(),
+            Some(r#"{"a":[1]}"#)
+        );
+        assert_eq!(
+            json("string", "say \"hi\"").as_deref(),
+            Some(r#""say \"hi\"""#)
+        );
+        for (kind, value) in [
+            ("bool", "yes"),
+            ("int", "1.5"),
+            ("float", "NaN"),
+            ("json", "{"),
+        ] {
+            assert_eq!(json(kind, value), None);
+        }
+    }
+}
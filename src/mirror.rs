@@ -0,0 +1,371 @@
+//! Module mirroring entries into files, so programs which only read files follow them, see `bind`
+//!
+//! A binding keeps the value of an entry & the contents of a file in sync, in one direction or
+//! both. The command line picks up edited files whenever it opens the db & updates the files after
+//! each command which changed entries, so files follow toggles at once & edited files are picked up
+//! by the next command (or at once with `bind --watch`). Files are replaced atomically, so readers
+//! never see half a value.
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::{
+    store::{dry_run, resolve, select},
+    time, Error, Result,
+};
+
+/// Which way changes are copied between an entry & its file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Direction {
+    /// Write the value into the file whenever it changes
+    ToFile,
+    /// Set the value to the contents of the file whenever it changes
+    FromFile,
+    /// Copy whichever changed last
+    Both,
+}
+
+impl Direction {
+    /// The name of the direction, as stored in the db
+    pub fn name(self) -> &'static str {
+        match self {
+            Direction::ToFile => "to-file",
+            Direction::FromFile => "from-file",
+            Direction::Both => "both",
+        }
+    }
+}
+
+/// An entry bound to a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binding {
+    pub name: String,
+    pub path: PathBuf,
+    pub direction: Direction,
+}
+
+/// Binds the entry `name` to the file at `path` & syncs them once, replacing an earlier binding
+///
+/// The entry wins the first sync unless the direction is [Direction::FromFile].
+pub fn bind(connection: &Connection, name: &str, path: &Path, direction: Direction) -> Result<()> {
+    let entry = select(connection, &resolve(connection, name)?)?;
+
+    connection.execute(
+        "INSERT OR REPLACE INTO bindings (entry_id, path, direction) VALUES (?, ?, ?)",
+        (
+            entry._id,
+            std::path::absolute(path)?.to_string_lossy(),
+            direction.name(),
+        ),
+    )?;
+
+    sync_entry(connection, entry._id, true)?;
+
+    Ok(())
+}
+
+/// Removes the binding of the entry `name`, keeping the file, & returns whether it had one
+pub fn unbind(connection: &Connection, name: &str) -> Result<bool> {
+    let entry = select(connection, &resolve(connection, name)?)?;
+
+    Ok(connection.execute("DELETE FROM bindings WHERE entry_id = ?", [entry._id])? > 0)
+}
+
+/// Returns all bindings ordered by the names of their entries
+pub fn list(connection: &Connection) -> Result<Vec<Binding>> {
+    connection
+        .prepare(
+            "SELECT data.name, bindings.path, bindings.direction FROM bindings
+                JOIN data ON data.id = bindings.entry_id ORDER BY data.name",
+        )?
+        .query_map((), |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .map(|row| {
+            let (name, path, direction) = row?;
+            Ok(Binding {
+                name,
+                path: path.into(),
+                direction: Direction::from_str(&direction, false).map_err(Error::Invalid)?,
+            })
+        })
+        .collect()
+}
+
+/// Syncs all bindings & returns the number of changed files & entries
+///
+/// Nothing is synced with a read-only db, since the time of the sync can't be stored. Files are
+/// only touched if others can't write the db, see [crate::permissions::trusted].
+pub fn sync(connection: &Connection) -> Result<usize> {
+    sync_all(connection, true)
+}
+
+/// Copies the files edited since the last sync into their entries, without writing any file, &
+/// returns the number of changed entries
+///
+/// Nothing is written to the db unless a file was changed, so reads stay reads.
+pub fn pull(connection: &Connection) -> Result<usize> {
+    sync_all(connection, false)
+}
+
+/// Helper function syncing all bindings, only into the entries unless `export` is set
+fn sync_all(connection: &Connection, export: bool) -> Result<usize> {
+    if connection.is_readonly(rusqlite::DatabaseName::Main)? {
+        return Ok(0);
+    }
+
+    let ids = connection
+        .prepare("SELECT entry_id FROM bindings")?
+        .query_map((), |row| row.get::<_, i32>(0))?
+        .collect::<rusqlite::Result<Vec<i32>>>()?;
+    if ids.is_empty() {
+        return Ok(0);
+    }
+
+    // Anyone who can write the db could bind any file of the current user to an entry
+    crate::permissions::trusted(connection, "sync bound files")?;
+
+    let mut synced = 0;
+    for id in ids {
+        synced += usize::from(sync_entry(connection, id, export)?);
+    }
+
+    Ok(synced)
+}
+
+/// Helper function syncing the binding of the entry with the id `id` & returning whether the file
+/// or the entry was changed
+///
+/// With [Direction::Both] the file wins if it was modified after the last sync & after the entry
+/// was changed, otherwise the entry wins. Files are only written with `export` & the time of the
+/// sync is only stored when something was copied. Nothing is synced during a dry run, see
+/// [crate::store::dry_run].
+fn sync_entry(connection: &Connection, id: i32, export: bool) -> Result<bool> {
+    if dry_run(connection)? {
+        return Ok(false);
+    }
+
+    let Some((value, updated_at, path, direction, synced_at)) = connection
+        .query_row(
+            "SELECT data.value, data.updated_at, bindings.path, bindings.direction,
+                bindings.synced_at
+            FROM bindings JOIN data ON data.id = bindings.entry_id WHERE entry_id = ?",
+            [id],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<i64>>(1)?,
+                    PathBuf::from(row.get::<_, String>(2)?),
+                    row.get::<_, String>(3)?,
+                    row.get::<_, Option<i64>>(4)?,
+                ))
+            },
+        )
+        .optional()?
+    else {
+        return Ok(false);
+    };
+    let direction = Direction::from_str(&direction, false).map_err(Error::Invalid)?;
+
+    let contents = read(&path)?;
+    if contents.as_ref() == Some(&value) && synced_at.is_some() {
+        return Ok(false);
+    }
+
+    let import = match (direction, synced_at) {
+        (Direction::ToFile, _) | (Direction::Both, None) => false,
+        (Direction::FromFile, _) => contents.is_some(),
+        (Direction::Both, Some(synced_at)) => modified(&path).is_some_and(|modified| {
+            modified > synced_at && modified > updated_at.unwrap_or_default()
+        }),
+    };
+
+    let changed = contents.as_ref() != Some(&value);
+    match contents {
+        Some(contents) if changed && import => {
+            connection.execute(
+                "UPDATE data SET value = ?, updated_at = ? WHERE id = ?",
+                (contents, time::now(), id),
+            )?;
+        }
+        _ if changed && export && direction != Direction::FromFile => write(&path, &value)?,
+        _ if synced_at.is_some() || !export => return Ok(false),
+        _ => {}
+    }
+
+    connection.execute(
+        "UPDATE bindings SET synced_at = ? WHERE entry_id = ?",
+        (time::now(), id),
+    )?;
+
+    Ok(changed)
+}
+
+/// Helper function returning when the file at `path` was last modified, `None` if it doesn't exist
+fn modified(path: &Path) -> Option<i64> {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|m| m.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|m| m.as_secs() as i64)
+}
+
+/// Helper function returning the value stored in the file at `path`, `None` if it doesn't exist
+///
+/// A single trailing newline is removed, since [write] adds one for programs expecting lines.
+fn read(path: &Path) -> Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(mut contents) => {
+            if contents.ends_with('\n') {
+                contents.pop();
+            }
+            Ok(Some(contents))
+        }
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(std::io::Error::new(
+            error.kind(),
+            format!("failed to read {}: {}", path.display(), error),
+        )
+        .into()),
+    }
+}
+
+/// Helper function replacing the file at `path` with `value` atomically
+fn write(path: &Path, value: &str) -> Result<()> {
+    let mut temporary = path.to_path_buf().into_os_string();
+    temporary.push(".tmp");
+
+    std::fs::write(&temporary, format!("{}\n", value)).map_err(|error| {
+        std::io::Error::new(
+            error.kind(),
+            format!("failed to write {}: {}", path.display(), error),
+        )
+    })?;
+    std::fs::rename(&temporary, path)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bindings() {
+        let store = crate::Store::open_in_memory().unwrap();
+        let connection = store.connection();
+        store.set("theme", Some("light"), Some("dark")).unwrap();
+        store.set("font", Some("mono"), None).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("config-store-mirror-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let theme = dir.join("theme");
+        let font = dir.join("font");
+        std::fs::write(&font, "serif\n").unwrap();
+
+        bind(connection, "theme", &theme, Direction::ToFile).unwrap();
+        assert_eq!(std::fs::read_to_string(&theme).unwrap(), "light\n");
+        store.toggle("theme").unwrap();
+        assert_eq!(sync(connection).unwrap(), 1);
+        assert_eq!(std::fs::read_to_string(&theme).unwrap(), "dark\n");
+
+        bind(connection, "font", &font, Direction::FromFile).unwrap();
+        assert_eq!(store.get("font").unwrap().value, "serif");
+        std::fs::write(&font, "sans").unwrap();
+        assert_eq!(sync(connection).unwrap(), 1);
+        assert_eq!(store.get("font").unwrap().value, "sans");
+        assert_eq!(sync(connection).unwrap(), 0);
+
+        assert_eq!(
+            list(connection).unwrap(),
+            vec![
+                Binding {
+                    name: "font".to_string(),
+                    path: font,
+                    direction: Direction::FromFile
+                },
+                Binding {
+                    name: "theme".to_string(),
+                    path: theme,
+                    direction: Direction::ToFile
+                },
+            ]
+        );
+
+        // Syncing without changes writes nothing
+        let synced_at = || -> i64 {
+            connection
+                .query_row("SELECT MAX(synced_at) FROM bindings", (), |row| row.get(0))
+                .unwrap()
+        };
+        connection
+            .execute("UPDATE bindings SET synced_at = synced_at - 10", ())
+            .unwrap();
+        let before = synced_at();
+        assert_eq!(sync(connection).unwrap(), 0);
+        assert_eq!(synced_at(), before);
+
+        // Both ways an edited file only wins over an entry changed before the edit
+        store.set("mode", Some("a"), None).unwrap();
+        let mode = dir.join("mode");
+        bind(connection, "mode", &mode, Direction::Both).unwrap();
+        assert_eq!(read(&mode).unwrap().as_deref(), Some("a"));
+        connection
+            .execute("UPDATE bindings SET synced_at = synced_at - 10", ())
+            .unwrap();
+        connection
+            .execute("UPDATE data SET updated_at = updated_at - 10", ())
+            .unwrap();
+        std::fs::write(&mode, "b").unwrap();
+        assert_eq!(pull(connection).unwrap(), 1);
+        assert_eq!(store.get("mode").unwrap().value, "b");
+        assert_eq!(pull(connection).unwrap(), 0);
+
+        store.set("mode", Some("c"), None).unwrap();
+        assert_eq!(pull(connection).unwrap(), 0);
+        assert_eq!(read(&mode).unwrap().as_deref(), Some("b"));
+        assert_eq!(sync(connection).unwrap(), 1);
+        assert_eq!(read(&mode).unwrap().as_deref(), Some("c"));
+
+        assert!(unbind(connection, "theme").unwrap());
+        assert!(!unbind(connection, "theme").unwrap());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn untrusted() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!(
+            "config-store-mirror-untrusted-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("test.db");
+        let store = crate::Store::open(&db).unwrap();
+        let connection = store.connection();
+        store.set("theme", Some("light"), None).unwrap();
+        let theme = dir.join("theme");
+        bind(connection, "theme", &theme, Direction::Both).unwrap();
+
+        // A db others may write can't be trusted with the paths of the bindings
+        std::fs::set_permissions(&db, std::fs::Permissions::from_mode(0o666)).unwrap();
+        store.set("theme", Some("dark"), None).unwrap();
+        std::fs::write(&theme, "edited").unwrap();
+        assert!(matches!(sync(connection), Err(Error::Invalid(_))));
+        assert!(matches!(pull(connection), Err(Error::Invalid(_))));
+        assert_eq!(read(&theme).unwrap().as_deref(), Some("edited"));
+        assert_eq!(store.get("theme").unwrap().value, "dark");
+
+        std::fs::set_permissions(&db, std::fs::Permissions::from_mode(0o600)).unwrap();
+        assert_eq!(sync(connection).unwrap(), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
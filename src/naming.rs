@@ -0,0 +1,183 @@
+//! Module enforcing a naming policy on new entries, so a shared db stays consistent, see `naming`
+//!
+//! The policy consists of the characters allowed in names, their maximum length & the prefixes
+//! (namespaces) one of which every name has to start with. Each rule is a setting of the db &
+//! optional. Existing entries are never refused, so entries created before a rule can still be
+//! changed.
+use regex::Regex;
+use rusqlite::Connection;
+
+use crate::{settings, store::exists, Error, Result};
+
+/// The rules new names have to follow
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Policy {
+    /// The allowed characters, like the contents of a regex character class, e.g. `a-z0-9._-`
+    pub charset: Option<String>,
+    /// The maximum length in characters
+    pub max_length: Option<usize>,
+    /// The prefixes a name has to start with one of, any if empty
+    pub prefixes: Vec<String>,
+}
+
+impl Policy {
+    /// Returns the policy of the db
+    pub fn get(connection: &Connection) -> Result<Policy> {
+        Ok(Policy {
+            charset: settings::get(connection, settings::NAME_CHARSET)?,
+            max_length: settings::get(connection, settings::NAME_MAX_LENGTH)?
+                .and_then(|max| max.parse().ok()),
+            prefixes: settings::get(connection, settings::NAME_PREFIXES)?
+                .map(|p| p.lines().map(str::to_string).collect())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Stores the rules given in `self`, leaving the others unchanged
+    ///
+    /// Will return [Error::Invalid] if the charset isn't a valid character class.
+    pub fn update(&self, connection: &Connection) -> Result<()> {
+        if let Some(charset) = &self.charset {
+            charset_regex(charset)?;
+            settings::set(connection, settings::NAME_CHARSET, Some(charset))?;
+        }
+        if let Some(max) = self.max_length {
+            settings::set(
+                connection,
+                settings::NAME_MAX_LENGTH,
+                Some(&max.to_string()),
+            )?;
+        }
+        if !self.prefixes.is_empty() {
+            settings::set(
+                connection,
+                settings::NAME_PREFIXES,
+                Some(&self.prefixes.join("\n")),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes all rules of the db
+    pub fn clear(connection: &Connection) -> Result<()> {
+        for key in [
+            settings::NAME_CHARSET,
+            settings::NAME_MAX_LENGTH,
+            settings::NAME_PREFIXES,
+        ] {
+            settings::set(connection, key, None)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns [Error::Invalid] explaining the first rule `name` breaks
+    pub fn check(&self, name: &str) -> Result<()> {
+        let invalid = |reason: String| {
+            Err(Error::Invalid(format!(
+                "invalid name '{}': {}",
+                name, reason
+            )))
+        };
+
+        if let Some(charset) = &self.charset {
+            if !charset_regex(charset)?.is_match(name) {
+                return invalid(format!("only the characters [{}] are allowed", charset));
+            }
+        }
+        if let Some(max) = self.max_length {
+            if name.chars().count() > max {
+                return invalid(format!("names can be at most {} characters long", max));
+            }
+        }
+        if !self.prefixes.is_empty() && !self.prefixes.iter().any(|p| name.starts_with(p)) {
+            return invalid(format!(
+                "names have to start with one of {}",
+                self.prefixes.join(", ")
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Policy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let none = || "any".to_string();
+        writeln!(
+            f,
+            "charset: {}",
+            self.charset
+                .as_ref()
+                .map_or_else(none, |c| format!("[{}]", c))
+        )?;
+        writeln!(
+            f,
+            "max length: {}",
+            self.max_length.map_or_else(none, |m| m.to_string())
+        )?;
+        write!(
+            f,
+            "prefixes: {}",
+            match self.prefixes.as_slice() {
+                [] => none(),
+                prefixes => prefixes.join(", "),
+            }
+        )
+    }
+}
+
+/// Returns [Error::Invalid] if `name` would create an entry breaking the policy of the db
+pub fn check(connection: &Connection, name: &str) -> Result<()> {
+    let policy = Policy::get(connection)?;
+    if policy == Policy::default() || exists(connection, name)? {
+        return Ok(());
+    }
+
+    policy.check(name)
+}
+
+/// Helper function compiling the regex matching names made of `charset`
+fn charset_regex(charset: &str) -> Result<Regex> {
+    Regex::new(&format!("^[{}]*$", charset))
+        .map_err(|_| Error::Invalid(format!("invalid charset [{}]", charset)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn policies() {
+        let store = crate::Store::open_in_memory().unwrap();
+        let connection = store.connection();
+        store.set("Legacy Name", Some("1"), None).unwrap();
+
+        Policy {
+            charset: Some("a-z0-9._-".to_string()),
+            max_length: Some(16),
+            prefixes: vec!["team.".to_string(), "ci.".to_string()],
+        }
+        .update(connection)
+        .unwrap();
+
+        for name in ["Team.theme", "team.a-very-long-name", "theme"] {
+            assert!(matches!(check(connection, name), Err(Error::Invalid(_))));
+        }
+        check(connection, "ci.job-1").unwrap();
+        store.set("Legacy Name", Some("2"), None).unwrap();
+
+        assert!(matches!(
+            Policy {
+                charset: Some("z-a".to_string()),
+                ..Default::default()
+            }
+            .update(connection),
+            Err(Error::Invalid(_))
+        ));
+
+        Policy::clear(connection).unwrap();
+        check(connection, "Any Name").unwrap();
+    }
+}
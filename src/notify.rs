@@ -0,0 +1,111 @@
+//! Module containing desktop notifications about changes
+//!
+//! Only available with the `notify` feature. Changes to entries tagged with [TAG] always trigger a
+//! notification, changes to all other entries only if `--notify` is passed. This gives feedback
+//! for toggles bound to hotkeys, which have no other UI.
+use notify_rust::Notification;
+use rusqlite::Connection;
+
+use crate::{audit, Result};
+
+/// The tag marking entries which should always trigger a notification when changed
+pub const TAG: &str = "notify";
+
+/// Sends a notification for every change made after the change with the id `since`
+///
+/// Only changes to entries tagged with [TAG] are included, unless `all == true`. Failing to show
+/// a notification (e.g. because no notification daemon is running) is only logged.
+pub fn send(connection: &Connection, since: i64, all: bool) -> Result<()> {
+    for (name, body) in pending(connection, since, all)? {
+        if let Err(error) = Notification::new()
+            .appname("config-store")
+            .summary(&name)
+            .body(&body)
+            .show()
+        {
+            tracing::warn!("Failed to show notification: {}", error);
+        }
+    }
+
+    Ok(())
+}
+
+/// Helper function returning the summary (the name) & body (the new value) of the notifications
+/// [send] shows
+fn pending(connection: &Connection, since: i64, all: bool) -> Result<Vec<(String, String)>> {
+    let mut notifications = Vec::new();
+
+    for change in audit::since(connection, since)? {
+        let Some(name) = change.name else {
+            continue;
+        };
+
+        if !all && !tagged(connection, &name)? {
+            continue;
+        }
+
+        let body = match change.new_value {
+            Some(value) => value,
+            None => "deleted".to_string(),
+        };
+        notifications.push((name, body));
+    }
+
+    Ok(notifications)
+}
+
+/// Helper function checking if the entry `name` is tagged with [TAG]
+fn tagged(connection: &Connection, name: &str) -> Result<bool> {
+    Ok(connection.query_row(
+        "SELECT EXISTS (
+            SELECT 1 FROM tags JOIN data ON data.id = tags.entry_id WHERE name = ? AND tag = ?
+        )",
+        (name, TAG),
+        |row| row.get(0),
+    )?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn notifications() {
+        let store = crate::Store::open_in_memory().unwrap();
+        let connection = store.connection();
+        store.set("theme", Some("dark"), Some("light")).unwrap();
+        store.set("volume", Some("30"), None).unwrap();
+        crate::commands::tag_add_cmd(connection, "theme".to_string(), vec![TAG.to_string()])
+            .unwrap();
+        let since = audit::last_id(connection).unwrap();
+        assert_eq!(pending(connection, since, true).unwrap(), []);
+
+        store.toggle("theme").unwrap();
+        store.set("volume", Some("40"), None).unwrap();
+        store.delete("volume").unwrap();
+
+        let pair = |name: &str, body: &str| (name.to_string(), body.to_string());
+        assert_eq!(
+            pending(connection, since, false).unwrap(),
+            [pair("theme", "light")]
+        );
+        assert_eq!(
+            pending(connection, since, true).unwrap(),
+            [
+                pair("theme", "light"),
+                pair("volume", "40"),
+                pair("volume", "deleted")
+            ]
+        );
+
+        // Without the tag the entry is only included with `all`
+        crate::commands::tag_remove_cmd(
+            connection,
+            "theme".to_string(),
+            vec![TAG.to_string()],
+            true,
+        )
+        .unwrap();
+        assert_eq!(pending(connection, since, false).unwrap(), []);
+    }
+}
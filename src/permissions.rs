@@ -0,0 +1,136 @@
+//! Module containing the permissions of the db file
+//!
+//! By default the db lives in the temporary directory, which every local user can read. So new dbs
+//! (& the directories leading to them) are created with restrictive permissions, `0600` unless
+//! changed with `--file-mode`, & opening a db anyone may write to is warned about. Permissions are
+//! only handled on unix, elsewhere the defaults of the system are used.
+use std::path::Path;
+
+use rusqlite::Connection;
+
+use crate::{Error, Result};
+
+/// The permissions new dbs are created with by default
+pub const DEFAULT_MODE: u32 = 0o600;
+
+/// Parses permissions given in octal like `600` or `0640`
+pub fn parse_mode(mode: &str) -> std::result::Result<u32, String> {
+    match u32::from_str_radix(mode, 8) {
+        Ok(mode) if mode <= 0o777 => Ok(mode),
+        _ => Err(format!("invalid mode '{}', expected octal like 600", mode)),
+    }
+}
+
+/// Creates the db file at `path` with the permissions `mode` if it doesn't exist yet
+///
+/// Missing parent directories are created as well, with the execute bits added wherever `mode`
+/// allows reading. Logs a warning if an existing db is world-writable.
+pub fn prepare(path: &Path, mode: u32) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+
+        if let Ok(metadata) = std::fs::metadata(path) {
+            if metadata.permissions().mode() & 0o002 != 0 {
+                tracing::warn!(
+                    "{} is writable by every user, anyone can change its entries",
+                    path.display()
+                );
+            }
+
+            return Ok(());
+        }
+
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::DirBuilder::new()
+                .recursive(true)
+                .mode(mode | (mode & 0o444) >> 2)
+                .create(parent)?;
+        }
+
+        // Only the creation itself is done here, sqlite keeps the permissions of the file
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(mode)
+            .open(path)?;
+    }
+
+    #[cfg(not(unix))]
+    let _ = (path, mode);
+
+    Ok(())
+}
+
+/// Checks that the db of `connection` can be trusted to run commands stored in it, e.g. those of
+/// computed entries
+///
+/// Anyone who can write the db can change the commands, so it has to be owned by the current user
+/// & not be writable by the group or others. Will return [Error::Invalid] otherwise, explaining
+/// that config-store refuses to `action`. In memory dbs & other systems than unix are trusted.
+pub fn trusted(connection: &Connection, action: &str) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let Some(path) = connection.path().filter(|p| !p.is_empty()) else {
+            return Ok(());
+        };
+        let metadata = std::fs::metadata(path)?;
+
+        // SAFETY: geteuid has no preconditions & always succeeds
+        let uid = unsafe { libc::geteuid() };
+        if metadata.uid() != uid || metadata.mode() & 0o022 != 0 {
+            return Err(Error::Invalid(format!(
+                "refusing to {}, {} has to be owned & only writable by the current user",
+                action, path
+            )));
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = (connection, action);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn modes() {
+        assert_eq!(parse_mode("600"), Ok(0o600));
+        assert_eq!(parse_mode("0640"), Ok(0o640));
+        assert!(parse_mode("800").is_err());
+        assert!(parse_mode("1777").is_err());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let dir =
+                std::env::temp_dir().join(format!("config-store-mode-{}", std::process::id()));
+            let path = dir.join("nested/test.db");
+            prepare(&path, DEFAULT_MODE).unwrap();
+            crate::Store::open(&path)
+                .unwrap()
+                .set("a", None, None)
+                .unwrap();
+
+            let mode = |p: &Path| std::fs::metadata(p).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode(&path), 0o600);
+            assert_eq!(mode(path.parent().unwrap()), 0o700);
+
+            let connection = Connection::open(&path).unwrap();
+            trusted(&connection, "run commands").unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o620)).unwrap();
+            assert!(matches!(
+                trusted(&connection, "run commands"),
+                Err(Error::Invalid(_))
+            ));
+
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+    }
+}
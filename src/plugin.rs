@@ -0,0 +1,170 @@
+//! Module running external subcommands, so config-store can be extended like git
+//!
+//! An unknown subcommand `foo` runs the program `config-store-foo` from the `PATH` with the
+//! remaining arguments. The global options are passed to it as environment variables (see [env]),
+//! so the plugin can call `$CONFIG_STORE_BIN` on the same db with the same settings.
+use std::path::{Path, PathBuf};
+
+use clap::{CommandFactory, ValueEnum};
+
+use crate::{cli::Args, Result};
+
+/// The prefix of the names of plugin programs
+pub const PREFIX: &str = "config-store-";
+
+/// Returns the path of the plugin for the subcommand `name`, if there is one in the `PATH`
+pub fn find(name: &str) -> Option<PathBuf> {
+    let program = format!("{}{}{}", PREFIX, name, std::env::consts::EXE_SUFFIX);
+
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(&program))
+        .find(|path| path.is_file())
+}
+
+/// The error message for an unknown subcommand `name` without a plugin, suggesting similar
+/// built-in commands
+pub fn unknown(name: &str) -> String {
+    let commands = Args::command()
+        .get_subcommands()
+        .map(|c| c.get_name().to_string())
+        .collect::<Vec<String>>();
+
+    match crate::suggest::similar(name, commands).first() {
+        Some(suggestion) => format!(
+            "unrecognized subcommand '{}', did you mean '{}'?",
+            name, suggestion
+        ),
+        None => format!(
+            "unrecognized subcommand '{}' & no {}{} in the PATH",
+            name, PREFIX, name
+        ),
+    }
+}
+
+/// Runs the plugin at `program` with `arguments`
+///
+/// On unix the plugin replaces this process, elsewhere this process exits with its exit code.
+pub fn run(args: &Args, program: &Path, arguments: &[String]) -> Result<()> {
+    let mut child = std::process::Command::new(program);
+    child.args(arguments).envs(env(args));
+
+    #[cfg(unix)]
+    let error = std::os::unix::process::CommandExt::exec(&mut child);
+    #[cfg(not(unix))]
+    let error = match child.status() {
+        Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+        Err(error) => error,
+    };
+
+    Err(std::io::Error::new(
+        error.kind(),
+        format!("failed to run {}: {}", program.display(), error),
+    )
+    .into())
+}
+
+/// Returns the environment variables describing the global options for plugins
+///
+/// `CONFIG_STORE_BIN` is the path of this binary & `CONFIG_STORE_DB_PATH` the absolute path of the
+/// db (paths of layers & the key are absolute as well). The other variables are named after the
+/// options, flags are `1` if given & missing if not.
+pub fn env(args: &Args) -> Vec<(&'static str, String)> {
+    let mut env = vec![
+        ("CONFIG_STORE_DB_PATH", db_path(&args.db_path)),
+        ("CONFIG_STORE_FILE_MODE", format!("{:o}", args.file_mode)),
+        ("CONFIG_STORE_KEEP_BACKUPS", args.keep_backups.to_string()),
+        ("CONFIG_STORE_VERBOSE", args.verbose.to_string()),
+    ];
+
+    if let Ok(binary) = std::env::current_exe() {
+        env.push(("CONFIG_STORE_BIN", binary.to_string_lossy().into_owned()));
+    }
+    if let Some(color) = args.color.to_possible_value() {
+        env.push(("CONFIG_STORE_COLOR", color.get_name().to_string()));
+    }
+    if let Some(backend) = args.backend.and_then(|b| b.to_possible_value()) {
+        env.push(("CONFIG_STORE_BACKEND", backend.get_name().to_string()));
+    }
+    if let Some(table) = &args.table {
+        env.push(("CONFIG_STORE_TABLE", table.clone()));
+    }
+    if !args.layers.is_empty() {
+        let layers =
+            std::env::join_paths(args.layers.iter().map(|l| db_path(&l.to_string_lossy())))
+                .map_or(String::new(), |l| l.to_string_lossy().into_owned());
+        env.push(("CONFIG_STORE_LAYERS", layers));
+    }
+    if let Some(key) = &args.hmac_key {
+        env.push(("CONFIG_STORE_HMAC_KEY", db_path(&key.to_string_lossy())));
+    }
+    if let Some(timeout) = args.timeout {
+        env.push(("CONFIG_STORE_TIMEOUT", timeout.to_string()));
+    }
+
+    for (name, set) in [
+        ("CONFIG_STORE_DRY_RUN", args.dry_run),
+        ("CONFIG_STORE_JSON", args.json),
+        ("CONFIG_STORE_YES", args.yes),
+        ("CONFIG_STORE_STRICT", args.strict),
+    ] {
+        if set {
+            env.push((name, "1".to_string()));
+        }
+    }
+
+    env
+}
+
+/// Helper function making the path of a db canonical, so it stays valid in another directory
+///
+/// Paths which don't exist (yet) are only made absolute. URIs & `:memory:` are returned unchanged.
+pub(crate) fn db_path(path: &str) -> String {
+    if path.starts_with("file:") || path.contains("://") || path == ":memory:" {
+        return path.to_string();
+    }
+
+    std::fs::canonicalize(path)
+        .or_else(|_| std::path::absolute(path))
+        .map_or(path.to_string(), |p| p.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use clap::Parser;
+
+    use super::*;
+
+    #[test]
+    fn plugins() {
+        let args = Args::parse_from([
+            "config-store",
+            "--db-path",
+            "plugin.db",
+            "--strict",
+            "--timeout",
+            "1m",
+            "sync",
+            "--remote",
+            "x",
+        ]);
+        assert!(matches!(
+            &args.command,
+            crate::cli::Action::External(command) if command == &["sync", "--remote", "x"]
+        ));
+
+        let env = env(&args);
+        let var = |name: &str| {
+            env.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, v)| v.as_str())
+        };
+        assert!(var("CONFIG_STORE_DB_PATH").is_some_and(|p| p.ends_with("/plugin.db")));
+        assert_eq!(var("CONFIG_STORE_STRICT"), Some("1"));
+        assert_eq!(var("CONFIG_STORE_TIMEOUT"), Some("60"));
+        assert_eq!(var("CONFIG_STORE_FILE_MODE"), Some("600"));
+        assert_eq!(var("CONFIG_STORE_DRY_RUN"), None);
+
+        assert!(find("surely-not-installed").is_none());
+        assert!(unknown("lst").ends_with("did you mean 'list'?"));
+    }
+}
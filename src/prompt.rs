@@ -0,0 +1,98 @@
+//! Module asking the user to confirm destructive commands & for values
+//!
+//! Only a person at a terminal is asked for confirmation, so scripts are never blocked by a
+//! prompt. `--yes` skips the question, see [Action::confirmation](crate::cli::Action::confirmation)
+//! for the commands which ask. Values read with [value] (see `set --prompt`) never show up in the
+//! shell history or the arguments of the process.
+use std::io::{BufRead, IsTerminal, Write};
+
+use crate::{Error, Result};
+
+/// Asks `question` on stderr & reads the answer from stdin if stdin is a terminal
+///
+/// Will return [Error::Aborted] unless the answer is yes.
+pub fn confirm(question: &str) -> Result<()> {
+    if !std::io::stdin().is_terminal() {
+        return Ok(());
+    }
+
+    ask(
+        question,
+        &mut std::io::stdin().lock(),
+        &mut std::io::stderr(),
+    )
+}
+
+/// Asks for the value of the entry `name` on stderr & reads it from stdin
+///
+/// With `hidden` the value isn't echoed & read from the terminal itself, like a password.
+pub fn value(name: &str, hidden: bool) -> Result<String> {
+    let question = format!("Value of '{}': ", name);
+
+    if hidden {
+        return rpassword::prompt_password(question).map_err(|e| {
+            std::io::Error::new(e.kind(), format!("failed to read from the terminal: {}", e)).into()
+        });
+    }
+
+    read_value(
+        &question,
+        &mut std::io::stdin().lock(),
+        &mut std::io::stderr(),
+    )
+}
+
+/// Helper function asking `question` on `output` & reading a line from `input`, without its line
+/// break
+fn read_value(question: &str, input: &mut impl BufRead, output: &mut impl Write) -> Result<String> {
+    write!(output, "{}", question)?;
+    output.flush()?;
+
+    let mut value = String::new();
+    input.read_line(&mut value)?;
+
+    Ok(value.trim_end_matches(['\n', '\r']).to_string())
+}
+
+/// Helper function asking `question` on `output` & reading the answer from `input`
+fn ask(question: &str, input: &mut impl BufRead, output: &mut impl Write) -> Result<()> {
+    write!(output, "{} [y/N] ", question)?;
+    output.flush()?;
+
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => Ok(()),
+        _ => Err(Error::Aborted),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn answers() {
+        let answer = |input: &str| {
+            let mut output = Vec::new();
+            let result = ask("Drop all entries?", &mut input.as_bytes(), &mut output);
+            assert_eq!(output, b"Drop all entries? [y/N] ");
+            result
+        };
+
+        assert!(answer("y\n").is_ok());
+        assert!(answer("Yes\n").is_ok());
+        assert!(matches!(answer("\n"), Err(Error::Aborted)));
+        assert!(matches!(answer("nope\n"), Err(Error::Aborted)));
+        assert!(matches!(answer(""), Err(Error::Aborted)));
+    }
+
+    #[test]
+    fn values() {
+        let mut output = Vec::new();
+        let value = read_value("Value of 'a': ", &mut "x y \r\n".as_bytes(), &mut output);
+        assert_eq!(value.unwrap(), "x y ");
+        assert_eq!(output, b"Value of 'a': ");
+    }
+}
@@ -0,0 +1,186 @@
+//! Module containing the python bindings
+//!
+//! Only available with the `python` feature. The module is built with
+//! [maturin](https://www.maturin.rs/) (`maturin develop` or `maturin build --release`) and can
+//! then be used like this:
+//!
+//! ```python
+//! import config_store
+//!
+//! store = config_store.Store("/tmp/config-store.db")
+//! store.set("theme", "dark", "light")
+//! store.toggle("theme")  # "light"
+//! ```
+
+// pyo3 0.22's `#[pymethods]` expansion trips this lint on every `PyResult` return
+#![allow(clippy::useless_conversion)]
+
+use pyo3::{
+    exceptions::{PyKeyError, PyRuntimeError},
+    prelude::*,
+};
+
+use crate::{Entry, Error, Filter, Store};
+
+impl From<Error> for PyErr {
+    fn from(error: Error) -> Self {
+        match error {
+            error @ Error::EntryNotFound { .. } => PyKeyError::new_err(error.to_string()),
+            error => PyRuntimeError::new_err(error.to_string()),
+        }
+    }
+}
+
+/// An entry in the store
+#[pyclass(name = "Entry", get_all, frozen)]
+pub struct PyEntry {
+    name: String,
+    value: String,
+    alternate: Option<String>,
+    description: Option<String>,
+    default: Option<String>,
+}
+
+#[pymethods]
+impl PyEntry {
+    fn __repr__(&self) -> String {
+        // Like python's `None` instead of rust's `Some(...)`
+        let alternate = self
+            .alternate
+            .as_ref()
+            .map_or("None".to_string(), |a| format!("{:?}", a));
+
+        format!(
+            "Entry(name={:?}, value={:?}, alternate={})",
+            self.name, self.value, alternate
+        )
+    }
+}
+
+impl From<Entry> for PyEntry {
+    fn from(entry: Entry) -> Self {
+        PyEntry {
+            name: entry.name,
+            value: entry.value,
+            alternate: entry.alternate,
+            description: entry.description,
+            default: entry.default,
+        }
+    }
+}
+
+/// A config-store db, see [Store]
+#[pyclass(name = "Store")]
+pub struct PyStore {
+    store: Store,
+}
+
+#[pymethods]
+impl PyStore {
+    /// Opens (or creates) the db at `path`
+    #[new]
+    fn new(path: &str) -> PyResult<PyStore> {
+        Ok(PyStore {
+            store: Store::open(path)?,
+        })
+    }
+
+    /// Gets an entry, raising a `KeyError` if it doesn't exist
+    fn get(&self, name: &str) -> PyResult<PyEntry> {
+        Ok(self.store.get(name)?.into())
+    }
+
+    /// Checks if an entry exists
+    fn exists(&self, name: &str) -> PyResult<bool> {
+        Ok(self.store.exists(name)?)
+    }
+
+    /// Creates or updates an entry
+    #[pyo3(signature = (name, value=None, alternate=None))]
+    fn set(&self, name: &str, value: Option<&str>, alternate: Option<&str>) -> PyResult<PyEntry> {
+        Ok(self.store.set(name, value, alternate)?.into())
+    }
+
+    /// Toggles an entry between its value & alternate returning the new value
+    fn toggle(&self, name: &str) -> PyResult<String> {
+        Ok(self.store.toggle(name)?)
+    }
+
+    /// Deletes an entry, returning whether it existed
+    fn delete(&self, name: &str) -> PyResult<bool> {
+        Ok(self.store.delete(name)?)
+    }
+
+    /// Lists all entries, optionally only those with a tag and/or prefix
+    #[pyo3(signature = (tag=None, prefix=None))]
+    fn list(&self, tag: Option<String>, prefix: Option<String>) -> PyResult<Vec<PyEntry>> {
+        let filter = Filter {
+            tag,
+            prefix,
+            ..Default::default()
+        };
+
+        Ok(self
+            .store
+            .list(&filter)?
+            .into_iter()
+            .map(PyEntry::from)
+            .collect())
+    }
+
+    /// Resets an entry's value to its default returning the new value
+    fn reset(&self, name: &str) -> PyResult<String> {
+        Ok(self.store.reset(name)?)
+    }
+}
+
+/// The `config_store` python module
+#[pymodule]
+fn config_store(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyStore>()?;
+    m.add_class::<PyEntry>()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use pyo3::types::IntoPyDict;
+
+    use super::*;
+
+    #[test]
+    fn module() {
+        pyo3::prepare_freethreaded_python();
+
+        Python::with_gil(|py| {
+            let module = pyo3::wrap_pymodule!(config_store)(py);
+            let locals = [("config_store", module)].into_py_dict_bound(py);
+            let run = |code: &str| py.run_bound(code, None, Some(&locals));
+
+            run(r#"
+store = config_store.Store(":memory:")
+entry = store.set("theme", "dark", "light")
+assert (entry.name, entry.value, entry.alternate) == ("theme", "dark", "light")
+assert repr(entry) == 'Entry(name="theme", value="dark", alternate="light")'
+assert store.toggle("theme") == "light"
+assert store.get("theme").value == "light"
+vpn = store.set("vpn.home")
+assert vpn.alternate is None
+assert repr(vpn) == 'Entry(name="vpn.home", value="", alternate=None)'
+assert [e.name for e in store.list(prefix="vpn.")] == ["vpn.home"]
+assert store.exists("theme")
+assert store.delete("theme")
+assert not store.delete("theme")
+"#)
+            .unwrap();
+
+            // Missing entries raise a KeyError, other errors a RuntimeError
+            let error = run(r#"store.get("theme")"#).unwrap_err();
+            assert!(error.is_instance_of::<PyKeyError>(py));
+            assert!(error.to_string().contains("no entry named 'theme'"));
+            let error = run(r#"store.reset("vpn.home")"#).unwrap_err();
+            assert!(error.is_instance_of::<PyRuntimeError>(py));
+        });
+    }
+}
@@ -0,0 +1,157 @@
+//! Module detecting a corrupt db when it's opened & recovering from it
+//!
+//! A db is corrupt if sqlite can't read it (e.g. it isn't a db at all) or `PRAGMA quick_check`
+//! finds problems. Recovering moves it aside to `<db path>.corrupt-<time>` (together with its WAL)
+//! so nothing is lost, & replaces it with the most recent backup which passes the check, see
+//! [crate::backup]. Without such a backup a new empty db is created when it's opened again.
+use std::path::{Path, PathBuf};
+
+use rusqlite::{Connection, ErrorCode, OpenFlags};
+
+use crate::{backup, time, Result};
+
+/// What [recover] did with a corrupt db
+#[derive(Debug)]
+pub struct Report {
+    /// The db which was corrupt
+    pub path: PathBuf,
+    /// What is wrong with it, an error of sqlite or the result of `PRAGMA quick_check`
+    pub problem: String,
+    /// Where it was moved to
+    pub moved_to: PathBuf,
+    /// The backup it was replaced with, `None` if a new db is created
+    pub restored: Option<PathBuf>,
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the db at {} is corrupt ({}), moved it to {} & ",
+            self.path.display(),
+            self.problem,
+            self.moved_to.display()
+        )?;
+
+        match &self.restored {
+            Some(backup) => write!(f, "restored the backup {}", backup.display()),
+            None => write!(f, "created a new empty db, there is no usable backup"),
+        }
+    }
+}
+
+/// Checks the db of `connection`, returning what is wrong with it if it's corrupt
+///
+/// Other errors, e.g. a db locked by another process, are returned as they are.
+pub fn check(connection: &Connection) -> Result<Option<String>> {
+    let result = connection
+        .prepare("PRAGMA quick_check")
+        .and_then(|mut statement| {
+            statement
+                .query_map((), |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()
+        });
+
+    match result {
+        Ok(lines) if lines == ["ok"] => Ok(None),
+        Ok(lines) => Ok(Some(lines.join("; "))),
+        Err(error)
+            if matches!(
+                error.sqlite_error_code(),
+                Some(ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase)
+            ) =>
+        {
+            Ok(Some(error.to_string()))
+        }
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Moves the corrupt db at `path` aside & restores its most recent usable backup
+///
+/// `problem` is what [check] found. No connection to the db may be open.
+pub fn recover(path: &Path, problem: String) -> Result<Report> {
+    let now = time::now();
+    let moved_to = (0..)
+        .map(|n| match n {
+            0 => PathBuf::from(format!("{}.corrupt-{}", path.display(), now)),
+            n => PathBuf::from(format!("{}.corrupt-{}-{}", path.display(), now, n)),
+        })
+        .find(|p| !p.exists())
+        .unwrap();
+
+    // The WAL belongs to the corrupt db, applying it to the backup would corrupt that as well
+    for suffix in ["", "-wal", "-shm"] {
+        let file = PathBuf::from(format!("{}{}", path.display(), suffix));
+        if file.exists() {
+            std::fs::rename(&file, format!("{}{}", moved_to.display(), suffix))?;
+        }
+    }
+
+    let mut restored = None;
+    for backup in backup::list(&backup::dir_of(path))?.into_iter().rev() {
+        let usable = Connection::open_with_flags(&backup, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(Into::into)
+            .and_then(|connection| check(&connection));
+
+        match usable {
+            Ok(None) => {
+                std::fs::copy(&backup, path)?;
+                restored = Some(backup);
+                break;
+            }
+            Ok(Some(problem)) => {
+                tracing::warn!("Backup {} is corrupt: {}", backup.display(), problem)
+            }
+            Err(error) => tracing::warn!("Failed to check backup {}: {}", backup.display(), error),
+        }
+    }
+
+    Ok(Report {
+        path: path.to_path_buf(),
+        problem,
+        moved_to,
+        restored,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn recover_corrupt_db() {
+        let dir =
+            std::env::temp_dir().join(format!("config-store-recovery-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test.db");
+
+        let store = crate::Store::open(&path).unwrap();
+        store.set("theme", Some("dark"), None).unwrap();
+        assert_eq!(check(store.connection()).unwrap(), None);
+        backup::create(store.connection(), 1).unwrap();
+        drop(store);
+
+        std::fs::write(&path, "not a db").unwrap();
+        let connection = Connection::open(&path).unwrap();
+        let problem = check(&connection).unwrap().unwrap();
+        drop(connection);
+
+        let report = recover(&path, problem).unwrap();
+        assert!(report.restored.is_some());
+        assert_eq!(std::fs::read(&report.moved_to).unwrap(), b"not a db");
+        let store = crate::Store::open(&path).unwrap();
+        assert_eq!(store.get("theme").unwrap().value, "dark");
+        drop(store);
+
+        // Without a usable backup the db starts empty
+        std::fs::remove_dir_all(backup::dir_of(&path)).unwrap();
+        std::fs::write(&path, "not a db either").unwrap();
+        let report = recover(&path, "broken".to_string()).unwrap();
+        assert!(report.restored.is_none());
+        assert!(!path.exists());
+        assert!(report.to_string().contains("created a new empty db"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
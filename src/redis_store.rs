@@ -0,0 +1,148 @@
+//! Module containing [RedisStore], a [Backend] storing entries on a Redis server
+//!
+//! Only available with the `redis` feature. Every entry is a hash with the key
+//! `config-store:entry:<name>` & the names of all entries are kept in the set
+//! `config-store:names`, so several machines can share their toggles through one server.
+use std::{cell::RefCell, collections::HashMap};
+
+use redis::Commands;
+
+use crate::{backend::Backend, time, Entry, Error, Filter, Result};
+
+/// The set containing the names of all entries
+const NAMES: &str = "config-store:names";
+
+/// A [Backend] using a Redis server
+pub struct RedisStore {
+    connection: RefCell<redis::Connection>,
+}
+
+impl std::fmt::Debug for RedisStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisStore").finish_non_exhaustive()
+    }
+}
+
+impl RedisStore {
+    /// Connects to the server at `url`, e.g. `redis://localhost:6379/0`
+    pub fn open(url: &str) -> Result<RedisStore> {
+        let connection = redis::Client::open(url)?.get_connection()?;
+
+        Ok(RedisStore {
+            connection: RefCell::new(connection),
+        })
+    }
+
+    /// Helper function returning the key of the hash storing the entry `name`
+    fn key(name: &str) -> String {
+        format!("config-store:entry:{}", name)
+    }
+}
+
+impl Backend for RedisStore {
+    fn get(&self, name: &str) -> Result<Entry> {
+        let mut fields: HashMap<String, String> =
+            self.connection.borrow_mut().hgetall(Self::key(name))?;
+
+        if fields.is_empty() {
+            return Err(Error::entry_not_found(name));
+        }
+
+        Ok(Entry {
+            _id: 0,
+            name: name.to_string(),
+            value: fields.remove("value").unwrap_or_default(),
+            alternate: fields.remove("alternate"),
+            created_at: fields.get("created_at").and_then(|t| t.parse().ok()),
+            updated_at: fields.get("updated_at").and_then(|t| t.parse().ok()),
+            description: fields.remove("description"),
+            default: fields.remove("default"),
+            modified_by: None,
+        })
+    }
+
+    fn exists(&self, name: &str) -> Result<bool> {
+        Ok(self.connection.borrow_mut().exists(Self::key(name))?)
+    }
+
+    fn set(&self, name: &str, value: Option<&str>, alternate: Option<&str>) -> Result<Entry> {
+        let now = time::now().to_string();
+        let mut fields = vec![("updated_at", now.as_str())];
+        fields.extend(value.map(|v| ("value", v)));
+        fields.extend(alternate.map(|a| ("alternate", a)));
+
+        // The fields of new entries which aren't given are filled in first
+        redis::pipe()
+            .atomic()
+            .hset_nx(Self::key(name), "created_at", &now)
+            .ignore()
+            .hset_nx(Self::key(name), "value", "")
+            .ignore()
+            .hset_multiple(Self::key(name), &fields)
+            .ignore()
+            .sadd(NAMES, name)
+            .ignore()
+            .query::<()>(&mut self.connection.borrow_mut())?;
+
+        self.get(name)
+    }
+
+    fn toggle(&self, name: &str) -> Result<String> {
+        let key = Self::key(name);
+        let mut connection = self.connection.borrow_mut();
+
+        // Retried by redis if the entry is changed by someone else in between
+        redis::transaction(&mut *connection, &[&key], |connection, pipe| {
+            let (value, alternate): (Option<String>, Option<String>) =
+                connection.hget(&key, &["value", "alternate"])?;
+
+            let Some(value) = value else {
+                return Ok(Some(Err(Error::entry_not_found(name))));
+            };
+            let alternate = alternate.unwrap_or_default();
+
+            pipe.hset_multiple(
+                &key,
+                &[
+                    ("value", alternate.as_str()),
+                    ("alternate", value.as_str()),
+                    ("updated_at", time::now().to_string().as_str()),
+                ],
+            )
+            .ignore()
+            .query::<()>(connection)?;
+
+            Ok(Some(Ok(alternate)))
+        })?
+    }
+
+    fn delete(&self, name: &str) -> Result<bool> {
+        let (deleted, _): (bool, i64) = redis::pipe()
+            .atomic()
+            .del(Self::key(name))
+            .srem(NAMES, name)
+            .query(&mut self.connection.borrow_mut())?;
+
+        Ok(deleted)
+    }
+
+    fn list(&self, filter: &Filter) -> Result<Vec<Entry>> {
+        let mut names: Vec<String> = self.connection.borrow_mut().smembers(NAMES)?;
+        names.sort();
+
+        let mut entries = Vec::new();
+        for name in names {
+            // Entries deleted by someone else since reading the names are skipped
+            let entry = match self.get(&name) {
+                Err(Error::EntryNotFound { .. }) => continue,
+                entry => entry?,
+            };
+
+            if filter.matches(&entry)? {
+                entries.push(entry);
+            }
+        }
+
+        Ok(entries)
+    }
+}
@@ -0,0 +1,140 @@
+//! Module containing the remote destinations backups are copied to
+//!
+//! The backup directory (see [crate::backup]) is mirrored to the destination configured with
+//! `backup remote`, including deletions. So the remote copy keeps the same backups as the local
+//! one, following `--keep-backups`. The copying is done by external tools, which have to be
+//! installed: `rsync` for hosts & local paths and the `aws` cli for S3 buckets.
+use std::{path::Path, process::Command};
+
+use crate::Result;
+
+/// A place the backups can be copied to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Remote {
+    /// A path on another host like `pi@backup-host:config-store/` or a local path (e.g. a mounted
+    /// network share), copied to with `rsync`
+    Rsync(String),
+    /// A bucket (& prefix) of an S3 compatible storage like `s3://bucket/config-store`, copied to
+    /// with `aws s3 sync`
+    ///
+    /// Only available with the `s3` feature. The endpoint & credentials are taken from the usual
+    /// `AWS_*` environment variables & config files.
+    #[cfg(feature = "s3")]
+    S3(String),
+}
+
+impl Remote {
+    /// Parses a destination, see the variants of [Remote] for the accepted formats
+    pub fn parse(destination: &str) -> std::result::Result<Remote, String> {
+        if destination.is_empty() {
+            return Err("empty destination".to_string());
+        }
+
+        if destination.starts_with("s3://") {
+            #[cfg(feature = "s3")]
+            return Ok(Remote::S3(destination.to_string()));
+            #[cfg(not(feature = "s3"))]
+            return Err("S3 destinations need the `s3` feature".to_string());
+        }
+
+        Ok(Remote::Rsync(destination.to_string()))
+    }
+
+    /// Mirrors the directory `dir` to the destination
+    ///
+    /// Will return [Error::Io](crate::Error::Io) if the tool can't be run or fails.
+    pub fn push(&self, dir: &Path) -> Result<()> {
+        let (tool, mut command) = self.command(dir);
+        let output = command
+            .output()
+            .map_err(|e| std::io::Error::new(e.kind(), format!("failed to run {}: {}", tool, e)))?;
+
+        if !output.status.success() {
+            return Err(std::io::Error::other(format!(
+                "{} failed: {}",
+                tool,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+            .into());
+        }
+
+        tracing::info!("Copied backups to {}", self);
+
+        Ok(())
+    }
+
+    /// Helper function returning the name of the tool & the command mirroring `dir`
+    fn command(&self, dir: &Path) -> (&'static str, Command) {
+        match self {
+            Remote::Rsync(destination) => {
+                let mut command = Command::new("rsync");
+                command
+                    .args(["-a", "--delete"])
+                    // Destinations starting with `-` mustn't be read as options, e.g. `-e`
+                    .arg("--")
+                    // The trailing slash copies the contents instead of the directory itself
+                    .arg(format!("{}/", dir.display()))
+                    .arg(destination);
+                ("rsync", command)
+            }
+            #[cfg(feature = "s3")]
+            Remote::S3(destination) => {
+                let mut command = Command::new("aws");
+                command
+                    .args(["s3", "sync", "--delete", "--only-show-errors"])
+                    .arg(dir)
+                    .arg(destination);
+                ("aws", command)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Remote {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Remote::Rsync(destination) => write!(f, "{}", destination),
+            #[cfg(feature = "s3")]
+            Remote::S3(destination) => write!(f, "{}", destination),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_destinations() {
+        assert_eq!(
+            Remote::parse("pi@host:backups/"),
+            Ok(Remote::Rsync("pi@host:backups/".to_string()))
+        );
+        assert!(Remote::parse("").is_err());
+
+        #[cfg(feature = "s3")]
+        assert_eq!(
+            Remote::parse("s3://bucket/prefix"),
+            Ok(Remote::S3("s3://bucket/prefix".to_string()))
+        );
+        #[cfg(not(feature = "s3"))]
+        assert!(Remote::parse("s3://bucket/prefix").is_err());
+    }
+
+    #[test]
+    fn rsync_arguments() {
+        let remote = Remote::parse("-e sh -c 'touch /tmp/pwned' x:").unwrap();
+        let (tool, command) = remote.command(Path::new("/db.backups"));
+        assert_eq!(tool, "rsync");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            [
+                "-a",
+                "--delete",
+                "--",
+                "/db.backups/",
+                "-e sh -c 'touch /tmp/pwned' x:"
+            ]
+        );
+    }
+}
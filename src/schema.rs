@@ -0,0 +1,298 @@
+//! Module containing the db schema & the logic to create / migrate it
+//!
+//! Older versions of config-store only created the `id`, `name`, `value` & `alternate` columns.
+//! Every column added since then is listed in `COLUMNS` and is added to existing dbs on open, so
+//! no data is lost when upgrading.
+use rusqlite::{Connection, OptionalExtension};
+
+/// The current version of the schema, stored in the db as `PRAGMA user_version`
+pub const VERSION: i32 = 28;
+
+/// Columns which have been added to the `data` table after the initial release
+///
+/// Each entry is the name of the column and its type. New columns must be nullable (or have a
+/// constant default), since they are added with `ALTER TABLE`.
+const COLUMNS: &[(&str, &str)] = &[
+    ("created_at", "INTEGER"),
+    ("updated_at", "INTEGER"),
+    ("description", "TEXT"),
+    ("default_value", "TEXT"),
+    ("accessed_at", "INTEGER"),
+    ("reads", "INTEGER"),
+    ("reset_at", "INTEGER"),
+    ("reset_value", "TEXT"),
+    ("modified_by", "TEXT"),
+    ("frozen", "INTEGER"),
+    ("protected", "INTEGER"),
+    ("command", "TEXT"),
+    ("command_ttl", "INTEGER"),
+    ("computed_at", "INTEGER"),
+    ("secret", "INTEGER"),
+    ("command_script", "INTEGER"),
+    ("checksum", "TEXT"),
+    ("hmac", "TEXT"),
+    ("session", "TEXT"),
+];
+
+/// Creates all tables & adds missing columns to existing ones
+///
+/// This also enables foreign keys for the connection, which is needed for removing the tags &
+/// metadata of deleted entries.
+pub fn init(connection: &Connection) -> rusqlite::Result<()> {
+    connection.pragma_update(None, "foreign_keys", true)?;
+    let version: i32 = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+    // Read-only dbs (e.g. opened with `?mode=ro`) are used as they are, only the temporary
+    // tables & triggers of the connection are created
+    if connection.is_readonly(rusqlite::DatabaseName::Main)? {
+        return crate::audit::init_temp(connection);
+    }
+
+    connection.execute(
+        "
+        CREATE TABLE IF NOT EXISTS data (
+            id INTEGER PRIMARY KEY,
+            name TEXT,
+            value TEXT,
+            alternate TEXT
+        );",
+        (),
+    )?;
+
+    let existing = connection
+        .prepare("SELECT name FROM pragma_table_info('data')")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+
+    for (column, column_type) in COLUMNS {
+        if !existing.iter().any(|c| c == column) {
+            connection.execute(
+                &format!("ALTER TABLE data ADD COLUMN {} {}", column, column_type),
+                (),
+            )?;
+        }
+    }
+
+    index_names(connection)?;
+
+    connection.execute(
+        "
+        CREATE TABLE IF NOT EXISTS tags (
+            entry_id INTEGER NOT NULL REFERENCES data(id) ON DELETE CASCADE,
+            tag TEXT NOT NULL,
+            PRIMARY KEY (entry_id, tag)
+        );",
+        (),
+    )?;
+
+    connection.execute(
+        "
+        CREATE TABLE IF NOT EXISTS meta (
+            entry_id INTEGER NOT NULL REFERENCES data(id) ON DELETE CASCADE,
+            key TEXT NOT NULL,
+            value TEXT NOT NULL,
+            PRIMARY KEY (entry_id, key)
+        );",
+        (),
+    )?;
+
+    connection.execute(
+        "
+        CREATE TABLE IF NOT EXISTS presets (
+            preset TEXT NOT NULL,
+            name TEXT NOT NULL,
+            value TEXT NOT NULL,
+            alternate TEXT NOT NULL,
+            PRIMARY KEY (preset, name)
+        );",
+        (),
+    )?;
+
+    connection.execute(
+        "
+        CREATE TABLE IF NOT EXISTS aliases (
+            alias TEXT PRIMARY KEY,
+            target TEXT NOT NULL
+        );",
+        (),
+    )?;
+
+    // The numeric readings appended to entries by `record`
+    connection.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS samples (
+            entry_id INTEGER NOT NULL REFERENCES data(id) ON DELETE CASCADE,
+            recorded_at INTEGER NOT NULL,
+            value REAL NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS samples_entry ON samples (entry_id, recorded_at);",
+    )?;
+
+    // Binary artifacts stored by `blob put`, which entries reference as `blob:HASH`
+    connection.execute(
+        "
+        CREATE TABLE IF NOT EXISTS blobs (
+            hash TEXT PRIMARY KEY,
+            data BLOB NOT NULL,
+            created_at INTEGER NOT NULL
+        );",
+        (),
+    )?;
+
+    // The files entries are mirrored into, see `bind`
+    connection.execute(
+        "
+        CREATE TABLE IF NOT EXISTS bindings (
+            entry_id INTEGER PRIMARY KEY REFERENCES data(id) ON DELETE CASCADE,
+            path TEXT NOT NULL,
+            direction TEXT NOT NULL,
+            synced_at INTEGER
+        );",
+        (),
+    )?;
+
+    // The token buckets of `ratelimit`, `updated_at` is in milliseconds to refill them smoothly
+    connection.execute(
+        "
+        CREATE TABLE IF NOT EXISTS ratelimits (
+            name TEXT PRIMARY KEY,
+            tokens REAL NOT NULL,
+            updated_at INTEGER NOT NULL
+        );",
+        (),
+    )?;
+
+    // The stopwatches of `timer` in milliseconds, `started_at` is `NULL` while one is stopped
+    connection.execute(
+        "
+        CREATE TABLE IF NOT EXISTS timers (
+            name TEXT PRIMARY KEY,
+            started_at INTEGER,
+            elapsed INTEGER NOT NULL
+        );",
+        (),
+    )?;
+
+    // Frozen entries can't be changed or deleted by anything, see `freeze`
+    connection.execute_batch(
+        "
+        CREATE TRIGGER IF NOT EXISTS frozen_update BEFORE UPDATE OF name, value, alternate ON data
+        WHEN OLD.frozen
+        BEGIN
+            SELECT RAISE(ABORT, 'frozen');
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS frozen_delete BEFORE DELETE ON data
+        WHEN OLD.frozen
+        BEGIN
+            SELECT RAISE(ABORT, 'frozen');
+        END;
+        ",
+    )?;
+
+    // Secret entries keep an empty value, setting another one makes them regular again, see
+    // `set --keyring`
+    connection.execute(
+        "
+        CREATE TRIGGER IF NOT EXISTS secret_update AFTER UPDATE OF value ON data
+        WHEN NEW.secret AND NEW.value != ''
+        BEGIN
+            UPDATE data SET secret = NULL WHERE id = NEW.id;
+        END;",
+        (),
+    )?;
+
+    index_search(connection)?;
+
+    crate::settings::init(connection)?;
+    crate::audit::init(connection)?;
+    crate::checksum::init(connection)?;
+    crate::limits::init(connection)?;
+
+    // Writing it unconditionally would change the file on every open, even for plain reads
+    if version != VERSION {
+        connection.pragma_update(None, "user_version", VERSION)?;
+    }
+
+    Ok(())
+}
+
+/// Creates the full-text index over the names, values & descriptions of entries, see `fts`
+///
+/// `data_fts` is an FTS5 table using `data` as its content, so it only stores the index itself.
+/// Triggers keep it in sync with every change, even those made by older versions. They're dropped
+/// together with `data` (see `drop`), so they're created again whenever they're missing. Entries
+/// which existed before the index or its triggers are indexed then.
+fn index_search(connection: &Connection) -> rusqlite::Result<()> {
+    let complete = connection
+        .prepare(
+            "SELECT COUNT(*) = 4 FROM sqlite_master WHERE name IN
+                ('data_fts', 'data_fts_insert', 'data_fts_delete', 'data_fts_update')",
+        )?
+        .query_row((), |row| row.get::<_, bool>(0))?;
+    if complete {
+        return Ok(());
+    }
+
+    connection.execute_batch(
+        "
+        CREATE VIRTUAL TABLE IF NOT EXISTS data_fts USING fts5(
+            name, value, description, content = 'data', content_rowid = 'id'
+        );
+        INSERT INTO data_fts (data_fts) VALUES ('rebuild');
+
+        CREATE TRIGGER IF NOT EXISTS data_fts_insert AFTER INSERT ON data
+        BEGIN
+            INSERT INTO data_fts (rowid, name, value, description)
+                VALUES (NEW.id, NEW.name, NEW.value, NEW.description);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS data_fts_delete AFTER DELETE ON data
+        BEGIN
+            INSERT INTO data_fts (data_fts, rowid, name, value, description)
+                VALUES ('delete', OLD.id, OLD.name, OLD.value, OLD.description);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS data_fts_update
+        AFTER UPDATE OF id, name, value, description ON data
+        BEGIN
+            INSERT INTO data_fts (data_fts, rowid, name, value, description)
+                VALUES ('delete', OLD.id, OLD.name, OLD.value, OLD.description);
+            INSERT INTO data_fts (rowid, name, value, description)
+                VALUES (NEW.id, NEW.name, NEW.value, NEW.description);
+        END;
+        ",
+    )
+}
+
+/// Creates the index on the names of entries, which is unique unless the db contains duplicates
+///
+/// The schema never prevented duplicate names, so dbs written by other tools or old versions may
+/// contain some (see `dedupe`). Their index stays non-unique until the duplicates are removed,
+/// which is checked whenever the db is opened.
+pub(crate) fn index_names(connection: &Connection) -> rusqlite::Result<()> {
+    let unique: Option<bool> = connection
+        .query_row(
+            "SELECT \"unique\" FROM pragma_index_list('data') WHERE name = 'data_name'",
+            (),
+            |row| row.get(0),
+        )
+        .optional()?;
+    if unique == Some(true) {
+        return Ok(());
+    }
+
+    let duplicates = connection
+        .prepare("SELECT 1 FROM data WHERE name IS NOT NULL GROUP BY name HAVING COUNT(*) > 1")?
+        .exists(())?;
+
+    if !duplicates {
+        connection.execute_batch(
+            "DROP INDEX IF EXISTS data_name; CREATE UNIQUE INDEX data_name ON data (name);",
+        )?;
+    } else if unique.is_none() {
+        connection.execute("CREATE INDEX data_name ON data (name)", ())?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,146 @@
+//! Module running [Rhai](https://rhai.rs) scripts, for logic too complex for `transform` or hooks
+//!
+//! Only available with the `script` feature. Scripts see the entry they run for as `name` &
+//! `value`, can read any entry with `get(name)` & change entries with `set(name, value)`. The
+//! result of the last expression is the result of the script, e.g. the new value for `transform
+//! --script`.
+//!
+//! Scripts are sandboxed: they can't access files, processes or the network & the number of
+//! operations as well as the size of strings & collections are limited, so a bad script can't
+//! hang config-store.
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use rhai::{Dynamic, Engine, EvalAltResult, Scope};
+use rusqlite::Connection;
+
+use crate::{
+    store::{select_filtered, set, transaction},
+    Error, Filter, Result,
+};
+
+/// The maximum number of operations a script may run
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// Runs `script` for the entry `name` with the value `value` & returns its result
+///
+/// Changes made with `set` are applied in a single transaction once the script finished, so a
+/// failing script changes nothing. A result of `()` is returned as an empty string. Will return
+/// [Error::Invalid] if the script fails.
+pub fn run(connection: &Connection, script: &str, name: &str, value: &str) -> Result<String> {
+    let values: HashMap<String, String> = select_filtered(connection, &Filter::default())?
+        .into_iter()
+        .map(|e| (e.name, e.value))
+        .collect();
+    let values = Rc::new(RefCell::new(values));
+    let changes = Rc::new(RefCell::new(Vec::new()));
+
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(32)
+        .set_max_expr_depths(64, 32)
+        .set_max_string_size(1 << 20)
+        .set_max_array_size(10_000)
+        .set_max_map_size(10_000)
+        .disable_symbol("eval");
+
+    let get_values = Rc::clone(&values);
+    engine.register_fn(
+        "get",
+        move |name: &str| -> std::result::Result<String, Box<EvalAltResult>> {
+            get_values
+                .borrow()
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("no entry named '{}'", name).into())
+        },
+    );
+    let set_values = Rc::clone(&values);
+    let set_changes = Rc::clone(&changes);
+    engine.register_fn("set", move |name: &str, value: Dynamic| {
+        let value = value.to_string();
+        set_values
+            .borrow_mut()
+            .insert(name.to_string(), value.clone());
+        set_changes.borrow_mut().push((name.to_string(), value));
+    });
+
+    let mut scope = Scope::new();
+    scope.push_constant("name", name.to_string());
+    scope.push_constant("value", value.to_string());
+
+    let result = engine
+        .eval_with_scope::<Dynamic>(&mut scope, script)
+        .map_err(|error| Error::Invalid(format!("script failed: {}", error)))?;
+
+    transaction(connection, || {
+        for (name, value) in changes.take() {
+            set(connection, &name, Some(value), None, None, None, false)?;
+        }
+        Ok(())
+    })?;
+
+    Ok(if result.is_unit() {
+        String::new()
+    } else {
+        result.to_string()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn scripts() {
+        let store = crate::Store::open_in_memory().unwrap();
+        let connection = store.connection();
+        store.set("port", Some("8080"), None).unwrap();
+
+        assert_eq!(
+            run(
+                connection,
+                r#"`${value}:${get("port").parse_int() + 1}`"#,
+                "host",
+                "a.org"
+            )
+            .unwrap(),
+            "a.org:8081"
+        );
+
+        run(
+            connection,
+            r#"set("mode", if value == "on" { "dark" } else { "light" })"#,
+            "x",
+            "on",
+        )
+        .unwrap();
+        assert_eq!(store.get("mode").unwrap().value, "dark");
+
+        assert!(matches!(
+            run(connection, r#"set("mode", "x"); get("missing")"#, "x", ""),
+            Err(Error::Invalid(_))
+        ));
+        assert_eq!(store.get("mode").unwrap().value, "dark");
+        assert!(run(connection, "loop {}", "x", "").is_err());
+
+        let transform = crate::transform::Transform {
+            script: Some("value.to_upper()".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            crate::commands::transform_cmd(connection, "mode".to_string(), transform).unwrap(),
+            "DARK"
+        );
+        crate::commands::compute_cmd(
+            connection,
+            "mode".to_string(),
+            Some(r#"get("port") + "/tcp""#.to_string()),
+            None,
+            true,
+        )
+        .unwrap();
+        let entry = crate::store::compute(connection, store.get("mode").unwrap()).unwrap();
+        assert_eq!(entry.value, "8080/tcp");
+    }
+}
@@ -0,0 +1,121 @@
+//! Module rendering an entry as a [Segment] of a shell prompt, see `prompt`
+//!
+//! Values like `false`, `off`, `no`, `0` & the empty string (as well as missing entries) count as
+//! off, anything else as on. The color codes are wrapped for the shell if asked to, so bash & zsh
+//! don't count them towards the width of the prompt.
+use clap::{Args, ValueEnum};
+
+/// The colors a [Segment] can be printed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+/// The shells whose prompts need color codes wrapped
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    /// Don't wrap the color codes, e.g. for starship or fish
+    #[default]
+    None,
+    /// Wrap them in `\[` & `\]` for `PS1`
+    Bash,
+    /// Wrap them in `%{` & `%}` for `PROMPT`
+    Zsh,
+}
+
+/// How an entry is shown in a prompt
+#[derive(Debug, Clone, Default, Args)]
+pub struct Segment {
+    /// Printed if the entry is on, the value itself if not given (`{value}` is replaced by it)
+    #[arg(long, value_name = "TEXT")]
+    pub true_text: Option<String>,
+    /// Printed if the entry is off or doesn't exist (`{value}` is replaced by the value)
+    #[arg(long, value_name = "TEXT", default_value = "")]
+    pub false_text: String,
+    /// The color of the segment if the entry is on
+    #[arg(long, value_enum, value_name = "COLOR")]
+    pub true_color: Option<Color>,
+    /// The color of the segment if the entry is off
+    #[arg(long, value_enum, value_name = "COLOR")]
+    pub false_color: Option<Color>,
+    /// Wrap the color codes for the prompt of this shell
+    #[arg(long, value_enum, default_value_t)]
+    pub shell: Shell,
+}
+
+impl Segment {
+    /// Returns the segment for an entry with the value `value`, colored if `color` is set
+    pub fn render(&self, value: Option<&str>, color: bool) -> String {
+        let on = value.is_some_and(|v| {
+            !matches!(
+                v.trim().to_lowercase().as_str(),
+                "" | "false" | "off" | "no" | "0"
+            )
+        });
+        let value = value.unwrap_or_default();
+
+        let (text, segment_color) = if on {
+            (self.true_text.as_deref().unwrap_or(value), self.true_color)
+        } else {
+            (self.false_text.as_str(), self.false_color)
+        };
+        let text = text.replace("{value}", value);
+
+        match segment_color.filter(|_| color && !text.is_empty()) {
+            Some(segment_color) => format!(
+                "{}{}{}",
+                self.escape(&format!("\x1b[{}m", 30 + segment_color as u8)),
+                text,
+                self.escape("\x1b[0m")
+            ),
+            None => text,
+        }
+    }
+
+    /// Helper function wrapping a color code for the shell
+    fn escape(&self, code: &str) -> String {
+        match self.shell {
+            Shell::None => code.to_string(),
+            Shell::Bash => format!("\\[{}\\]", code),
+            Shell::Zsh => format!("%{{{}%}}", code),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn render() {
+        let segment = Segment {
+            true_text: Some("VPN {value}".to_string()),
+            false_text: "-".to_string(),
+            true_color: Some(Color::Green),
+            ..Default::default()
+        };
+
+        assert_eq!(segment.render(Some("on"), false), "VPN on");
+        assert_eq!(segment.render(Some("Off"), true), "-");
+        assert_eq!(segment.render(None, true), "-");
+        assert_eq!(segment.render(Some("1"), true), "\x1b[32mVPN 1\x1b[0m");
+
+        let segment = Segment {
+            true_color: Some(Color::Red),
+            shell: Shell::Bash,
+            ..Default::default()
+        };
+        assert_eq!(
+            segment.render(Some("dark"), true),
+            "\\[\x1b[31m\\]dark\\[\x1b[0m\\]"
+        );
+        assert_eq!(segment.render(Some("false"), true), "");
+    }
+}
@@ -0,0 +1,118 @@
+//! Module binding entries to a session, so per-login state doesn't outlive it, see `set --session`
+//!
+//! An entry is bound either to the current login session (`$XDG_SESSION_ID`) or to any token, e.g.
+//! one per CI job. Entries of login sessions are deleted automatically once logind doesn't know
+//! the session anymore, those of tokens with `session end TOKEN`.
+use std::path::Path;
+
+use rusqlite::Connection;
+
+use crate::{store::resolve, Error, Result};
+
+/// The prefix of the sessions of logind, whose end is detected automatically
+const LOGIN: &str = "login:";
+
+/// The directory logind keeps a file per running session in
+const SESSIONS: &str = "/run/systemd/sessions";
+
+/// Returns the current login session
+///
+/// Will return [Error::Invalid] if there is none, e.g. in a cron job.
+pub fn current() -> Result<String> {
+    match std::env::var("XDG_SESSION_ID") {
+        Ok(id) if !id.is_empty() => Ok(format!("{}{}", LOGIN, id)),
+        _ => Err(Error::Invalid(
+            "not in a login session, $XDG_SESSION_ID isn't set".to_string(),
+        )),
+    }
+}
+
+/// Binds the entry `name` to `session`, the current login session if it's `None`
+pub fn bind(connection: &Connection, name: &str, session: Option<String>) -> Result<()> {
+    let session = match session {
+        Some(session) => session,
+        None => current()?,
+    };
+
+    connection.execute(
+        "UPDATE data SET session = ? WHERE name = ?",
+        (session, resolve(connection, name)?),
+    )?;
+
+    Ok(())
+}
+
+/// Deletes the entries of `session` & returns their number
+///
+/// Frozen entries are kept, but no longer bound to the session.
+pub fn end(connection: &Connection, session: &str) -> Result<usize> {
+    let deleted = connection.execute(
+        "DELETE FROM data WHERE session = ? AND frozen IS NULL",
+        [session],
+    )?;
+    connection.execute(
+        "UPDATE data SET session = NULL WHERE session = ?",
+        [session],
+    )?;
+
+    Ok(deleted)
+}
+
+/// Deletes the entries of login sessions which have ended & returns their number
+///
+/// Nothing is deleted on systems without logind.
+pub fn end_stale(connection: &Connection) -> Result<usize> {
+    end_stale_in(connection, Path::new(SESSIONS))
+}
+
+/// Helper function for [end_stale] with the directory of logind's sessions
+fn end_stale_in(connection: &Connection, sessions: &Path) -> Result<usize> {
+    if !sessions.is_dir() || connection.is_readonly(rusqlite::DatabaseName::Main)? {
+        return Ok(0);
+    }
+
+    let ended = connection
+        .prepare("SELECT DISTINCT session FROM data WHERE session LIKE ? || '%'")?
+        .query_map([LOGIN], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?
+        .into_iter()
+        .filter(|s| !sessions.join(&s[LOGIN.len()..]).exists());
+
+    let mut deleted = 0;
+    for session in ended {
+        deleted += end(connection, &session)?;
+    }
+
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sessions() {
+        let store = crate::Store::open_in_memory().unwrap();
+        let connection = store.connection();
+        for name in ["durable", "job", "login", "running"] {
+            store.set(name, Some("1"), None).unwrap();
+        }
+
+        bind(connection, "job", Some("ci-42".to_string())).unwrap();
+        bind(connection, "login", Some(format!("{}3", LOGIN))).unwrap();
+        bind(connection, "running", Some(format!("{}4", LOGIN))).unwrap();
+
+        let sessions =
+            std::env::temp_dir().join(format!("config-store-sessions-{}", std::process::id()));
+        std::fs::create_dir_all(&sessions).unwrap();
+        std::fs::write(sessions.join("4"), "").unwrap();
+
+        assert_eq!(end_stale_in(connection, &sessions).unwrap(), 1);
+        assert!(!store.exists("login").unwrap());
+        assert!(store.exists("running").unwrap());
+        std::fs::remove_dir_all(sessions).unwrap();
+
+        assert_eq!(end(connection, "ci-42").unwrap(), 1);
+        assert!(store.exists("durable").unwrap());
+    }
+}
@@ -0,0 +1,70 @@
+//! Module containing the settings of a db, stored in the `settings` table
+//!
+//! Settings belong to the db rather than the binary, so every tool using the same db (the command
+//! line, [Store](crate::Store), the bindings) follows them.
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::Result;
+
+/// The maximum number of entries, see [crate::store::evict]
+pub const CAP: &str = "cap";
+/// Whether reads of entries are counted, see [crate::store::record_access]
+pub const TRACK_ACCESS: &str = "track_access";
+/// The git repository changes are committed to, see [crate::git]
+pub const GIT_REPO: &str = "git_repo";
+/// The destination backups are copied to, see [crate::remote]
+pub const BACKUP_REMOTE: &str = "backup_remote";
+/// The manifest declaring the known entries, see [crate::manifest::declared]
+pub const SCHEMA: &str = "schema";
+/// Whether the checksums of values are kept, see [crate::checksum]
+pub const CHECKSUMS: &str = "checksums";
+/// Whether entries are signed & checked on read, see [crate::signing]
+pub const SIGNED: &str = "signed";
+/// Whether names are case-insensitive, see [crate::store::canonical]
+pub const IGNORE_CASE: &str = "ignore_case";
+/// The characters allowed in new names, see [crate::naming]
+pub const NAME_CHARSET: &str = "name_charset";
+/// The maximum length of new names, see [crate::naming]
+pub const NAME_MAX_LENGTH: &str = "name_max_length";
+/// The prefixes new names have to start with, one per line, see [crate::naming]
+pub const NAME_PREFIXES: &str = "name_prefixes";
+/// The maximum size of values in bytes, see [crate::limits]
+pub const MAX_VALUE_SIZE: &str = "max_value_size";
+/// The maximum size of alternates in bytes, see [crate::limits]
+pub const MAX_ALTERNATE_SIZE: &str = "max_alternate_size";
+
+/// Creates the `settings` table
+pub fn init(connection: &Connection) -> rusqlite::Result<()> {
+    connection.execute(
+        "
+        CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );",
+        (),
+    )?;
+
+    Ok(())
+}
+
+/// Returns the value of a setting, or `None` if it isn't set
+pub fn get(connection: &Connection, key: &str) -> Result<Option<String>> {
+    Ok(connection
+        .query_row("SELECT value FROM settings WHERE key = ?", [key], |row| {
+            row.get(0)
+        })
+        .optional()?)
+}
+
+/// Changes the value of a setting, removing it if `value` is `None`
+pub fn set(connection: &Connection, key: &str, value: Option<&str>) -> Result<()> {
+    match value {
+        Some(value) => connection.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES (?, ?)",
+            [key, value],
+        )?,
+        None => connection.execute("DELETE FROM settings WHERE key = ?", [key])?,
+    };
+
+    Ok(())
+}
@@ -0,0 +1,166 @@
+//! Module expanding user-defined command aliases, so frequent commands become short ones
+//!
+//! The aliases are defined in the `[aliases]` section of the config file (see [path]) with lines
+//! like `dark = toggle theme.dark`. An unknown command `dark` is replaced by the words of its
+//! alias, arguments after it are kept, so `vols = get volume` allows `config-store vols -v`.
+//! Built-in commands can't be redefined & aliases aren't expanded recursively.
+use std::path::PathBuf;
+
+use crate::{Error, Result};
+
+/// The section of the config file containing the aliases
+const SECTION: &str = "aliases";
+
+/// Returns the path of the config file
+///
+/// This is `$CONFIG_STORE_CONFIG` if it's set, else `config-store/config` in
+/// `$XDG_CONFIG_HOME` (or `~/.config`).
+pub fn path() -> Option<PathBuf> {
+    let var = |name| std::env::var_os(name).filter(|v| !v.is_empty());
+
+    if let Some(path) = var("CONFIG_STORE_CONFIG") {
+        return Some(path.into());
+    }
+    let config = var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| var("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config.join("config-store").join("config"))
+}
+
+/// Returns the words the command `name` expands to, if it's an alias in the config file
+///
+/// A missing config file has no aliases. Will return [Error::Invalid] if the file is malformed.
+pub fn expand(name: &str) -> Result<Option<Vec<String>>> {
+    let Some(path) = path().filter(|p| p.is_file()) else {
+        return Ok(None);
+    };
+    let config = std::fs::read_to_string(&path)?;
+
+    Ok(parse(&config)
+        .map_err(|error| Error::Invalid(format!("{}: {}", path.display(), error)))?
+        .into_iter()
+        .find(|(alias, _)| alias == name)
+        .map(|(_, words)| words))
+}
+
+/// Helper function parsing the aliases of a config file into their names & words
+///
+/// Empty lines & lines starting with `#` or `;` are skipped, as are other sections.
+fn parse(config: &str) -> std::result::Result<Vec<(String, Vec<String>)>, String> {
+    let mut aliases = Vec::new();
+    let mut section = None;
+
+    for (i, line) in config.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[') {
+            section = Some(
+                header
+                    .strip_suffix(']')
+                    .ok_or_else(|| format!("line {}: unterminated section header", i + 1))?
+                    .trim(),
+            );
+            continue;
+        }
+        if section != Some(SECTION) {
+            continue;
+        }
+
+        let (name, command) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {}: expected name = command", i + 1))?;
+        let words = split(command).map_err(|error| format!("line {}: {}", i + 1, error))?;
+        if name.trim().is_empty() || words.is_empty() {
+            return Err(format!("line {}: expected name = command", i + 1));
+        }
+
+        aliases.push((name.trim().to_string(), words));
+    }
+
+    Ok(aliases)
+}
+
+/// Helper function splitting a command into words like a shell, with quotes & backslashes
+fn split(command: &str) -> std::result::Result<Vec<String>, &'static str> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' | '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some('\\') if c == '"' => word.push(chars.next().ok_or("unclosed quote")?),
+                        Some(q) => word.push(q),
+                        None => return Err("unclosed quote"),
+                    }
+                }
+            }
+            '\\' => word
+                .get_or_insert_with(String::new)
+                .push(chars.next().ok_or("trailing backslash")?),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+
+    Ok(words)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parsing() {
+        let config = "
+            # shortcuts
+            [aliases]
+            dark = toggle theme.dark
+            vols=get volume -v
+            greet = set greeting -v 'hello world' -a \"it's \\\"me\\\"\" ''
+
+            [other]
+            ignored = get x
+        ";
+        assert_eq!(
+            parse(config).unwrap(),
+            vec![
+                (
+                    "dark".to_string(),
+                    vec!["toggle".to_string(), "theme.dark".to_string()]
+                ),
+                (
+                    "vols".to_string(),
+                    ["get", "volume", "-v"].map(String::from).to_vec()
+                ),
+                (
+                    "greet".to_string(),
+                    [
+                        "set",
+                        "greeting",
+                        "-v",
+                        "hello world",
+                        "-a",
+                        "it's \"me\"",
+                        ""
+                    ]
+                    .map(String::from)
+                    .to_vec()
+                ),
+            ]
+        );
+
+        assert!(parse("[aliases]\nbroken").is_err());
+        assert!(parse("[aliases]\nempty = ").is_err());
+        assert!(parse("[aliases]\nquote = get 'x").is_err());
+    }
+}
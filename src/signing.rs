@@ -0,0 +1,194 @@
+//! Module signing entries with an HMAC, so scripts can refuse values changed without the key
+//!
+//! Signing is opt-in (`sign` with `--hmac-key FILE`). While a key is given, temporary triggers of
+//! the connection store the HMAC-SHA256 of the name, value & alternate of each changed entry in
+//! the `hmac` column. Every read with the key checks it, so values changed by a process (or user)
+//! without the key are refused, even if it also turned signing off, see [verified]. Entries of
+//! layers aren't signed & not checked.
+use std::sync::Arc;
+
+use hmac::{Hmac, Mac};
+use rusqlite::{functions::FunctionFlags, Connection, OptionalExtension};
+use sha2::Sha256;
+
+use crate::{settings, Error, Result};
+
+/// The prefix of the errors raised by `hmac_verify`, see [Error::Tampered]
+pub const TAMPERED: &str = "tampered: ";
+
+/// Registers the `hmac_sign` & `hmac_verify` SQL functions for `key` & creates the triggers
+/// signing changes
+pub fn init(connection: &Connection, key: Vec<u8>) -> rusqlite::Result<()> {
+    let key = Arc::new(key);
+    let flags = FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC;
+
+    let signing = key.clone();
+    connection.create_scalar_function("hmac_sign", 3, flags, move |context| {
+        Ok(sign(
+            &signing,
+            &context.get::<Option<String>>(0)?.unwrap_or_default(),
+            &context.get::<Option<String>>(1)?.unwrap_or_default(),
+            context.get::<Option<String>>(2)?.as_deref(),
+        ))
+    })?;
+
+    // Fails instead of filtering, so a tampered entry can't simply look deleted
+    connection.create_scalar_function("hmac_verify", 4, flags, move |context| {
+        let name = context.get::<Option<String>>(0)?.unwrap_or_default();
+        let expected = sign(
+            &key,
+            &name,
+            &context.get::<Option<String>>(1)?.unwrap_or_default(),
+            context.get::<Option<String>>(2)?.as_deref(),
+        );
+
+        if context.get::<Option<String>>(3)? == Some(expected) {
+            Ok(true)
+        } else {
+            Err(rusqlite::Error::UserFunctionError(
+                format!("{}{}", TAMPERED, name).into(),
+            ))
+        }
+    })?;
+
+    connection.execute_batch(
+        "
+        CREATE TEMP TRIGGER IF NOT EXISTS hmac_insert AFTER INSERT ON data
+        BEGIN
+            UPDATE data SET hmac = hmac_sign(NEW.name, NEW.value, NEW.alternate)
+                WHERE id = NEW.id;
+        END;
+
+        CREATE TEMP TRIGGER IF NOT EXISTS hmac_update
+        AFTER UPDATE OF name, value, alternate ON data
+        BEGIN
+            UPDATE data SET hmac = hmac_sign(NEW.name, NEW.value, NEW.alternate)
+                WHERE id = NEW.id;
+        END;",
+    )
+}
+
+/// Turns signing on, signing all entries with the key of the connection, or off, removing the
+/// signatures
+///
+/// Will return [Error::Invalid] if signing should be turned on without a key.
+pub fn enable(connection: &Connection, on: bool) -> Result<()> {
+    if on && !keyed(connection)? {
+        return Err(Error::Invalid(
+            "signing needs a key, see --hmac-key".to_string(),
+        ));
+    }
+
+    settings::set(connection, settings::SIGNED, on.then_some("on"))?;
+    // `hmac_sign` only exists with a key
+    connection.execute(
+        if on {
+            "UPDATE data SET hmac = hmac_sign(name, value, alternate)"
+        } else {
+            "UPDATE data SET hmac = NULL"
+        },
+        (),
+    )?;
+
+    Ok(())
+}
+
+/// Returns whether reads have to check the signatures, which they do whenever a key was given
+///
+/// Reading entries with `hmac_verify(name, value, alternate, hmac)` as a condition fails with
+/// [Error::Tampered] for entries which are unsigned or signed with another key, see
+/// [layers::source](crate::layers). Will return [Error::Invalid] if signing is on but no key was
+/// given.
+pub fn verified(connection: &Connection) -> Result<bool> {
+    if keyed(connection)? {
+        return Ok(true);
+    }
+    if settings::get(connection, settings::SIGNED)?.is_some() {
+        return Err(Error::Invalid(
+            "the entries are signed, give the key with --hmac-key".to_string(),
+        ));
+    }
+
+    Ok(false)
+}
+
+/// Helper function returning whether a key was given for the connection, see [init]
+fn keyed(connection: &Connection) -> Result<bool> {
+    Ok(connection
+        .query_row(
+            "SELECT 1 FROM temp.sqlite_master WHERE type = 'trigger' AND name = 'hmac_insert'",
+            (),
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some())
+}
+
+/// Helper function returning the hex encoded HMAC-SHA256 of an entry
+///
+/// The name is signed as well, so values can't be swapped between entries. So is the alternate,
+/// which becomes the value when toggling.
+fn sign(key: &[u8], name: &str, value: &str, alternate: Option<&str>) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(name.as_bytes());
+    mac.update(&[0]);
+    mac.update(value.as_bytes());
+    // A missing alternate has to differ from an empty one
+    match alternate {
+        Some(alternate) => {
+            mac.update(&[1]);
+            mac.update(alternate.as_bytes());
+        }
+        None => mac.update(&[0]),
+    }
+
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Filter;
+
+    #[test]
+    fn signing() {
+        let store = crate::Store::open_in_memory().unwrap();
+        let connection = store.connection();
+        store.set("a", Some("1"), Some("0")).unwrap();
+
+        assert!(matches!(enable(connection, true), Err(Error::Invalid(_))));
+        init(connection, b"secret".to_vec()).unwrap();
+        // Unsigned entries are refused as soon as a key is given
+        assert!(matches!(store.get("a"), Err(Error::Tampered(name)) if name == "a"));
+
+        enable(connection, true).unwrap();
+        store.set("b", Some("2"), None).unwrap();
+        assert_eq!(store.get("a").unwrap().value, "1");
+        assert_eq!(store.toggle("a").unwrap(), "0");
+        assert_eq!(store.list(&Filter::default()).unwrap().len(), 2);
+
+        // Without the trigger, like a process without the key
+        connection
+            .execute_batch("DROP TRIGGER temp.hmac_update")
+            .unwrap();
+        connection
+            .execute("UPDATE data SET value = '3' WHERE name = 'b'", ())
+            .unwrap();
+        assert!(matches!(store.get("b"), Err(Error::Tampered(_))));
+        assert!(matches!(
+            store.list(&Filter::default()),
+            Err(Error::Tampered(_))
+        ));
+
+        // Swapping the alternate or turning signing off doesn't get around the check
+        connection
+            .execute("UPDATE data SET alternate = 'evil' WHERE name = 'a'", ())
+            .unwrap();
+        settings::set(connection, settings::SIGNED, None).unwrap();
+        assert!(matches!(store.get("a"), Err(Error::Tampered(_))));
+    }
+}
@@ -0,0 +1,931 @@
+//! Module containing the [Store], the typed API for using config-store as a library
+//!
+//! The helper functions in this module are shared with [crate::commands], which formats their
+//! results for the command line.
+use std::path::Path;
+
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
+
+use crate::{
+    entry::Entry,
+    error::{Error, Result},
+    filter::Filter,
+    layers, schema, settings, time,
+};
+
+/// A config-store db
+///
+/// ```no_run
+/// use config_store::Store;
+///
+/// let store = Store::open("/tmp/config-store.db")?;
+///
+/// store.set("theme", Some("dark"), Some("light"))?;
+/// assert_eq!(store.toggle("theme")?, "light");
+/// assert_eq!(store.get("theme")?.value, "light");
+/// # Ok::<(), config_store::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct Store {
+    connection: Connection,
+}
+
+impl Store {
+    /// Opens (or creates) the db at `path`
+    ///
+    /// `path` may also be a `file:` URI or `:memory:`, see [open_flags].
+    pub fn open(path: impl AsRef<Path>) -> Result<Store> {
+        let flags = path
+            .as_ref()
+            .to_str()
+            .map_or(OpenFlags::default(), open_flags);
+
+        Store::from_connection(Connection::open_with_flags(path, flags)?)
+    }
+
+    /// Opens a new db, which only lives in memory
+    pub fn open_in_memory() -> Result<Store> {
+        Store::from_connection(Connection::open_in_memory()?)
+    }
+
+    /// Uses an existing connection as a [Store], creating all tables if needed
+    pub fn from_connection(connection: Connection) -> Result<Store> {
+        schema::init(&connection)?;
+
+        Ok(Store { connection })
+    }
+
+    /// Adds the db at `path` as the lowest layer, see [crate::layers]
+    ///
+    /// Returns `false` if there is no config-store db at `path`.
+    pub fn add_layer(&self, path: impl AsRef<Path>) -> Result<bool> {
+        layers::attach(&self.connection, path.as_ref())
+    }
+
+    /// The underlying connection to the db
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// Gets an [Entry] by its name or alias
+    ///
+    /// Will return [Error::EntryNotFound] if the entry doesn't exist.
+    pub fn get(&self, name: &str) -> Result<Entry> {
+        apply_resets(&self.connection)?;
+        let entry = select(&self.connection, &resolve(&self.connection, name)?)?;
+        record_access(&self.connection, entry._id)?;
+
+        Ok(entry)
+    }
+
+    /// Checks if an [Entry] with the name (or alias) exists
+    pub fn exists(&self, name: &str) -> Result<bool> {
+        exists(&self.connection, &resolve(&self.connection, name)?)
+    }
+
+    /// Creates or updates an [Entry], returning its new state
+    ///
+    /// `value` & `alternate` which are `None` are left unchanged for existing entries. New ones
+    /// get an empty value & no alternate. With `ignore-case` the entry differing only in case is
+    /// changed, like on the command line.
+    pub fn set(&self, name: &str, value: Option<&str>, alternate: Option<&str>) -> Result<Entry> {
+        let name = &canonical(&self.connection, name)?;
+        set(
+            &self.connection,
+            name,
+            value.map(str::to_string),
+            alternate.map(str::to_string),
+            None,
+            None,
+            false,
+        )?;
+
+        select(&self.connection, name)
+    }
+
+    /// Toggles an [Entry]'s value & alternate returning the new value
+    pub fn toggle(&self, name: &str) -> Result<String> {
+        toggle(&self.connection, name)
+    }
+
+    /// Deletes an [Entry], returning whether it existed
+    ///
+    /// With `ignore-case` the entry differing only in case is deleted, like on the command line.
+    pub fn delete(&self, name: &str) -> Result<bool> {
+        let name = canonical(&self.connection, name)?;
+
+        Ok(self
+            .connection
+            .execute("DELETE FROM data WHERE name = ?", [name])?
+            > 0)
+    }
+
+    /// Lists all entries matching the [Filter]
+    pub fn list(&self, filter: &Filter) -> Result<Vec<Entry>> {
+        select_filtered(&self.connection, filter)
+    }
+
+    /// Carries out all scheduled resets (see `set --reset-at`) which are due
+    ///
+    /// Returns the number of reset entries. The command line does this whenever it opens the db.
+    pub fn apply_resets(&self) -> Result<usize> {
+        apply_resets(&self.connection)
+    }
+
+    /// Resets an [Entry]'s value to its default returning the new value
+    ///
+    /// Will return [Error::NoDefault] if the entry has no default.
+    pub fn reset(&self, name: &str) -> Result<String> {
+        layers::copy_up(&self.connection, &resolve(&self.connection, name)?)?;
+        let entry = self.get(name)?;
+
+        let default = entry.default.ok_or(Error::NoDefault)?;
+
+        self.connection.execute(
+            "UPDATE data SET value = ?, updated_at = ? WHERE id = ?",
+            (&default, time::now(), entry._id),
+        )?;
+
+        Ok(default)
+    }
+
+    /// Runs `f` in a transaction, so all of its changes are made at once or not at all
+    ///
+    /// If `f` returns an error, everything it changed is rolled back & the error is returned.
+    /// Transactions can be nested, an inner one failing only rolls back its own changes.
+    ///
+    /// ```
+    /// # let store = config_store::Store::open_in_memory()?;
+    /// store.transaction(|tx| {
+    ///     tx.set("theme", Some("dark"), Some("light"))?;
+    ///     tx.toggle("theme")
+    /// })?;
+    /// # Ok::<(), config_store::Error>(())
+    /// ```
+    pub fn transaction<T>(&self, f: impl FnOnce(&Store) -> Result<T>) -> Result<T> {
+        transaction(&self.connection, || f(self))
+    }
+}
+
+/// Helper function to get an [Entry] from the db
+///
+/// Since it uses [rusqlite::Connection::query_row] it will only ever return the first match.
+///
+/// Having multiple different entries with the same name is not supported.
+pub(crate) fn select(connection: &Connection, name: &str) -> Result<Entry> {
+    connection
+        .query_row(
+            &format!(
+                "SELECT {} FROM {} WHERE name = ?",
+                Entry::COLUMNS,
+                layers::source(connection)?
+            ),
+            [name],
+            Entry::from_row,
+        )
+        .optional()?
+        .ok_or_else(|| not_found(connection, name))
+}
+
+/// Helper function creating an [Error::EntryNotFound] for `name`, suggesting similar names of
+/// entries & aliases
+pub(crate) fn not_found(connection: &Connection, name: &str) -> Error {
+    let names = || -> Result<Vec<String>> {
+        Ok(connection
+            .prepare(&format!(
+                "SELECT name FROM {} WHERE name IS NOT NULL UNION SELECT alias FROM aliases",
+                layers::source(connection)?
+            ))?
+            .query_map((), |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?)
+    };
+
+    Error::EntryNotFound {
+        name: name.to_string(),
+        // Failing to suggest something shouldn't hide the actual error
+        suggestions: names()
+            .map(|names| crate::suggest::similar(name, names))
+            .unwrap_or_default(),
+    }
+}
+
+/// Helper function to check if an [Entry] exists
+pub(crate) fn exists(connection: &Connection, name: &str) -> Result<bool> {
+    Ok(connection
+        .prepare(&format!(
+            "SELECT name FROM {} WHERE name = ?",
+            layers::source(connection)?
+        ))?
+        .exists([name])?)
+}
+
+/// Helper function to resolve an alias to the name of the [Entry] it points to
+///
+/// Names which aren't aliases are returned like [canonical].
+pub(crate) fn resolve(connection: &Connection, name: &str) -> Result<String> {
+    match connection.query_row(
+        "SELECT target FROM aliases WHERE alias = ?",
+        [name],
+        |row| row.get(0),
+    ) {
+        Ok(target) => Ok(target),
+        Err(rusqlite::Error::QueryReturnedNoRows) => canonical(connection, name),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Helper function returning the name of the [Entry] `name` refers to if names are
+/// case-insensitive (see `ignore-case`), preferring an exact match
+///
+/// Names without a match (& all names while the setting is off) are returned unchanged.
+pub(crate) fn canonical(connection: &Connection, name: &str) -> Result<String> {
+    if settings::get(connection, settings::IGNORE_CASE)?.is_none() {
+        return Ok(name.to_string());
+    }
+
+    Ok(connection
+        .prepare_cached(
+            "SELECT name FROM data WHERE name = ?1 COLLATE NOCASE
+            ORDER BY name = ?1 DESC, name LIMIT 1",
+        )?
+        .query_row([name], |row| row.get(0))
+        .optional()?
+        .unwrap_or_else(|| name.to_string()))
+}
+
+/// Returns the groups of names which only differ in case
+pub(crate) fn case_duplicates(connection: &Connection) -> Result<Vec<Vec<String>>> {
+    let groups = connection
+        .prepare(
+            "SELECT group_concat(name, char(0)) FROM data
+            GROUP BY name COLLATE NOCASE HAVING count(*) > 1 ORDER BY name COLLATE NOCASE",
+        )?
+        .query_map((), |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+
+    Ok(groups
+        .iter()
+        .map(|group| {
+            let mut names: Vec<String> = group.split('\0').map(str::to_string).collect();
+            names.sort();
+            names
+        })
+        .collect())
+}
+
+/// Helper function to create a new [Entry]
+pub(crate) fn insert(
+    connection: &Connection,
+    name: String,
+    value: String,
+    alternate: String,
+    description: Option<String>,
+    default: Option<String>,
+) -> Result<()> {
+    connection.execute(
+        "INSERT INTO data (name, value, alternate, description, default_value, created_at, updated_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+        (name, value, alternate, description, default, time::now()),
+    )?;
+
+    evict(connection)?;
+
+    Ok(())
+}
+
+/// Returns the flags to open the db at `path` with
+///
+/// `file:` URIs like `file:/etc/config-store.db?mode=ro` are passed to sqlite, which understands
+/// parameters like `mode=ro|rw|rwc|memory` & `immutable=1`. Read-only URIs are opened without
+/// write access at all, so sqlite never tries to create or change the file (or a journal next to
+/// it). Commands which would change a read-only db fail.
+pub fn open_flags(path: &str) -> OpenFlags {
+    let read_only = path.strip_prefix("file:").is_some_and(|uri| {
+        uri.split_once('?').is_some_and(|(_, query)| {
+            query.split('&').any(|parameter| {
+                matches!(
+                    parameter,
+                    "mode=ro" | "immutable=1" | "immutable=true" | "immutable=yes" | "immutable=on"
+                )
+            })
+        })
+    });
+
+    if read_only {
+        OpenFlags::SQLITE_OPEN_READ_ONLY
+            | OpenFlags::SQLITE_OPEN_URI
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX
+    } else {
+        OpenFlags::default()
+    }
+}
+
+/// Helper function carrying out the scheduled resets which are due, see [Store::apply_resets]
+///
+/// The value is set to `reset_value`. If the alternate is the reset value, value & alternate are
+/// swapped instead, so toggling keeps working. Entries which already have the reset value are
+/// left alone. Nothing is done for read-only dbs.
+pub(crate) fn apply_resets(connection: &Connection) -> Result<usize> {
+    if connection.is_readonly(rusqlite::DatabaseName::Main)? {
+        return Ok(0);
+    }
+
+    transaction(connection, || {
+        let reset = connection.execute(
+            "UPDATE data SET
+                alternate = CASE WHEN alternate = reset_value THEN value ELSE alternate END,
+                value = reset_value,
+                updated_at = ?1
+            WHERE reset_at <= ?1 AND value IS NOT reset_value AND frozen IS NULL",
+            [time::now()],
+        )?;
+        connection.execute(
+            "UPDATE data SET reset_at = NULL, reset_value = NULL WHERE reset_at <= ?",
+            [time::now()],
+        )?;
+
+        Ok(reset)
+    })
+}
+
+/// Helper function to remember that an [Entry] was read, see [evict]
+///
+/// Reads are only recorded while a [settings::CAP] is set or [settings::TRACK_ACCESS] is on, the
+/// latter counts them as well, so plain reads never write to the db. Only `accessed_at` & `reads`
+/// are changed, so reads don't show up in the audit log. Nothing is recorded for read-only dbs.
+pub(crate) fn record_access(connection: &Connection, id: i32) -> Result<()> {
+    if connection.is_readonly(rusqlite::DatabaseName::Main)? {
+        return Ok(());
+    }
+
+    let counted = settings::get(connection, settings::TRACK_ACCESS)?.is_some();
+    if !counted && settings::get(connection, settings::CAP)?.is_none() {
+        return Ok(());
+    }
+
+    connection.execute(
+        "UPDATE data SET accessed_at = ?, reads = IFNULL(reads, 0) + ? WHERE id = ?",
+        (time::now(), counted as i64, id),
+    )?;
+
+    Ok(())
+}
+
+/// Helper function giving a computed [Entry] (see `compute`) the output of its command as value
+///
+/// The command is run through `sh -c` (or as a Rhai script, see `compute --script`) on every read,
+/// unless it has a TTL: then the output is stored as the value & reused until the TTL has passed.
+/// Other entries are returned unchanged. Will return [Error::Io] if the command fails & refuses to
+/// run it for dbs others can write, see [crate::permissions::trusted].
+pub(crate) fn compute(connection: &Connection, mut entry: Entry) -> Result<Entry> {
+    let Some((command, ttl, computed_at, script)) = connection
+        .query_row(
+            "SELECT command, command_ttl, computed_at, command_script FROM data
+                WHERE name = ? AND command IS NOT NULL",
+            [&entry.name],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<i64>>(1)?,
+                    row.get::<_, Option<i64>>(2)?,
+                    row.get::<_, Option<bool>>(3)?,
+                ))
+            },
+        )
+        .optional()?
+    else {
+        return Ok(entry);
+    };
+
+    let now = time::now();
+    if ttl.zip(computed_at).is_some_and(|(ttl, at)| now < at + ttl) {
+        return Ok(entry);
+    }
+
+    crate::permissions::trusted(
+        connection,
+        &format!("run the command of the computed entry '{}'", entry.name),
+    )?;
+
+    entry.value = if script == Some(true) {
+        run_script(connection, &command, &entry.name, &entry.value)?
+    } else {
+        let output = std::process::Command::new("sh")
+            .args(["-c", &command])
+            .output()
+            .map_err(|e| std::io::Error::new(e.kind(), format!("failed to run sh: {}", e)))?;
+        if !output.status.success() {
+            return Err(std::io::Error::other(format!(
+                "`{}` of '{}' failed: {}",
+                command,
+                entry.name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+            .into());
+        }
+
+        String::from_utf8_lossy(&output.stdout)
+            .trim_end_matches(['\n', '\r'])
+            .to_string()
+    };
+
+    if ttl.is_some() && !connection.is_readonly(rusqlite::DatabaseName::Main)? {
+        connection.execute(
+            "UPDATE data SET value = ?, computed_at = ? WHERE name = ?",
+            (&entry.value, now, &entry.name),
+        )?;
+    }
+
+    Ok(entry)
+}
+
+/// Helper function running a Rhai script for the entry `name` (see [crate::script::run])
+///
+/// Will return [Error::Invalid] if config-store was built without the `script` feature.
+pub(crate) fn run_script(
+    connection: &Connection,
+    script: &str,
+    name: &str,
+    value: &str,
+) -> Result<String> {
+    #[cfg(feature = "script")]
+    return crate::script::run(connection, script, name, value);
+    #[cfg(not(feature = "script"))]
+    {
+        let _ = (connection, script, name, value);
+        Err(Error::Invalid(
+            "scripts need config-store built with the `script` feature".to_string(),
+        ))
+    }
+}
+
+/// Helper function expanding `{{name}}` placeholders in the value & alternate of an [Entry] with
+/// the values of the entries they reference, see `get --resolve`
+///
+/// Referenced values are expanded as well, so placeholders can be nested. Will return
+/// [Error::Invalid] if entries reference each other in a cycle & [Error::EntryNotFound] if a
+/// referenced entry doesn't exist.
+pub(crate) fn interpolate(connection: &Connection, mut entry: Entry) -> Result<Entry> {
+    let placeholder = regex::Regex::new(r"\{\{\s*([^{}\s]+)\s*\}\}").expect("valid regex");
+    let mut chain = vec![entry.name.clone()];
+
+    entry.value = expand(connection, &placeholder, &entry.value, &mut chain)?;
+    entry.alternate = entry
+        .alternate
+        .map(|alternate| expand(connection, &placeholder, &alternate, &mut chain))
+        .transpose()?;
+
+    Ok(entry)
+}
+
+/// Helper function for [interpolate], `chain` holds the names of the entries being expanded
+fn expand(
+    connection: &Connection,
+    placeholder: &regex::Regex,
+    text: &str,
+    chain: &mut Vec<String>,
+) -> Result<String> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut last = 0;
+
+    for captures in placeholder.captures_iter(text) {
+        let whole = captures.get(0).expect("group 0 always matches");
+        let name = resolve(connection, &captures[1])?;
+
+        if chain.contains(&name) {
+            chain.push(name);
+            return Err(Error::Invalid(format!(
+                "values reference each other in a cycle: {}",
+                chain.join(" -> ")
+            )));
+        }
+
+        let referenced = compute(connection, select(connection, &name)?)?;
+        chain.push(name);
+        let value = expand(connection, placeholder, &referenced.value, chain)?;
+        chain.pop();
+
+        expanded.push_str(&text[last..whole.start()]);
+        expanded.push_str(&value);
+        last = whole.end();
+    }
+
+    expanded.push_str(&text[last..]);
+    Ok(expanded)
+}
+
+/// Helper function deleting the least recently used entries exceeding the [settings::CAP]
+///
+/// Entries count as used when they are read or changed. Returns the number of deleted entries.
+pub(crate) fn evict(connection: &Connection) -> Result<usize> {
+    let Some(cap) = settings::get(connection, settings::CAP)? else {
+        return Ok(0);
+    };
+    let cap = cap
+        .parse::<i64>()
+        .map_err(|_| Error::Invalid(format!("invalid cap '{}'", cap)))?;
+
+    Ok(connection.execute(
+        "DELETE FROM data WHERE id IN (
+            SELECT id FROM data WHERE frozen IS NULL AND protected IS NULL
+            ORDER BY MAX(IFNULL(accessed_at, 0), IFNULL(updated_at, 0)), id
+            LIMIT MAX((SELECT COUNT(*) FROM data) - ?, 0)
+        )",
+        [cap],
+    )?)
+}
+
+/// Helper function running `f` inside a transaction
+///
+/// If `f` returns an error, all changes made by it are rolled back. Since this uses a savepoint
+/// it can be nested.
+pub(crate) fn transaction<T>(connection: &Connection, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    connection.execute_batch("SAVEPOINT config_store")?;
+
+    match f() {
+        Ok(result) => {
+            connection.execute_batch("RELEASE config_store")?;
+            Ok(result)
+        }
+        Err(error) => {
+            connection.execute_batch("ROLLBACK TO config_store; RELEASE config_store")?;
+            Err(error)
+        }
+    }
+}
+
+/// Starts a dry run: every change made until [end_dry_run] is rolled back, see `--dry-run`
+///
+/// Side effects outside the db (the keyring, hooks, files, ...) are skipped while [dry_run] says
+/// so. The marker is a temporary table created inside the transaction, so it's rolled back as
+/// well. Releasing a savepoint which was rolled back still changes the file, so this has to be
+/// the outermost transaction.
+pub fn begin_dry_run(connection: &Connection) -> Result<()> {
+    connection.execute_batch("BEGIN; CREATE TEMP TABLE dry_run (id INTEGER);")?;
+
+    Ok(())
+}
+
+/// Ends the dry run started by [begin_dry_run], rolling back all changes
+pub fn end_dry_run(connection: &Connection) -> Result<()> {
+    connection.execute_batch("ROLLBACK")?;
+
+    Ok(())
+}
+
+/// Returns whether the connection is in a dry run, see [begin_dry_run]
+pub fn dry_run(connection: &Connection) -> Result<bool> {
+    Ok(connection
+        .prepare("SELECT 1 FROM temp.sqlite_master WHERE name = 'dry_run'")?
+        .exists(())?)
+}
+
+/// Condition selecting every entry for which a more recently updated entry with the same name
+/// exists
+///
+/// The schema doesn't enforce unique names, so dbs written by other tools (or very old versions)
+/// may contain such duplicates.
+pub(crate) const DUPLICATE: &str = "EXISTS (
+    SELECT 1 FROM data AS newer WHERE newer.name = data.name
+        AND (IFNULL(newer.updated_at, 0), newer.id) > (IFNULL(data.updated_at, 0), data.id)
+)";
+
+/// Helper function to get all entries matching the [Filter]
+pub(crate) fn select_filtered(connection: &Connection, filter: &Filter) -> Result<Vec<Entry>> {
+    let (clause, params) = filter.sql();
+
+    Ok(connection
+        .prepare(&format!(
+            "SELECT {} FROM {} {}",
+            Entry::COLUMNS,
+            layers::source(connection)?,
+            clause
+        ))?
+        .query_map(rusqlite::params_from_iter(params), Entry::from_row)?
+        .collect::<rusqlite::Result<Vec<Entry>>>()?)
+}
+
+/// Statement updating the entry named `?1`, see [set]
+///
+/// The parameters are the name, value, alternate, description, default & the current time. Fields
+/// which are `NULL` are left unchanged.
+pub(crate) const UPDATE: &str = "UPDATE data SET value = IFNULL(?2, value),
+    alternate = IFNULL(?3, alternate), description = IFNULL(?4, description),
+    default_value = IFNULL(?5, default_value), updated_at = ?6
+    WHERE name = ?1";
+
+/// Statement creating the entry named `?1` with the parameters of [UPDATE]
+///
+/// A `WHERE` clause can be appended to only create the entry under some condition.
+pub(crate) const INSERT: &str = "INSERT INTO data
+    (name, value, alternate, description, default_value, created_at, updated_at)
+    SELECT ?1, IFNULL(?2, ''), ?3, ?4, ?5, ?6, ?6";
+
+/// Helper function to create a new (if not `change_only`) [Entry] or update an existing one
+///
+/// Any of `new_value`, `new_alternate`, `new_description` & `new_default` which are `None` are
+/// left unchanged for existing entries. Each case is a single statement, an upsert for creating
+/// or updating. A secret entry getting a regular value loses its secret in the keyring.
+///
+/// Will return [Error::EntryNotFound] if `change_only == true` and the entry doesn't exist.
+pub(crate) fn set(
+    connection: &Connection,
+    name: &str,
+    new_value: Option<String>,
+    new_alternate: Option<String>,
+    new_description: Option<String>,
+    new_default: Option<String>,
+    change_only: bool,
+) -> Result<()> {
+    let name = &canonical(connection, name)?;
+    crate::naming::check(connection, name)?;
+    layers::copy_up(connection, name)?;
+    let secret = crate::keyring::overwritten(connection, name, new_value.as_deref())?;
+
+    let params = (
+        name,
+        new_value,
+        new_alternate,
+        new_description,
+        new_default,
+        time::now(),
+    );
+
+    if change_only {
+        if connection.prepare_cached(UPDATE)?.execute(params)? == 0 {
+            return Err(not_found(connection, name));
+        }
+        if secret {
+            crate::keyring::forget(connection, name);
+        }
+
+        return Ok(());
+    }
+
+    // Entries updated in the second they were created are mistaken for new ones, which only means
+    // checking the cap needlessly
+    let upsert = format!(
+        "{} WHERE true ON CONFLICT (name) DO UPDATE SET value = IFNULL(?2, value),
+            alternate = IFNULL(?3, alternate), description = IFNULL(?4, description),
+            default_value = IFNULL(?5, default_value), updated_at = ?6
+        RETURNING created_at IS ?6",
+        INSERT
+    );
+
+    let created = match connection.prepare_cached(&upsert) {
+        Ok(mut statement) => statement.query_row(params, |row| row.get(0))?,
+        // Without a unique index on the names (see `schema::index_names`) there is no conflict
+        // to upsert on, so the entry is updated or created separately
+        Err(rusqlite::Error::SqliteFailure(_, Some(message)))
+            if message.contains("ON CONFLICT clause does not match") =>
+        {
+            connection.prepare_cached(UPDATE)?.execute(params.clone())? == 0
+                && connection.prepare_cached(INSERT)?.execute(params)? > 0
+        }
+        Err(error) => return Err(error.into()),
+    };
+
+    if created {
+        evict(connection)?;
+    }
+    if secret {
+        crate::keyring::forget(connection, name);
+    }
+
+    Ok(())
+}
+
+/// Helper function to toggle an [Entry]'s value & alternate returning the new value
+///
+/// An alternate which was never set is toggled like an empty one.
+pub(crate) fn toggle(connection: &Connection, name: &str) -> Result<String> {
+    let name = resolve(connection, name)?;
+    layers::copy_up(connection, &name)?;
+    let entry = select(connection, &name)?;
+    let alternate = entry.alternate.unwrap_or_default();
+
+    connection.execute(
+        "UPDATE data SET value = ?, alternate = ?, updated_at = ? WHERE name = ?",
+        (&alternate, entry.value, time::now(), entry.name),
+    )?;
+
+    Ok(alternate)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn store_api() {
+        let store = Store::open_in_memory().unwrap();
+
+        let entry = store.set("theme", Some("dark"), Some("light")).unwrap();
+        assert_eq!(entry.value, "dark");
+
+        assert_eq!(store.toggle("theme").unwrap(), "light");
+        assert_eq!(
+            store.get("theme").unwrap().alternate.as_deref(),
+            Some("dark")
+        );
+        assert!(store.exists("theme").unwrap());
+        assert_eq!(store.list(&Filter::default()).unwrap().len(), 1);
+
+        assert!(matches!(store.reset("theme"), Err(Error::NoDefault)));
+
+        assert!(store.delete("theme").unwrap());
+        assert!(!store.delete("theme").unwrap());
+        assert!(matches!(
+            store.get("theme"),
+            Err(Error::EntryNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn upsert() {
+        let store = Store::open_in_memory().unwrap();
+        let connection = store.connection();
+        let set = |value: Option<&str>, description: Option<&str>, change_only| {
+            set(
+                connection,
+                "theme",
+                value.map(str::to_string),
+                None,
+                description.map(str::to_string),
+                None,
+                change_only,
+            )
+        };
+
+        assert!(matches!(
+            set(Some("dark"), None, true),
+            Err(Error::EntryNotFound { .. })
+        ));
+        assert!(!store.exists("theme").unwrap());
+
+        set(Some("dark"), Some("colors"), false).unwrap();
+        // Fields which aren't given are kept
+        set(None, None, false).unwrap();
+        set(Some("light"), None, true).unwrap();
+        let entry = store.get("theme").unwrap();
+        assert_eq!(entry.value, "light");
+        assert_eq!(entry.description.as_deref(), Some("colors"));
+        assert_eq!(store.list(&Filter::default()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn upsert_with_duplicates() {
+        let store = Store::open_in_memory().unwrap();
+        let connection = store.connection();
+        let unique = || -> bool {
+            connection
+                .query_row(
+                    "SELECT \"unique\" FROM pragma_index_list('data') WHERE name = 'data_name'",
+                    (),
+                    |row| row.get(0),
+                )
+                .unwrap()
+        };
+        assert!(unique());
+
+        // Written by another tool, which doesn't know about the unique index
+        connection
+            .execute_batch(
+                "DROP INDEX data_name;
+                INSERT INTO data (name, value) VALUES ('theme', 'dark'), ('theme', 'light');",
+            )
+            .unwrap();
+        crate::schema::index_names(connection).unwrap();
+        assert!(!unique());
+
+        // Without a conflict to upsert on the entries are updated or created separately
+        store.set("theme", Some("solarized"), None).unwrap();
+        store.set("font", Some("mono"), None).unwrap();
+        let values = connection
+            .prepare("SELECT value FROM data ORDER BY id")
+            .unwrap()
+            .query_map((), |row| row.get::<_, String>(0))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<String>>>()
+            .unwrap();
+        assert_eq!(values, ["solarized", "solarized", "mono"]);
+
+        // Once the duplicates are gone the index is unique again
+        crate::commands::dedupe_cmd(connection, crate::commands::DedupeStrategy::Newest).unwrap();
+        crate::schema::index_names(connection).unwrap();
+        assert!(unique());
+        let error = connection
+            .execute(
+                "INSERT INTO data (name, value) VALUES ('font', 'serif')",
+                (),
+            )
+            .unwrap_err();
+        assert!(matches!(Error::from(error), Error::EntryExists));
+    }
+
+    #[test]
+    fn ignore_case() {
+        let store = Store::open_in_memory().unwrap();
+        settings::set(store.connection(), settings::IGNORE_CASE, Some("on")).unwrap();
+
+        store.set("Theme", Some("dark"), None).unwrap();
+        let entry = store.set("theme", Some("light"), None).unwrap();
+        assert_eq!(
+            (entry.name.as_str(), entry.value.as_str()),
+            ("Theme", "light")
+        );
+        assert_eq!(store.list(&Filter::default()).unwrap().len(), 1);
+        assert_eq!(store.get("THEME").unwrap().value, "light");
+
+        assert!(store.delete("theme").unwrap());
+        assert!(!store.exists("Theme").unwrap());
+        assert!(!store.delete("theme").unwrap());
+
+        // Without the setting names differing in case are different entries
+        settings::set(store.connection(), settings::IGNORE_CASE, None).unwrap();
+        store.set("Font", Some("mono"), None).unwrap();
+        assert!(matches!(
+            store.get("font"),
+            Err(Error::EntryNotFound { .. })
+        ));
+        assert!(!store.delete("font").unwrap());
+    }
+
+    #[test]
+    fn transactions() {
+        let store = Store::open_in_memory().unwrap();
+
+        let result = store.transaction(|tx| {
+            tx.set("a", Some("1"), None)?;
+            tx.toggle("missing")
+        });
+        assert!(matches!(result, Err(Error::EntryNotFound { .. })));
+        assert!(!store.exists("a").unwrap());
+
+        store
+            .transaction(|tx| {
+                tx.set("a", Some("1"), Some("0"))?;
+                let inner = tx.transaction(|inner| {
+                    inner.delete("a")?;
+                    Err::<(), _>(Error::Mismatch)
+                });
+                assert!(inner.is_err());
+                tx.toggle("a")
+            })
+            .unwrap();
+        assert_eq!(store.get("a").unwrap().value, "0");
+    }
+
+    #[test]
+    fn read_only() {
+        let path = std::env::temp_dir().join(format!("config-store-ro-{}.db", std::process::id()));
+        Store::open(&path)
+            .unwrap()
+            .set("a", Some("1"), None)
+            .unwrap();
+
+        let uri = format!("file:{}?mode=ro", path.display());
+        assert_eq!(
+            open_flags(&uri),
+            OpenFlags::SQLITE_OPEN_READ_ONLY
+                | OpenFlags::SQLITE_OPEN_URI
+                | OpenFlags::SQLITE_OPEN_NO_MUTEX
+        );
+        assert_eq!(open_flags(":memory:"), OpenFlags::default());
+
+        let store = Store::open(&uri).unwrap();
+        assert_eq!(store.get("a").unwrap().value, "1");
+        assert!(store.set("a", Some("2"), None).is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reads_dont_write() {
+        let path =
+            std::env::temp_dir().join(format!("config-store-reads-{}.db", std::process::id()));
+        Store::open(&path)
+            .unwrap()
+            .set("a", Some("1"), None)
+            .unwrap();
+        let before = std::fs::read(&path).unwrap();
+
+        let store = Store::open(&path).unwrap();
+        assert_eq!(store.get("a").unwrap().value, "1");
+        drop(store);
+        assert_eq!(std::fs::read(&path).unwrap(), before);
+
+        // With a cap reads are recorded for evicting the least recently used entries
+        let store = Store::open(&path).unwrap();
+        settings::set(store.connection(), settings::CAP, Some("10")).unwrap();
+        store.get("a").unwrap();
+        let accessed_at: Option<i64> = store
+            .connection()
+            .query_row("SELECT accessed_at FROM data", (), |row| row.get(0))
+            .unwrap();
+        assert!(accessed_at.is_some());
+        drop(store);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,74 @@
+//! Module finding names similar to one which doesn't exist, for "did you mean" suggestions
+//!
+//! Most missing entries are typos, so names within a small edit distance are suggested, as well
+//! as names starting with the given one (e.g. `vpn` for `vpn.home`).
+
+/// The maximum number of suggestions
+const LIMIT: usize = 3;
+
+/// Returns up to three of `names` similar to `name`, closest first
+pub fn similar<I>(name: &str, names: I) -> Vec<String>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    // One typo per three characters, so short names don't match everything
+    let max_distance = (name.chars().count() / 3).max(1);
+
+    let mut similar = names
+        .into_iter()
+        .filter_map(|candidate| {
+            let candidate = candidate.as_ref();
+            if candidate == name {
+                return None;
+            }
+
+            let distance = distance(name, candidate);
+            (distance <= max_distance || candidate.starts_with(name))
+                .then(|| (distance, candidate.to_string()))
+        })
+        .collect::<Vec<(usize, String)>>();
+
+    similar.sort();
+    similar.dedup();
+    similar
+        .into_iter()
+        .take(LIMIT)
+        .map(|(_, name)| name)
+        .collect()
+}
+
+/// Helper function computing the Levenshtein distance between two strings
+fn distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<char>>();
+    let mut previous = (0..=b.len()).collect::<Vec<usize>>();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn suggestions() {
+        let names = ["theme", "vpn.home", "vpn.work", "volume", "them"];
+
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(similar("thme", names), ["theme"]);
+        assert_eq!(similar("vpn.hom", names), ["vpn.home"]);
+        assert_eq!(similar("vpn", names), ["vpn.home", "vpn.work"]);
+        assert!(similar("wallpaper", names).is_empty());
+    }
+}
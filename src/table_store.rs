@@ -0,0 +1,212 @@
+//! Module containing [TableStore], a [Backend] keeping a separate set of entries in its own table
+//!
+//! This lets several logical stores share one db file (& so its backups), see `--table`. The
+//! table of a store named `<name>` is `store_<name>`. Only the entries themselves are stored, so
+//! like other backends it only supports the basic commands.
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::{audit, backend::Backend, time, Entry, Error, Filter, Result};
+
+/// The prefix of the tables of named stores
+pub const PREFIX: &str = "store_";
+
+/// A [Backend] using its own table in a sqlite db
+#[derive(Debug)]
+pub struct TableStore {
+    connection: Connection,
+    table: String,
+}
+
+impl TableStore {
+    /// Opens the store named `name` in the db at `path`, creating its table if needed
+    ///
+    /// Will return [Error::Invalid] if the name contains anything but ASCII letters, digits & `_`.
+    pub fn open(path: &str, name: &str) -> Result<TableStore> {
+        TableStore::from_connection(Connection::open(path)?, name)
+    }
+
+    /// Uses an existing connection, see [TableStore::open]
+    pub fn from_connection(connection: Connection, name: &str) -> Result<TableStore> {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(Error::Invalid(format!("invalid table name '{}'", name)));
+        }
+
+        let table = format!("{}{}", PREFIX, name);
+        create(&connection, &table)?;
+
+        Ok(TableStore { connection, table })
+    }
+}
+
+/// Helper function creating the table `table` if it doesn't exist
+pub(crate) fn create(connection: &Connection, table: &str) -> Result<()> {
+    connection.execute(
+        &format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE,
+                value TEXT NOT NULL,
+                alternate TEXT NOT NULL,
+                created_at INTEGER,
+                updated_at INTEGER,
+                description TEXT,
+                default_value TEXT,
+                modified_by TEXT
+            )",
+            table
+        ),
+        (),
+    )?;
+
+    // Tables created before `modified_by` was tracked
+    let tracked = connection
+        .prepare("SELECT 1 FROM pragma_table_info(?) WHERE name = 'modified_by'")?
+        .exists([table])?;
+    if !tracked {
+        connection.execute(
+            &format!("ALTER TABLE {} ADD COLUMN modified_by TEXT", table),
+            (),
+        )?;
+    }
+
+    Ok(())
+}
+
+impl Backend for TableStore {
+    fn get(&self, name: &str) -> Result<Entry> {
+        self.connection
+            .query_row(
+                &format!(
+                    "SELECT {} FROM {} WHERE name = ?",
+                    Entry::COLUMNS,
+                    self.table
+                ),
+                [name],
+                Entry::from_row,
+            )
+            .optional()?
+            .ok_or_else(|| Error::entry_not_found(name))
+    }
+
+    fn exists(&self, name: &str) -> Result<bool> {
+        Ok(self
+            .connection
+            .prepare(&format!("SELECT 1 FROM {} WHERE name = ?", self.table))?
+            .exists([name])?)
+    }
+
+    fn set(&self, name: &str, value: Option<&str>, alternate: Option<&str>) -> Result<Entry> {
+        self.connection.execute(
+            &format!(
+                "INSERT INTO {} (name, value, alternate, created_at, updated_at, modified_by)
+                    VALUES (?1, IFNULL(?2, ''), ?3, ?4, ?4, ?5)
+                ON CONFLICT (name) DO UPDATE SET
+                    value = IFNULL(?2, value), alternate = IFNULL(?3, alternate), updated_at = ?4,
+                    modified_by = ?5",
+                self.table
+            ),
+            (name, value, alternate, time::now(), audit::modifier()),
+        )?;
+
+        self.get(name)
+    }
+
+    fn toggle(&self, name: &str) -> Result<String> {
+        let toggled = self.connection.execute(
+            &format!(
+                "UPDATE {} SET value = IFNULL(alternate, ''), alternate = value, updated_at = ?,
+                    modified_by = ?
+                    WHERE name = ?",
+                self.table
+            ),
+            (time::now(), audit::modifier(), name),
+        )?;
+
+        if toggled == 0 {
+            return Err(Error::entry_not_found(name));
+        }
+
+        Ok(self.get(name)?.value)
+    }
+
+    fn delete(&self, name: &str) -> Result<bool> {
+        Ok(self.connection.execute(
+            &format!("DELETE FROM {} WHERE name = ?", self.table),
+            [name],
+        )? > 0)
+    }
+
+    fn list(&self, filter: &Filter) -> Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+
+        for entry in self
+            .connection
+            .prepare(&format!(
+                "SELECT {} FROM {} ORDER BY id",
+                Entry::COLUMNS,
+                self.table
+            ))?
+            .query_map((), Entry::from_row)?
+        {
+            let entry = entry?;
+
+            if filter.matches(&entry)? {
+                entries.push(entry);
+            }
+        }
+
+        Ok(entries)
+    }
+}
+
+/// Returns the names of all stores with their own table in the db
+pub fn names(connection: &Connection) -> Result<Vec<String>> {
+    Ok(connection
+        .prepare(
+            "SELECT substr(name, length(?1) + 1) FROM sqlite_master
+                WHERE type = 'table' AND instr(name, ?1) = 1 ORDER BY name",
+        )?
+        .query_map([PREFIX], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn separate_tables() {
+        let connection = Connection::open_in_memory().unwrap();
+        crate::schema::init(&connection).unwrap();
+        let work = TableStore::from_connection(connection, "work").unwrap();
+
+        work.set("theme", Some("dark"), Some("light")).unwrap();
+        work.set("theme", None, Some("solarized")).unwrap();
+        assert_eq!(work.toggle("theme").unwrap(), "solarized");
+        assert_eq!(
+            work.get("theme").unwrap().alternate.as_deref(),
+            Some("dark")
+        );
+        assert!(matches!(
+            work.toggle("missing"),
+            Err(Error::EntryNotFound { .. })
+        ));
+
+        let connection = &work.connection;
+        let in_data: i64 = connection
+            .query_row("SELECT COUNT(*) FROM data", (), |row| row.get(0))
+            .unwrap();
+        assert_eq!(in_data, 0);
+        assert_eq!(names(connection).unwrap(), vec!["work".to_string()]);
+        assert_eq!(
+            crate::commands::tables_cmd(connection).unwrap(),
+            "(default) 0\nwork 1"
+        );
+
+        assert!(work.delete("theme").unwrap());
+        assert!(matches!(
+            TableStore::open(":memory:", "a; DROP TABLE data"),
+            Err(Error::Invalid(_))
+        ));
+    }
+}
@@ -0,0 +1,230 @@
+//! Module containing helpers for working with timestamps
+//!
+//! All timestamps are stored in the db as seconds since the unix epoch (UTC).
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Returns the current time as seconds since the unix epoch
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Returns the current time as milliseconds since the unix epoch, for measuring short intervals
+pub fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+/// Parses a duration like `90s`, `30m`, `1h30m`, `7d` or `2w` into seconds
+///
+/// A number without a unit is interpreted as seconds.
+pub fn parse_duration(duration: &str) -> Result<i64, String> {
+    let mut total = 0;
+    let mut number = String::new();
+
+    for c in duration.trim().chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86_400,
+            'w' => 604_800,
+            _ => return Err(format!("invalid unit '{}' in duration '{}'", c, duration)),
+        };
+
+        let value = number
+            .parse::<i64>()
+            .map_err(|_| format!("missing number before '{}' in duration '{}'", c, duration))?;
+        total += value * unit;
+        number.clear();
+    }
+
+    if !number.is_empty() {
+        total += number
+            .parse::<i64>()
+            .map_err(|e| format!("invalid duration '{}': {}", duration, e))?;
+    } else if total == 0 && !duration.trim().starts_with('0') {
+        return Err(format!("invalid duration '{}'", duration));
+    }
+
+    Ok(total)
+}
+
+/// Formats a duration in seconds like [parse_duration] accepts it, e.g. `1h30m` or `0s`
+pub fn format_duration(seconds: i64) -> String {
+    let mut rest = seconds.max(0);
+    let mut formatted = String::new();
+
+    for (unit, length) in [('d', 86_400), ('h', 3600), ('m', 60), ('s', 1)] {
+        if rest >= length || (unit == 's' && formatted.is_empty()) {
+            formatted += &format!("{}{}", rest / length, unit);
+            rest %= length;
+        }
+    }
+
+    formatted
+}
+
+/// A point in time given relative to now, see [parse_at]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum At {
+    /// After a duration in seconds
+    After(i64),
+    /// The next time the local clock shows this hour & minute
+    Clock(u32, u32),
+}
+
+impl At {
+    /// Returns the point in time as seconds since the unix epoch
+    ///
+    /// The local time zone is taken from sqlite, so [At::Clock] needs a connection.
+    pub fn timestamp(self, connection: &rusqlite::Connection) -> rusqlite::Result<i64> {
+        match self {
+            At::After(duration) => Ok(now() + duration),
+            At::Clock(hour, minute) => {
+                let today: i64 = connection.query_row(
+                    "SELECT CAST(strftime(
+                        '%s', date('now', 'localtime') || printf(' %02d:%02d', ?, ?), 'utc'
+                    ) AS INTEGER)",
+                    (hour, minute),
+                    |row| row.get(0),
+                )?;
+
+                Ok(if today > now() { today } else { today + 86_400 })
+            }
+        }
+    }
+}
+
+/// Parses a local time of day like `22:00` or a duration like `30m` (see [parse_duration])
+pub fn parse_at(at: &str) -> Result<At, String> {
+    let Some((hour, minute)) = at.trim().split_once(':') else {
+        return parse_duration(at).map(At::After);
+    };
+
+    match (hour.parse::<u32>(), minute.parse::<u32>()) {
+        (Ok(hour), Ok(minute)) if hour < 24 && minute < 60 => Ok(At::Clock(hour, minute)),
+        _ => Err(format!("invalid time '{}', expected HH:MM", at)),
+    }
+}
+
+/// Parses a date & time like `2024-05-01 10:00` (local time) or a duration ago like `2h`
+///
+/// Everything sqlite's date & time functions understand is accepted, including RFC 3339 date-times
+/// with an offset like `2024-05-01T10:00:00Z`.
+pub fn parse_datetime(connection: &rusqlite::Connection, datetime: &str) -> Result<i64, String> {
+    if let Ok(ago) = parse_duration(datetime) {
+        return Ok(now() - ago);
+    }
+
+    connection
+        .query_row(
+            "SELECT CAST(strftime('%s', ?, 'utc') AS INTEGER)",
+            [datetime],
+            |row| row.get::<_, Option<i64>>(0),
+        )
+        .ok()
+        .flatten()
+        .ok_or_else(|| format!("invalid date & time '{}'", datetime))
+}
+
+/// Like [parse_datetime] without a db, for options parsed before the db is opened
+pub fn parse_timestamp(datetime: &str) -> Result<i64, String> {
+    let connection = rusqlite::Connection::open_in_memory().map_err(|e| e.to_string())?;
+
+    parse_datetime(&connection, datetime)
+}
+
+/// Formats a timestamp as an RFC 3339 date-time in UTC, e.g. `2024-05-01T10:00:00Z`
+pub fn format(timestamp: i64) -> String {
+    let days = timestamp.div_euclid(86_400);
+    let seconds = timestamp.rem_euclid(86_400);
+
+    let (year, month, day) = civil_from_days(days);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+/// Converts days since the unix epoch into a `(year, month, day)` date
+///
+/// See <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn format_timestamps() {
+        assert_eq!(format(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format(1_714_557_600), "2024-05-01T10:00:00Z");
+    }
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(parse_duration("90"), Ok(90));
+        assert_eq!(parse_duration("1h30m"), Ok(5400));
+        assert_eq!(parse_duration("7d"), Ok(604_800));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("5y").is_err());
+    }
+
+    #[test]
+    fn format_durations() {
+        assert_eq!(format_duration(0), "0s");
+        assert_eq!(format_duration(5400), "1h30m");
+        assert_eq!(format_duration(90_061), "1d1h1m1s");
+        assert_eq!(parse_duration(&format_duration(3725)), Ok(3725));
+    }
+
+    #[test]
+    fn parse_ats() {
+        assert_eq!(parse_at("22:00"), Ok(At::Clock(22, 0)));
+        assert_eq!(parse_at("1h"), Ok(At::After(3600)));
+        assert!(parse_at("24:00").is_err());
+        assert!(parse_at("12:5x").is_err());
+
+        let connection = rusqlite::Connection::open_in_memory().unwrap();
+        assert_eq!(
+            parse_datetime(&connection, "2024-05-01T10:00:00Z"),
+            Ok(1_714_557_600)
+        );
+        assert_eq!(parse_datetime(&connection, "1h"), Ok(now() - 3600));
+        assert!(parse_datetime(&connection, "soon").is_err());
+        assert_eq!(parse_timestamp("2024-05-01T10:00:00Z"), Ok(1_714_557_600));
+
+        let next = parse_at("07:30").unwrap().timestamp(&connection).unwrap();
+        assert!(next > now() && next <= now() + 86_400);
+    }
+}
@@ -0,0 +1,115 @@
+//! Module mirroring entries into the global environment or user options of tmux, see `tmux-sync`
+//!
+//! New panes inherit the global environment, while user options (`@name`) can be used by the
+//! status line as `#{@name}`, so both see changed entries without running config-store. They are
+//! changed by running `tmux`, which talks to the running server.
+use std::process::Command;
+
+use crate::Result;
+
+/// Sets the global variables (or with `options` the user options) `vars` to their values &
+/// unsets those named in `unset`, all with a single call of tmux
+pub fn set(vars: &[(String, String)], unset: &[String], options: bool) -> Result<()> {
+    if vars.is_empty() && unset.is_empty() {
+        return Ok(());
+    }
+
+    run(&arguments(vars, unset, options)).map(|_| ())
+}
+
+/// Returns the names of the global variables (or with `options` the user options) starting with
+/// `prefix`
+pub fn names(prefix: &str, options: bool) -> Result<Vec<String>> {
+    let output = run(&[
+        if options {
+            "show-options"
+        } else {
+            "show-environment"
+        }
+        .to_string(),
+        "-g".to_string(),
+    ])?;
+
+    Ok(output
+        .lines()
+        .filter_map(|line| {
+            if options {
+                line.split_whitespace().next()
+            } else {
+                // Removed variables are listed as `-NAME`
+                line.split('=').next().filter(|name| !name.starts_with('-'))
+            }
+        })
+        .filter(|name| name.starts_with(prefix))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Helper function returning the arguments of tmux setting `vars` & unsetting `unset`, as commands
+/// separated by `;`
+fn arguments(vars: &[(String, String)], unset: &[String], options: bool) -> Vec<String> {
+    let command = if options {
+        "set-option"
+    } else {
+        "set-environment"
+    };
+
+    vars.iter()
+        .map(|(name, value)| vec![command, "-g", name, value])
+        .chain(unset.iter().map(|name| vec![command, "-gu", name]))
+        .collect::<Vec<Vec<&str>>>()
+        .join(&";")
+        .into_iter()
+        .map(str::to_string)
+        .collect()
+}
+
+/// Helper function running tmux with `arguments` & returning its output
+fn run(arguments: &[String]) -> Result<String> {
+    let output = Command::new("tmux")
+        .args(arguments)
+        .output()
+        .map_err(|error| {
+            std::io::Error::new(error.kind(), format!("failed to run tmux: {}", error))
+        })?;
+
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!(
+            "tmux failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+        .into());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn commands() {
+        assert_eq!(
+            arguments(
+                &[("CS_THEME".to_string(), "dark mode".to_string())],
+                &["CS_OLD".to_string()],
+                false
+            ),
+            [
+                "set-environment",
+                "-g",
+                "CS_THEME",
+                "dark mode",
+                ";",
+                "set-environment",
+                "-gu",
+                "CS_OLD"
+            ]
+        );
+        assert_eq!(
+            arguments(&[("@cs-theme".to_string(), "dark".to_string())], &[], true),
+            ["set-option", "-g", "@cs-theme", "dark"]
+        );
+    }
+}
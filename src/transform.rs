@@ -0,0 +1,152 @@
+//! Module containing the [Transform]ations `transform` can apply to values
+use clap::Args;
+use regex::Regex;
+
+/// An edit of a value
+///
+/// Exactly one of the options has to be given.
+#[derive(Debug, Clone, Default, Args)]
+#[group(id = "transform", required = true, multiple = false)]
+pub struct Transform {
+    /// Replace matches of a regex like sed, e.g. `s/foo/bar/` or `s|a(.)|$1|g`
+    ///
+    /// Only the first match is replaced, unless the `g` flag is given. The `i` flag makes the
+    /// match case-insensitive. Captures are referenced with `$1` or `${name}`.
+    #[arg(short, long, value_name = "s/REGEX/REPLACEMENT/FLAGS", value_parser = Substitution::parse)]
+    pub regex: Option<Substitution>,
+    /// Convert to upper case
+    #[arg(short, long)]
+    pub upper: bool,
+    /// Convert to lower case
+    #[arg(short, long)]
+    pub lower: bool,
+    /// Remove leading & trailing whitespace
+    #[arg(short, long)]
+    pub trim: bool,
+    /// Replace the value with the result of a Rhai script, which gets the old one as `value`
+    #[cfg(feature = "script")]
+    #[arg(long)]
+    pub script: Option<String>,
+}
+
+impl Transform {
+    /// The Rhai script to run instead of [Transform::apply], if one was given
+    pub fn script(&self) -> Option<&str> {
+        #[cfg(feature = "script")]
+        return self.script.as_deref();
+        #[cfg(not(feature = "script"))]
+        None
+    }
+
+    /// Returns the transformed `value`
+    pub fn apply(&self, value: &str) -> String {
+        if let Some(substitution) = &self.regex {
+            substitution.apply(value)
+        } else if self.upper {
+            value.to_uppercase()
+        } else if self.lower {
+            value.to_lowercase()
+        } else if self.trim {
+            value.trim().to_string()
+        } else {
+            value.to_string()
+        }
+    }
+}
+
+/// A sed like substitution, see [Transform::regex]
+#[derive(Debug, Clone)]
+pub struct Substitution {
+    regex: Regex,
+    replacement: String,
+    global: bool,
+}
+
+impl Substitution {
+    /// Parses a substitution like `s/foo/bar/g`
+    ///
+    /// Any character can be used as the delimiter instead of `/`. A delimiter inside the regex or
+    /// the replacement has to be escaped with `\`.
+    pub fn parse(substitution: &str) -> Result<Substitution, String> {
+        let mut chars = substitution.chars();
+
+        let delimiter = match (chars.next(), chars.next()) {
+            (Some('s'), Some(delimiter)) => delimiter,
+            _ => return Err("expected s/REGEX/REPLACEMENT/FLAGS".to_string()),
+        };
+
+        let mut parts = vec![String::new()];
+        let mut escaped = false;
+        for c in chars {
+            let part = parts.last_mut().unwrap();
+
+            if escaped {
+                // Escapes other than the delimiter are left for the regex
+                if c != delimiter {
+                    part.push('\\');
+                }
+                part.push(c);
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == delimiter {
+                parts.push(String::new());
+            } else {
+                part.push(c);
+            }
+        }
+
+        let [pattern, replacement, flags] = <[String; 3]>::try_from(parts)
+            .map_err(|_| "expected s/REGEX/REPLACEMENT/FLAGS".to_string())?;
+
+        let mut case_insensitive = false;
+        let mut global = false;
+        for flag in flags.chars() {
+            match flag {
+                'g' => global = true,
+                'i' => case_insensitive = true,
+                flag => return Err(format!("unknown flag '{}'", flag)),
+            }
+        }
+
+        let regex = regex::RegexBuilder::new(&pattern)
+            .case_insensitive(case_insensitive)
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        Ok(Substitution {
+            regex,
+            replacement,
+            global,
+        })
+    }
+
+    /// Returns `value` with the matches replaced
+    pub fn apply(&self, value: &str) -> String {
+        let limit = if self.global { 0 } else { 1 };
+
+        self.regex
+            .replacen(value, limit, self.replacement.as_str())
+            .into_owned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn substitutions() {
+        let apply = |s: &str, value: &str| Substitution::parse(s).unwrap().apply(value);
+
+        assert_eq!(apply("s/o/0/", "foo"), "f0o");
+        assert_eq!(apply("s/o/0/g", "foo"), "f00");
+        assert_eq!(apply("s/F(o)/${1}x/i", "foo"), "oxo");
+        assert_eq!(apply(r"s#a\#b#c#", "a#b"), "c");
+        assert_eq!(apply(r"s/\d+/N/g", "1 22"), "N N");
+
+        assert!(Substitution::parse("s/a/b").is_err());
+        assert!(Substitution::parse("s/a/b/x").is_err());
+        assert!(Substitution::parse("s/(/b/").is_err());
+    }
+}
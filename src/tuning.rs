@@ -0,0 +1,123 @@
+//! Module containing the [Tuning] of a connection, trading durability for speed
+//!
+//! The defaults are sqlite's own, so a db is as durable as sqlite makes it unless asked otherwise.
+//! On slow storage (e.g. SD cards) `--synchronous normal` avoids most waits for the disk, while a
+//! db on a tmpfs gains nothing from syncing at all & can use `--synchronous off`.
+use clap::{Args, ValueEnum};
+use rusqlite::Connection;
+
+use crate::Result;
+
+/// When sqlite waits for changes to reach the disk, see `PRAGMA synchronous`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Synchronous {
+    /// Never wait, a power loss or OS crash can corrupt the db
+    Off,
+    /// Wait at the most critical moments only, a power loss can corrupt the db in rare cases
+    Normal,
+    /// Wait for every change, so committed changes survive a power loss
+    Full,
+    /// Like full, but also wait for deleted journals
+    Extra,
+}
+
+/// Where sqlite keeps temporary tables & indices, see `PRAGMA temp_store`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TempStore {
+    /// As sqlite was compiled, which is in files
+    Default,
+    /// In files
+    File,
+    /// In memory
+    Memory,
+}
+
+/// The pragmas applied to a connection when opening a db
+#[derive(Debug, Clone, Args)]
+pub struct Tuning {
+    /// When to wait for changes to reach the disk (`off` is fastest but risks corrupting the db)
+    #[arg(long, value_enum, value_name = "LEVEL", default_value = "full")]
+    pub synchronous: Synchronous,
+    /// The size of the page cache in KiB
+    #[arg(long, value_name = "KIB", default_value_t = 2000)]
+    pub cache_size: u64,
+    /// The number of bytes of the db to access via memory-mapped I/O (0 disables it)
+    #[arg(long, value_name = "BYTES", default_value_t = 0)]
+    pub mmap_size: u64,
+    /// Where to keep temporary tables & indices, e.g. used for sorting
+    #[arg(long, value_enum, value_name = "WHERE", default_value = "default")]
+    pub temp_store: TempStore,
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Tuning {
+            synchronous: Synchronous::Full,
+            cache_size: 2000,
+            mmap_size: 0,
+            temp_store: TempStore::Default,
+        }
+    }
+}
+
+impl Tuning {
+    /// Sets the pragmas on `connection`
+    ///
+    /// Should be called right after opening the db, since some pragmas can't be changed inside a
+    /// transaction.
+    pub fn apply(&self, connection: &Connection) -> Result<()> {
+        let synchronous = match self.synchronous {
+            Synchronous::Off => "OFF",
+            Synchronous::Normal => "NORMAL",
+            Synchronous::Full => "FULL",
+            Synchronous::Extra => "EXTRA",
+        };
+        let temp_store = match self.temp_store {
+            TempStore::Default => "DEFAULT",
+            TempStore::File => "FILE",
+            TempStore::Memory => "MEMORY",
+        };
+
+        // A negative cache size is in KiB instead of pages
+        connection.execute_batch(&format!(
+            "PRAGMA synchronous = {synchronous};
+            PRAGMA cache_size = -{};
+            PRAGMA mmap_size = {};
+            PRAGMA temp_store = {temp_store};",
+            self.cache_size, self.mmap_size
+        ))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn apply() {
+        let connection = Connection::open_in_memory().unwrap();
+        let pragma = |name: &str| -> i64 {
+            connection
+                .pragma_query_value(None, name, |row| row.get(0))
+                .unwrap()
+        };
+
+        Tuning::default().apply(&connection).unwrap();
+        assert_eq!(pragma("synchronous"), 2);
+        assert_eq!(pragma("cache_size"), -2000);
+
+        Tuning {
+            synchronous: Synchronous::Normal,
+            cache_size: 8192,
+            mmap_size: 0,
+            temp_store: TempStore::Memory,
+        }
+        .apply(&connection)
+        .unwrap();
+        assert_eq!(pragma("synchronous"), 1);
+        assert_eq!(pragma("cache_size"), -8192);
+        assert_eq!(pragma("temp_store"), 2);
+    }
+}